@@ -207,3 +207,8 @@ pub fn path_popdat() -> String {
 pub fn path_raw_map(name: &MapName) -> String {
     path(format!("input/{}/raw_maps/{}.bin", name.city, name.map))
 }
+
+/// How IDs in the previous version of a map correspond to the current version.
+pub fn path_id_remap(name: &MapName) -> String {
+    path(format!("input/{}/id_remaps/{}.bin", name.city, name.map))
+}
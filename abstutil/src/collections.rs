@@ -319,6 +319,12 @@ impl<K: IndexableKey, V> FixedMap<K, V> {
     pub fn values(&self) -> std::iter::Flatten<std::slice::Iter<'_, std::option::Option<V>>> {
         self.inner.iter().flatten()
     }
+
+    pub fn values_mut(
+        &mut self,
+    ) -> std::iter::Flatten<std::slice::IterMut<'_, std::option::Option<V>>> {
+        self.inner.iter_mut().flatten()
+    }
 }
 
 impl<K: IndexableKey, V> std::ops::Index<&K> for FixedMap<K, V> {
@@ -3,12 +3,15 @@ use std::collections::{BTreeMap, HashMap};
 use osm::{NodeID, OsmID, RelationID, WayID};
 
 use abstutil::{retain_btreemap, Tags, Timer};
-use geom::{HashablePt2D, Polygon, Pt2D, Ring};
+use geom::{HashablePt2D, LonLat, Polygon, Pt2D, Ring};
 use kml::{ExtraShape, ExtraShapes};
-use map_model::raw::{RawArea, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionType};
+use map_model::raw::{
+    RawArea, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionConditions, RestrictionType,
+};
 use map_model::{osm, Amenity, AreaType, NamePerLanguage};
 
 use crate::osm_geom::{get_multipolygon_members, glue_multipolygon, multipoly_geometry};
+use crate::reader::Document;
 use crate::{transit, Options};
 
 pub struct OsmExtract {
@@ -16,17 +19,42 @@ pub struct OsmExtract {
     pub roads: Vec<(WayID, RawRoad)>,
     /// Traffic signals to the direction they apply (or just true if unspecified)
     pub traffic_signals: HashMap<HashablePt2D, bool>,
+    /// Yield signs to the direction they apply (or just true if unspecified)
+    pub give_way_signs: HashMap<HashablePt2D, bool>,
+    /// Stop signs to the direction they apply, and whether they're tagged as an all-way stop
+    pub stop_signs: HashMap<HashablePt2D, (bool, bool)>,
     pub osm_node_ids: HashMap<HashablePt2D, NodeID>,
-    /// (ID, restriction type, from way ID, via node ID, to way ID)
-    pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID)>,
-    /// (relation ID, from way ID, via way ID, to way ID)
-    pub complicated_turn_restrictions: Vec<(RelationID, WayID, WayID, WayID)>,
+    /// (restriction type, from way ID, via node ID, to way ID, conditions)
+    pub simple_turn_restrictions:
+        Vec<(RestrictionType, WayID, NodeID, WayID, RestrictionConditions)>,
+    /// (relation ID, restriction type, from way ID, via way IDs, to way ID, conditions)
+    pub complicated_turn_restrictions: Vec<(
+        RelationID,
+        RestrictionType,
+        WayID,
+        Vec<WayID>,
+        WayID,
+        RestrictionConditions,
+    )>,
     /// (location, amenity)
     pub amenities: Vec<(Pt2D, Amenity)>,
+    /// Separately mapped sidewalks and crossings (`footway=sidewalk` and `footway=crossing`). They
+    /// aren't in `roads`; see `snappy::snap_sidewalks`.
+    pub separate_footways: Vec<(WayID, RawRoad)>,
+    /// Where a separately mapped crossing meets a road, and whether the crossing is marked. Filled
+    /// out by `snappy::snap_sidewalks`.
+    pub crossings: HashMap<HashablePt2D, bool>,
+    /// Cycleways that aren't roads themselves. They're merged into the road they run alongside by
+    /// `snappy::snap_cycleways`.
+    pub separate_cycleways: Vec<(WayID, RawRoad)>,
 }
 
-pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmExtract {
-    let mut doc = crate::reader::read(&opts.osm_input, &map.gps_bounds, timer).unwrap();
+pub fn extract_osm(
+    map: &mut RawMap,
+    mut doc: Document,
+    opts: &Options,
+    timer: &mut Timer,
+) -> OsmExtract {
     if opts.clip.is_none() {
         // Use the boundary from .osm.
         map.gps_bounds = doc.gps_bounds.clone();
@@ -36,10 +64,15 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
     let mut out = OsmExtract {
         roads: Vec::new(),
         traffic_signals: HashMap::new(),
+        give_way_signs: HashMap::new(),
+        stop_signs: HashMap::new(),
         osm_node_ids: HashMap::new(),
         simple_turn_restrictions: Vec::new(),
         complicated_turn_restrictions: Vec::new(),
         amenities: Vec::new(),
+        separate_footways: Vec::new(),
+        crossings: HashMap::new(),
+        separate_cycleways: Vec::new(),
     };
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -52,6 +85,17 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
             out.traffic_signals
                 .insert(node.pt.to_hashable(), !backwards);
         }
+        if node.tags.is(osm::HIGHWAY, "give_way") {
+            let backwards = node.tags.is("direction", "backward");
+            out.give_way_signs.insert(node.pt.to_hashable(), !backwards);
+        }
+        if node.tags.is(osm::HIGHWAY, "stop") {
+            let backwards = node.tags.is("direction", "backward");
+            out.stop_signs.insert(
+                node.pt.to_hashable(),
+                (!backwards, node.tags.is("stop", "all")),
+            );
+        }
         for amenity in get_bldg_amenities(&node.tags) {
             out.amenities.push((node.pt, amenity));
         }
@@ -78,15 +122,17 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                 way.tags.insert(osm::SIDEWALK, "right");
             }
 
-            out.roads.push((
-                id,
-                RawRoad {
-                    center_points: way.pts.clone(),
-                    osm_tags: way.tags.clone(),
-                    turn_restrictions: Vec::new(),
-                    complicated_turn_restrictions: Vec::new(),
-                },
-            ));
+            let road = RawRoad {
+                center_points: way.pts.clone(),
+                osm_tags: way.tags.clone(),
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+            };
+            if is_separate_footway(&way.tags) {
+                out.separate_footways.push((id, road));
+            } else {
+                out.roads.push((id, road));
+            }
             continue;
         } else if way.tags.is(osm::HIGHWAY, "service") {
             // If we got here, is_road didn't interpret it as a normal road
@@ -104,6 +150,19 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                 points: map.gps_bounds.convert_back(&way.pts),
                 attributes: way.tags.inner().clone(),
             });
+            let road = RawRoad {
+                center_points: way.pts.clone(),
+                osm_tags: way.tags.clone(),
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+            };
+            // These aren't roads, but they still tell us which side of a road has a cycle track
+            // or sidewalk.
+            if way.tags.is(osm::HIGHWAY, "cycleway") {
+                out.separate_cycleways.push((id, road));
+            } else if is_separate_footway(&way.tags) {
+                out.separate_footways.push((id, road));
+            }
         } else if way.tags.is("natural", "coastline") && !way.tags.is("place", "island") {
             coastline_groups.push((id, way.pts.clone()));
             continue;
@@ -197,7 +256,8 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
         } else if rel.tags.is("type", "restriction") {
             let mut from_way_id: Option<WayID> = None;
             let mut via_node_id: Option<NodeID> = None;
-            let mut via_way_id: Option<WayID> = None;
+            // There can be several via ways, in order
+            let mut via_way_ids: Vec<WayID> = Vec::new();
             let mut to_way_id: Option<WayID> = None;
             for (role, member) in &rel.members {
                 match member {
@@ -207,7 +267,7 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                         } else if role == "to" {
                             to_way_id = Some(*w);
                         } else if role == "via" {
-                            via_way_id = Some(*w);
+                            via_way_ids.push(*w);
                         }
                     }
                     OsmID::Node(n) => {
@@ -220,24 +280,29 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                     }
                 }
             }
-            if let Some(restriction) = rel.tags.get("restriction") {
-                if let Some(rt) = RestrictionType::new(restriction) {
-                    if let (Some(from), Some(via), Some(to)) = (from_way_id, via_node_id, to_way_id)
-                    {
-                        out.simple_turn_restrictions.push((rt, from, via, to));
-                    } else if let (Some(from), Some(via), Some(to)) =
-                        (from_way_id, via_way_id, to_way_id)
-                    {
-                        if rt == RestrictionType::BanTurns {
-                            out.complicated_turn_restrictions.push((id, from, via, to));
-                        } else {
-                            timer.warn(format!(
-                                "Weird complicated turn restriction \"{}\" from {} to {} via {}: \
-                                 {}",
-                                restriction, from, to, via, id
-                            ));
-                        }
-                    }
+            let (rt, conditions) = match RestrictionConditions::parse(&rel.tags) {
+                Ok(Some(pair)) => pair,
+                Ok(None) => {
+                    continue;
+                }
+                Err(err) => {
+                    timer.warn(format!("Skipping turn restriction {}: {}", id, err));
+                    continue;
+                }
+            };
+            if let (Some(from), Some(via), Some(to)) = (from_way_id, via_node_id, to_way_id) {
+                out.simple_turn_restrictions
+                    .push((rt, from, via, to, conditions));
+            } else if let (Some(from), Some(to)) = (from_way_id, to_way_id) {
+                if !via_way_ids.is_empty() {
+                    out.complicated_turn_restrictions.push((
+                        id,
+                        rt,
+                        from,
+                        via_way_ids,
+                        to,
+                        conditions,
+                    ));
                 }
             }
         } else if is_bldg(&rel.tags) {
@@ -377,6 +442,20 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
     out
 }
 
+/// Reads the .osm or .osm.pbf input, using the map's GPS bounds.
+pub(crate) fn read_input(map: &RawMap, opts: &Options, timer: &mut Timer) -> Document {
+    if opts.osm_input.ends_with(".pbf") {
+        // Large extracts are clipped while reading, instead of keeping everything in memory
+        let clip = opts
+            .clip
+            .as_ref()
+            .map(|path| LonLat::read_osmosis_polygon(path).unwrap());
+        crate::pbf::read(&opts.osm_input, &map.gps_bounds, clip, timer).unwrap()
+    } else {
+        crate::reader::read(&opts.osm_input, &map.gps_bounds, timer).unwrap()
+    }
+}
+
 fn is_road(tags: &mut Tags, opts: &Options) -> bool {
     if tags.is("area", "yes") {
         return false;
@@ -498,6 +577,10 @@ fn is_road(tags: &mut Tags, opts: &Options) -> bool {
     true
 }
 
+fn is_separate_footway(tags: &Tags) -> bool {
+    tags.is(osm::HIGHWAY, "footway") && tags.is_any("footway", vec!["sidewalk", "crossing"])
+}
+
 fn is_bldg(tags: &Tags) -> bool {
     // Sorry, the towers at Gasworks don't count. :)
     tags.contains_key("building") && !tags.contains_key("abandoned:man_made")
@@ -9,8 +9,10 @@ use serde::{Deserialize, Serialize};
 
 mod clip;
 mod extract;
+pub mod osc;
 pub mod osm_geom;
 mod parking;
+pub mod pbf;
 pub mod reader;
 mod snappy;
 mod split_ways;
@@ -71,6 +73,15 @@ pub enum PrivateOffstreetParking {
 }
 
 pub fn convert(opts: Options, timer: &mut abstutil::Timer) -> RawMap {
+    convert_with_changes(opts, Vec::new(), timer)
+}
+
+/// Like `convert`, but first applies some OsmChanges to the input, in order.
+pub fn convert_with_changes(
+    opts: Options,
+    changes: Vec<osc::OsmChange>,
+    timer: &mut abstutil::Timer,
+) -> RawMap {
     let mut map = RawMap::blank(opts.name.clone());
     if let Some(ref path) = opts.clip {
         let pts = LonLat::read_osmosis_polygon(path).unwrap();
@@ -79,7 +90,13 @@ pub fn convert(opts: Options, timer: &mut abstutil::Timer) -> RawMap {
         map.gps_bounds = gps_bounds;
     }
 
-    let extract = extract::extract_osm(&mut map, &opts, timer);
+    let mut doc = extract::read_input(&map, &opts, timer);
+    for change in changes {
+        change.apply(&mut doc, timer);
+    }
+    let mut extract = extract::extract_osm(&mut map, doc, &opts, timer);
+    snappy::snap_sidewalks(&mut extract, !opts.map_config.inferred_sidewalks, timer);
+    let separate_cycleways = std::mem::replace(&mut extract.separate_cycleways, Vec::new());
     let (amenities, pt_to_road) = split_ways::split_up_roads(&mut map, extract, timer);
     clip::clip_map(&mut map, timer);
 
@@ -110,9 +127,9 @@ pub fn convert(opts: Options, timer: &mut abstutil::Timer) -> RawMap {
         use_elevation(&mut map, path, timer);
     }
 
-    snappy::snap_cycleways(&mut map, timer);
-
     map.config = opts.map_config;
+    snappy::snap_cycleways(&mut map, separate_cycleways, timer);
+
     map
 }
 
@@ -0,0 +1,206 @@
+//! Applies OsmChange (.osc) files, so local fixes and upstream OSM edits can be staged without
+//! waiting for a new extract.
+//!
+//! The changes are applied to the original OSM input, not to the saved RawMap, and then the whole
+//! conversion runs again. That way, clipping, parking, elevation, turn restrictions, bus routes,
+//! and anything tagged on nodes are handled exactly like a normal import. Each run starts from the
+//! original input again, so pass every change to stage, in order. Hand edits made in `map_editor`
+//! should be exported to an .osc first.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use abstutil::{prettyprint_usize, retain_btreemap, slurp_file, Tags, Timer};
+use geom::LonLat;
+use map_model::osm::{NodeID, OsmID, RelationID, WayID};
+use map_model::raw::RawMap;
+
+use crate::reader::{read_tags, Document, Node, Relation, Way};
+use crate::Options;
+
+/// Everything described by an .osc file. Creations and modifications are treated the same.
+pub struct OsmChange {
+    pub upsert_nodes: BTreeMap<NodeID, (LonLat, Tags)>,
+    pub upsert_ways: BTreeMap<WayID, (Vec<NodeID>, Tags)>,
+    /// Role, member
+    pub upsert_relations: BTreeMap<RelationID, (Vec<(String, OsmID)>, Tags)>,
+    pub delete_nodes: BTreeSet<NodeID>,
+    pub delete_ways: BTreeSet<WayID>,
+    pub delete_relations: BTreeSet<RelationID>,
+}
+
+impl OsmChange {
+    pub fn read(path: &str) -> Result<OsmChange, Box<dyn Error>> {
+        let bytes = slurp_file(path)?;
+        let raw_string = std::str::from_utf8(&bytes)?;
+        let tree = roxmltree::Document::parse(raw_string)?;
+
+        let mut change = OsmChange {
+            upsert_nodes: BTreeMap::new(),
+            upsert_ways: BTreeMap::new(),
+            upsert_relations: BTreeMap::new(),
+            delete_nodes: BTreeSet::new(),
+            delete_ways: BTreeSet::new(),
+            delete_relations: BTreeSet::new(),
+        };
+        for action in tree.root_element().children() {
+            let delete = match action.tag_name().name() {
+                "create" | "modify" => false,
+                "delete" => true,
+                _ => continue,
+            };
+            for obj in action.children() {
+                if !obj.is_element() {
+                    continue;
+                }
+                let id = obj
+                    .attribute("id")
+                    .ok_or("object missing an id")?
+                    .parse::<i64>()?;
+                match (obj.tag_name().name(), delete) {
+                    ("node", false) => {
+                        let pt = LonLat::new(
+                            obj.attribute("lon").ok_or("node missing lon")?.parse()?,
+                            obj.attribute("lat").ok_or("node missing lat")?.parse()?,
+                        );
+                        change.upsert_nodes.insert(NodeID(id), (pt, read_tags(obj)));
+                    }
+                    ("node", true) => {
+                        change.delete_nodes.insert(NodeID(id));
+                    }
+                    ("way", false) => {
+                        let mut nodes = Vec::new();
+                        for child in obj.children() {
+                            if child.tag_name().name() == "nd" {
+                                nodes.push(NodeID(
+                                    child.attribute("ref").ok_or("nd missing ref")?.parse()?,
+                                ));
+                            }
+                        }
+                        change
+                            .upsert_ways
+                            .insert(WayID(id), (nodes, read_tags(obj)));
+                    }
+                    ("way", true) => {
+                        change.delete_ways.insert(WayID(id));
+                    }
+                    ("relation", false) => {
+                        let mut members = Vec::new();
+                        for child in obj.children() {
+                            if child.tag_name().name() != "member" {
+                                continue;
+                            }
+                            let member_id = child
+                                .attribute("ref")
+                                .ok_or("member missing ref")?
+                                .parse::<i64>()?;
+                            let member = match child.attribute("type") {
+                                Some("node") => OsmID::Node(NodeID(member_id)),
+                                Some("way") => OsmID::Way(WayID(member_id)),
+                                Some("relation") => OsmID::Relation(RelationID(member_id)),
+                                _ => continue,
+                            };
+                            let role = child.attribute("role").unwrap_or("").to_string();
+                            members.push((role, member));
+                        }
+                        change
+                            .upsert_relations
+                            .insert(RelationID(id), (members, read_tags(obj)));
+                    }
+                    ("relation", true) => {
+                        change.delete_relations.insert(RelationID(id));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(change)
+    }
+
+    /// Modifies OSM input. Like reading the input in the first place, references to missing
+    /// objects are filtered out, so deleting a node removes it from every way.
+    pub fn apply(&self, doc: &mut Document, timer: &mut Timer) {
+        timer.note(format!(
+            "Applying {} nodes, {} ways, {} relations created or modified, and {} nodes, {} ways, \
+             {} relations deleted",
+            prettyprint_usize(self.upsert_nodes.len()),
+            prettyprint_usize(self.upsert_ways.len()),
+            prettyprint_usize(self.upsert_relations.len()),
+            prettyprint_usize(self.delete_nodes.len()),
+            prettyprint_usize(self.delete_ways.len()),
+            prettyprint_usize(self.delete_relations.len())
+        ));
+
+        for id in &self.delete_nodes {
+            doc.nodes.remove(id);
+        }
+        for (id, (pt, tags)) in &self.upsert_nodes {
+            doc.nodes.insert(
+                *id,
+                Node {
+                    pt: pt.to_pt(&doc.gps_bounds),
+                    tags: tags.clone(),
+                },
+            );
+        }
+
+        for id in &self.delete_ways {
+            doc.ways.remove(id);
+        }
+        for (id, (nodes, tags)) in &self.upsert_ways {
+            doc.ways.insert(
+                *id,
+                Way {
+                    nodes: nodes.clone(),
+                    pts: Vec::new(),
+                    tags: tags.clone(),
+                },
+            );
+        }
+        // Nodes might've moved or disappeared, so recalculate the geometry of every way
+        let nodes = &doc.nodes;
+        for way in doc.ways.values_mut() {
+            way.nodes.retain(|n| nodes.contains_key(n));
+            way.pts = way.nodes.iter().map(|n| nodes[n].pt).collect();
+        }
+        retain_btreemap(&mut doc.ways, |_, way| !way.nodes.is_empty());
+
+        for id in &self.delete_relations {
+            doc.relations.remove(id);
+        }
+        for (id, (members, tags)) in &self.upsert_relations {
+            doc.relations.insert(
+                *id,
+                Relation {
+                    tags: tags.clone(),
+                    members: members.clone(),
+                },
+            );
+        }
+        // Relations can refer to each other in any order, so only check members once they all
+        // exist
+        let ids: BTreeSet<RelationID> = doc.relations.keys().cloned().collect();
+        let ways = &doc.ways;
+        for rel in doc.relations.values_mut() {
+            rel.members.retain(|(_, member)| match member {
+                OsmID::Node(n) => nodes.contains_key(n),
+                OsmID::Way(w) => ways.contains_key(w),
+                OsmID::Relation(r) => ids.contains(r),
+            });
+        }
+    }
+}
+
+/// Apply .osc files, in order, to the original OSM input, then convert it into a RawMap again. The
+/// options should be the same ones used to originally import the map.
+pub fn apply_osc(
+    paths: &[String],
+    opts: Options,
+    timer: &mut Timer,
+) -> Result<RawMap, Box<dyn Error>> {
+    let mut changes = Vec::new();
+    for path in paths {
+        changes.push(OsmChange::read(path)?);
+    }
+    Ok(crate::convert_with_changes(opts, changes, timer))
+}
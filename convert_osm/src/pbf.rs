@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use osmpbf::{Element, ElementReader, RelMemberType};
+
+use abstutil::{prettyprint_usize, Tags, Timer};
+use geom::{GPSBounds, LonLat, Polygon, Ring};
+use map_model::osm::{NodeID, OsmID, RelationID, WayID};
+
+use crate::reader::{insert_relations, is_useless_tag, Document, Node, Way};
+
+/// Reads a .osm.pbf file. If a clipping polygon is provided, only objects inside of it are kept.
+/// Like osmconvert's --complete-ways, all nodes of a way partly inside the boundary are kept.
+pub fn read(
+    path: &str,
+    input_gps_bounds: &GPSBounds,
+    clip: Option<Vec<LonLat>>,
+    timer: &mut Timer,
+) -> Result<Document, Box<dyn Error>> {
+    let clipping = clip.is_some();
+    let scraped = scrape(path, clip, timer)?;
+    Ok(scraped.into_document(input_gps_bounds, clipping, timer))
+}
+
+/// Clips a .osm.pbf file to an osmosis boundary polygon, writing the result as a .osm file.
+/// Reading a large extract takes a while, so importers do this once per map and reuse the result.
+pub fn clip(
+    input: &str,
+    clipping_polygon: &str,
+    output: &str,
+    timer: &mut Timer,
+) -> Result<(), Box<dyn Error>> {
+    let boundary = LonLat::read_osmosis_polygon(clipping_polygon)?;
+    let bounds = GPSBounds::from(boundary.clone());
+    let scraped = scrape(input, Some(boundary), timer)?;
+    // Write the original coordinates, not ones converted to map-space and back
+    let lon_lats: BTreeMap<NodeID, LonLat> = scraped
+        .nodes
+        .iter()
+        .map(|(id, (pt, _))| (*id, *pt))
+        .collect();
+    let doc = scraped.into_document(&bounds, true, timer);
+
+    timer.start(format!("write {}", output));
+    let mut f = BufWriter::new(File::create(output)?);
+    writeln!(f, r#"<?xml version='1.0' encoding='UTF-8'?>"#)?;
+    writeln!(f, r#"<osm version="0.6" generator="abst">"#)?;
+    writeln!(
+        f,
+        r#"  <bounds minlon="{}" maxlon="{}" minlat="{}" maxlat="{}"/>"#,
+        bounds.min_lon, bounds.max_lon, bounds.min_lat, bounds.max_lat
+    )?;
+    for (id, node) in &doc.nodes {
+        let pt = lon_lats[id];
+        write!(
+            f,
+            r#"  <node id="{}" lon="{}" lat="{}""#,
+            id.0,
+            pt.x(),
+            pt.y()
+        )?;
+        if node.tags.is_empty() {
+            writeln!(f, "/>")?;
+        } else {
+            writeln!(f, ">")?;
+            write_tags(&mut f, &node.tags)?;
+            writeln!(f, "  </node>")?;
+        }
+    }
+    for (id, way) in &doc.ways {
+        writeln!(f, r#"  <way id="{}">"#, id.0)?;
+        for n in &way.nodes {
+            writeln!(f, r#"    <nd ref="{}"/>"#, n.0)?;
+        }
+        write_tags(&mut f, &way.tags)?;
+        writeln!(f, "  </way>")?;
+    }
+    for (id, rel) in &doc.relations {
+        writeln!(f, r#"  <relation id="{}">"#, id.0)?;
+        for (role, member) in &rel.members {
+            let (member_type, member_id) = match member {
+                OsmID::Node(n) => ("node", n.0),
+                OsmID::Way(w) => ("way", w.0),
+                OsmID::Relation(r) => ("relation", r.0),
+            };
+            writeln!(
+                f,
+                r#"    <member type="{}" ref="{}" role="{}"/>"#,
+                member_type,
+                member_id,
+                escape(role)
+            )?;
+        }
+        write_tags(&mut f, &rel.tags)?;
+        writeln!(f, "  </relation>")?;
+    }
+    writeln!(f, "</osm>")?;
+    timer.stop(format!("write {}", output));
+    Ok(())
+}
+
+fn write_tags<W: Write>(f: &mut W, tags: &Tags) -> std::io::Result<()> {
+    for (k, v) in tags.inner() {
+        writeln!(f, r#"    <tag k="{}" v="{}"/>"#, escape(k), escape(v))?;
+    }
+    Ok(())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Everything read from a .osm.pbf, before relations are resolved
+struct Scraped {
+    nodes: BTreeMap<NodeID, (LonLat, Tags)>,
+    ways: Vec<(WayID, Vec<NodeID>, Tags)>,
+    relations: Vec<(RelationID, Vec<(String, OsmID)>, Tags)>,
+}
+
+impl Scraped {
+    fn into_document(
+        self,
+        input_gps_bounds: &GPSBounds,
+        clipping: bool,
+        timer: &mut Timer,
+    ) -> Document {
+        let mut doc = Document {
+            gps_bounds: input_gps_bounds.clone(),
+            nodes: BTreeMap::new(),
+            ways: BTreeMap::new(),
+            relations: BTreeMap::new(),
+        };
+        if doc.gps_bounds == GPSBounds::new() {
+            timer.warn(
+                "No clipping polygon provided, so figuring out the bounds of the .pbf manually."
+                    .to_string(),
+            );
+            for (pt, _) in self.nodes.values() {
+                doc.gps_bounds.update(*pt);
+            }
+        }
+
+        timer.start("scrape objects");
+        for (id, (pt, tags)) in self.nodes {
+            doc.nodes.insert(
+                id,
+                Node {
+                    pt: pt.to_pt(&doc.gps_bounds),
+                    tags,
+                },
+            );
+        }
+        for (id, nodes, tags) in self.ways {
+            let pts = nodes.iter().map(|n| doc.nodes[n].pt).collect();
+            doc.ways.insert(id, Way { nodes, pts, tags });
+        }
+        // When clipping, most relations in a large extract won't have anything left
+        insert_relations(&mut doc, self.relations, clipping);
+        timer.stop("scrape objects");
+        timer.note(format!(
+            "Found {} nodes, {} ways, {} relations",
+            prettyprint_usize(doc.nodes.len()),
+            prettyprint_usize(doc.ways.len()),
+            prettyprint_usize(doc.relations.len())
+        ));
+        doc
+    }
+}
+
+fn scrape(
+    path: &str,
+    clip: Option<Vec<LonLat>>,
+    timer: &mut Timer,
+) -> Result<Scraped, Box<dyn Error>> {
+    let keep_nodes = if let Some(pts) = clip {
+        timer.start(format!("find objects inside the boundary in {}", path));
+        let boundary = Boundary::new(pts);
+        let mut inside = HashSet::new();
+        let mut keep = HashSet::new();
+        ElementReader::from_path(path)?.for_each(|element| match element {
+            Element::Node(node) => {
+                if boundary.contains(LonLat::new(node.lon(), node.lat())) {
+                    inside.insert(node.id());
+                }
+            }
+            Element::DenseNode(node) => {
+                if boundary.contains(LonLat::new(node.lon(), node.lat())) {
+                    inside.insert(node.id());
+                }
+            }
+            Element::Way(way) => {
+                let refs: Vec<i64> = way.refs().collect();
+                if refs.iter().any(|n| inside.contains(n)) {
+                    keep.extend(refs);
+                }
+            }
+            Element::Relation(_) => {}
+        })?;
+        keep.extend(inside);
+        timer.stop(format!("find objects inside the boundary in {}", path));
+        Some(keep)
+    } else {
+        None
+    };
+    let keep_node = |id: i64| keep_nodes.as_ref().map(|k| k.contains(&id)).unwrap_or(true);
+
+    timer.start(format!("read {}", path));
+    let mut nodes: BTreeMap<NodeID, (LonLat, Tags)> = BTreeMap::new();
+    let mut ways: Vec<(WayID, Vec<NodeID>, Tags)> = Vec::new();
+    let mut relations: Vec<(RelationID, Vec<(String, OsmID)>, Tags)> = Vec::new();
+    ElementReader::from_path(path)?.for_each(|element| match element {
+        Element::Node(node) => {
+            if keep_node(node.id()) {
+                nodes.insert(
+                    NodeID(node.id()),
+                    (LonLat::new(node.lon(), node.lat()), read_tags(node.tags())),
+                );
+            }
+        }
+        Element::DenseNode(node) => {
+            if keep_node(node.id()) {
+                nodes.insert(
+                    NodeID(node.id()),
+                    (LonLat::new(node.lon(), node.lat()), read_tags(node.tags())),
+                );
+            }
+        }
+        Element::Way(way) => {
+            // Just skip missing nodes
+            let refs: Vec<NodeID> = way
+                .refs()
+                .map(NodeID)
+                .filter(|n| nodes.contains_key(n))
+                .collect();
+            if !refs.is_empty() {
+                ways.push((WayID(way.id()), refs, read_tags(way.tags())));
+            }
+        }
+        Element::Relation(rel) => {
+            let mut members = Vec::new();
+            for member in rel.members() {
+                let id = match member.member_type {
+                    RelMemberType::Node => OsmID::Node(NodeID(member.member_id)),
+                    RelMemberType::Way => OsmID::Way(WayID(member.member_id)),
+                    RelMemberType::Relation => OsmID::Relation(RelationID(member.member_id)),
+                };
+                let role = member.role().unwrap_or("").to_string();
+                members.push((role, id));
+            }
+            relations.push((RelationID(rel.id()), members, read_tags(rel.tags())));
+        }
+    })?;
+    timer.stop(format!("read {}", path));
+
+    Ok(Scraped {
+        nodes,
+        ways,
+        relations,
+    })
+}
+
+fn read_tags<'a, I: Iterator<Item = (&'a str, &'a str)>>(iter: I) -> Tags {
+    let mut tags = Tags::new(BTreeMap::new());
+    for (k, v) in iter {
+        if !is_useless_tag(k) {
+            tags.insert(k, v);
+        }
+    }
+    tags
+}
+
+struct Boundary {
+    gps_bounds: GPSBounds,
+    polygon: Polygon,
+}
+
+impl Boundary {
+    fn new(pts: Vec<LonLat>) -> Boundary {
+        let gps_bounds = GPSBounds::from(pts.clone());
+        let polygon = Ring::must_new(gps_bounds.convert(&pts)).to_polygon();
+        Boundary {
+            gps_bounds,
+            polygon,
+        }
+    }
+
+    fn contains(&self, pt: LonLat) -> bool {
+        self.gps_bounds.contains(pt) && self.polygon.contains_pt(pt.to_pt(&self.gps_bounds))
+    }
+}
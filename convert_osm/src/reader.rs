@@ -1,13 +1,14 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 
-use abstutil::{prettyprint_usize, slurp_file, Tags, Timer};
+use abstutil::{prettyprint_usize, retain_btreemap, slurp_file, Tags, Timer};
 use geom::{GPSBounds, LonLat, Pt2D};
 use map_model::osm::{NodeID, OsmID, RelationID, WayID};
 
 // References to missing objects are just filtered out.
 // Per https://wiki.openstreetmap.org/wiki/OSM_XML#Certainties_and_Uncertainties, we assume
-// elements come in order: nodes, ways, then relations.
+// elements come in order: nodes, ways, then relations. Relations may refer to each other in any
+// order.
 //
 // TODO Filter out visible=false
 // TODO NodeID, WayID, RelationID are nice. Plumb forward through map_model.
@@ -58,6 +59,8 @@ pub fn read(
     };
 
     timer.start("scrape objects");
+    let mut relations = Vec::new();
+    let mut relation_ids = BTreeSet::new();
     for obj in tree.descendants() {
         if !obj.is_element() {
             continue;
@@ -124,49 +127,29 @@ pub fn read(
             }
             "relation" => {
                 let id = RelationID(obj.attribute("id").unwrap().parse::<i64>().unwrap());
-                if doc.relations.contains_key(&id) {
+                if !relation_ids.insert(id) {
                     return Err(format!("Duplicate {}, your .osm is corrupt", id).into());
                 }
                 let tags = read_tags(obj);
                 let mut members = Vec::new();
                 for child in obj.children() {
                     if child.tag_name().name() == "member" {
+                        let member_id = child.attribute("ref").unwrap().parse::<i64>().unwrap();
                         let member = match child.attribute("type").unwrap() {
-                            "node" => {
-                                let n =
-                                    NodeID(child.attribute("ref").unwrap().parse::<i64>().unwrap());
-                                if !doc.nodes.contains_key(&n) {
-                                    continue;
-                                }
-                                OsmID::Node(n)
-                            }
-                            "way" => {
-                                let w =
-                                    WayID(child.attribute("ref").unwrap().parse::<i64>().unwrap());
-                                if !doc.ways.contains_key(&w) {
-                                    continue;
-                                }
-                                OsmID::Way(w)
-                            }
-                            "relation" => {
-                                let r = RelationID(
-                                    child.attribute("ref").unwrap().parse::<i64>().unwrap(),
-                                );
-                                if !doc.relations.contains_key(&r) {
-                                    continue;
-                                }
-                                OsmID::Relation(r)
-                            }
+                            "node" => OsmID::Node(NodeID(member_id)),
+                            "way" => OsmID::Way(WayID(member_id)),
+                            "relation" => OsmID::Relation(RelationID(member_id)),
                             _ => continue,
                         };
                         members.push((child.attribute("role").unwrap().to_string(), member));
                     }
                 }
-                doc.relations.insert(id, Relation { tags, members });
+                relations.push((id, members, tags));
             }
             _ => {}
         }
     }
+    insert_relations(&mut doc, relations, false);
     timer.stop("scrape objects");
     timer.note(format!(
         "Found {} nodes, {} ways, {} relations",
@@ -178,13 +161,12 @@ pub fn read(
     Ok(doc)
 }
 
-fn read_tags(obj: roxmltree::Node) -> Tags {
+pub(crate) fn read_tags(obj: roxmltree::Node) -> Tags {
     let mut tags = Tags::new(BTreeMap::new());
     for child in obj.children() {
         if child.tag_name().name() == "tag" {
             let key = child.attribute("k").unwrap();
-            // Filter out really useless data
-            if key.starts_with("tiger:") || key.starts_with("old_name:") {
+            if is_useless_tag(key) {
                 continue;
             }
             tags.insert(key, child.attribute("v").unwrap());
@@ -193,6 +175,47 @@ fn read_tags(obj: roxmltree::Node) -> Tags {
     tags
 }
 
+/// Adds relations to a document that already has all of its nodes and ways. Members that don't
+/// exist are filtered out. Relations can refer to relations later in the input, so that's only
+/// checked once they've all been read. If `drop_empty` is true, relations left without members
+/// are skipped, and so are references to them.
+pub(crate) fn insert_relations(
+    doc: &mut Document,
+    relations: Vec<(RelationID, Vec<(String, OsmID)>, Tags)>,
+    drop_empty: bool,
+) {
+    let mut relations: BTreeMap<RelationID, (Vec<(String, OsmID)>, Tags)> = relations
+        .into_iter()
+        .map(|(id, members, tags)| (id, (members, tags)))
+        .collect();
+    // Dropping one relation might leave another one empty, so repeat until nothing changes
+    loop {
+        let ids: BTreeSet<RelationID> = relations.keys().cloned().collect();
+        for (members, _) in relations.values_mut() {
+            members.retain(|(_, member)| match member {
+                OsmID::Node(n) => doc.nodes.contains_key(n),
+                OsmID::Way(w) => doc.ways.contains_key(w),
+                OsmID::Relation(r) => ids.contains(r),
+            });
+        }
+        let before = relations.len();
+        if drop_empty {
+            retain_btreemap(&mut relations, |_, (members, _)| !members.is_empty());
+        }
+        if relations.len() == before {
+            break;
+        }
+    }
+    for (id, (members, tags)) in relations {
+        doc.relations.insert(id, Relation { tags, members });
+    }
+}
+
+/// Filter out really useless data
+pub(crate) fn is_useless_tag(key: &str) -> bool {
+    key.starts_with("tiger:") || key.starts_with("old_name:")
+}
+
 fn scrape_bounds(doc: &roxmltree::Document) -> GPSBounds {
     let mut b = GPSBounds::new();
     for obj in doc.descendants() {
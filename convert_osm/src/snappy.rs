@@ -1,49 +1,274 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 use abstutil::MultiMap;
-use abstutil::Timer;
-use geom::{Distance, FindClosest, Line, PolyLine};
-use kml::{ExtraShape, ExtraShapes};
+use abstutil::{Counter, Tags, Timer};
+use geom::{Bounds, Distance, FindClosest, HashablePt2D, Line, PolyLine, Pt2D};
 use map_model::osm::WayID;
-use map_model::raw::{OriginalRoad, RawMap};
+use map_model::raw::{OriginalRoad, RawMap, RawRoad};
 use map_model::{osm, Direction};
 
-/// Attempt to snap separately mapped cycleways to main roads. Emit extra KML files to debug later.
-pub fn snap_cycleways(map: &RawMap, timer: &mut Timer) {
-    if map.name.city == "oneshot" {
+use crate::extract::OsmExtract;
+
+/// Separately mapped sidewalks (`footway=sidewalk`) are matched to the road they run alongside,
+/// which then gets a sidewalk on that side. Separately mapped crossings (`footway=crossing`) are
+/// matched to the road they cross, remembering if they're marked.
+///
+/// If footways are imported as their own roads (when sidewalks aren't inferred), the matched
+/// sidewalks and crossings are removed, since the parent road's sidewalks and crosswalks replace
+/// them. Other footways connected to them are reattached to the parent road. Anything that
+/// couldn't be matched is kept as a normal footway.
+pub fn snap_sidewalks(input: &mut OsmExtract, keep_footways: bool, timer: &mut Timer) {
+    let footways = std::mem::replace(&mut input.separate_footways, Vec::new());
+    if footways.is_empty() {
         return;
     }
 
-    // TODO Hack! Fix upstream problems.
-    if map.name.city == "xian" {
+    // Index the centers of the roads that could have sidewalks
+    let mut road_lines: BTreeMap<usize, PolyLine> = BTreeMap::new();
+    let mut road_pts: HashMap<HashablePt2D, usize> = HashMap::new();
+    for (idx, (_, r)) in input.roads.iter().enumerate() {
+        if r.is_light_rail()
+            || r.is_footway()
+            || r.osm_tags
+                .is_any(osm::HIGHWAY, vec!["motorway", "motorway_link"])
+        {
+            continue;
+        }
+        if let Ok(pl) = PolyLine::new(r.center_points.clone()) {
+            road_lines.insert(idx, pl);
+        }
+        for pt in &r.center_points {
+            road_pts.insert(pt.to_hashable(), idx);
+        }
+    }
+    if road_lines.is_empty() {
+        input
+            .roads
+            .extend(footways.into_iter().filter(|_| keep_footways));
         return;
     }
+    let mut closest: FindClosest<usize> = FindClosest::new(&Bounds::from(
+        &road_lines
+            .values()
+            .flat_map(|pl| pl.points().clone())
+            .collect(),
+    ));
+    for (idx, pl) in &road_lines {
+        closest.add(*idx, pl.points());
+    }
 
-    // TODO The output here is nondeterministic and I haven't figured out why. Instead of spurious
-    // data diffs, just totally disable this experiment for now. Will fix when this becomes active
-    // work again.
-    if true {
-        return;
+    // (road index, is the sidewalk on the right side of the road)
+    let mut sidewalks_per_road: BTreeMap<usize, (bool, bool)> = BTreeMap::new();
+    // For each footway that's replaced, the road it belongs to
+    let mut replaced: Vec<(WayID, Vec<Pt2D>, usize)> = Vec::new();
+    let mut unmatched = Vec::new();
+    timer.start_iter("snap separate sidewalks and crossings", footways.len());
+    for (id, footway) in footways {
+        timer.next();
+        if footway.osm_tags.is("footway", "crossing") {
+            let marked = !(footway.osm_tags.is_any("crossing", vec!["unmarked", "no"])
+                || footway.osm_tags.is("crossing:markings", "no"));
+            let mut parent = None;
+            for pt in &footway.center_points {
+                if let Some(idx) = road_pts.get(&pt.to_hashable()) {
+                    input.crossings.insert(pt.to_hashable(), marked);
+                    parent = Some(*idx);
+                }
+            }
+            if let Some(idx) = parent {
+                replaced.push((id, footway.center_points.clone(), idx));
+            } else {
+                unmatched.push((id, footway));
+            }
+            continue;
+        }
+
+        let matches = match_sidewalk(&footway.center_points, &road_lines, &closest);
+        if let Some(((idx, _), _)) = matches.iter().max_by_key(|(_, votes)| *votes) {
+            replaced.push((id, footway.center_points.clone(), *idx));
+            for ((idx, right), _) in matches {
+                let sides = sidewalks_per_road.entry(idx).or_insert((false, false));
+                if right {
+                    sides.1 = true;
+                } else {
+                    sides.0 = true;
+                }
+            }
+        } else {
+            unmatched.push((id, footway));
+        }
     }
+    timer.note(format!(
+        "{} separate sidewalks and crossings snapped to roads, {} couldn't be",
+        replaced.len(),
+        unmatched.len()
+    ));
 
-    let mut cycleways = BTreeMap::new();
-    for shape in abstutil::read_binary::<ExtraShapes>(
-        abstutil::path(format!("input/{}/footways.bin", map.name.city)),
-        timer,
-    )
-    .shapes
+    for (idx, (left, right)) in sidewalks_per_road {
+        let tags = &mut input.roads[idx].1.osm_tags;
+        if let Some(value) = snapped_sidewalks(tags, left, right) {
+            tags.remove(osm::INFERRED_SIDEWALKS);
+            tags.insert(osm::SIDEWALK, value);
+        }
+    }
+
+    if keep_footways {
+        reattach_footways(input, replaced, unmatched, &road_lines);
+    }
+}
+
+/// Decides the `sidewalk` tag of a road, after separate sidewalks were matched to its left and/or
+/// right side. None means the road's own tags win.
+fn snapped_sidewalks(tags: &Tags, left: bool, right: bool) -> Option<&'static str> {
+    // Explicitly tagged sidewalks win
+    if tags.contains_key(osm::SIDEWALK)
+        && !tags.is(osm::SIDEWALK, "separate")
+        && !tags.is(osm::INFERRED_SIDEWALKS, "true")
     {
-        // Just cycleways for now. This same general strategy should later work for sidewalks,
-        // tramways, and blockface parking too.
-        if shape.attributes.get("highway") == Some(&"cycleway".to_string()) {
-            cycleways.insert(
-                WayID(shape.attributes[osm::OSM_WAY_ID].parse().unwrap()),
-                shape,
-            );
+        return None;
+    }
+    // A side tagged as separately mapped still has a sidewalk, even if it couldn't be matched.
+    let separate = |side: &str| {
+        tags.is(osm::SIDEWALK, "separate")
+            || tags.is("sidewalk:both", "separate")
+            || tags.is(&format!("sidewalk:{}", side), "separate")
+    };
+    match (left || separate("left"), right || separate("right")) {
+        (true, true) => Some("both"),
+        (true, false) => Some("left"),
+        (false, true) => Some("right"),
+        (false, false) => None,
+    }
+}
+
+/// Returns every (road index, right side) that a sidewalk runs alongside, with the number of
+/// sample points that matched.
+fn match_sidewalk(
+    pts: &Vec<Pt2D>,
+    road_lines: &BTreeMap<usize, PolyLine>,
+    closest: &FindClosest<usize>,
+) -> Vec<((usize, bool), usize)> {
+    let pl = match PolyLine::new(pts.clone()) {
+        Ok(pl) => pl,
+        Err(_) => {
+            return Vec::new();
+        }
+    };
+
+    let step_size = Distance::meters(5.0);
+    // Sidewalks may be mapped some distance away from the center of wide roads
+    let max_dist = Distance::meters(20.0);
+    // How many degrees difference to consider parallel ways
+    let parallel_threshold = 30.0;
+
+    let mut votes = Counter::new();
+    let mut num_samples = 0;
+    let mut dist = Distance::ZERO;
+    loop {
+        num_samples += 1;
+        let (pt, sidewalk_angle) = pl.must_dist_along(dist);
+        let perp_line = Line::must_new(
+            pt.project_away(max_dist, sidewalk_angle.rotate_degs(90.0)),
+            pt.project_away(max_dist, sidewalk_angle.rotate_degs(-90.0)),
+        )
+        .to_polyline();
+        let mut best: Option<((usize, bool), Distance)> = None;
+        for (idx, _, _) in closest.all_close_pts(pt, max_dist) {
+            if let Some((hit, road_angle)) = road_lines[&idx].intersection(&perp_line) {
+                // Sidewalks may be mapped in either direction
+                if !road_angle.approx_eq(sidewalk_angle, parallel_threshold)
+                    && !road_angle.approx_eq(sidewalk_angle.opposite(), parallel_threshold)
+                {
+                    continue;
+                }
+                let right = hit
+                    .angle_to(pt)
+                    .approx_eq(road_angle.rotate_degs(90.0), 90.0);
+                let dist_away = hit.dist_to(pt);
+                if best.map(|(_, d)| dist_away < d).unwrap_or(true) {
+                    best = Some(((idx, right), dist_away));
+                }
+            }
+        }
+        if let Some((key, _)) = best {
+            votes.inc(key);
+        }
+
+        if dist == pl.length() {
+            break;
+        }
+        dist += step_size;
+        dist = dist.min(pl.length());
+    }
+
+    // A sidewalk may wrap around a corner and follow a few roads. Require a decent stretch
+    // alongside each one.
+    votes
+        .consume()
+        .into_iter()
+        .filter(|(_, cnt)| *cnt >= 3 || 2 * *cnt >= num_samples)
+        .collect()
+}
+
+/// Remove sidewalks and crossings replaced by their parent road. Other footways connected to them
+/// are moved to the closest point on the parent road.
+fn reattach_footways(
+    input: &mut OsmExtract,
+    replaced: Vec<(WayID, Vec<Pt2D>, usize)>,
+    unmatched: Vec<(WayID, RawRoad)>,
+    road_lines: &BTreeMap<usize, PolyLine>,
+) {
+    input.roads.extend(unmatched);
+
+    // Where each point of a removed footway should go
+    let mut moved_pts: HashMap<HashablePt2D, (Pt2D, usize)> = HashMap::new();
+    for (_, pts, parent) in replaced {
+        for pt in pts {
+            let new_pt = road_lines[&parent].project_pt(pt);
+            moved_pts.insert(pt.to_hashable(), (new_pt, parent));
+        }
+    }
+
+    let mut new_road_pts: Vec<(usize, Pt2D)> = Vec::new();
+    for (_, r) in input.roads.iter_mut() {
+        if !r.is_footway() {
+            continue;
+        }
+        for pt in r.center_points.iter_mut() {
+            if let Some((new_pt, parent)) = moved_pts.get(&pt.to_hashable()) {
+                if let Some(id) = input.osm_node_ids.get(&pt.to_hashable()).cloned() {
+                    input.osm_node_ids.insert(new_pt.to_hashable(), id);
+                }
+                *pt = *new_pt;
+                new_road_pts.push((*parent, *new_pt));
+            }
         }
     }
 
-    let mut road_edges: HashMap<(OriginalRoad, Direction), PolyLine> = HashMap::new();
+    // The parent roads need the new points too, so they're split there
+    for (idx, pt) in new_road_pts {
+        let pts = &mut input.roads[idx].1.center_points;
+        if pts.contains(&pt) {
+            continue;
+        }
+        if let Some(insert_at) = (0..pts.len() - 1)
+            .filter_map(|i| {
+                Line::new(pts[i], pts[i + 1])
+                    .map(|l| (i, l.to_polyline().project_pt(pt).dist_to(pt)))
+            })
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(i, _)| i + 1)
+        {
+            pts.insert(insert_at, pt);
+        }
+    }
+}
+
+/// Attempt to snap separately mapped cycleways to main roads. Emit extra KML files to debug later.
+/// Separately mapped cycle tracks (`highway=cycleway`) running alongside a road are merged into
+/// that road as protected bike lanes.
+pub fn snap_cycleways(map: &mut RawMap, cycleways: Vec<(WayID, RawRoad)>, timer: &mut Timer) {
+    let mut road_edges: BTreeMap<(OriginalRoad, Direction), PolyLine> = BTreeMap::new();
     for (id, r) in &map.roads {
         if r.is_light_rail() || r.is_footway() || r.is_service() {
             continue;
@@ -58,70 +283,57 @@ pub fn snap_cycleways(map: &RawMap, timer: &mut Timer) {
             pl.must_shift_left(total_width / 2.0),
         );
     }
+    if road_edges.is_empty() {
+        return;
+    }
 
-    let matches = v1(map, &cycleways, &road_edges);
+    let cycleways: BTreeMap<WayID, RawRoad> = cycleways.into_iter().collect();
+    let matches = v1(map, &cycleways, &road_edges, timer);
     // TODO A v2 idea: just look for cycleways strictly overlapping a thick road polygon
-    dump_output(map, &cycleways, &road_edges, matches);
-}
 
-fn dump_output(
-    map: &RawMap,
-    cycleways: &BTreeMap<WayID, ExtraShape>,
-    road_edges: &HashMap<(OriginalRoad, Direction), PolyLine>,
-    matches: MultiMap<(OriginalRoad, Direction), WayID>,
-) {
-    let mut separate_cycleways = ExtraShapes { shapes: Vec::new() };
-    let mut snapped_cycleways = ExtraShapes { shapes: Vec::new() };
-
-    let mut used_cycleways = HashSet::new();
+    let mut snapped = HashSet::new();
     for ((r, dir), ids) in matches.consume() {
-        let mut attributes = BTreeMap::new();
-        used_cycleways.extend(ids.clone());
-        attributes.insert(
-            "cycleways".to_string(),
-            ids.into_iter()
-                .map(|x| x.to_string())
-                .collect::<Vec<_>>()
-                .join(", "),
-        );
-        snapped_cycleways.shapes.push(ExtraShape {
-            points: map.gps_bounds.convert_back(road_edges[&(r, dir)].points()),
-            attributes,
-        });
-    }
-
-    for (id, shape) in cycleways {
-        if !used_cycleways.contains(id) {
-            separate_cycleways.shapes.push(shape.clone());
-        }
-    }
-
-    abstutil::write_binary(
-        abstutil::path(format!(
-            "input/{}/{}_separate_cycleways.bin",
-            map.name.city, map.name.map
-        )),
-        &separate_cycleways,
-    );
-    abstutil::write_binary(
-        abstutil::path(format!(
-            "input/{}/{}_snapped_cycleways.bin",
-            map.name.city, map.name.map
-        )),
-        &snapped_cycleways,
-    );
+        let side = if dir == Direction::Fwd {
+            "cycleway:right"
+        } else {
+            "cycleway:left"
+        };
+        let tags = &mut map.roads.get_mut(&r).unwrap().osm_tags;
+        // Bike lanes already tagged on the road win
+        let existing = vec!["lane", "track", "opposite_lane", "opposite_track"];
+        if tags.is_any("cycleway", existing.clone())
+            || tags.is_any("cycleway:both", existing.clone())
+            || tags.is_any(side, existing)
+        {
+            continue;
+        }
+        tags.insert(side, "track");
+        // Cycleways are two-way by default
+        if ids
+            .iter()
+            .any(|id| !cycleways[id].osm_tags.is_any("oneway", vec!["yes", "-1"]))
+        {
+            tags.insert(format!("{}:oneway", side), "no");
+        }
+        snapped.extend(ids);
+    }
+    timer.note(format!(
+        "{} of {} separate cycleways snapped to roads",
+        snapped.len(),
+        cycleways.len()
+    ));
 }
 
-// Walk along every cycleway, form a perpendicular line, and mark all road edges that it hits.
+// Walk along every cycleway, form a perpendicular line, and find the closest road edge parallel
+// to it.
 //
 // TODO Inverse idea: Walk every road, project perpendicular from each of the 4 corners and see what
 // cycleways hit.
-//
-// TODO Should we run this before splitting ways? Possibly less work to do.
 fn v1(
     map: &RawMap,
-    cycleways: &BTreeMap<WayID, ExtraShape>,
-    road_edges: &HashMap<(OriginalRoad, Direction), PolyLine>,
+    cycleways: &BTreeMap<WayID, RawRoad>,
+    road_edges: &BTreeMap<(OriginalRoad, Direction), PolyLine>,
+    timer: &mut Timer,
 ) -> MultiMap<(OriginalRoad, Direction), WayID> {
     let mut matches: MultiMap<(OriginalRoad, Direction), WayID> = MultiMap::new();
 
@@ -137,8 +349,13 @@ fn v1(
     let cycleway_half_width = Distance::meters(3.0);
     // How many degrees difference to consider parallel ways
     let parallel_threshold = 30.0;
+    // A cycleway that only briefly touches a road edge, like where it crosses or joins the road,
+    // isn't running alongside it.
+    let min_hits = 2;
+    timer.start_iter("snap cycleways", cycleways.len());
     for (cycleway_id, cycleway) in cycleways {
-        let pl = match PolyLine::new(map.gps_bounds.convert(&cycleway.points)) {
+        timer.next();
+        let pl = match PolyLine::new(cycleway.center_points.clone()) {
             Ok(pl) => pl,
             Err(err) => {
                 warn!("Not snapping cycleway {}: {}", cycleway_id, err);
@@ -146,24 +363,33 @@ fn v1(
             }
         };
 
+        let mut hits = Counter::new();
         let mut dist = Distance::ZERO;
         loop {
             let (pt, cycleway_angle) = pl.must_dist_along(dist);
             let perp_line = Line::must_new(
                 pt.project_away(cycleway_half_width, cycleway_angle.rotate_degs(90.0)),
                 pt.project_away(cycleway_half_width, cycleway_angle.rotate_degs(-90.0)),
-            );
-            for (id, _, _) in closest.all_close_pts(perp_line.pt1(), cycleway_half_width) {
-                if let Some((_, road_angle)) =
-                    road_edges[&id].intersection(&perp_line.to_polyline())
-                {
-                    if road_angle.approx_eq(cycleway_angle, parallel_threshold) {
-                        matches.insert(id, *cycleway_id);
-                        // TODO Just stop at the first hit?
-                        break;
+            )
+            .to_polyline();
+            // Pick the closest edge, so the result doesn't depend on the order of the candidates
+            let mut best: Option<((OriginalRoad, Direction), Distance)> = None;
+            for (id, _, _) in closest.all_close_pts(pt, cycleway_half_width * 2.0) {
+                if let Some((hit, road_angle)) = road_edges[&id].intersection(&perp_line) {
+                    // Cycleways may be mapped in either direction
+                    if road_angle.approx_eq(cycleway_angle, parallel_threshold)
+                        || road_angle.approx_eq(cycleway_angle.opposite(), parallel_threshold)
+                    {
+                        let dist_away = hit.dist_to(pt);
+                        if best.map(|(_, d)| dist_away < d).unwrap_or(true) {
+                            best = Some((id, dist_away));
+                        }
                     }
                 }
             }
+            if let Some((id, _)) = best {
+                hits.inc(id);
+            }
 
             if dist == pl.length() {
                 break;
@@ -171,7 +397,123 @@ fn v1(
             dist += step_size;
             dist = dist.min(pl.length());
         }
+
+        for (id, cnt) in hits.consume() {
+            if cnt >= min_hits {
+                matches.insert(id, *cycleway_id);
+            }
+        }
     }
 
     matches
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two roads meeting at a corner: one heading east, then one heading south. Y points down, so
+    // the right side of the first road is its south side, and the right side of the second is its
+    // west side.
+    fn matches(sidewalk: Vec<(f64, f64)>) -> Vec<((usize, bool), usize)> {
+        let mut road_lines = BTreeMap::new();
+        road_lines.insert(
+            0,
+            PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+        );
+        road_lines.insert(
+            1,
+            PolyLine::must_new(vec![Pt2D::new(100.0, 0.0), Pt2D::new(100.0, 100.0)]),
+        );
+        let mut closest = FindClosest::new(&Bounds::from(
+            &road_lines
+                .values()
+                .flat_map(|pl| pl.points().clone())
+                .collect(),
+        ));
+        for (idx, pl) in &road_lines {
+            closest.add(*idx, pl.points());
+        }
+        let pts = sidewalk.into_iter().map(|(x, y)| Pt2D::new(x, y)).collect();
+        let mut result = match_sidewalk(&pts, &road_lines, &closest);
+        result.sort();
+        result
+    }
+
+    fn sides(sidewalk: Vec<(f64, f64)>) -> Vec<(usize, bool)> {
+        matches(sidewalk).into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn match_sidewalk_sides() {
+        // Every sample point along the road votes for its right side
+        let result = matches(vec![(0.0, 8.0), (100.0, 8.0)]);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, (0, true));
+        assert!(result[0].1 >= 20);
+        // The direction the sidewalk is mapped doesn't matter
+        assert_eq!(sides(vec![(100.0, -8.0), (0.0, -8.0)]), vec![(0, false)]);
+        assert_eq!(sides(vec![(0.0, -8.0), (100.0, -8.0)]), vec![(0, false)]);
+        // A sidewalk wrapping around the corner follows both roads
+        assert_eq!(
+            sides(vec![(0.0, 8.0), (92.0, 8.0), (92.0, 100.0)]),
+            vec![(0, true), (1, true)]
+        );
+    }
+
+    #[test]
+    fn match_sidewalk_ignores() {
+        // Too far away
+        assert!(sides(vec![(0.0, 30.0), (100.0, 30.0)]).is_empty());
+        // Crossing the road, not running alongside it
+        assert!(sides(vec![(50.0, -20.0), (50.0, 20.0)]).is_empty());
+        // Only briefly alongside the road, before turning away from it
+        assert!(sides(vec![(0.0, 8.0), (6.0, 8.0), (6.0, 80.0)]).is_empty());
+    }
+
+    #[test]
+    fn separate_sidewalks_on_unmatched_side() {
+        let tags = |kv: Vec<(&str, &str)>| {
+            let mut tags = Tags::new(BTreeMap::new());
+            for (k, v) in kv {
+                tags.insert(k, v);
+            }
+            tags
+        };
+        // Both sides are separately mapped, but only one matched
+        assert_eq!(
+            snapped_sidewalks(&tags(vec![(osm::SIDEWALK, "separate")]), true, false),
+            Some("both")
+        );
+        assert_eq!(
+            snapped_sidewalks(&tags(vec![("sidewalk:both", "separate")]), false, true),
+            Some("both")
+        );
+        // Only one side is separately mapped
+        assert_eq!(
+            snapped_sidewalks(&tags(vec![("sidewalk:right", "separate")]), true, false),
+            Some("both")
+        );
+        assert_eq!(
+            snapped_sidewalks(&tags(vec![("sidewalk:left", "separate")]), true, false),
+            Some("left")
+        );
+        // Guesses are replaced
+        assert_eq!(
+            snapped_sidewalks(
+                &tags(vec![
+                    (osm::SIDEWALK, "both"),
+                    (osm::INFERRED_SIDEWALKS, "true")
+                ]),
+                false,
+                true
+            ),
+            Some("right")
+        );
+        // Explicit tags win
+        assert_eq!(
+            snapped_sidewalks(&tags(vec![(osm::SIDEWALK, "right")]), true, false),
+            None
+        );
+    }
+}
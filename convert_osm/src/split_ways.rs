@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use abstutil::{Counter, Timer};
 use geom::{Distance, HashablePt2D, Pt2D};
@@ -98,7 +98,7 @@ pub fn split_up_roads(
 
     // Resolve simple turn restrictions (via a node)
     let mut restrictions = Vec::new();
-    for (restriction, from_osm, via_osm, to_osm) in input.simple_turn_restrictions {
+    for (restriction, from_osm, via_osm, to_osm, conditions) in input.simple_turn_restrictions {
         let roads = map.roads_per_intersection(via_osm);
         // If some of the roads are missing, they were likely filtered out -- usually service
         // roads.
@@ -106,65 +106,46 @@ pub fn split_up_roads(
             roads.iter().find(|r| r.osm_way_id == from_osm),
             roads.iter().find(|r| r.osm_way_id == to_osm),
         ) {
-            restrictions.push((*from, restriction, *to));
+            restrictions.push((*from, restriction, *to, conditions));
         }
     }
-    for (from, rt, to) in restrictions {
+    for (from, rt, to, conditions) in restrictions {
         map.roads
             .get_mut(&from)
             .unwrap()
             .turn_restrictions
-            .push((rt, to));
+            .push((rt, to, conditions));
     }
 
-    // Resolve complicated turn restrictions (via a way). TODO Only handle via ways immediately
-    // connected to both roads, for now
+    // Resolve complicated turn restrictions (via one or more ways). Each via way may have been
+    // split into several roads.
     let mut complicated_restrictions = Vec::new();
-    for (rel_osm, from_osm, via_osm, to_osm) in input.complicated_turn_restrictions {
+    for (rel_osm, rt, from_osm, via_osm, to_osm, conditions) in input.complicated_turn_restrictions
+    {
         let via_candidates: Vec<OriginalRoad> = map
             .roads
             .keys()
-            .filter(|r| r.osm_way_id == via_osm)
+            .filter(|r| via_osm.contains(&r.osm_way_id))
             .cloned()
             .collect();
-        if via_candidates.len() != 1 {
-            timer.warn(format!(
-                "Couldn't resolve turn restriction from way {} to way {} via way {}. Candidate \
-                 roads for via: {:?}. See {}",
-                from_osm, to_osm, via_osm, via_candidates, rel_osm
-            ));
-            continue;
-        }
-        let via = via_candidates[0];
-
-        let maybe_from = map
-            .roads_per_intersection(via.i1)
-            .into_iter()
-            .chain(map.roads_per_intersection(via.i2).into_iter())
-            .find(|r| r.osm_way_id == from_osm);
-        let maybe_to = map
-            .roads_per_intersection(via.i1)
-            .into_iter()
-            .chain(map.roads_per_intersection(via.i2).into_iter())
-            .find(|r| r.osm_way_id == to_osm);
-        match (maybe_from, maybe_to) {
-            (Some(from), Some(to)) => {
-                complicated_restrictions.push((from, via, to));
+        match chain_via_roads(&map, from_osm, via_candidates, to_osm) {
+            Some((from, via, to)) => {
+                complicated_restrictions.push((from, rt, via, to, conditions));
             }
-            _ => {
+            None => {
                 timer.warn(format!(
-                    "Couldn't resolve turn restriction from {} to {} via {:?}",
-                    from_osm, to_osm, via
+                    "Couldn't resolve turn restriction from way {} to way {} via ways {:?}. See {}",
+                    from_osm, to_osm, via_osm, rel_osm
                 ));
             }
         }
     }
-    for (from, via, to) in complicated_restrictions {
+    for (from, rt, via, to, conditions) in complicated_restrictions {
         map.roads
             .get_mut(&from)
             .unwrap()
             .complicated_turn_restrictions
-            .push((via, to));
+            .push((rt, via, to, conditions));
     }
 
     timer.start("match traffic signals to intersections");
@@ -190,10 +171,109 @@ pub fn split_up_roads(
     }
     timer.stop("match traffic signals to intersections");
 
+    // Likewise, yield and stop signs are usually tagged on the incoming way, just before the
+    // intersection, but sometimes on the intersection itself.
+    for (pt, forwards) in input.give_way_signs {
+        tag_sign(
+            map,
+            pt,
+            forwards,
+            false,
+            (osm::GIVE_WAY_FWD, osm::GIVE_WAY_BACK),
+            &pt_to_road,
+            &pt_to_intersection,
+        );
+    }
+    for (pt, (forwards, all_way)) in input.stop_signs {
+        tag_sign(
+            map,
+            pt,
+            forwards,
+            all_way,
+            (osm::STOP_FWD, osm::STOP_BACK),
+            &pt_to_road,
+            &pt_to_intersection,
+        );
+    }
+
+    // Separately mapped crossings usually cross a road just before the intersection. Remember
+    // which end they belong to.
+    for (pt, marked) in input.crossings {
+        if let Some(r) = pt_to_road.get(&pt) {
+            let road = map.roads.get_mut(r).unwrap();
+            let pt = pt.to_pt2d();
+            let dist_to_start = pt.dist_to(road.center_points[0]);
+            let dist_to_end = pt.dist_to(*road.center_points.last().unwrap());
+            // TODO Mid-block crossings aren't modeled yet
+            if dist_to_start.min(dist_to_end) > Distance::meters(30.0) {
+                continue;
+            }
+            road.osm_tags.insert(
+                if dist_to_end < dist_to_start {
+                    osm::CROSSING_FWD
+                } else {
+                    osm::CROSSING_BACK
+                },
+                if marked { "marked" } else { "unmarked" },
+            );
+        }
+    }
+
     timer.stop("splitting up roads");
     (input.amenities, pt_to_road)
 }
 
+/// Marks the end of a road that a yield or stop sign applies to. A sign on a road's interior points
+/// toward one end. A sign on the intersection itself applies to the roads with a lower rank than
+/// the others there, or to all of them if they're the same rank or it's an all-way stop.
+fn tag_sign(
+    map: &mut RawMap,
+    pt: HashablePt2D,
+    forwards: bool,
+    all_way: bool,
+    (fwd_key, back_key): (&str, &str),
+    pt_to_road: &HashMap<HashablePt2D, OriginalRoad>,
+    pt_to_intersection: &HashMap<HashablePt2D, osm::NodeID>,
+) {
+    if let Some(r) = pt_to_road.get(&pt) {
+        map.roads
+            .get_mut(r)
+            .unwrap()
+            .osm_tags
+            .insert(if forwards { fwd_key } else { back_key }, "true");
+        return;
+    }
+    let i = match pt_to_intersection.get(&pt) {
+        Some(i) => *i,
+        None => {
+            return;
+        }
+    };
+    let rank = |r: &OriginalRoad| {
+        map.roads[r]
+            .osm_tags
+            .get(osm::HIGHWAY)
+            .map(|hwy| osm::RoadRank::from_highway(hwy))
+            .unwrap_or(osm::RoadRank::Local)
+    };
+    let roads = map.roads_per_intersection(i);
+    let highest = roads.iter().map(rank).max();
+    let lowest = roads.iter().map(rank).min();
+    let minor_roads: Vec<OriginalRoad> = roads
+        .into_iter()
+        .filter(|r| all_way || highest == lowest || Some(rank(r)) != highest)
+        .collect();
+    for r in minor_roads {
+        let tags = &mut map.roads.get_mut(&r).unwrap().osm_tags;
+        if r.i2 == i {
+            tags.insert(fwd_key, "true");
+        }
+        if r.i1 == i {
+            tags.insert(back_key, "true");
+        }
+    }
+}
+
 // TODO Consider doing this in PolyLine::new always. extend() there does this too.
 fn dedupe_angles(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     let mut result = Vec::new();
@@ -213,3 +293,46 @@ fn dedupe_angles(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     }
     result
 }
+
+/// Find the road of the `from` way that leads into the via roads, the order to traverse all of the
+/// via roads, and the road of the `to` way at the end. If any via road is missing (usually from
+/// clipping), this fails.
+fn chain_via_roads(
+    map: &RawMap,
+    from_osm: osm::WayID,
+    via_roads: Vec<OriginalRoad>,
+    to_osm: osm::WayID,
+) -> Option<(OriginalRoad, Vec<OriginalRoad>, OriginalRoad)> {
+    let mut endpoints = BTreeSet::new();
+    for r in &via_roads {
+        endpoints.insert(r.i1);
+        endpoints.insert(r.i2);
+    }
+    for start in endpoints {
+        for from in map.roads_per_intersection(start) {
+            if from.osm_way_id != from_osm || via_roads.contains(&from) {
+                continue;
+            }
+
+            let mut at = start;
+            let mut remaining = via_roads.clone();
+            let mut chain = Vec::new();
+            while let Some(idx) = remaining.iter().position(|r| r.i1 == at || r.i2 == at) {
+                let r = remaining.remove(idx);
+                at = if r.i1 == at { r.i2 } else { r.i1 };
+                chain.push(r);
+            }
+            if !remaining.is_empty() {
+                continue;
+            }
+            if let Some(to) = map
+                .roads_per_intersection(at)
+                .into_iter()
+                .find(|r| r.osm_way_id == to_osm && !chain.contains(r))
+            {
+                return Some((from, chain, to));
+            }
+        }
+    }
+    None
+}
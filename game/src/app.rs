@@ -10,8 +10,8 @@ use map_gui::colors::ColorScheme;
 use map_gui::options::Options;
 use map_gui::render::{unzoomed_agent_radius, AgentCache, DrawMap, DrawOptions, Renderable};
 use map_gui::ID;
-use map_model::{IntersectionID, LaneID, Map, Traversable};
-use sim::{AgentID, Analytics, Scenario, Sim, SimCallback, SimFlags};
+use map_model::{EditCmd, IntersectionID, LaneID, Map, Traversable};
+use sim::{AgentID, Analytics, Scenario, SeedRuns, Sim, SimCallback, SimFlags};
 use widgetry::{Canvas, EventCtx, GfxCtx, Prerender, SharedAppState, State};
 
 use crate::challenges::HighScore;
@@ -63,6 +63,19 @@ impl App {
     pub fn prebaked(&self) -> &Analytics {
         &self.primary.prebaked.as_ref().unwrap().2
     }
+    /// Results from running the prebaked scenario with several RNG seeds, before and after the
+    /// edits. None if they haven't been calculated, or the scenario or edits have changed since.
+    pub fn seed_runs(&self) -> Option<&SeedRuns> {
+        let (scenario, commands, runs) = self.primary.seed_runs.as_ref()?;
+        if self.has_prebaked().map(|(_, s)| s) == Some(scenario)
+            && !self.primary.has_modified_trips
+            && &self.primary.map.get_edits().commands == commands
+        {
+            Some(runs)
+        } else {
+            None
+        }
+    }
     pub fn set_prebaked(&mut self, prebaked: Option<(MapName, String, Analytics)>) {
         self.primary.prebaked = prebaked;
 
@@ -622,6 +635,8 @@ pub struct PerMap {
     /// Storing this may cost some memory, but otherwise resetting to midnight would require
     /// loading it again from a file. This is particularly painful on the web!
     pub scenario: Option<Scenario>,
+    /// The scenario name and edits used to calculate these. See `App::seed_runs`.
+    pub seed_runs: Option<(String, Vec<EditCmd>, SeedRuns)>,
 }
 
 impl PerMap {
@@ -655,6 +670,7 @@ impl PerMap {
             suspended_sim: None,
             prebaked: None,
             scenario: None,
+            seed_runs: None,
         };
 
         let mut rng = per_map.current_flags.sim_flags.make_rng();
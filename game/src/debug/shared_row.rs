@@ -72,6 +72,7 @@ fn lane(lane: &Lane) -> Option<serde_json::Map<String, serde_json::value::Value>
             LaneType::Bus => "bus_lane".into(),
             LaneType::SharedLeftTurn => "turn_lane".into(),
             LaneType::Construction => "construction_zone".into(),
+            LaneType::Buffer => "buffer".into(),
             LaneType::LightRail => {
                 return None;
             }
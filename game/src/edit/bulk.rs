@@ -246,6 +246,7 @@ fn make_lt_switcher(
                     Choice::new("bike", Some(LaneType::Biking)),
                     Choice::new("bus", Some(LaneType::Bus)),
                     Choice::new("construction", Some(LaneType::Construction)),
+                    Choice::new("buffer", Some(LaneType::Buffer)),
                 ],
             ),
             "lanes to".draw_text(ctx).centered_vert(),
@@ -260,6 +261,7 @@ fn make_lt_switcher(
                     Choice::new("bike", Some(LaneType::Biking)),
                     Choice::new("bus", Some(LaneType::Bus)),
                     Choice::new("construction", Some(LaneType::Construction)),
+                    Choice::new("buffer", Some(LaneType::Buffer)),
                 ],
             ),
             Btn::plaintext_custom(
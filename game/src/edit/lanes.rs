@@ -1,4 +1,6 @@
+use geom::Distance;
 use map_gui::render::Renderable;
+use map_gui::tools::PopupMsg;
 use map_gui::ID;
 use map_model::{EditCmd, LaneID, LaneType, Map};
 use widgetry::{
@@ -9,6 +11,8 @@ use widgetry::{
 use crate::app::App;
 use crate::app::Transition;
 use crate::common::{CommonState, SimpleState};
+use crate::edit::neighborhood::NeighborhoodPlanner;
+use crate::edit::timed_closures::TimedClosureEditor;
 use crate::edit::zones::ZoneEditor;
 use crate::edit::{
     apply_map_edits, can_edit_lane, maybe_edit_intersection, speed_limit_choices, try_change_lt,
@@ -38,10 +42,16 @@ impl LaneEditor {
             ),
             (
                 "bike",
-                "convert to a protected bike lane",
+                "convert to a bike lane",
                 Key::B,
                 lt != LaneType::Biking,
             ),
+            (
+                "buffer",
+                "convert to a buffer protecting a bike lane",
+                Key::U,
+                lt != LaneType::Buffer,
+            ),
             (
                 "bus",
                 "convert to a bus-only lane",
@@ -84,6 +94,19 @@ impl LaneEditor {
             "Type of lane".draw_text(ctx),
             Widget::custom_row(row).centered(),
             Btn::text_fg("reverse direction").build_def(ctx, Key::F),
+            Btn::text_fg("close the road at certain times").build_def(ctx, Key::W),
+            Btn::text_fg("remove this road").build_def(ctx, Key::X),
+            {
+                let width = app.primary.map.get_l(l).width;
+                let mut choices = lane_width_choices();
+                if !choices.iter().any(|c| c.data == width) {
+                    choices.push(Choice::new(format!("{}", width), width));
+                }
+                Widget::row(vec![
+                    "Change lane width:".draw_text(ctx).centered_vert(),
+                    Widget::dropdown(ctx, "lane width", width, choices),
+                ])
+            },
             {
                 let mut choices = speed_limit_choices(app);
                 if !choices.iter().any(|c| c.data == parent.speed_limit) {
@@ -98,6 +121,7 @@ impl LaneEditor {
                 ])
             },
             Btn::text_fg("Change access restrictions").build_def(ctx, Key::A),
+            Btn::text_fg("Plan a low-traffic neighborhood").build_def(ctx, Key::L),
             Btn::text_bg2("Finish").build_def(ctx, Key::Escape),
         ];
         let panel = Panel::new(Widget::col(col))
@@ -121,6 +145,28 @@ impl SimpleState for LaneEditor {
                 app,
                 app.primary.map.get_l(self.l).parent,
             )),
+            "close the road at certain times" => Transition::Push(TimedClosureEditor::new(
+                ctx,
+                app,
+                app.primary.map.get_l(self.l).parent,
+            )),
+            "Plan a low-traffic neighborhood" => Transition::Push(NeighborhoodPlanner::new(
+                ctx,
+                app,
+                app.primary.map.get_l(self.l).parent,
+            )),
+            "remove this road" => {
+                let map = &app.primary.map;
+                match map.remove_road_cmd(map.get_l(self.l).parent) {
+                    Ok(cmd) => {
+                        let mut edits = map.get_edits().clone();
+                        edits.commands.push(cmd);
+                        apply_map_edits(ctx, app, edits);
+                        Transition::Pop
+                    }
+                    Err(err) => Transition::Push(PopupMsg::new(ctx, "Error", vec![err])),
+                }
+            }
             "Finish" => Transition::Pop,
             x => {
                 let map = &mut app.primary.map;
@@ -129,8 +175,9 @@ impl SimpleState for LaneEditor {
                     "convert to a driving lane" => {
                         try_change_lt(ctx, map, self.l, LaneType::Driving)
                     }
-                    "convert to a protected bike lane" => {
-                        try_change_lt(ctx, map, self.l, LaneType::Biking)
+                    "convert to a bike lane" => try_change_lt(ctx, map, self.l, LaneType::Biking),
+                    "convert to a buffer protecting a bike lane" => {
+                        try_change_lt(ctx, map, self.l, LaneType::Buffer)
                     }
                     "convert to a bus-only lane" => try_change_lt(ctx, map, self.l, LaneType::Bus),
                     "convert to an on-street parking lane" => {
@@ -161,20 +208,35 @@ impl SimpleState for LaneEditor {
         app: &mut App,
         panel: &Panel,
     ) -> Option<Transition> {
-        let mut edits = app.primary.map.get_edits().clone();
-        edits.commands.push(app.primary.map.edit_road_cmd(
-            app.primary.map.get_l(self.l).parent,
-            |new| {
-                new.speed_limit = panel.dropdown_value("speed limit");
-            },
-        ));
+        let map = &app.primary.map;
+        let r = map.get_l(self.l).parent;
+        let old = map.get_r_edit(r);
+        let mut new = old.clone();
+        new.speed_limit = panel.dropdown_value("speed limit");
+        new.lane_widths[map.get_r(r).offset(self.l)] = panel.dropdown_value("lane width");
+        let warnings = match map.check_cross_section(r, &new) {
+            Ok(warnings) => warnings,
+            Err(err) => {
+                return Some(Transition::Multi(vec![
+                    Transition::Replace(LaneEditor::new(ctx, app, self.l, self.mode.clone())),
+                    Transition::Push(PopupMsg::new(ctx, "Error", vec![err])),
+                ]));
+            }
+        };
+
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeRoad { r, old, new });
         apply_map_edits(ctx, app, edits);
-        Some(Transition::Replace(LaneEditor::new(
+        let mut transitions = vec![Transition::Replace(LaneEditor::new(
             ctx,
             app,
             self.l,
             self.mode.clone(),
-        )))
+        ))];
+        if !warnings.is_empty() {
+            transitions.push(Transition::Push(PopupMsg::new(ctx, "Warning", warnings)));
+        }
+        Some(Transition::Multi(transitions))
     }
 
     fn on_mouseover(&mut self, ctx: &mut EventCtx, app: &mut App) {
@@ -217,6 +279,16 @@ impl SimpleState for LaneEditor {
     }
 }
 
+fn lane_width_choices() -> Vec<Choice<Distance>> {
+    // Narrow bike lanes and buffers up to wide curbside lanes
+    (2..=9)
+        .map(|half_meters| {
+            let width = Distance::meters(0.5 * (half_meters as f64));
+            Choice::new(format!("{}", width), width)
+        })
+        .collect()
+}
+
 // Allow doing this anywhere. Players can create really wacky roads with many direction changes,
 // but it's not really useful to limit creativity. ;)
 fn reverse_lane(map: &Map, l: LaneID) -> EditCmd {
@@ -5,10 +5,10 @@ use maplit::btreeset;
 use abstutil::{prettyprint_usize, Timer};
 use geom::Speed;
 use map_gui::options::OptionsPanel;
-use map_gui::render::DrawMap;
+use map_gui::render::{DrawBusStop, DrawLane, DrawMap};
 use map_gui::tools::{grey_out_map, ChooseSomething, ColorLegend, PopupMsg};
 use map_gui::ID;
-use map_model::{EditCmd, IntersectionID, LaneID, LaneType, MapEdits};
+use map_model::{EditCmd, IntersectionID, LaneID, MapEdits};
 use widgetry::{
     lctrl, Btn, Choice, Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Menu,
     Outcome, Panel, State, Text, TextExt, VerticalAlignment, Widget,
@@ -16,7 +16,7 @@ use widgetry::{
 
 pub use self::cluster_traffic_signals::ClusterTrafficSignalEditor;
 pub use self::lanes::LaneEditor;
-pub use self::routes::RouteEditor;
+pub use self::routes::{RouteEditor, RouteStops};
 pub use self::stop_signs::StopSignEditor;
 pub use self::traffic_signals::TrafficSignalEditor;
 pub use self::validate::{check_blackholes, check_sidewalk_connectivity, try_change_lt};
@@ -28,9 +28,11 @@ use crate::sandbox::{GameplayMode, SandboxMode, TimeWarpScreen};
 mod bulk;
 mod cluster_traffic_signals;
 mod lanes;
+mod neighborhood;
 mod routes;
 mod select;
 mod stop_signs;
+mod timed_closures;
 mod traffic_signals;
 mod validate;
 mod zones;
@@ -61,7 +63,7 @@ impl EditMode {
         let layer = crate::layer::map::Static::edits(ctx, app);
         Box::new(EditMode {
             tool_panel: tool_panel(ctx),
-            top_center: make_topcenter(ctx, app),
+            top_center: make_topcenter(ctx, app, &mode),
             changelist: make_changelist(ctx, app),
             orig_edits: edits.clone(),
             orig_dirty,
@@ -179,6 +181,9 @@ impl State<App> for EditMode {
                 "finish editing" => {
                     return self.quit(ctx, app);
                 }
+                "Create a bus route" => {
+                    return Transition::Push(RouteStops::new(ctx, app, None));
+                }
                 _ => unreachable!(),
             },
             _ => {}
@@ -617,7 +622,7 @@ impl State<App> for LoadEdits {
     }
 }
 
-fn make_topcenter(ctx: &mut EventCtx, app: &App) -> Panel {
+fn make_topcenter(ctx: &mut EventCtx, app: &App, mode: &GameplayMode) -> Panel {
     Panel::new(Widget::col(vec![
         Line("Editing map")
             .small_heading()
@@ -633,6 +638,13 @@ fn make_topcenter(ctx: &mut EventCtx, app: &App) -> Panel {
                 .ampm_tostring()
         ))
         .build(ctx, "finish editing", Key::Escape),
+        if mode.can_edit_lanes() {
+            Btn::text_fg("Create a bus route")
+                .build_def(ctx, None)
+                .centered_horiz()
+        } else {
+            Widget::nothing()
+        },
     ]))
     .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
     .build(ctx)
@@ -656,9 +668,10 @@ pub fn apply_map_edits(ctx: &mut EventCtx, app: &mut App, edits: MapEdits) {
         app.primary.draw_map.roads[r.0].clear_rendering();
 
         // An edit to one lane potentially affects markings in all lanes in the same road, because
-        // of one-way markings, driving lines, etc.
+        // of one-way markings, driving lines, etc. If a lane's width changed, all of them moved.
         for l in road.all_lanes() {
-            app.primary.draw_map.lanes[l.0].clear_rendering();
+            app.primary.draw_map.lanes[l.0] =
+                DrawLane::new(app.primary.map.get_l(l), &app.primary.map);
         }
     }
 
@@ -676,6 +689,21 @@ pub fn apply_map_edits(ctx: &mut EventCtx, app: &mut App, edits: MapEdits) {
         app.primary.draw_map.intersections[i.0].clear_rendering();
     }
 
+    // Bus stops may have been added or removed
+    let map = &app.primary.map;
+    app.primary
+        .draw_map
+        .bus_stops
+        .retain(|id, _| map.maybe_get_bs(*id).is_some());
+    for bs in map.all_bus_stops().values() {
+        if !app.primary.draw_map.bus_stops.contains_key(&bs.id) {
+            app.primary
+                .draw_map
+                .bus_stops
+                .insert(bs.id, DrawBusStop::new(ctx, bs, map, &app.cs));
+        }
+    }
+
     if app.primary.layer.as_ref().and_then(|l| l.name()) == Some("map edits") {
         app.primary.layer = Some(Box::new(crate::layer::map::Static::edits(ctx, app)));
     }
@@ -685,12 +713,7 @@ pub fn apply_map_edits(ctx: &mut EventCtx, app: &mut App, edits: MapEdits) {
 }
 
 pub fn can_edit_lane(mode: &GameplayMode, l: LaneID, app: &App) -> bool {
-    let l = app.primary.map.get_l(l);
-    mode.can_edit_lanes()
-        && !l.is_walkable()
-        && l.lane_type != LaneType::SharedLeftTurn
-        && !l.is_light_rail()
-        && !app.primary.map.get_parent(l.id).is_service()
+    mode.can_edit_lanes() && app.primary.map.can_change_lane_type(l)
 }
 
 pub fn speed_limit_choices(app: &App) -> Vec<Choice<Speed>> {
@@ -803,9 +826,10 @@ fn make_changelist(ctx: &mut EventCtx, app: &App) -> Panel {
 // TODO Ideally a Tab.
 fn cmd_to_id(cmd: &EditCmd) -> Option<ID> {
     match cmd {
-        EditCmd::ChangeRoad { r, .. } => Some(ID::Road(*r)),
+        EditCmd::ChangeRoad { r, .. } | EditCmd::RemoveRoad { r, .. } => Some(ID::Road(*r)),
         EditCmd::ChangeIntersection { i, .. } => Some(ID::Intersection(*i)),
-        EditCmd::ChangeRouteSchedule { .. } => None,
+        EditCmd::ChangeRouteSchedule { .. } | EditCmd::ChangeRoute { .. } => None,
+        EditCmd::ChangeBusStop { id, .. } => Some(ID::Lane(id.sidewalk)),
     }
 }
 
@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+
+use geom::{Circle, Distance, Pt2D};
+use map_gui::tools::{ColorLegend, ColorNetwork, PopupMsg};
+use map_gui::ID;
+use map_model::{IntersectionID, Neighborhood, RoadID};
+use widgetry::{
+    Btn, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome,
+    Panel, State, Text, TextExt, VerticalAlignment, Widget,
+};
+
+use crate::app::{App, Transition};
+use crate::common::CommonState;
+use crate::edit::apply_map_edits;
+
+/// Place modal filters in a low-traffic neighborhood, and see how they split it into cells and
+/// what rat-runs are left.
+pub struct NeighborhoodPlanner {
+    panel: Panel,
+    neighborhood: Neighborhood,
+    rat_runs_before: usize,
+    unzoomed: Drawable,
+    zoomed: Drawable,
+}
+
+impl NeighborhoodPlanner {
+    pub fn new(ctx: &mut EventCtx, app: &App, start: RoadID) -> Box<dyn State<App>> {
+        let neighborhood = match Neighborhood::from_road(&app.primary.map, start) {
+            Some(n) => n,
+            None => {
+                return PopupMsg::new(
+                    ctx,
+                    "Error",
+                    vec![format!(
+                        "{} isn't a local road for cars, so it's not part of a neighborhood",
+                        start
+                    )],
+                );
+            }
+        };
+        let rat_runs_before = neighborhood
+            .find_rat_runs_without_filters(&app.primary.map)
+            .len();
+        let mut planner = NeighborhoodPlanner {
+            panel: Panel::empty(ctx),
+            neighborhood,
+            rat_runs_before,
+            unzoomed: Drawable::empty(ctx),
+            zoomed: Drawable::empty(ctx),
+        };
+        planner.recalculate(ctx, app);
+        Box::new(planner)
+    }
+
+    fn recalculate(&mut self, ctx: &mut EventCtx, app: &App) {
+        let map = &app.primary.map;
+        let cells = self.neighborhood.find_cells(map);
+        let rat_runs = self.neighborhood.find_rat_runs(map);
+
+        let mut colorer = ColorNetwork::new(app);
+        let mut unreachable = 0;
+        for (idx, cell) in cells.iter().enumerate() {
+            let color = if cell.borders.is_empty() {
+                unreachable += 1;
+                Color::RED
+            } else {
+                CELL_COLORS[idx % CELL_COLORS.len()]
+            };
+            for r in &cell.roads {
+                colorer.add_r(*r, color);
+            }
+        }
+        for i in &self.neighborhood.borders {
+            colorer.add_i(*i, Color::BLACK);
+        }
+        let mut batch = GeomBatch::new();
+        let rat_run_roads: BTreeSet<RoadID> = rat_runs
+            .iter()
+            .flat_map(|run| run.roads.iter().cloned())
+            .collect();
+        for r in rat_run_roads {
+            batch.push(
+                Color::ORANGE,
+                map.get_r(r).center_pts.make_polygons(Distance::meters(2.0)),
+            );
+        }
+        for r in &self.neighborhood.interior {
+            let road = map.get_r(*r);
+            for filter in &road.modal_filters {
+                let pt = filter_position(app, *r, filter.intersection(road));
+                batch.push(
+                    Color::GREEN,
+                    Circle::new(pt, Distance::meters(3.0)).to_polygon(),
+                );
+            }
+        }
+        colorer.unzoomed.append(batch.clone());
+        colorer.zoomed.append(batch);
+        let (unzoomed, zoomed) = colorer.build(ctx);
+        self.unzoomed = unzoomed;
+        self.zoomed = zoomed;
+
+        let mut txt = Text::new();
+        txt.add(Line(format!(
+            "{} rat-runs through the neighborhood without filters, {} now",
+            self.rat_runs_before,
+            rat_runs.len()
+        )));
+        txt.add(Line(format!(
+            "Filters split the neighborhood into {} cells",
+            cells.len()
+        )));
+        if unreachable > 0 {
+            txt.add(Line(format!("{} cells can't be reached by car", unreachable)).fg(Color::RED));
+        }
+        self.panel = Panel::new(Widget::col(vec![
+            Line("Plan a low-traffic neighborhood")
+                .small_heading()
+                .draw(ctx),
+            "Click a road to add or remove a filter at the nearest end. Click an intersection to \
+             filter every road there."
+                .draw_text(ctx),
+            txt.draw(ctx),
+            ColorLegend::row(ctx, Color::ORANGE, "road used by a rat-run"),
+            ColorLegend::row(ctx, Color::GREEN, "modal filter"),
+            ColorLegend::row(ctx, Color::BLACK, "entrance from a main road"),
+            ColorLegend::row(ctx, Color::RED, "unreachable cell"),
+            Btn::text_bg2("Finish").build_def(ctx, Key::Escape),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+        .build(ctx);
+    }
+
+    fn interior_roads_at(&self, app: &App, i: IntersectionID) -> Vec<RoadID> {
+        app.primary
+            .map
+            .get_i(i)
+            .roads
+            .iter()
+            .filter(|r| self.neighborhood.interior.contains(r))
+            .cloned()
+            .collect()
+    }
+}
+
+impl State<App> for NeighborhoodPlanner {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+        if ctx.redo_mouseover() {
+            app.primary.current_selection =
+                match app.mouseover_unzoomed_roads_and_intersections(ctx) {
+                    Some(ID::Lane(l)) => Some(ID::Road(app.primary.map.get_l(l).parent)),
+                    x => x,
+                }
+                .filter(|id| match id {
+                    ID::Road(r) => self.neighborhood.interior.contains(r),
+                    ID::Intersection(i) => !self.interior_roads_at(app, *i).is_empty(),
+                    _ => false,
+                });
+        }
+
+        let map = &app.primary.map;
+        let mut cmds = Vec::new();
+        match app.primary.current_selection {
+            Some(ID::Road(r)) => {
+                if app.per_obj.left_click(ctx, "add or remove a modal filter") {
+                    let road = map.get_r(r);
+                    let i = if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+                        if pt.dist_to(filter_position(app, r, road.src_i))
+                            < pt.dist_to(filter_position(app, r, road.dst_i))
+                        {
+                            road.src_i
+                        } else {
+                            road.dst_i
+                        }
+                    } else {
+                        road.dst_i
+                    };
+                    cmds.push(map.toggle_modal_filter_cmd(r, i));
+                }
+            }
+            Some(ID::Intersection(i)) => {
+                if app.per_obj.left_click(ctx, "filter every road here") {
+                    let roads = self.interior_roads_at(app, i);
+                    // Add the missing filters, or remove them all if every road is filtered
+                    let missing: Vec<RoadID> = roads
+                        .iter()
+                        .filter(|r| map.get_r(**r).modal_filter_at(i).is_none())
+                        .cloned()
+                        .collect();
+                    for r in if missing.is_empty() { roads } else { missing } {
+                        cmds.push(map.toggle_modal_filter_cmd(r, i));
+                    }
+                }
+            }
+            _ => {}
+        }
+        if !cmds.is_empty() {
+            let mut edits = map.get_edits().clone();
+            edits.commands.extend(cmds);
+            apply_map_edits(ctx, app, edits);
+            self.recalculate(ctx, app);
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "Finish" => {
+                    return Transition::Pop;
+                }
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        if g.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            g.redraw(&self.unzoomed);
+        } else {
+            g.redraw(&self.zoomed);
+        }
+        self.panel.draw(g);
+        CommonState::draw_osd(g, app);
+    }
+}
+
+const CELL_COLORS: [Color; 4] = [Color::CYAN, Color::PURPLE, Color::BLUE, Color::YELLOW];
+
+/// Where to draw a filter on a road next to an intersection
+fn filter_position(app: &App, r: RoadID, i: IntersectionID) -> Pt2D {
+    let road = app.primary.map.get_r(r);
+    let pl = &road.center_pts;
+    let dist = Distance::meters(5.0).min(pl.length() / 2.0);
+    if road.src_i == i {
+        pl.must_dist_along(dist).0
+    } else {
+        pl.must_dist_along(pl.length() - dist).0
+    }
+}
@@ -1,12 +1,15 @@
-use geom::{Duration, Time};
-use map_model::{BusRouteID, EditCmd};
+use geom::{Distance, Duration, Time};
+use map_gui::tools::PopupMsg;
+use map_gui::ID;
+use map_model::{BusRouteID, BusStopID, EditCmd, PathConstraints, Position};
 use widgetry::{
-    Btn, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner, State, TextExt,
-    VerticalAlignment, Widget,
+    Btn, Checkbox, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome,
+    Panel, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
 };
 
 use crate::app::App;
 use crate::app::Transition;
+use crate::common::CommonState;
 use crate::edit::apply_map_edits;
 
 pub struct RouteEditor {
@@ -32,6 +35,18 @@ impl RouteEditor {
                     Spinner::new(ctx, (1, 120), 60).named("freq_mins"),
                 ]),
                 Btn::text_bg2("Apply").build_def(ctx, Key::Enter),
+                if route.route_type == PathConstraints::Bus {
+                    Btn::text_bg2("Change stops").build_def(ctx, None)
+                } else {
+                    Widget::nothing()
+                },
+                Btn::text_bg2("Stop running this route").build_def(ctx, None),
+                // Only the newest route created by edits can be removed
+                if app.primary.map.remove_bus_route_cmd(id).is_ok() {
+                    Btn::text_bg2("Remove this route").build_def(ctx, None)
+                } else {
+                    Widget::nothing()
+                },
             ]))
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
             .build(ctx),
@@ -68,6 +83,29 @@ impl State<App> for RouteEditor {
 
                     return Transition::Pop;
                 }
+                "Change stops" => {
+                    return Transition::Replace(RouteStops::new(ctx, app, Some(self.route)));
+                }
+                "Stop running this route" => {
+                    let mut edits = app.primary.map.get_edits().clone();
+                    edits.commands.push(EditCmd::ChangeRouteSchedule {
+                        id: self.route,
+                        old: app.primary.map.get_br(self.route).spawn_times.clone(),
+                        new: Vec::new(),
+                    });
+                    apply_map_edits(ctx, app, edits);
+
+                    return Transition::Pop;
+                }
+                "Remove this route" => {
+                    let mut edits = app.primary.map.get_edits().clone();
+                    edits
+                        .commands
+                        .push(app.primary.map.remove_bus_route_cmd(self.route).unwrap());
+                    apply_map_edits(ctx, app, edits);
+
+                    return Transition::Pop;
+                }
                 _ => unreachable!(),
             },
             _ => {}
@@ -80,3 +118,224 @@ impl State<App> for RouteEditor {
         self.panel.draw(g);
     }
 }
+
+/// Pick the stops for a new or existing bus route, in order. Bus stops can also be added to
+/// sidewalks or deleted along the way.
+pub struct RouteStops {
+    panel: Panel,
+    // None means a new route is being created
+    route: Option<BusRouteID>,
+    stops: Vec<BusStopID>,
+    draw_stops: Drawable,
+}
+
+impl RouteStops {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        route: Option<BusRouteID>,
+    ) -> Box<dyn State<App>> {
+        app.primary.current_selection = None;
+
+        let stops = route
+            .map(|id| app.primary.map.get_br(id).stops.clone())
+            .unwrap_or_else(Vec::new);
+        let mut state = RouteStops {
+            panel: Panel::empty(ctx),
+            route,
+            stops,
+            draw_stops: Drawable::empty(ctx),
+        };
+        state.recalc(ctx, app);
+        Box::new(state)
+    }
+
+    fn recalc(&mut self, ctx: &mut EventCtx, app: &App) {
+        let map = &app.primary.map;
+        // Preserve whatever was filled out before
+        let name = if let Some(id) = self.route {
+            map.get_br(id).short_name.clone()
+        } else if self.panel.has_widget("name") {
+            self.panel.text_box("name")
+        } else {
+            String::new()
+        };
+        let delete_stops = self
+            .panel
+            .maybe_is_checked("delete stops instead")
+            .unwrap_or(false);
+
+        self.panel = Panel::new(Widget::col(vec![
+            Widget::row(vec![
+                Line(if self.route.is_some() {
+                    "Change stops"
+                } else {
+                    "Create a bus route"
+                })
+                .small_heading()
+                .draw(ctx),
+                Btn::close(ctx),
+            ]),
+            if self.route.is_some() {
+                Line(format!("Route {}", name)).draw(ctx)
+            } else {
+                Widget::row(vec![
+                    "Name:".draw_text(ctx),
+                    Widget::text_entry(ctx, name, false).named("name"),
+                ])
+            },
+            Text::from_multiline(vec![
+                Line("Click bus stops in the order the route visits them").secondary(),
+                Line("Click a sidewalk to add a new stop there").secondary(),
+            ])
+            .draw(ctx),
+            format!("{} stops picked", self.stops.len()).draw_text(ctx),
+            Checkbox::switch(ctx, "delete stops instead", None, delete_stops),
+            Widget::row(vec![
+                if self.stops.is_empty() {
+                    Btn::text_bg2("Remove last stop").inactive(ctx)
+                } else {
+                    Btn::text_bg2("Remove last stop").build_def(ctx, None)
+                },
+                Btn::text_bg2("Finish").build_def(ctx, Key::Enter),
+            ]),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+        .build(ctx);
+
+        let mut batch = GeomBatch::new();
+        for (idx, bs) in self.stops.iter().enumerate() {
+            batch.append(
+                Text::from(Line(format!("{}", idx + 1)))
+                    .with_bg()
+                    .render_to_batch(ctx.prerender)
+                    .scale(0.1)
+                    .centered_on(map.get_bs(*bs).sidewalk_pos.pt(map)),
+            );
+        }
+        self.draw_stops = ctx.upload(batch);
+    }
+
+    fn finish(&self, app: &App) -> Result<EditCmd, String> {
+        let map = &app.primary.map;
+        if let Some(id) = self.route {
+            map.reroute_bus_cmd(id, self.stops.clone())
+        } else {
+            let name = self.panel.text_box("name");
+            if name.is_empty() {
+                return Err("The route needs a name".to_string());
+            }
+            map.new_bus_route_cmd(name, self.stops.clone())
+        }
+    }
+}
+
+impl State<App> for RouteStops {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+        if ctx.redo_mouseover() {
+            app.recalculate_current_selection(ctx);
+            if match app.primary.current_selection {
+                Some(ID::BusStop(bs)) => app.primary.map.get_bs(bs).is_train_stop,
+                Some(ID::Lane(l)) => !app.primary.map.get_l(l).is_walkable(),
+                _ => true,
+            } {
+                app.primary.current_selection = None;
+            }
+        }
+
+        match app.primary.current_selection {
+            Some(ID::BusStop(bs)) => {
+                if self.panel.is_checked("delete stops instead") {
+                    if app.per_obj.left_click(ctx, "delete this bus stop") {
+                        if self.stops.contains(&bs) {
+                            return Transition::Push(PopupMsg::new(
+                                ctx,
+                                "Error",
+                                vec!["Remove this stop from the route first"],
+                            ));
+                        }
+                        match app.primary.map.remove_bus_stop_cmd(bs) {
+                            Ok(cmd) => {
+                                let mut edits = app.primary.map.get_edits().clone();
+                                edits.commands.push(cmd);
+                                apply_map_edits(ctx, app, edits);
+                                app.primary.current_selection = None;
+                                self.recalc(ctx, app);
+                            }
+                            Err(err) => {
+                                return Transition::Push(PopupMsg::new(ctx, "Error", vec![err]));
+                            }
+                        }
+                    }
+                } else if app.per_obj.left_click(ctx, "add this stop to the route") {
+                    self.stops.push(bs);
+                    self.recalc(ctx, app);
+                }
+            }
+            Some(ID::Lane(l)) => {
+                if app.per_obj.left_click(ctx, "add a bus stop here") {
+                    if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+                        let map = &app.primary.map;
+                        let lane = map.get_l(l);
+                        let dist = lane
+                            .dist_along_of_point(lane.lane_center_pts.project_pt(pt))
+                            .unwrap_or(Distance::ZERO);
+                        let name = map.get_parent(l).get_name(app.opts.language.as_ref());
+                        match map.new_bus_stop_cmd(Position::new(l, dist), name) {
+                            Ok(cmd) => {
+                                let id = match cmd {
+                                    EditCmd::ChangeBusStop { id, .. } => id,
+                                    _ => unreachable!(),
+                                };
+                                let mut edits = map.get_edits().clone();
+                                edits.commands.push(cmd);
+                                apply_map_edits(ctx, app, edits);
+                                self.stops.push(id);
+                                app.primary.current_selection = None;
+                                self.recalc(ctx, app);
+                            }
+                            Err(err) => {
+                                return Transition::Push(PopupMsg::new(ctx, "Error", vec![err]));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Transition::Pop;
+                }
+                "Remove last stop" => {
+                    self.stops.pop();
+                    self.recalc(ctx, app);
+                }
+                "Finish" => match self.finish(app) {
+                    Ok(cmd) => {
+                        let mut edits = app.primary.map.get_edits().clone();
+                        edits.commands.push(cmd);
+                        apply_map_edits(ctx, app, edits);
+                        return Transition::Pop;
+                    }
+                    Err(err) => {
+                        return Transition::Push(PopupMsg::new(ctx, "Error", vec![err]));
+                    }
+                },
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.draw_stops);
+        self.panel.draw(g);
+        CommonState::draw_osd(g, app);
+    }
+}
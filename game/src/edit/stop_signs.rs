@@ -7,6 +7,7 @@ use geom::Polygon;
 use map_gui::render::DrawIntersection;
 use map_model::{
     ControlStopSign, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, RoadID,
+    SignType,
 };
 use widgetry::{
     Btn, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Panel, State, Text,
@@ -24,7 +25,7 @@ use crate::sandbox::GameplayMode;
 pub struct StopSignEditor {
     id: IntersectionID,
     mode: GameplayMode,
-    // (octagon, pole)
+    // (sign, pole)
     geom: HashMap<RoadID, (Polygon, Polygon)>,
     selected_sign: Option<RoadID>,
 }
@@ -44,14 +45,16 @@ impl StopSignEditor {
             .roads
             .iter()
             .map(|(r, ss)| {
-                let (octagon, pole) =
-                    DrawIntersection::stop_sign_geom(ss, &app.primary.map).unwrap();
-                (*r, (octagon, pole))
+                let (sign, pole) = DrawIntersection::stop_sign_geom(ss, &app.primary.map).unwrap();
+                (*r, (sign, pole))
             })
             .collect();
 
         let panel = Panel::new(Widget::col(vec![
             Line("Stop sign editor").small_heading().draw(ctx),
+            Line("Click a sign to switch between stop, yield, and no sign")
+                .secondary()
+                .draw(ctx),
             if ControlStopSign::new(&app.primary.map, id)
                 != app.primary.map.get_stop_sign(id).clone()
             {
@@ -123,6 +126,7 @@ impl SimpleState for StopSignEditor {
                             &mut Timer::throwaway(),
                         )
                         .export(&app.primary.map),
+                        None,
                     ),
                 });
                 apply_map_edits(ctx, app, edits);
@@ -143,8 +147,8 @@ impl SimpleState for StopSignEditor {
     fn on_mouseover(&mut self, ctx: &mut EventCtx, _: &mut App) {
         self.selected_sign = None;
         if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
-            for (r, (octagon, _)) in &self.geom {
-                if octagon.contains_pt(pt) {
+            for (r, (sign, _)) in &self.geom {
+                if sign.contains_pt(pt) {
                     self.selected_sign = Some(*r);
                     break;
                 }
@@ -157,10 +161,10 @@ impl SimpleState for StopSignEditor {
 
         if let Some(r) = self.selected_sign {
             let mut sign = app.primary.map.get_stop_sign(self.id).clone();
-            let label = if sign.roads[&r].must_stop {
-                "remove stop sign"
-            } else {
-                "add stop sign"
+            let label = match sign.roads[&r].sign_type {
+                SignType::NoSign => "add stop sign",
+                SignType::Stop => "change to yield sign",
+                SignType::Yield => "remove yield sign",
             };
             if app.per_obj.left_click(ctx, label) {
                 sign.flip_sign(r);
@@ -190,15 +194,16 @@ impl SimpleState for StopSignEditor {
 
         let mut batch = GeomBatch::new();
 
-        for (r, (octagon, pole)) in &self.geom {
-            // The intersection will already draw enabled stop signs
+        for (r, (sign_geom, pole)) in &self.geom {
+            // The intersection will already draw enabled signs
+            let no_sign = sign.roads[r].sign_type == SignType::NoSign;
             if Some(*r) == self.selected_sign {
-                batch.push(app.cs.perma_selected_object, octagon.clone());
-                if !sign.roads[r].must_stop {
+                batch.push(app.cs.perma_selected_object, sign_geom.clone());
+                if no_sign {
                     batch.push(app.cs.stop_sign_pole.alpha(0.6), pole.clone());
                 }
-            } else if !sign.roads[r].must_stop {
-                batch.push(app.cs.stop_sign.alpha(0.6), octagon.clone());
+            } else if no_sign {
+                batch.push(app.cs.stop_sign.alpha(0.6), sign_geom.clone());
                 batch.push(app.cs.stop_sign_pole.alpha(0.6), pole.clone());
             }
         }
@@ -208,7 +213,7 @@ impl SimpleState for StopSignEditor {
         if let Some(r) = self.selected_sign {
             let mut osd = Text::new();
             osd.add_appended(vec![
-                Line("Stop sign for "),
+                Line("Sign for "),
                 Line(
                     app.primary
                         .map
@@ -0,0 +1,128 @@
+use geom::{Duration, Time};
+use map_gui::tools::PopupMsg;
+use map_model::RoadID;
+use widgetry::{
+    Btn, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Panel, Spinner, State, Text, TextExt,
+    VerticalAlignment, Widget,
+};
+
+use crate::app::{App, Transition};
+use crate::common::{CommonState, SimpleState};
+use crate::edit::apply_map_edits;
+
+/// Close a road to everything except bikes and buses during part of the day, like a school street.
+pub struct TimedClosureEditor {
+    r: RoadID,
+}
+
+impl TimedClosureEditor {
+    pub fn new(ctx: &mut EventCtx, app: &App, r: RoadID) -> Box<dyn State<App>> {
+        let road = app.primary.map.get_r(r);
+        let windows = road
+            .modal_filters
+            .iter()
+            .map(|filter| filter.conditions.time_windows.clone())
+            .find(|windows| !windows.is_empty())
+            .unwrap_or_else(Vec::new);
+        // Default to the morning school run
+        let (start, end) = windows.get(0).cloned().unwrap_or((
+            Time::START_OF_DAY + Duration::hours(8) + Duration::minutes(30),
+            Time::START_OF_DAY + Duration::hours(9) + Duration::minutes(15),
+        ));
+
+        let mut txt = Text::new();
+        txt.add(Line(
+            "Only people walking, cycling, or on buses can use the road during this time. Vehicles \
+             already on their way find another route when the closure starts.",
+        ));
+        if windows.len() > 1 {
+            txt.add(Line(format!(
+                "The road is closed {} times a day now. Applying replaces all of them.",
+                windows.len()
+            )));
+        }
+
+        let mut col = vec![
+            Line(format!(
+                "Close {} at certain times",
+                road.get_name(app.opts.language.as_ref())
+            ))
+            .small_heading()
+            .draw(ctx),
+            txt.wrap_to_pct(ctx, 30).draw(ctx),
+            time_picker(ctx, "From", "start", start),
+            time_picker(ctx, "Until", "end", end),
+        ];
+        let mut buttons = vec![Btn::text_fg("Apply").build_def(ctx, Key::Enter)];
+        if !windows.is_empty() {
+            buttons.push(Btn::text_fg("Remove the closure").build_def(ctx, Key::R));
+        }
+        buttons.push(Btn::text_fg("Cancel").build_def(ctx, Key::Escape));
+        col.push(Widget::custom_row(buttons).evenly_spaced());
+
+        let panel = Panel::new(Widget::col(col))
+            .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+            .build(ctx);
+        SimpleState::new(panel, Box::new(TimedClosureEditor { r }))
+    }
+}
+
+impl SimpleState for TimedClosureEditor {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        panel: &Panel,
+    ) -> Transition {
+        let time_windows = match x {
+            "Apply" => vec![(picked_time(panel, "start"), picked_time(panel, "end"))],
+            "Remove the closure" => Vec::new(),
+            "Cancel" => {
+                return Transition::Pop;
+            }
+            _ => unreachable!(),
+        };
+        match app.primary.map.timed_closure_cmd(self.r, time_windows) {
+            Ok(cmd) => {
+                let mut edits = app.primary.map.get_edits().clone();
+                edits.commands.push(cmd);
+                apply_map_edits(ctx, app, edits);
+                Transition::Pop
+            }
+            Err(err) => Transition::Push(PopupMsg::new(ctx, "Error", vec![err])),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, _: &mut App) -> Transition {
+        ctx.canvas_movement();
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.draw_polygon(
+            app.cs.perma_selected_object,
+            app.primary
+                .map
+                .get_r(self.r)
+                .get_thick_polygon(&app.primary.map),
+        );
+        CommonState::draw_osd(g, app);
+    }
+}
+
+fn time_picker(ctx: &mut EventCtx, label: &str, name: &str, time: Time) -> Widget {
+    let (hours, minutes, _, _) = time.get_parts();
+    Widget::row(vec![
+        label.draw_text(ctx).centered_vert(),
+        Spinner::new(ctx, (0, 24), hours as isize).named(format!("{} hours", name)),
+        ":".draw_text(ctx).centered_vert(),
+        Spinner::new(ctx, (0, 59), minutes as isize).named(format!("{} minutes", name)),
+    ])
+}
+
+fn picked_time(panel: &Panel, name: &str) -> Time {
+    Time::START_OF_DAY
+        + Duration::hours(panel.spinner(&format!("{} hours", name)) as usize)
+        + Duration::minutes(panel.spinner(&format!("{} minutes", name)) as usize)
+}
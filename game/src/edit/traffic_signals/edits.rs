@@ -3,6 +3,7 @@ use geom::Duration;
 use map_gui::tools::ChooseSomething;
 use map_model::{
     ControlStopSign, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, PhaseType,
+    TransitPriority,
 };
 use widgetry::{
     Btn, Checkbox, Choice, DrawBaselayer, EventCtx, Key, Line, Panel, Spinner, State, TextExt,
@@ -109,6 +110,111 @@ impl SimpleState for ChangeDuration {
     }
 }
 
+pub struct ChangeTransitPriority;
+
+impl ChangeTransitPriority {
+    pub fn new(ctx: &mut EventCtx, signal: &ControlTrafficSignal) -> Box<dyn State<App>> {
+        let tsp = signal
+            .transit_priority
+            .clone()
+            .unwrap_or_else(TransitPriority::default_limits);
+        let panel = Panel::new(Widget::col(vec![
+            Widget::row(vec![
+                Line("Transit signal priority").small_heading().draw(ctx),
+                Btn::close(ctx),
+            ]),
+            Checkbox::switch(
+                ctx,
+                "enable transit signal priority",
+                None,
+                signal.transit_priority.is_some(),
+            ),
+            Widget::row(vec![
+                "Maximum green extension (seconds):".draw_text(ctx),
+                Spinner::new(
+                    ctx,
+                    (0, 60),
+                    tsp.max_green_extension.inner_seconds() as isize,
+                )
+                .named("extension"),
+            ]),
+            Widget::row(vec![
+                "Maximum early end of a conflicting stage (seconds):".draw_text(ctx),
+                Spinner::new(
+                    ctx,
+                    (0, 60),
+                    tsp.max_early_termination.inner_seconds() as isize,
+                )
+                .named("early termination"),
+            ]),
+            Widget::row(vec![
+                "Queue jump head start for bus lanes (seconds):".draw_text(ctx),
+                Spinner::new(
+                    ctx,
+                    (0, 30),
+                    tsp.queue_jump
+                        .map(|d| d.inner_seconds() as isize)
+                        .unwrap_or(0),
+                )
+                .named("queue jump"),
+            ]),
+            Line("A head start of 0 seconds disables queue jumps")
+                .secondary()
+                .draw(ctx),
+            Btn::text_bg2("Apply").build_def(ctx, Key::Enter),
+        ]))
+        .build(ctx);
+        SimpleState::new(panel, Box::new(ChangeTransitPriority))
+    }
+}
+
+impl SimpleState for ChangeTransitPriority {
+    fn on_click(&mut self, _: &mut EventCtx, _: &mut App, x: &str, panel: &Panel) -> Transition {
+        match x {
+            "close" => Transition::Pop,
+            "Apply" => {
+                let tsp = if panel.is_checked("enable transit signal priority") {
+                    let queue_jump = panel.spinner("queue jump");
+                    Some(TransitPriority {
+                        max_green_extension: Duration::seconds(panel.spinner("extension") as f64),
+                        max_early_termination: Duration::seconds(
+                            panel.spinner("early termination") as f64,
+                        ),
+                        queue_jump: if queue_jump == 0 {
+                            None
+                        } else {
+                            Some(Duration::seconds(queue_jump as f64))
+                        },
+                    })
+                } else {
+                    None
+                };
+                Transition::Multi(vec![
+                    Transition::Pop,
+                    Transition::ModifyState(Box::new(move |state, ctx, app| {
+                        let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                        editor.add_new_edit(ctx, app, 0, |ts| {
+                            ts.transit_priority = tsp.clone();
+                        });
+                    })),
+                ])
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, _: &mut App) -> Transition {
+        if ctx.normal_left_click() && ctx.canvas.get_cursor_in_screen_space().is_none() {
+            return Transition::Pop;
+        }
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+}
+
 pub fn edit_entire_signal(
     ctx: &mut EventCtx,
     app: &App,
@@ -125,6 +231,7 @@ pub fn edit_entire_signal(
 
     let use_template = "use template";
     let all_walk = "add an all-walk stage at the end";
+    let transit_priority = "configure transit signal priority";
     let stop_sign = "convert to stop signs";
     let close = "close intersection for construction";
     let reset = "reset to default";
@@ -133,6 +240,7 @@ pub fn edit_entire_signal(
     if has_sidewalks {
         choices.push(all_walk);
     }
+    choices.push(transit_priority);
     // TODO Conflating stop signs and construction here
     if mode.can_edit_stop_signs() {
         choices.push(stop_sign);
@@ -177,6 +285,10 @@ pub fn edit_entire_signal(
                     }
                 })),
             ]),
+            x if x == transit_priority => Transition::Replace(ChangeTransitPriority::new(
+                ctx,
+                app.primary.map.get_traffic_signal(i),
+            )),
             x if x == stop_sign => {
                 original.apply(app);
 
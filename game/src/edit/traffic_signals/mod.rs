@@ -702,7 +702,10 @@ impl BundleEdits {
             edits.commands.push(EditCmd::ChangeIntersection {
                 i: signal.id,
                 old: app.primary.map.get_i_edit(signal.id),
-                new: EditIntersection::TrafficSignal(signal.export(&app.primary.map)),
+                new: EditIntersection::TrafficSignal(
+                    signal.export(&app.primary.map),
+                    signal.transit_priority.clone(),
+                ),
             });
         }
         apply_map_edits(ctx, app, edits);
@@ -120,50 +120,17 @@ pub fn check_blackholes(
 
 pub fn try_change_lt(
     ctx: &mut EventCtx,
-    map: &mut Map,
+    map: &Map,
     l: LaneID,
     new_lt: LaneType,
 ) -> Result<EditCmd, Box<dyn State<App>>> {
-    let orig_edits = map.get_edits().clone();
-
-    let mut edits = orig_edits.clone();
-    let cmd = {
-        let r = map.get_l(l).parent;
-        map.edit_road_cmd(r, |new| {
-            new.lanes_ltr[map.get_r(r).offset(l)].0 = new_lt;
-        })
-    };
-    edits.commands.push(cmd.clone());
-    map.try_apply_edits(edits, &mut Timer::throwaway());
-
-    let mut errors = Vec::new();
-    let r = map.get_parent(l);
-
-    // TODO Ban two adjacent parking lanes (What about dppd though?)
-
-    // A parking lane must have a driving lane somewhere on the road.
-    let all_types: BTreeSet<LaneType> = r.lanes_ltr().into_iter().map(|(_, _, lt)| lt).collect();
-    if all_types.contains(&LaneType::Parking) && !all_types.contains(&LaneType::Driving) {
-        errors.push(format!(
-            "A parking lane needs a driving lane somewhere on the same road"
-        ));
-    }
-
-    // Don't let players orphan a bus stop.
-    // TODO This allows a bus stop switching sides of the road. Really need to re-do bus matching
-    // and make sure nothing's broken (https://github.com/dabreegster/abstreet/issues/93).
-    if !r.all_bus_stops(map).is_empty()
-        && !r
-            .lanes_ltr()
-            .into_iter()
-            .any(|(l, _, _)| PathConstraints::Bus.can_use(map.get_l(l), map))
-    {
-        errors.push(format!("You need a driving or bus lane for the bus stop!"));
-    }
-
-    map.must_apply_edits(orig_edits, &mut Timer::throwaway());
+    let r = map.get_l(l).parent;
+    let old = map.get_r_edit(r);
+    let mut new = old.clone();
+    new.lanes_ltr[map.get_r(r).offset(l)].0 = new_lt;
+    let errors = map.check_lane_types(r, &new);
     if errors.is_empty() {
-        Ok(cmd)
+        Ok(EditCmd::ChangeRoad { r, old, new })
     } else {
         Err(PopupMsg::new(ctx, "Error", errors))
     }
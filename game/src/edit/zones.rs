@@ -4,7 +4,7 @@ use enumset::EnumSet;
 use maplit::btreeset;
 
 use map_gui::tools::ColorDiscrete;
-use map_model::{AccessRestrictions, PathConstraints, RoadID};
+use map_model::{AccessRestrictions, PathConstraints, RoadID, Toll};
 use sim::TripMode;
 use widgetry::{
     Btn, Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
@@ -24,6 +24,8 @@ pub struct ZoneEditor {
     zoomed: Drawable,
 
     orig_members: BTreeSet<RoadID>,
+    orig_toll: Option<Toll>,
+    orig_toll_dollars: isize,
 }
 
 impl ZoneEditor {
@@ -42,6 +44,18 @@ impl ZoneEditor {
             .map(|c| TripMode::from_constraints(c))
             .collect();
         let cap_vehicles_per_hour = start.access_restrictions.cap_vehicles_per_hour;
+        // Tolls with a schedule or prices for other vehicles can only be made by editing the JSON
+        // directly. Keep them unless the price here is changed.
+        let orig_toll = start.access_restrictions.toll.clone();
+        let orig_toll_dollars = orig_toll
+            .as_ref()
+            .and_then(|toll| {
+                toll.prices
+                    .iter()
+                    .find(|(c, _)| *c == PathConstraints::Car)
+                    .map(|(_, dollars)| dollars.round() as isize)
+            })
+            .unwrap_or(0);
 
         let (unzoomed, zoomed, legend) = draw_zone(ctx, app, &members);
         let orig_members = members.clone();
@@ -62,6 +76,10 @@ impl ZoneEditor {
                     Spinner::new(ctx, (0, 1000), cap_vehicles_per_hour.unwrap_or(0) as isize)
                         .named("cap_vehicles"),
                 ]),
+                Widget::row(vec![
+                    "Toll in dollars for cars entering the zone (0 = free):".draw_text(ctx),
+                    Spinner::new(ctx, (0, 100), orig_toll_dollars).named("toll"),
+                ]),
                 Widget::custom_row(vec![
                     Btn::text_fg("Apply").build_def(ctx, Key::Enter),
                     Btn::text_fg("Cancel").build_def(ctx, Key::Escape),
@@ -71,6 +89,8 @@ impl ZoneEditor {
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
             .build(ctx),
             orig_members,
+            orig_toll,
+            orig_toll_dollars,
             selector,
             allow_through_traffic,
             unzoomed,
@@ -114,6 +134,16 @@ impl State<App> for ZoneEditor {
                                 Some(n)
                             }
                         },
+                        toll: {
+                            let dollars = self.panel.spinner("toll");
+                            if dollars == self.orig_toll_dollars {
+                                self.orig_toll.clone()
+                            } else if dollars == 0 {
+                                None
+                            } else {
+                                Some(Toll::flat(dollars as f64))
+                            }
+                        },
                     };
                     for r in &self.selector.roads {
                         let old_access_restrictions =
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 
+use abstutil::prettyprint_usize;
 use geom::{Angle, Circle, Distance, Speed, Time};
 use map_gui::render::DrawPedestrian;
 use map_model::{BuildingID, LaneID, OffstreetParking, Traversable, SIDEWALK_THICKNESS};
@@ -69,6 +70,18 @@ pub fn info(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BuildingID
         }
     }
 
+    if !b.jobs_by_sector.is_empty() {
+        txt.add(Line(""));
+        txt.add(Line("Jobs by sector:"));
+        for (sector, num_jobs) in &b.jobs_by_sector {
+            txt.add(Line(format!(
+                "  {}: {}",
+                sector,
+                prettyprint_usize(*num_jobs)
+            )));
+        }
+    }
+
     txt.add(Line(""));
     if let Some(pl) = app
         .primary
@@ -384,6 +384,9 @@ fn header(
     let i = app.primary.map.get_i(id);
 
     let label = match i.intersection_type {
+        IntersectionType::StopSign if i.is_roundabout(&app.primary.map) => {
+            format!("{} (Roundabout)", id)
+        }
         IntersectionType::StopSign => format!("{} (Stop signs)", id),
         IntersectionType::TrafficSignal => format!("{} (Traffic signals)", id),
         IntersectionType::Border => format!("Border #{}", id.0),
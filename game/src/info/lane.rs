@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use abstutil::prettyprint_usize;
+use map_model::raw::{RestrictionConditions, RestrictionType};
 use map_model::{LaneID, PathConstraints};
 use widgetry::{Btn, EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
 
@@ -38,6 +39,41 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
                 ),
             ));
         }
+        if let Some(ref toll) = r.access_restrictions.toll {
+            kv.push((
+                "Toll for cars to enter now",
+                format!(
+                    "${:.2}",
+                    toll.price(PathConstraints::Car, app.primary.sim.time())
+                ),
+            ));
+        }
+    }
+    for filter in &r.modal_filters {
+        let except = filter
+            .conditions
+            .except
+            .iter()
+            .map(|c| format!("{:?}", c).to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let mut txt = if except.is_empty() {
+            format!("near {}, no vehicles can pass", filter.intersection(r))
+        } else {
+            format!(
+                "near {}, only {} can pass",
+                filter.intersection(r),
+                except.join(", ")
+            )
+        };
+        for (start, end) in &filter.conditions.time_windows {
+            txt = format!(
+                "{}, {} to {}",
+                txt,
+                start.ampm_tostring(),
+                end.ampm_tostring()
+            );
+        }
+        kv.push(("Modal filter", txt));
     }
 
     if l.is_parking() {
@@ -122,10 +158,16 @@ pub fn debug(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Ve
             format!("{:?}", types.into_iter().collect::<Vec<_>>()),
         ));
     }
-    for (restriction, to) in &r.turn_restrictions {
+    for (restriction, to, conditions) in &r.turn_restrictions {
         kv.push((
             format!("Restriction from this road to {}", to),
-            format!("{:?}", restriction),
+            describe_restriction(*restriction, conditions),
+        ));
+    }
+    for (restriction, via, to, conditions) in &r.complicated_turn_restrictions {
+        kv.push((
+            format!("Restriction from this road via {:?} to {}", via, to),
+            describe_restriction(*restriction, conditions),
         ));
     }
 
@@ -264,3 +306,26 @@ fn header(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID, tab: Tab
 
     rows
 }
+
+fn describe_restriction(
+    restriction: RestrictionType,
+    conditions: &RestrictionConditions,
+) -> String {
+    let mut txt = format!("{:?}", restriction);
+    if !conditions.except.is_empty() {
+        txt = format!(
+            "{}, except {:?}",
+            txt,
+            conditions.except.iter().collect::<Vec<_>>()
+        );
+    }
+    for (start, end) in &conditions.time_windows {
+        txt = format!(
+            "{}, {} to {}",
+            txt,
+            start.ampm_tostring(),
+            end.ampm_tostring()
+        );
+    }
+    txt
+}
@@ -4,9 +4,9 @@ use maplit::hashset;
 
 use abstutil::{prettyprint_usize, Counter, MultiMap};
 use geom::{Distance, PolyLine, Polygon, Time};
-use map_gui::tools::ColorLegend;
+use map_gui::tools::{ColorLegend, PopupMsg};
 use map_model::{osm, BuildingID, BuildingType, IntersectionID, LaneID, Map, RoadID, TurnType};
-use sim::{TripEndpoint, TripInfo, TripMode};
+use sim::{TripEndpoint, TripID, TripInfo, TripMode};
 use widgetry::{
     Btn, Checkbox, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line,
     Outcome, Panel, RewriteColor, Slider, State, Text, TextExt, VerticalAlignment, Widget,
@@ -14,6 +14,7 @@ use widgetry::{
 
 use crate::app::{App, Transition};
 use crate::common::{checkbox_per_mode, CommonState};
+use crate::sandbox::dashboards::seeds;
 
 pub struct CommuterPatterns {
     bldg_to_block: HashMap<BuildingID, BlockID>,
@@ -23,8 +24,8 @@ pub struct CommuterPatterns {
     filter: Filter,
 
     // Indexed by BlockID
-    trips_from_block: Vec<Vec<TripInfo>>,
-    trips_to_block: Vec<Vec<TripInfo>>,
+    trips_from_block: Vec<Vec<(TripID, TripInfo)>>,
+    trips_to_block: Vec<Vec<(TripID, TripInfo)>>,
 
     panel: Panel,
     draw_all_blocks: Drawable,
@@ -44,6 +45,8 @@ struct PanelState<'a> {
     building_counts: Vec<(&'a str, u32)>,
     max_count: usize,
     total_trips: usize,
+    /// The trips counted in total_trips
+    trips: BTreeSet<TripID>,
 }
 
 // Group many buildings into a single block
@@ -73,11 +76,11 @@ impl CommuterPatterns {
         let (bldg_to_block, border_to_block, blocks) =
             ctx.loading_screen("group buildings into blocks", |_, _| group_bldgs(app));
 
-        let mut trips_from_block: Vec<Vec<TripInfo>> = std::iter::repeat_with(Vec::new)
+        let mut trips_from_block: Vec<Vec<(TripID, TripInfo)>> = std::iter::repeat_with(Vec::new)
             .take(blocks.len())
             .collect();
-        let mut trips_to_block: Vec<Vec<TripInfo>> = trips_from_block.clone();
-        for (_, trip) in app.primary.sim.all_trip_info() {
+        let mut trips_to_block: Vec<Vec<(TripID, TripInfo)>> = trips_from_block.clone();
+        for (id, trip) in app.primary.sim.all_trip_info() {
             let block1 = match trip.start {
                 TripEndpoint::Bldg(b) => bldg_to_block[&b],
                 TripEndpoint::Border(i) => border_to_block[&i],
@@ -90,8 +93,8 @@ impl CommuterPatterns {
             };
             // Totally ignore trips within the same block
             if block1 != block2 {
-                trips_from_block[block1].push(trip.clone());
-                trips_to_block[block2].push(trip);
+                trips_from_block[block1].push((id, trip.clone()));
+                trips_to_block[block2].push((id, trip));
             }
         }
 
@@ -125,15 +128,17 @@ impl CommuterPatterns {
         })
     }
 
-    // For all trips from (or to) the base block, how many of them go to all other blocks?
-    fn count_per_block(&self, base: &Block) -> Vec<(&Block, usize)> {
+    // For all trips from (or to) the base block, how many of them go to all other blocks? Also
+    // returns the trips counted.
+    fn count_per_block(&self, base: &Block) -> (Vec<(&Block, usize)>, BTreeSet<TripID>) {
         let candidates = if self.filter.from_block {
             &self.trips_from_block[base.id]
         } else {
             &self.trips_to_block[base.id]
         };
         let mut count: Counter<BlockID> = Counter::new();
-        for trip in candidates {
+        let mut trips = BTreeSet::new();
+        for (id, trip) in candidates {
             if trip.departure < self.filter.depart_from || trip.departure > self.filter.depart_until
             {
                 continue;
@@ -141,38 +146,33 @@ impl CommuterPatterns {
             if !self.filter.modes.contains(&trip.mode) {
                 continue;
             }
-            if self.filter.from_block {
-                match trip.end {
-                    TripEndpoint::Bldg(b) => {
-                        count.inc(self.bldg_to_block[&b]);
-                    }
-                    TripEndpoint::Border(i) => {
-                        if self.filter.include_borders {
-                            count.inc(self.border_to_block[&i]);
-                        }
-                    }
-                    TripEndpoint::SuddenlyAppear(_) => {}
-                }
+            let other_end = if self.filter.from_block {
+                &trip.end
             } else {
-                match trip.start {
-                    TripEndpoint::Bldg(b) => {
-                        count.inc(self.bldg_to_block[&b]);
-                    }
-                    TripEndpoint::Border(i) => {
-                        if self.filter.include_borders {
-                            count.inc(self.border_to_block[&i]);
-                        }
+                &trip.start
+            };
+            let block = match other_end {
+                TripEndpoint::Bldg(b) => self.bldg_to_block[b],
+                TripEndpoint::Border(i) => {
+                    if !self.filter.include_borders {
+                        continue;
                     }
-                    TripEndpoint::SuddenlyAppear(_) => {}
+                    self.border_to_block[i]
                 }
-            }
+                TripEndpoint::SuddenlyAppear(_) => {
+                    continue;
+                }
+            };
+            count.inc(block);
+            trips.insert(*id);
         }
 
-        count
+        let counts = count
             .consume()
             .into_iter()
             .map(|(id, cnt)| (&self.blocks[id], cnt))
-            .collect()
+            .collect();
+        (counts, trips)
     }
 
     fn build_block_drawable<'a>(
@@ -246,7 +246,7 @@ impl CommuterPatterns {
                     batch.append(icon);
                 }
 
-                let others = self.count_per_block(&base_block);
+                let (others, trips) = self.count_per_block(&base_block);
 
                 let mut total_trips = 0;
                 let max_count = others.iter().map(|(_, cnt)| *cnt).max().unwrap_or(0);
@@ -299,6 +299,7 @@ impl CommuterPatterns {
                     building_counts,
                     total_trips,
                     max_count,
+                    trips,
                 };
                 (ctx.upload(batch), Some(panel_data))
             }
@@ -319,6 +320,22 @@ impl CommuterPatterns {
                 }
             }
 
+            if let Some(runs) = app.seed_runs() {
+                let x = runs.trip_estimates(&|id, _| state.trips.contains(&id));
+                txt.add(Line(format!(
+                    "Across {} RNG seeds, with 95% confidence intervals:",
+                    runs.seeds.len()
+                )));
+                txt.add(Line(format!(
+                    "Finished trips: {} before, {} after",
+                    x.finished_before, x.finished_after
+                )));
+                txt.add(Line(format!(
+                    "Mean trip time change: {}",
+                    seeds::describe_duration_change(&x.mean_trip_time_change)
+                )));
+            }
+
             self.panel.replace(ctx, "current", txt.draw(ctx));
 
             let new_scale = ColorLegend::gradient(
@@ -347,6 +364,21 @@ impl State<App> for CommuterPatterns {
                     app.primary.sim = app.primary.suspended_sim.take().unwrap();
                     return Transition::Pop;
                 }
+                seeds::RUN_SEEDS => {
+                    if let Err(err) = seeds::run_seeds(ctx, app) {
+                        return Transition::Push(PopupMsg::new(
+                            ctx,
+                            "Can't compare across RNG seeds",
+                            vec![err],
+                        ));
+                    }
+                    self.panel
+                        .replace(ctx, "seeds", Widget::col(vec![seeds::button(ctx, app)]));
+                    let (drawable, per_block_counts) =
+                        self.build_block_drawable(self.current_block.0, ctx, app);
+                    self.redraw_panel(per_block_counts.as_ref(), ctx, app);
+                    self.current_block.1 = drawable;
+                }
                 _ => unreachable!(),
             },
             _ => {}
@@ -699,6 +731,7 @@ fn make_panel(ctx: &mut EventCtx, app: &App) -> Panel {
         checkbox_per_mode(ctx, app, &TripMode::all().into_iter().collect()),
         ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["0", "0"]).named("scale"),
         "None selected".draw_text(ctx).named("current"),
+        Widget::col(vec![seeds::button(ctx, app)]).named("seeds"),
     ]))
     .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
     .build(ctx)
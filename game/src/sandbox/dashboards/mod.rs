@@ -11,6 +11,7 @@ mod commuter;
 mod generic_trip_table;
 mod misc;
 mod parking_overhead;
+mod seeds;
 mod summaries;
 mod table;
 mod traffic_signals;
@@ -0,0 +1,78 @@
+//! One run of a simulation can't tell a real change in trip times from noise. These helpers run
+//! the scenario with several RNG seeds, before and after the current edits; see `sim::SeedRuns`.
+
+use geom::{Duration, MeanEstimate};
+use sim::{SeedRuns, SimFlags};
+use widgetry::{Btn, EventCtx, Widget};
+
+use crate::app::App;
+
+const NUM_SEEDS: u64 = 5;
+pub const RUN_SEEDS: &str = "Compare across RNG seeds";
+
+/// A button to run the seeds, if there's a scenario to compare against and it hasn't been done
+/// already.
+pub fn button(ctx: &EventCtx, app: &App) -> Widget {
+    if app.has_prebaked().is_none() || app.seed_runs().is_some() {
+        return Widget::nothing();
+    }
+    Btn::text_bg2(RUN_SEEDS).build_def(ctx, None)
+}
+
+/// Runs the prebaked scenario with every seed on the map without and with the current edits. This
+/// takes a while, so only happens when the player asks.
+pub fn run_seeds(ctx: &mut EventCtx, app: &mut App) -> Result<(), String> {
+    let scenario_name = match app.has_prebaked() {
+        Some((_, name)) => name.clone(),
+        None => {
+            return Err("There's no scenario to compare against".to_string());
+        }
+    };
+    if app.primary.has_modified_trips {
+        return Err("The trips have been modified, so they don't match the scenario".to_string());
+    }
+    let scenario = match app.primary.scenario {
+        Some(ref scenario) if scenario.scenario_name == scenario_name => scenario.clone(),
+        _ => {
+            return Err(format!("The {} scenario isn't loaded", scenario_name));
+        }
+    };
+
+    app.primary.calculate_unedited_map();
+    let seeds: Vec<u64> = (0..NUM_SEEDS).map(|i| SimFlags::RNG_SEED + i).collect();
+    let runs = ctx.loading_screen("compare across RNG seeds", |_, timer| {
+        let unedited_map = app.primary.unedited_map.borrow();
+        SeedRuns::new(
+            unedited_map.as_ref().unwrap_or(&app.primary.map),
+            &app.primary.map,
+            &scenario,
+            &app.primary.current_flags.sim_flags.opts,
+            seeds,
+            timer,
+        )
+    });
+    app.primary.seed_runs = Some((
+        scenario_name,
+        app.primary.map.get_edits().commands.clone(),
+        runs,
+    ));
+    Ok(())
+}
+
+/// Describes an estimate in seconds, like "1m3.5s ± 20.1s"
+pub fn describe_duration(x: &MeanEstimate) -> String {
+    format!(
+        "{} ± {}",
+        Duration::seconds(x.mean),
+        Duration::seconds(x.margin)
+    )
+}
+
+/// Like `describe_duration`, but also says if the change is significant
+pub fn describe_duration_change(x: &MeanEstimate) -> String {
+    if x.is_significant() {
+        format!("{} (significant)", describe_duration(x))
+    } else {
+        format!("{} (within noise)", describe_duration(x))
+    }
+}
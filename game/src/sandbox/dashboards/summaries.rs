@@ -13,7 +13,7 @@ use widgetry::{
 
 use crate::app::{App, Transition};
 use crate::common::color_for_mode;
-use crate::sandbox::dashboards::DashTab;
+use crate::sandbox::dashboards::{seeds, DashTab};
 
 pub struct TripSummaries {
     panel: Panel,
@@ -54,6 +54,7 @@ impl TripSummaries {
                     Widget::col(filters).padding(16).outline(2.0, Color::WHITE),
                     Widget::col(vec![
                         summary_boxes(ctx, app, &filter),
+                        seed_estimates(ctx, app, &filter),
                         Widget::row(vec![
                             contingency_table(ctx, app, &filter),
                             scatter_plot(ctx, app, &filter),
@@ -68,6 +69,21 @@ impl TripSummaries {
     }
 }
 
+impl TripSummaries {
+    fn filter(&self) -> Filter {
+        let mut filter = Filter {
+            changes_pct: self.panel.dropdown_value("filter"),
+            modes: BTreeSet::new(),
+        };
+        for m in TripMode::all() {
+            if self.panel.is_checked(m.ongoing_verb()) {
+                filter.modes.insert(m);
+            }
+        }
+        filter
+    }
+}
+
 impl State<App> for TripSummaries {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         match self.panel.event(ctx) {
@@ -82,6 +98,16 @@ impl State<App> for TripSummaries {
                         Err(err) => PopupMsg::new(ctx, "Export failed", vec![err.to_string()]),
                     });
                 }
+                seeds::RUN_SEEDS => {
+                    if let Err(err) = seeds::run_seeds(ctx, app) {
+                        return Transition::Push(PopupMsg::new(
+                            ctx,
+                            "Can't compare across RNG seeds",
+                            vec![err],
+                        ));
+                    }
+                    Transition::Replace(TripSummaries::new(ctx, app, self.filter()))
+                }
                 "close" => {
                     return Transition::Pop;
                 }
@@ -92,16 +118,7 @@ impl State<App> for TripSummaries {
                     return t;
                 }
 
-                let mut filter = Filter {
-                    changes_pct: self.panel.dropdown_value("filter"),
-                    modes: BTreeSet::new(),
-                };
-                for m in TripMode::all() {
-                    if self.panel.is_checked(m.ongoing_verb()) {
-                        filter.modes.insert(m);
-                    }
-                }
-                Transition::Replace(TripSummaries::new(ctx, app, filter))
+                Transition::Replace(TripSummaries::new(ctx, app, self.filter()))
             }
             _ => Transition::Keep,
         }
@@ -218,6 +235,57 @@ fn summary_boxes(ctx: &mut EventCtx, app: &App, filter: &Filter) -> Widget {
     .evenly_spaced()
 }
 
+// The summary boxes compare against one run of the scenario, which might just be noise. Once the
+// player runs the scenario with more seeds, show how consistent the changes are.
+fn seed_estimates(ctx: &mut EventCtx, app: &App, filter: &Filter) -> Widget {
+    let runs = match app.seed_runs() {
+        Some(runs) => runs,
+        None => {
+            return seeds::button(ctx, app);
+        }
+    };
+
+    let mut txt = Text::from(
+        Line(format!(
+            "Across {} RNG seeds, with 95% confidence intervals",
+            runs.seeds.len()
+        ))
+        .small_heading(),
+    );
+    txt.add(Line("The change filter doesn't apply here").secondary());
+    let mut rows = vec![(
+        "All selected trips".to_string(),
+        runs.trip_estimates(&|_, mode| filter.modes.contains(&mode)),
+    )];
+    for mode in &filter.modes {
+        rows.push((
+            mode.noun().to_string(),
+            runs.trip_estimates(&|_, m| m == *mode),
+        ));
+    }
+    for (name, x) in rows {
+        txt.add(Line(format!(
+            "{}: mean trip time change {}",
+            name,
+            seeds::describe_duration_change(&x.mean_trip_time_change)
+        )));
+        txt.add(
+            Line(format!(
+                "{} faster, saved {}. {} slower, lost {}",
+                x.num_faster,
+                seeds::describe_duration(&x.total_saved),
+                x.num_slower,
+                seeds::describe_duration(&x.total_lost)
+            ))
+            .secondary(),
+        );
+    }
+    txt.draw(ctx)
+        .container()
+        .padding(16)
+        .outline(2.0, Color::WHITE)
+}
+
 fn scatter_plot(ctx: &mut EventCtx, app: &App, filter: &Filter) -> Widget {
     if app.has_prebaked().is_none() {
         return Widget::nothing();
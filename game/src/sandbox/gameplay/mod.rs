@@ -161,6 +161,15 @@ impl GameplayMode {
                     _ => {}
                 },
                 EditCmd::ChangeRouteSchedule { .. } => {}
+                // Adding stops, rerouting buses, and removing roads are as drastic as changing
+                // lanes
+                EditCmd::ChangeBusStop { .. }
+                | EditCmd::ChangeRoute { .. }
+                | EditCmd::RemoveRoad { .. } => {
+                    if !self.can_edit_lanes() {
+                        return false;
+                    }
+                }
             }
         }
         true
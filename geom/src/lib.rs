@@ -12,7 +12,7 @@ pub use crate::polyline::{ArrowCap, PolyLine};
 pub use crate::pt::{HashablePt2D, Pt2D};
 pub use crate::ring::Ring;
 pub use crate::speed::Speed;
-pub use crate::stats::{HgramValue, Histogram, Statistic};
+pub use crate::stats::{HgramValue, Histogram, MeanEstimate, Statistic};
 pub use crate::time::Time;
 
 mod angle;
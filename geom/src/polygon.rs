@@ -297,6 +297,16 @@ impl Polygon {
         result
     }
 
+    /// Unlike `union_all`, this calculates the actual union of the polygons, merging ones that
+    /// overlap. Disjoint input results in multiple polygons.
+    pub fn merge_all(list: Vec<Polygon>) -> Vec<Polygon> {
+        let mut result = geo::MultiPolygon(Vec::new());
+        for p in list {
+            result = result.union(&to_geo(p.points()));
+        }
+        from_multi(result)
+    }
+
     // TODO Result won't be a nice Ring
     pub fn intersection(&self, other: &Polygon) -> Vec<Polygon> {
         from_multi(to_geo(self.points()).intersection(&to_geo(other.points())))
@@ -502,3 +512,24 @@ fn downsize(input: Vec<usize>) -> Vec<u16> {
     }
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_all() {
+        let square = |x: f64, y: f64| Polygon::rectangle(10.0, 10.0).translate(x, y);
+
+        let overlapping = Polygon::merge_all(vec![square(0.0, 0.0), square(5.0, 5.0)]);
+        assert_eq!(overlapping.len(), 1);
+        assert!((overlapping[0].area() - 175.0).abs() < 0.1);
+
+        let touching = Polygon::merge_all(vec![square(0.0, 0.0), square(10.0, 0.0)]);
+        assert_eq!(touching.len(), 1);
+        assert!((touching[0].area() - 200.0).abs() < 0.1);
+
+        let disjoint = Polygon::merge_all(vec![square(0.0, 0.0), square(20.0, 0.0)]);
+        assert_eq!(disjoint.len(), 2);
+    }
+}
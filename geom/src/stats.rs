@@ -40,6 +40,92 @@ impl std::fmt::Display for Statistic {
     }
 }
 
+/// Estimates the mean of some measurement repeated across independent runs, like simulations
+/// using different RNG seeds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeanEstimate {
+    pub samples: Vec<f64>,
+    pub mean: f64,
+    /// Half the width of the 95% confidence interval around the mean, using Student's
+    /// t-distribution. Zero when there are fewer than two samples.
+    pub margin: f64,
+}
+
+impl MeanEstimate {
+    pub fn new(samples: Vec<f64>) -> MeanEstimate {
+        let n = samples.len();
+        let mean = if n == 0 {
+            0.0
+        } else {
+            samples.iter().sum::<f64>() / (n as f64)
+        };
+        let margin = if n < 2 {
+            0.0
+        } else {
+            let variance =
+                samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / ((n - 1) as f64);
+            t_critical_95(n - 1) * (variance / (n as f64)).sqrt()
+        };
+        MeanEstimate {
+            samples,
+            mean,
+            margin,
+        }
+    }
+
+    /// The 95% confidence interval for the mean
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        (self.mean - self.margin, self.mean + self.margin)
+    }
+
+    /// True if the 95% confidence interval excludes zero. If the samples are paired differences
+    /// (after minus before, for the same RNG seed), this is a two-sided paired t-test at the 5%
+    /// level. A single sample is never significant.
+    pub fn is_significant(&self) -> bool {
+        if self.samples.len() < 2 {
+            return false;
+        }
+        let (low, high) = self.confidence_interval();
+        low > 0.0 || high < 0.0
+    }
+
+    /// Describes a change with its confidence interval, like "-3.50 ± 1.20 (significant)".
+    pub fn describe_change(&self) -> String {
+        if self.is_significant() {
+            format!("{} (significant)", self)
+        } else {
+            format!("{} (within noise)", self)
+        }
+    }
+}
+
+impl std::fmt::Display for MeanEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.2} ± {:.2}", self.mean, self.margin)
+    }
+}
+
+/// The two-sided 95% critical value of Student's t-distribution
+fn t_critical_95(degrees_of_freedom: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    if degrees_of_freedom == 0 {
+        panic!("Need at least one degree of freedom");
+    }
+    if degrees_of_freedom <= TABLE.len() {
+        TABLE[degrees_of_freedom - 1]
+    } else if degrees_of_freedom <= 60 {
+        2.000
+    } else if degrees_of_freedom <= 120 {
+        1.980
+    } else {
+        1.960
+    }
+}
+
 pub trait HgramValue<T>: Copy + std::cmp::Ord + std::fmt::Display {
     // TODO Weird name because I can't figure out associated type mess in FanChart
     fn hgram_zero() -> T;
@@ -186,3 +272,23 @@ impl<T: HgramValue<T>> Histogram<T> {
         self.describe() == other.describe()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_estimate() {
+        let x = MeanEstimate::new(vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(x.mean, 2.5);
+        // The sample standard deviation is sqrt(5/3), so the margin is 3.182 * sqrt(5/3) / 2
+        assert!((x.margin - 2.054).abs() < 0.001);
+        assert!(x.is_significant());
+        assert!(!MeanEstimate::new(vec![-1.0, 2.0, 3.0]).is_significant());
+        assert!(!MeanEstimate::new(vec![5.0]).is_significant());
+        assert_eq!(
+            MeanEstimate::new(vec![5.0]).describe_change(),
+            "5.00 ± 0.00 (within noise)"
+        );
+    }
+}
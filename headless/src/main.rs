@@ -192,7 +192,7 @@ fn handle_command(
             edits.commands.push(EditCmd::ChangeIntersection {
                 i: id,
                 old: map.get_i_edit(id),
-                new: EditIntersection::TrafficSignal(ts.export(map)),
+                new: EditIntersection::TrafficSignal(ts.export(map), ts.transit_priority.clone()),
             });
             map.must_apply_edits(edits, &mut Timer::throwaway());
             map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
@@ -334,6 +334,11 @@ fn handle_command(
                 &map.edit_road_cmd(r, |_| {}).to_perma(map),
             ))
         }
+        "/map/check-edits" => {
+            let edits = abstutil::from_json::<PermanentMapEdits>(body)?.to_edits(map)?;
+            let problems = map.check_edits(edits, &mut Timer::new("check edits"));
+            Ok(abstutil::to_json(&problems))
+        }
         "/map/get-intersection-geometry" => {
             let i = IntersectionID(params["id"].parse::<usize>()?);
             Ok(abstutil::to_json(&export_geometry(map, i)))
@@ -0,0 +1,59 @@
+//! Applies an `EditScript` to a map and saves the resulting edits, so a policy can be applied to
+//! a whole city reproducibly. For example, this script lowers speed limits on residential roads
+//! and turns parking into bike lanes along one street:
+//!
+//! ```json
+//! {
+//!   "edits_name": "slow streets",
+//!   "rules": [
+//!     {
+//!       "roads": { "highway": ["residential"], "speed_limit_above_mph": 25.0 },
+//!       "changes": [{ "SpeedLimitMph": 20.0 }]
+//!     },
+//!     {
+//!       "roads": { "name": "Roosevelt Way Northeast" },
+//!       "changes": [{ "ChangeLaneType": { "from": "Parking", "to": "Biking" } }]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Usage: apply_edit_script --map=data/system/seattle/maps/montlake.bin --script=slow.json
+//! [--edits=existing.json]
+//!
+//! If `--edits` is passed, the script is applied on top of those edits.
+
+use abstutil::{CmdArgs, Timer};
+use map_model::{EditScript, Map, MapEdits};
+
+fn main() {
+    let mut args = CmdArgs::new();
+    let map_path = args.required("--map");
+    let script_path = args.required("--script");
+    let edits_path = args.optional("--edits");
+    args.done();
+
+    let mut timer = Timer::new("apply edit script");
+    let mut map = Map::new(map_path, &mut timer);
+    if let Some(path) = edits_path {
+        match MapEdits::load(&map, path.clone(), &mut timer) {
+            Ok(edits) => {
+                map.must_apply_edits(edits, &mut timer);
+            }
+            Err(err) => panic!("Couldn't load {}: {}", path, err),
+        }
+    }
+    let script: EditScript = abstutil::read_json(script_path, &mut timer);
+
+    let (edits, problems) = script.evaluate(&map);
+    for problem in problems {
+        println!("{}", problem);
+    }
+    println!(
+        "{} commands, saving to {}",
+        edits.commands.len(),
+        abstutil::path_edits(map.get_name(), &edits.edits_name)
+    );
+    map.must_apply_edits(edits, &mut timer);
+    map.save_edits();
+}
@@ -0,0 +1,36 @@
+//! Lists problems that some edits introduce to a map, like transit routes that can't reach their
+//! stops anymore. Exits with an error if there are any, so this can be used to check proposals
+//! automatically.
+//!
+//! Usage: check_edits data/player/edits/seattle/montlake/proposal.json [--output=problems.json]
+
+use abstutil::{CmdArgs, Timer};
+use map_model::{Map, MapEdits, PermanentMapEdits};
+
+fn main() {
+    let mut args = CmdArgs::new();
+    let path = args.required_free();
+    let output = args.optional("--output");
+    args.done();
+
+    let mut timer = Timer::new("check edits");
+    let map_name = abstutil::read_json::<PermanentMapEdits>(path.clone(), &mut timer).map_name;
+    let mut map = Map::new(map_name.path(), &mut timer);
+    let edits = match MapEdits::load(&map, path.clone(), &mut timer) {
+        Ok(edits) => edits,
+        Err(err) => panic!("Couldn't load {}: {}", path, err),
+    };
+    let problems = map.check_edits(edits, &mut timer);
+
+    for problem in &problems {
+        println!("{}", problem.describe(&map));
+    }
+    if let Some(output) = output {
+        abstutil::write_json(output, &problems);
+    }
+    if !problems.is_empty() {
+        println!("{} problems found", problems.len());
+        std::process::exit(1);
+    }
+    println!("No problems found");
+}
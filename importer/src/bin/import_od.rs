@@ -0,0 +1,143 @@
+use std::error::Error;
+
+use geojson::{GeoJson, Value};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use serde::Deserialize;
+
+use abstutil::{CmdArgs, Timer};
+use geom::{GPSBounds, LonLat, Polygon, Ring};
+use map_model::Map;
+use sim::{DepartureProfile, ODEntry, ODMatrix, ODZone, Scenario, TripMode, TripPurpose};
+
+/// Turns an origin-destination matrix between zones into a scenario.
+///
+/// - `--zones` is a GeoJSON file with a polygon for each zone. `--zone_field` names the property
+///   identifying each zone; it defaults to `name`.
+/// - `--od` is a CSV file with columns `origin`, `destination`, `mode` (walk, bike, transit, or
+///   drive), `trips`, and optionally `period` and `purpose`.
+/// - `--profile` is an optional JSON file describing when trips in each period depart, like
+///   `{"periods": {"AM": [[7, 2.0], [8, 3.0]], "PM": [[17, 3.0], [18, 2.0]]}}`. Without it, trips
+///   depart evenly throughout the day, and the `period` column must be empty.
+fn main() {
+    let mut args = CmdArgs::new();
+    let map = args.required("--map");
+    let zones_path = args.required("--zones");
+    let zone_field = args
+        .optional("--zone_field")
+        .unwrap_or_else(|| "name".to_string());
+    let od_path = args.required("--od");
+    let profile_path = args.optional("--profile");
+    let scenario_name = args.required("--scenario_name");
+    let seed: u64 = args.optional_parse("--rng", |s| s.parse()).unwrap_or(42);
+    args.done();
+
+    let mut timer = Timer::new("import origin-destination matrix");
+    let map = Map::new(map, &mut timer);
+    let od = ODMatrix {
+        zones: read_zones(&zones_path, &zone_field, map.get_gps_bounds()).unwrap(),
+        entries: read_entries(&od_path).unwrap(),
+        profile: if let Some(path) = profile_path {
+            abstutil::read_json(path, &mut timer)
+        } else {
+            DepartureProfile::all_day()
+        },
+    };
+
+    let mut s = Scenario::empty(&map, &scenario_name);
+    // Include all buses/trains
+    s.only_seed_buses = None;
+    s.people = od
+        .disaggregate(&map, &mut XorShiftRng::seed_from_u64(seed), &mut timer)
+        .unwrap();
+    s.save();
+}
+
+fn read_zones(
+    path: &str,
+    zone_field: &str,
+    gps_bounds: &GPSBounds,
+) -> Result<Vec<ODZone>, Box<dyn Error>> {
+    let raw = String::from_utf8(abstutil::slurp_file(path)?)?;
+    let features = match raw.parse::<GeoJson>()? {
+        GeoJson::FeatureCollection(collection) => collection.features,
+        GeoJson::Feature(feature) => vec![feature],
+        GeoJson::Geometry(_) => {
+            return Err(format!("{} has no features", path).into());
+        }
+    };
+    let mut zones = Vec::new();
+    for feature in features {
+        let name = match feature.properties.as_ref().and_then(|p| p.get(zone_field)) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(x) => x.to_string(),
+            None => {
+                return Err(format!("A zone is missing the {} property", zone_field).into());
+            }
+        };
+        // Holes are ignored
+        let rings: Vec<Vec<Vec<f64>>> = match feature.geometry.map(|g| g.value) {
+            Some(Value::Polygon(polygon)) => vec![polygon[0].clone()],
+            Some(Value::MultiPolygon(polygons)) => {
+                polygons.into_iter().map(|p| p[0].clone()).collect()
+            }
+            _ => {
+                return Err(format!("Zone {} isn't a polygon", name).into());
+            }
+        };
+        let mut polygons = Vec::new();
+        for pts in rings {
+            let pts: Vec<LonLat> = pts
+                .into_iter()
+                .map(|pt| LonLat::new(pt[0], pt[1]))
+                .collect();
+            polygons.push(Ring::new(gps_bounds.convert(&pts))?.to_polygon());
+        }
+        zones.push(ODZone {
+            name,
+            polygon: Polygon::union_all(polygons),
+        });
+    }
+    Ok(zones)
+}
+
+#[derive(Deserialize)]
+struct Record {
+    origin: String,
+    destination: String,
+    mode: String,
+    trips: f64,
+    #[serde(default)]
+    period: Option<String>,
+    #[serde(default)]
+    purpose: Option<TripPurpose>,
+}
+
+fn read_entries(path: &str) -> Result<Vec<ODEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for rec in csv::Reader::from_path(path)?.deserialize() {
+        let rec: Record = rec?;
+        let mode = match rec.mode.to_lowercase().as_ref() {
+            "walk" => TripMode::Walk,
+            "bike" => TripMode::Bike,
+            "transit" => TripMode::Transit,
+            "drive" => TripMode::Drive,
+            x => {
+                return Err(format!("Unknown mode {}", x).into());
+            }
+        };
+        entries.push(ODEntry {
+            origin: rec.origin,
+            destination: rec.destination,
+            mode,
+            // TODO OD matrices rarely say why people travel. Same default as import_traffic.
+            purpose: rec.purpose.unwrap_or(TripPurpose::Shopping),
+            period: rec
+                .period
+                .filter(|p| !p.is_empty())
+                .unwrap_or_else(|| "all day".to_string()),
+            count: rec.trips,
+        });
+    }
+    Ok(entries)
+}
@@ -0,0 +1,105 @@
+//! Combine and compare proposals for the same map, made by different people.
+//!
+//! - `map_edits merge a.json b.json --output=c.json` combines both proposals. If they change the
+//!   same road, intersection, route, or bus stop differently, the conflicts are listed and nothing
+//!   is written, unless `--keep_first` is passed.
+//! - `map_edits rebase a.json --onto=b.json --output=c.json` replays `a` on top of `b`. When both
+//!   change the same thing, the version in `a` wins, but the conflicts are still listed.
+//! - `map_edits subtract a.json b.json --output=c.json` removes everything from `a` that `b` also
+//!   does.
+//! - `map_edits diff a.json b.json` lists everything different between them.
+//!
+//! The result keeps the name and description of `a`, unless `--name` is passed.
+
+use abstutil::{CmdArgs, Timer};
+use map_model::{Map, MapEdits, PermanentMapEdits};
+
+fn main() {
+    let mut args = CmdArgs::new();
+    let command = args.required_free();
+    let first_path = args.required_free();
+    let second_path = if command == "rebase" {
+        args.required("--onto")
+    } else {
+        args.required_free()
+    };
+    let output = if command == "diff" {
+        None
+    } else {
+        Some(args.required("--output"))
+    };
+    let name = args.optional("--name");
+    let keep_first = args.enabled("--keep_first");
+    args.done();
+
+    let mut timer = Timer::new("load edits");
+    let map_name = abstutil::maybe_read_json::<PermanentMapEdits>(first_path.clone(), &mut timer)
+        .map(|perma| perma.map_name)
+        .unwrap_or_else(|err| {
+            panic!(
+                "Couldn't read {}: {}. Edits in an old format have to be opened and saved in the \
+                 game first.",
+                first_path, err
+            )
+        });
+    let mut map = Map::new(map_name.path(), &mut timer);
+    let first = load(&map, first_path, &mut timer);
+    let second = load(&map, second_path, &mut timer);
+
+    let mut result = match command.as_ref() {
+        "merge" => {
+            let (merged, conflicts) = first.merge(&second);
+            for conflict in &conflicts {
+                println!("Conflict: {}", conflict.describe(&map));
+            }
+            if !conflicts.is_empty() && !keep_first {
+                println!(
+                    "{} conflicts. Pass --keep_first to use the version from {} for these.",
+                    conflicts.len(),
+                    first.edits_name
+                );
+                std::process::exit(1);
+            }
+            merged
+        }
+        "rebase" => {
+            let (rebased, conflicts) = first.rebase(&second);
+            for conflict in &conflicts {
+                println!(
+                    "Conflict, using {}: {}",
+                    first.edits_name,
+                    conflict.describe(&map)
+                );
+            }
+            rebased
+        }
+        "subtract" => first.subtract(&second),
+        "diff" => {
+            println!("- {}", first.edits_name);
+            println!("+ {}", second.edits_name);
+            for diff in first.diff(&second) {
+                println!("{}", diff.describe(&map));
+            }
+            return;
+        }
+        x => panic!(
+            "Unknown command {}. Use merge, rebase, subtract, or diff",
+            x
+        ),
+    };
+    if let Some(name) = name {
+        result.edits_name = name;
+    }
+    println!("Result has {} commands", result.commands.len());
+
+    // Bus stops and routes created by the edits have to exist in the map before they can be saved.
+    map.must_apply_edits(result, &mut timer);
+    abstutil::write_json(output.unwrap(), &map.get_edits().to_permanent(&map));
+}
+
+fn load(map: &Map, path: String, timer: &mut Timer) -> MapEdits {
+    match MapEdits::load(map, path.clone(), timer) {
+        Ok(edits) => edits,
+        Err(err) => panic!("Couldn't load {}: {}", path, err),
+    }
+}
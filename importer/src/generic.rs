@@ -1,40 +1,247 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-use abstutil::MapName;
+use abstutil::{MapName, Timer};
+use geom::{GPSBounds, LonLat, Polygon, Ring};
 
 use crate::configuration::ImporterConfiguration;
-use crate::utils::{download, osmconvert};
+use crate::population::PopulationSource;
+use crate::utils::{clip_osm, download, download_kml};
 
 /// Importing a new city can be done just by filling out this config file and specifying some
-/// polygon boundaries. Most fields are directly from `convert_osm::Options`.
+/// polygon boundaries. It lives in `importer/config/$city/cfg.json`. Most fields are directly from
+/// `convert_osm::Options`.
 ///
-/// If any extra data is imported for a city (like collisions or population), then for now, don't
-/// use this.
+/// Paths to local files are relative to the data directory, like `input/seattle/blockface.bin`.
 #[derive(Serialize, Deserialize)]
 pub struct GenericCityImporter {
     /// The URL to a .osm or .osm.pbf file containing the entire city.
     /// http://download.geofabrik.de/ is recommended.
     pub osm_url: String,
+    /// Where the file from `osm_url` is saved. If missing, it's named after the end of the URL,
+    /// in `input/$city/osm/`.
+    #[serde(default)]
+    pub osm_local_path: Option<String>,
+    /// Any other files to download before importing.
+    #[serde(default)]
+    pub extra_downloads: Vec<ExtraDownload>,
+    /// KML files to download, clip, and convert to `ExtraShapes` before importing.
+    #[serde(default)]
+    pub kml_downloads: Vec<KmlDownload>,
+    /// If provided, import collisions from this UK STATS19 dataset after importing each map.
+    #[serde(default)]
+    pub stats19_collisions: Option<ExtraDownload>,
+    /// If provided, process extra input files in a custom format after importing each map.
+    #[serde(default)]
+    pub extra_data: Option<ExtraDataSource>,
 
     pub map_config: map_model::MapConfig,
     pub onstreet_parking: convert_osm::OnstreetParking,
     pub public_offstreet_parking: convert_osm::PublicOffstreetParking,
     pub private_offstreet_parking: convert_osm::PrivateOffstreetParking,
+    /// Overrides private_offstreet_parking for individual maps, keyed by map name.
+    #[serde(default)]
+    pub private_offstreet_parking_per_map: BTreeMap<String, convert_osm::PrivateOffstreetParking>,
     /// If provided, pull elevation data from this SRTM file. The SRTM parser is incorrect, so the
     /// results will be nonsense.
     pub elevation: Option<String>,
     /// OSM railway=rail will be included as light rail if so. Cosmetic only.
     pub include_railroads: bool,
+
+    /// Maps covering several adjacent boundaries, stitched together into one map. This is keyed
+    /// by the name of the new map, and lists the names of the boundaries to combine. The
+    /// boundaries must overlap or touch.
+    #[serde(default)]
+    pub stitched_maps: BTreeMap<String, Vec<String>>,
+
+    /// If provided, join residents and jobs from local census or parcel data to buildings after
+    /// importing each map.
+    #[serde(default)]
+    pub population: Option<PopulationSource>,
+    /// If true, add bus schedules from GTFS to each map. Only King County Metro's GTFS, downloaded
+    /// to `input/seattle/google_transit/`, is understood so far.
+    #[serde(default)]
+    pub gtfs_schedules: bool,
+
+    /// How to produce the "weekday" scenario with `--scenario`. If missing, the city has no
+    /// scenarios.
+    #[serde(default)]
+    pub scenario: Option<ScenarioSource>,
+    /// After generating the scenario for these maps, set the number of private offstreet spots in
+    /// each building to the number of cars parked there overnight.
+    #[serde(default)]
+    pub adjust_parking_from_scenario: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExtraDownload {
+    pub url: String,
+    /// .zip and .gz files are automatically uncompressed. For a .zip, this is the path of the
+    /// file extracted from it.
+    pub local_path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KmlDownload {
+    pub url: String,
+    /// Where the .bin file is written.
+    pub local_path: String,
+    /// The path to an osmosis polygon. Shapes are clipped to its bounding box.
+    pub bounds: String,
+    /// If false, keep shapes partly out-of-bounds.
+    pub require_all_pts_in_bounds: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ExtraDataSource {
+    /// Count the residents of each planning area in Berlin, for `population` to use.
+    BerlinPopulation,
+    /// Convert Seattle's collision data.
+    SeattleCollisions,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ScenarioSource {
+    /// Transform travel demand from https://github.com/psrc/soundcast. Seattle specific, and
+    /// requires the `scenarios` feature.
+    Soundcast,
+    /// Generate commuting trips between residential and commercial buildings, deterministically
+    /// seeded.
+    ProletariatRobot { rng_seed: u64 },
 }
 
 impl GenericCityImporter {
+    pub fn load(city: &str, timer: &mut Timer) -> GenericCityImporter {
+        match abstutil::maybe_read_json::<GenericCityImporter>(
+            format!("importer/config/{}/cfg.json", city),
+            timer,
+        ) {
+            Ok(city_cfg) => city_cfg,
+            Err(err) => {
+                panic!("Can't import city {}: {}", city, err);
+            }
+        }
+    }
+
+    /// Download all input files, if they're missing.
+    pub fn input(&self, name: &MapName, timer: &mut Timer, config: &ImporterConfiguration) {
+        download(config, &self.local_osm_file(name), &self.osm_url);
+        for dl in self.extra_downloads.iter().chain(&self.stats19_collisions) {
+            download(config, &dl.local_path, &dl.url);
+        }
+        for dl in &self.kml_downloads {
+            let bounds =
+                geom::GPSBounds::from(geom::LonLat::read_osmosis_polygon(&dl.bounds).unwrap());
+            download_kml(
+                &dl.local_path,
+                &dl.url,
+                &bounds,
+                dl.require_all_pts_in_bounds,
+                timer,
+            );
+        }
+    }
+
     pub fn osm_to_raw(
         &self,
         name: MapName,
         timer: &mut abstutil::Timer,
         config: &ImporterConfiguration,
     ) {
-        let local_osm_file = format!(
+        self.input(&name, timer, config);
+        if let Some(parts) = self.stitched_maps.get(&name.map) {
+            stitch_boundaries(&name, parts, &self.clip_path(&name));
+        }
+        self.clip_input(&name, timer);
+
+        let map = convert_osm::convert(self.options(&name), timer);
+        map.save();
+
+        // Always do this, it's idempotent and fast
+        if let Some(ref dl) = self.stats19_collisions {
+            let shapes =
+                kml::ExtraShapes::load_csv(&abstutil::path(&dl.local_path), &map.gps_bounds, timer)
+                    .unwrap();
+            let collisions = collisions::import_stats19(shapes, &dl.url);
+            abstutil::write_binary(
+                abstutil::path(format!("input/{}/collisions.bin", name.city)),
+                &collisions,
+            );
+        }
+    }
+
+    /// Apply OsmChange files to a map's OSM input, in order, and regenerate the RawMap.
+    pub fn apply_osc(&self, name: MapName, osc_paths: &[String], timer: &mut Timer) {
+        timer.start(format!("apply {:?} to {}", osc_paths, name.describe()));
+        self.clip_input(&name, timer);
+        let map = convert_osm::osc::apply_osc(osc_paths, self.options(&name), timer).unwrap();
+        timer.stop(format!("apply {:?} to {}", osc_paths, name.describe()));
+        map.save();
+    }
+
+    fn options(&self, name: &MapName) -> convert_osm::Options {
+        convert_osm::Options {
+            osm_input: abstutil::path(self.osm_input(name)),
+            name: name.clone(),
+
+            clip: Some(self.clip_path(name)),
+            map_config: self.map_config.clone(),
+            onstreet_parking: match self.onstreet_parking {
+                convert_osm::OnstreetParking::Blockface(ref path) => {
+                    convert_osm::OnstreetParking::Blockface(abstutil::path(path))
+                }
+                ref x => x.clone(),
+            },
+            public_offstreet_parking: match self.public_offstreet_parking {
+                convert_osm::PublicOffstreetParking::GIS(ref path) => {
+                    convert_osm::PublicOffstreetParking::GIS(abstutil::path(path))
+                }
+                ref x => x.clone(),
+            },
+            private_offstreet_parking: self
+                .private_offstreet_parking_per_map
+                .get(&name.map)
+                .unwrap_or(&self.private_offstreet_parking)
+                .clone(),
+            elevation: self.elevation.as_ref().map(abstutil::path),
+            include_railroads: self.include_railroads,
+        }
+    }
+
+    fn clip_path(&self, name: &MapName) -> String {
+        if self.stitched_maps.contains_key(&name.map) {
+            abstutil::path(format!(
+                "input/{}/stitched_boundaries/{}.poly",
+                name.city, name.map
+            ))
+        } else {
+            format!("importer/config/{}/{}.poly", name.city, name.map)
+        }
+    }
+
+    /// Large .osm.pbf extracts are clipped once per map, then the smaller .osm is imported.
+    fn osm_input(&self, name: &MapName) -> String {
+        let local = self.local_osm_file(name);
+        if local.ends_with(".pbf") {
+            format!("input/{}/osm/{}.osm", name.city, name.map)
+        } else {
+            local
+        }
+    }
+
+    fn clip_input(&self, name: &MapName, timer: &mut Timer) {
+        let local = self.local_osm_file(name);
+        if local.ends_with(".pbf") {
+            clip_osm(&local, &self.clip_path(name), self.osm_input(name), timer);
+        }
+    }
+
+    fn local_osm_file(&self, name: &MapName) -> String {
+        if let Some(ref path) = self.osm_local_path {
+            return path.clone();
+        }
+        format!(
             "input/{}/osm/{}",
             name.city,
             std::path::Path::new(&self.osm_url)
@@ -43,31 +250,101 @@ impl GenericCityImporter {
                 .to_os_string()
                 .into_string()
                 .unwrap()
-        );
-        download(config, &local_osm_file, &self.osm_url);
-
-        osmconvert(
-            &local_osm_file,
-            format!("importer/config/{}/{}.poly", name.city, name.map),
-            format!("input/{}/osm/{}.osm", name.city, name.map),
-            config,
-        );
-
-        let map = convert_osm::convert(
-            convert_osm::Options {
-                osm_input: abstutil::path(format!("input/{}/osm/{}.osm", name.city, name.map)),
-                name: name.clone(),
-
-                clip: Some(format!("importer/config/{}/{}.poly", name.city, name.map)),
-                map_config: self.map_config.clone(),
-                onstreet_parking: self.onstreet_parking.clone(),
-                public_offstreet_parking: self.public_offstreet_parking.clone(),
-                private_offstreet_parking: self.private_offstreet_parking.clone(),
-                elevation: self.elevation.clone(),
-                include_railroads: self.include_railroads,
-            },
-            timer,
-        );
-        map.save();
+        )
+    }
+}
+
+/// Combine several boundaries into one, writing a new osmosis polygon.
+fn stitch_boundaries(name: &MapName, parts: &Vec<String>, output: &str) {
+    let boundaries: Vec<Vec<LonLat>> = parts
+        .iter()
+        .map(|part| {
+            LonLat::read_osmosis_polygon(&format!("importer/config/{}/{}.poly", name.city, part))
+                .unwrap()
+        })
+        .collect();
+    let pts = match merge_boundaries(boundaries) {
+        Ok(pts) => pts,
+        Err(err) => panic!(
+            "Can't stitch together {} for {}: {}",
+            parts.join(", "),
+            name.describe(),
+            err
+        ),
+    };
+    std::fs::create_dir_all(std::path::Path::new(output).parent().unwrap()).unwrap();
+    LonLat::write_osmosis_polygon(output, &pts).unwrap();
+}
+
+fn merge_boundaries(boundaries: Vec<Vec<LonLat>>) -> Result<Vec<LonLat>, String> {
+    let gps_bounds = GPSBounds::from(boundaries.iter().flatten().cloned().collect());
+    let mut merged = Polygon::merge_all(
+        boundaries
+            .iter()
+            .map(|pts| Ring::must_new(gps_bounds.convert(pts)).to_polygon())
+            .collect(),
+    );
+    if merged.len() != 1 {
+        return Err(format!(
+            "the boundaries don't overlap, so they form {} separate areas",
+            merged.len()
+        ));
+    }
+    Ok(gps_bounds.convert_back(merged.pop().unwrap().points()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(lon: f64, lat: f64) -> Vec<LonLat> {
+        vec![
+            LonLat::new(lon, lat),
+            LonLat::new(lon + 0.01, lat),
+            LonLat::new(lon + 0.01, lat + 0.01),
+            LonLat::new(lon, lat + 0.01),
+            LonLat::new(lon, lat),
+        ]
+    }
+
+    #[test]
+    fn merge_adjacent_boundaries() {
+        let close = |x: f64, y: f64| (x - y).abs() < 1e-6;
+
+        // Overlapping and touching boundaries merge into one covering all of them
+        let pts = merge_boundaries(vec![
+            square(-122.33, 47.6),
+            square(-122.325, 47.605),
+            square(-122.315, 47.605),
+        ])
+        .unwrap();
+        let min_lon = pts.iter().map(|pt| pt.x()).fold(f64::MAX, f64::min);
+        let max_lon = pts.iter().map(|pt| pt.x()).fold(f64::MIN, f64::max);
+        let min_lat = pts.iter().map(|pt| pt.y()).fold(f64::MAX, f64::min);
+        let max_lat = pts.iter().map(|pt| pt.y()).fold(f64::MIN, f64::max);
+        assert!(close(min_lon, -122.33));
+        assert!(close(max_lon, -122.305));
+        assert!(close(min_lat, 47.6));
+        assert!(close(max_lat, 47.615));
+
+        assert!(merge_boundaries(vec![square(-122.33, 47.6), square(-122.3, 47.6)]).is_err());
+    }
+
+    #[test]
+    fn parse_every_config() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("config");
+        let mut cities = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path().join("cfg.json");
+            if !path.exists() {
+                continue;
+            }
+            let file = std::fs::File::open(&path).unwrap();
+            if let Err(err) = abstutil::from_json_reader::<_, GenericCityImporter>(file) {
+                panic!("Can't parse {}: {}", path.display(), err);
+            }
+            cities += 1;
+        }
+        assert!(cities > 0);
     }
 }
@@ -1,17 +1,20 @@
 //! It's assumed that the importer is run with the current directory as the project repository; aka
 //! `./data/` and `./importer/config` must exist.
 
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
 use abstutil::{basename, MapName};
 
 use configuration::{load_configuration, ImporterConfiguration};
 use dependencies::are_dependencies_callable;
+use generic::{ExtraDataSource, GenericCityImporter, ScenarioSource};
 
 mod berlin;
 mod configuration;
 mod dependencies;
 mod generic;
-mod leeds;
-mod london;
+mod population;
 mod seattle;
 #[cfg(feature = "scenarios")]
 mod soundcast;
@@ -28,6 +31,8 @@ struct Job {
 
     skip_ch: bool,
     keep_bldg_tags: bool,
+    remap_ids: bool,
+    apply_osc: Option<String>,
 
     only_map: Option<String>,
 
@@ -54,6 +59,12 @@ fn main() {
         skip_ch: args.enabled("--skip_ch"),
         // Preserve OSM tags for buildings, increasing the file size.
         keep_bldg_tags: args.enabled("--keep_bldg_tags"),
+        // When rebuilding a map that already exists, match objects between the old and new
+        // versions, then translate saved scenarios and prebaked results to the new IDs.
+        remap_ids: args.enabled("--remap_ids"),
+        // Apply OsmChange (.osc) files, separated by commas, to the OSM input and regenerate the
+        // RawMap. Combine with --map to rebuild.
+        apply_osc: args.optional("--apply_osc"),
 
         // Only process one map. If not specified, process all maps defined by clipping polygons in
         // importer/config/$city/.
@@ -67,14 +78,15 @@ fn main() {
     };
     args.done();
     if !job.osm_to_raw
+        && job.apply_osc.is_none()
         && !job.raw_to_map
         && !job.scenario
         && !job.city_overview
         && job.oneshot.is_none()
     {
         println!(
-            "Nothing to do! Pass some combination of --raw, --map, --scenario, --city_overview, \
-             or --oneshot"
+            "Nothing to do! Pass some combination of --raw, --apply_osc, --map, --scenario, \
+             --city_overview, or --oneshot"
         );
         std::process::exit(1);
     }
@@ -102,34 +114,43 @@ fn main() {
         return;
     }
 
+    let mut timer = abstutil::Timer::new("import map data");
+
+    let city_cfg = GenericCityImporter::load(&job.city, &mut timer);
+
     let names = if let Some(n) = job.only_map {
         println!("- Just working on {}", n);
         vec![n]
     } else {
         println!("- Working on all {} maps", job.city);
-        abstutil::list_dir(format!("importer/config/{}", job.city))
+        let mut names: Vec<String> = abstutil::list_dir(format!("importer/config/{}", job.city))
             .into_iter()
             .filter(|path| path.ends_with(".poly"))
             .map(basename)
-            .collect()
+            .collect();
+        names.extend(city_cfg.stitched_maps.keys().cloned());
+        names
     };
 
-    let mut timer = abstutil::Timer::new("import map data");
-
     let (maybe_popdat, maybe_huge_map) = if job.scenario {
-        assert_eq!(job.city, "seattle");
-
-        #[cfg(feature = "scenarios")]
-        {
-            let (popdat, huge_map) = seattle::ensure_popdat_exists(&mut timer, &config);
-            (Some(popdat), Some(huge_map))
-        }
+        match city_cfg.scenario {
+            Some(ScenarioSource::Soundcast) => {
+                #[cfg(feature = "scenarios")]
+                {
+                    let (popdat, huge_map) =
+                        seattle::ensure_popdat_exists(&city_cfg, &mut timer, &config);
+                    (Some(popdat), Some(huge_map))
+                }
 
-        #[cfg(not(feature = "scenarios"))]
-        {
-            panic!("Can't do --scenario without the scenarios feature compiled in");
-            // Nonsense to make the type-checker work
-            (Some(true), Some(true))
+                #[cfg(not(feature = "scenarios"))]
+                {
+                    panic!("Can't do --scenario without the scenarios feature compiled in");
+                    // Nonsense to make the type-checker work
+                    (Some(true), Some(true))
+                }
+            }
+            Some(ScenarioSource::ProletariatRobot { .. }) => (None, None),
+            None => panic!("{} has no scenario defined in its cfg.json", job.city),
         }
     } else {
         (None, None)
@@ -137,43 +158,44 @@ fn main() {
 
     for name in names {
         if job.osm_to_raw {
-            match job.city.as_ref() {
-                "berlin" => berlin::osm_to_raw(&name, &mut timer, &config),
-                "leeds" => leeds::osm_to_raw(&name, &mut timer, &config),
-                "london" => london::osm_to_raw(&name, &mut timer, &config),
-                "seattle" => seattle::osm_to_raw(&name, &mut timer, &config),
-                x => {
-                    match abstutil::maybe_read_json::<generic::GenericCityImporter>(
-                        format!("importer/config/{}/cfg.json", x),
-                        &mut timer,
-                    ) {
-                        Ok(city_cfg) => {
-                            city_cfg.osm_to_raw(MapName::new(x, &name), &mut timer, &config);
-                        }
-                        Err(err) => {
-                            panic!("Can't import city {}: {}", x, err);
-                        }
-                    }
-                }
+            city_cfg.osm_to_raw(MapName::new(&job.city, &name), &mut timer, &config);
+
+            match city_cfg.extra_data {
+                Some(ExtraDataSource::BerlinPopulation) => berlin::import_extra_data(&mut timer),
+                Some(ExtraDataSource::SeattleCollisions) => seattle::import_extra_data(&mut timer),
+                None => {}
             }
         }
+        if let Some(ref osc) = job.apply_osc {
+            let paths: Vec<String> = osc.split(',').map(|x| x.to_string()).collect();
+            city_cfg.apply_osc(MapName::new(&job.city, &name), &paths, &mut timer);
+        }
         let name = MapName::new(&job.city, &name);
 
         let mut maybe_map = if job.raw_to_map {
+            let maybe_old_map = if job.remap_ids && abstutil::file_exists(name.path()) {
+                Some(map_model::Map::new(name.path(), &mut timer))
+            } else {
+                None
+            };
             let mut map = utils::raw_to_map(&name, !job.skip_ch, job.keep_bldg_tags, &mut timer);
+            if let Some(old_map) = maybe_old_map {
+                utils::remap_saved_data(&old_map, &map, &mut timer);
+            }
 
-            // Another strange step in the pipeline.
-            if name == MapName::new("berlin", "center") {
-                timer.start(format!(
-                    "distribute residents from planning areas for {}",
-                    name.describe()
-                ));
-                berlin::distribute_residents(&mut map, &mut timer);
-                timer.stop(format!(
-                    "distribute residents from planning areas for {}",
-                    name.describe()
-                ));
-            } else if name.city == "seattle" {
+            if let Some(ref source) = city_cfg.population {
+                timer.start(format!("distribute population for {}", name.describe()));
+                if let Err(err) = population::distribute(&mut map, source, &mut timer) {
+                    panic!(
+                        "Can't distribute population for {}: {}",
+                        name.describe(),
+                        err
+                    );
+                }
+                map.save();
+                timer.stop(format!("distribute population for {}", name.describe()));
+            }
+            if city_cfg.gtfs_schedules {
                 timer.start(format!("add GTFS schedules for {}", name.describe()));
                 seattle::add_gtfs_schedules(&mut map);
                 timer.stop(format!("add GTFS schedules for {}", name.describe()));
@@ -186,8 +208,22 @@ fn main() {
             None
         };
 
+        if let (true, Some(ScenarioSource::ProletariatRobot { rng_seed })) =
+            (job.scenario, &city_cfg.scenario)
+        {
+            timer.start(format!("scenario for {}", name.describe()));
+            let mut scenario = sim::ScenarioGenerator::proletariat_robot(
+                maybe_map.as_ref().unwrap(),
+                &mut XorShiftRng::seed_from_u64(*rng_seed),
+                &mut timer,
+            );
+            scenario.scenario_name = "weekday".to_string();
+            scenario.save();
+            timer.stop(format!("scenario for {}", name.describe()));
+        }
+
         #[cfg(feature = "scenarios")]
-        if job.scenario {
+        if job.scenario && maybe_popdat.is_some() {
             timer.start(format!("scenario for {}", name.describe()));
             let scenario = soundcast::make_weekday_scenario(
                 maybe_map.as_ref().unwrap(),
@@ -199,7 +235,7 @@ fn main() {
             timer.stop(format!("scenario for {}", name.describe()));
 
             // This is a strange ordering.
-            if name.map == "downtown" || name.map == "south_seattle" {
+            if city_cfg.adjust_parking_from_scenario.contains(&name.map) {
                 timer.start(format!("adjust parking for {}", name.describe()));
                 seattle::adjust_private_parking(maybe_map.as_mut().unwrap(), &scenario);
                 timer.stop(format!("adjust parking for {}", name.describe()));
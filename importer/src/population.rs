@@ -0,0 +1,531 @@
+//! Join residents and jobs from local census or parcel data to buildings. Building types are
+//! otherwise guessed from OSM tags, and the number of residents and workers estimated from the
+//! building size.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use aabb_quadtree::QuadTree;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Distance, FindClosest, LonLat, Polygon, Pt2D, Ring};
+use kml::{ExtraShape, ExtraShapes};
+use map_model::{BuildingID, BuildingType, Map};
+
+/// Where to find population data, and which fields describe what. Specified in a city's
+/// cfg.json.
+#[derive(Serialize, Deserialize)]
+pub struct PopulationSource {
+    /// A local file, relative to the data directory. It can be GeoJSON (`.geojson`), KML
+    /// (`.kml`), already converted `ExtraShapes` (`.bin`), or a CSV file (`.csv`) with
+    /// `Longitude` and `Latitude` columns.
+    ///
+    /// Areas like census blocks are split between the buildings inside them, in proportion to
+    /// floor area. Points like parcel centroids are assigned to the building containing them, or
+    /// the closest one nearby.
+    pub path: String,
+    /// The field with the number of residents. If missing, residents are still estimated.
+    #[serde(default)]
+    pub residents_field: Option<String>,
+    /// The fields with the number of jobs, keyed by the name of the sector. Buildings get the
+    /// total number of workers and the number per sector. If empty, workers are still estimated.
+    #[serde(default)]
+    pub jobs_fields: BTreeMap<String, String>,
+}
+
+enum Geometry {
+    Point(Pt2D),
+    Areas(Vec<Polygon>),
+}
+
+struct Record {
+    geometry: Geometry,
+    residents: f64,
+    /// Keyed by sector
+    jobs: BTreeMap<String, f64>,
+}
+
+/// Overwrite the number of residents and workers in every building covered by the population
+/// data.
+pub fn distribute(
+    map: &mut Map,
+    source: &PopulationSource,
+    timer: &mut Timer,
+) -> Result<(), Box<dyn Error>> {
+    if source.residents_field.is_none() && source.jobs_fields.is_empty() {
+        return Err(format!(
+            "Population data from {} doesn't specify residents_field or jobs_fields",
+            source.path
+        )
+        .into());
+    }
+    let records = load(map, source, timer)?;
+
+    // Buildings covered by the data get their counts replaced, even if they wind up with 0. Every
+    // covered building has an entry in residents.
+    let mut residents: BTreeMap<BuildingID, f64> = BTreeMap::new();
+    let mut jobs: BTreeMap<&String, BTreeMap<BuildingID, f64>> = source
+        .jobs_fields
+        .keys()
+        .map(|sector| (sector, BTreeMap::new()))
+        .collect();
+    let mut closest = FindClosest::new(map.get_bounds());
+    // The quadtree's ID is just a building, found by its bounding box
+    let mut quadtree: QuadTree<BuildingID> = QuadTree::default(map.get_bounds().as_bbox());
+    for b in map.all_buildings() {
+        closest.add(b.id, b.polygon.points());
+        quadtree.insert_with_box(b.id, b.polygon.get_bounds().as_bbox());
+    }
+    let mut unmatched = 0;
+    let mut total_residents = 0.0;
+
+    timer.start_iter("match population data to buildings", records.len());
+    for rec in records {
+        timer.next();
+        match rec.geometry {
+            Geometry::Point(pt) => {
+                // Prefer the building containing the point, then the closest one
+                let candidates = closest.all_close_pts(pt, Distance::meters(50.0));
+                let b = candidates
+                    .iter()
+                    .find(|(b, _, _)| map.get_b(*b).polygon.contains_pt(pt))
+                    .or_else(|| candidates.iter().min_by_key(|(_, _, dist)| *dist))
+                    .map(|(b, _, _)| *b);
+                if let Some(b) = b {
+                    *residents.entry(b).or_insert(0.0) += rec.residents;
+                    for (sector, num_jobs) in &rec.jobs {
+                        *jobs.get_mut(sector).unwrap().entry(b).or_insert(0.0) += num_jobs;
+                    }
+                    total_residents += rec.residents;
+                } else {
+                    unmatched += 1;
+                }
+            }
+            Geometry::Areas(polygons) => {
+                // If the area is partly out-of-bounds, then scale down linearly based on the area
+                // of the overlapping part.
+                let total_area: f64 = polygons.iter().map(|p| p.area()).sum();
+                let overlap_area: f64 = polygons
+                    .iter()
+                    .flat_map(|p| p.intersection(map.get_boundary_polygon()))
+                    .map(|p| p.area())
+                    .sum();
+                if overlap_area == 0.0 || total_area == 0.0 {
+                    continue;
+                }
+                let pct_overlap = (overlap_area / total_area).min(1.0);
+
+                let mut bldgs: BTreeSet<BuildingID> = BTreeSet::new();
+                for p in &polygons {
+                    for (b, _, _) in quadtree.query(p.get_bounds().as_bbox()) {
+                        if p.contains_pt(map.get_b(*b).label_center) {
+                            bldgs.insert(*b);
+                        }
+                    }
+                }
+                if bldgs.is_empty() {
+                    unmatched += 1;
+                    continue;
+                }
+                let bldgs: Vec<BuildingID> = bldgs.into_iter().collect();
+                for b in &bldgs {
+                    residents.entry(*b).or_insert(0.0);
+                }
+                let num_residents = pct_overlap * rec.residents;
+                split_by_floor_area(map, &bldgs, num_residents, &mut residents, |t| {
+                    t.has_residents()
+                });
+                for (sector, num_jobs) in &rec.jobs {
+                    split_by_floor_area(
+                        map,
+                        &bldgs,
+                        pct_overlap * num_jobs,
+                        jobs.get_mut(sector).unwrap(),
+                        |t| match t {
+                            BuildingType::Commercial(_)
+                            | BuildingType::ResidentialCommercial(_, _) => true,
+                            BuildingType::Residential { .. } | BuildingType::Empty => false,
+                        },
+                    );
+                }
+                total_residents += num_residents;
+            }
+        }
+    }
+    timer.note(format!(
+        "{} residents assigned to {} buildings. {} records didn't match any building.",
+        prettyprint_usize(total_residents as usize),
+        prettyprint_usize(residents.len()),
+        prettyprint_usize(unmatched)
+    ));
+
+    let replace_residents = source.residents_field.is_some();
+    let replace_jobs = !source.jobs_fields.is_empty();
+    let residents = round_preserving_sum(residents);
+    // Round each sector separately, so the total is the sum of the sectors
+    let mut jobs_by_sector: BTreeMap<BuildingID, BTreeMap<String, usize>> = BTreeMap::new();
+    for (sector, counts) in jobs {
+        let counts = round_preserving_sum(counts);
+        timer.note(format!(
+            "{} {} jobs assigned",
+            prettyprint_usize(counts.values().sum()),
+            sector
+        ));
+        for (b, num_jobs) in counts {
+            if num_jobs > 0 {
+                jobs_by_sector
+                    .entry(b)
+                    .or_insert_with(BTreeMap::new)
+                    .insert(sector.clone(), num_jobs);
+            }
+        }
+    }
+    for (b, num_residents) in residents {
+        let sectors = jobs_by_sector.remove(&b).unwrap_or_else(BTreeMap::new);
+        let bldg_type = new_bldg_type(
+            &map.get_b(b).bldg_type,
+            if replace_residents {
+                Some(num_residents)
+            } else {
+                None
+            },
+            if replace_jobs {
+                Some(sectors.values().sum())
+            } else {
+                None
+            },
+        );
+        map.hack_override_bldg_type(b, bldg_type);
+        if replace_jobs {
+            map.hack_override_jobs_by_sector(b, sectors);
+        }
+    }
+    Ok(())
+}
+
+fn load(
+    map: &Map,
+    source: &PopulationSource,
+    timer: &mut Timer,
+) -> Result<Vec<Record>, Box<dyn Error>> {
+    let path = abstutil::path(&source.path);
+    let gps_bounds = map.get_gps_bounds();
+    let shapes: Vec<ExtraShape> = if path.ends_with(".geojson") {
+        load_geojson(&path)?
+    } else if path.ends_with(".kml") {
+        kml::load(&path, gps_bounds, false, timer)?.shapes
+    } else if path.ends_with(".bin") {
+        abstutil::maybe_read_binary::<ExtraShapes>(path.clone(), timer)?.shapes
+    } else if path.ends_with(".csv") {
+        ExtraShapes::load_csv(&path, gps_bounds, timer)?.shapes
+    } else {
+        return Err(format!("Don't know how to read population data from {}", path).into());
+    };
+
+    let mut records: Vec<Record> = Vec::new();
+    // Multipolygons show up as several shapes. Group them back up, so the counts aren't applied
+    // more than once.
+    let mut multipolygons: BTreeMap<String, usize> = BTreeMap::new();
+    for shape in shapes {
+        let geometry = if shape.points.len() == 1 {
+            if !gps_bounds.contains(shape.points[0]) {
+                continue;
+            }
+            Geometry::Point(shape.points[0].to_pt(gps_bounds))
+        } else {
+            match Ring::new(gps_bounds.convert(&shape.points)) {
+                Ok(ring) => Geometry::Areas(vec![ring.to_polygon()]),
+                Err(err) => {
+                    timer.warn(format!("Skipping population area: {}", err));
+                    continue;
+                }
+            }
+        };
+        if let Some(key) = shape.attributes.get(MULTIPOLYGON_KEY) {
+            if let Some(idx) = multipolygons.get(key) {
+                if let (Geometry::Areas(existing), Geometry::Areas(polygons)) =
+                    (&mut records[*idx].geometry, geometry)
+                {
+                    existing.extend(polygons);
+                }
+                continue;
+            }
+            multipolygons.insert(key.clone(), records.len());
+        }
+        let residents = if let Some(ref field) = source.residents_field {
+            parse_count(&shape, field)?
+        } else {
+            0.0
+        };
+        let mut jobs = BTreeMap::new();
+        for (sector, field) in &source.jobs_fields {
+            jobs.insert(sector.clone(), parse_count(&shape, field)?);
+        }
+        records.push(Record {
+            geometry,
+            residents,
+            jobs,
+        });
+    }
+    Ok(records)
+}
+
+/// Each feature becomes one shape. Multipolygons become one shape per polygon, with an extra
+/// attribute to group them back up.
+fn load_geojson(path: &str) -> Result<Vec<ExtraShape>, Box<dyn Error>> {
+    let raw = String::from_utf8(abstutil::slurp_file(path)?)?;
+    let features = match raw.parse::<geojson::GeoJson>()? {
+        geojson::GeoJson::FeatureCollection(collection) => collection.features,
+        geojson::GeoJson::Feature(feature) => vec![feature],
+        geojson::GeoJson::Geometry(_) => {
+            return Err(format!("{} has no features with properties", path).into());
+        }
+    };
+
+    let mut shapes = Vec::new();
+    for (idx, feature) in features.into_iter().enumerate() {
+        let mut attributes = BTreeMap::new();
+        for (k, v) in feature.properties.unwrap_or_default() {
+            attributes.insert(
+                k,
+                match v {
+                    serde_json::Value::String(s) => s,
+                    x => x.to_string(),
+                },
+            );
+        }
+        let to_lon_lat = |pts: &Vec<Vec<f64>>| -> Vec<LonLat> {
+            pts.iter().map(|pt| LonLat::new(pt[0], pt[1])).collect()
+        };
+        let rings: Vec<Vec<LonLat>> = match feature.geometry.map(|g| g.value) {
+            Some(geojson::Value::Point(pt)) => vec![vec![LonLat::new(pt[0], pt[1])]],
+            // Holes are ignored
+            Some(geojson::Value::Polygon(polygon)) => vec![to_lon_lat(&polygon[0])],
+            Some(geojson::Value::MultiPolygon(polygons)) => {
+                attributes.insert(MULTIPOLYGON_KEY.to_string(), idx.to_string());
+                polygons.iter().map(|p| to_lon_lat(&p[0])).collect()
+            }
+            _ => {
+                continue;
+            }
+        };
+        for points in rings {
+            shapes.push(ExtraShape {
+                points,
+                attributes: attributes.clone(),
+            });
+        }
+    }
+    Ok(shapes)
+}
+
+const MULTIPOLYGON_KEY: &str = "abst:multipolygon";
+
+fn parse_count(shape: &ExtraShape, field: &str) -> Result<f64, Box<dyn Error>> {
+    match shape.attributes.get(field) {
+        // Missing values are common in census data for unpopulated areas
+        None => Ok(0.0),
+        Some(value) if value.trim().is_empty() => Ok(0.0),
+        Some(value) => match value.trim().parse::<f64>() {
+            Ok(n) if n >= 0.0 && n.is_finite() => Ok(n),
+            _ => Err(format!("{}={} isn't a count", field, value).into()),
+        },
+    }
+}
+
+/// Add `total` to the buildings, weighted by their floor area. Only buildings of the right type
+/// are used, unless none of them are.
+fn split_by_floor_area<F: Fn(&BuildingType) -> bool>(
+    map: &Map,
+    bldgs: &Vec<BuildingID>,
+    total: f64,
+    counts: &mut BTreeMap<BuildingID, f64>,
+    right_type: F,
+) {
+    if total == 0.0 {
+        return;
+    }
+    let mut candidates: Vec<BuildingID> = bldgs
+        .iter()
+        .filter(|b| right_type(&map.get_b(**b).bldg_type))
+        .cloned()
+        .collect();
+    if candidates.is_empty() {
+        candidates = bldgs.clone();
+    }
+    let floor_area = |b: BuildingID| {
+        let bldg = map.get_b(b);
+        bldg.polygon.area() * bldg.levels.max(1.0)
+    };
+    let total_area: f64 = candidates.iter().map(|b| floor_area(*b)).sum();
+    for b in candidates {
+        let share = if total_area > 0.0 {
+            floor_area(b) / total_area
+        } else {
+            1.0 / (candidates.len() as f64)
+        };
+        *counts.entry(b).or_insert(0.0) += share * total;
+    }
+}
+
+/// Round each count, keeping the total the same. The buildings with the largest remainders get
+/// rounded up.
+fn round_preserving_sum(counts: BTreeMap<BuildingID, f64>) -> BTreeMap<BuildingID, usize> {
+    let total = counts.values().sum::<f64>().round() as usize;
+    let mut result: BTreeMap<BuildingID, usize> = counts
+        .iter()
+        .map(|(b, n)| (*b, n.floor() as usize))
+        .collect();
+    let mut remaining = total.saturating_sub(result.values().sum());
+    let mut remainders: Vec<(BuildingID, f64)> = counts
+        .into_iter()
+        .map(|(b, n)| (b, n - n.floor()))
+        .collect();
+    // Sort by remainder descending, breaking ties deterministically
+    remainders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    for (b, _) in remainders {
+        if remaining == 0 {
+            break;
+        }
+        *result.get_mut(&b).unwrap() += 1;
+        remaining -= 1;
+    }
+    result
+}
+
+fn new_bldg_type(
+    orig: &BuildingType,
+    residents: Option<usize>,
+    jobs: Option<usize>,
+) -> BuildingType {
+    let (orig_residents, orig_jobs, num_housing_units) = match orig {
+        BuildingType::Residential {
+            num_residents,
+            num_housing_units,
+        } => (*num_residents, 0, *num_housing_units),
+        BuildingType::ResidentialCommercial(residents, workers) => (*residents, *workers, 0),
+        BuildingType::Commercial(workers) => (0, *workers, 0),
+        BuildingType::Empty => (0, 0, 0),
+    };
+    let residents = residents.unwrap_or(orig_residents);
+    let jobs = jobs.unwrap_or(orig_jobs);
+    match (residents > 0, jobs > 0) {
+        (true, true) => BuildingType::ResidentialCommercial(residents, jobs),
+        (true, false) => BuildingType::Residential {
+            num_residents: residents,
+            num_housing_units,
+        },
+        (false, true) => BuildingType::Commercial(jobs),
+        // Nobody lives or works here, according to the data. Keep the kind of building from OSM.
+        (false, false) => match orig {
+            BuildingType::Residential { .. } => BuildingType::Residential {
+                num_residents: 0,
+                num_housing_units,
+            },
+            BuildingType::ResidentialCommercial(_, _) => BuildingType::ResidentialCommercial(0, 0),
+            BuildingType::Commercial(_) => BuildingType::Commercial(0),
+            BuildingType::Empty => BuildingType::Empty,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_counts() {
+        let counts: BTreeMap<BuildingID, f64> = vec![
+            (BuildingID(0), 1.4),
+            (BuildingID(1), 2.7),
+            (BuildingID(2), 0.4),
+            (BuildingID(3), 0.5),
+        ]
+        .into_iter()
+        .collect();
+        // The total is 5.0, and buildings 1 and 3 have the largest remainders
+        let rounded = round_preserving_sum(counts);
+        assert_eq!(rounded.values().sum::<usize>(), 5);
+        assert_eq!(
+            rounded.into_iter().collect::<Vec<_>>(),
+            vec![
+                (BuildingID(0), 1),
+                (BuildingID(1), 3),
+                (BuildingID(2), 0),
+                (BuildingID(3), 1)
+            ]
+        );
+
+        // Ties go to the lowest ID
+        let counts: BTreeMap<BuildingID, f64> = (0..4).map(|i| (BuildingID(i), 0.5)).collect();
+        let rounded = round_preserving_sum(counts);
+        assert_eq!(rounded[&BuildingID(0)], 1);
+        assert_eq!(rounded[&BuildingID(1)], 1);
+        assert_eq!(rounded[&BuildingID(2)], 0);
+        assert_eq!(rounded[&BuildingID(3)], 0);
+    }
+
+    #[test]
+    fn parse_counts() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert("POP".to_string(), " 12.5 ".to_string());
+        attributes.insert("BLANK".to_string(), "".to_string());
+        attributes.insert("NEGATIVE".to_string(), "-3".to_string());
+        attributes.insert("TEXT".to_string(), "n/a".to_string());
+        let shape = ExtraShape {
+            points: Vec::new(),
+            attributes,
+        };
+        assert_eq!(parse_count(&shape, "POP").unwrap(), 12.5);
+        assert_eq!(parse_count(&shape, "BLANK").unwrap(), 0.0);
+        assert_eq!(parse_count(&shape, "MISSING").unwrap(), 0.0);
+        assert!(parse_count(&shape, "NEGATIVE").is_err());
+        assert!(parse_count(&shape, "TEXT").is_err());
+    }
+
+    #[test]
+    fn new_bldg_types() {
+        let house = BuildingType::Residential {
+            num_residents: 3,
+            num_housing_units: 2,
+        };
+        // Only residents are replaced, and the housing units are kept
+        assert!(matches!(
+            new_bldg_type(&house, Some(5), None),
+            BuildingType::Residential {
+                num_residents: 5,
+                num_housing_units: 2
+            }
+        ));
+        // Gaining jobs makes it mixed-use
+        assert!(matches!(
+            new_bldg_type(&house, None, Some(4)),
+            BuildingType::ResidentialCommercial(3, 4)
+        ));
+        // Losing residents makes it commercial
+        assert!(matches!(
+            new_bldg_type(&BuildingType::ResidentialCommercial(3, 4), Some(0), None),
+            BuildingType::Commercial(4)
+        ));
+        // Nobody lives or works here, but the kind of building stays
+        assert!(matches!(
+            new_bldg_type(&house, Some(0), Some(0)),
+            BuildingType::Residential {
+                num_residents: 0,
+                num_housing_units: 2
+            }
+        ));
+        assert!(matches!(
+            new_bldg_type(&BuildingType::Empty, None, Some(0)),
+            BuildingType::Empty
+        ));
+        assert!(matches!(
+            new_bldg_type(&BuildingType::Empty, Some(2), None),
+            BuildingType::Residential {
+                num_residents: 2,
+                num_housing_units: 0
+            }
+        ));
+    }
+}
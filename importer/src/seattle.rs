@@ -4,149 +4,36 @@ use std::fs::File;
 use aabb_quadtree::QuadTree;
 use serde::Deserialize;
 
-use abstutil::{MapName, MultiMap, Timer};
+use abstutil::{MultiMap, Timer};
 use geom::{Duration, Polygon, Ring, Time};
 use kml::ExtraShapes;
 use map_model::{BuildingID, BuildingType, BusRouteID, Map};
 use sim::Scenario;
 
-use crate::configuration::ImporterConfiguration;
-use crate::utils::{download, download_kml, osmconvert};
-
-fn input(config: &ImporterConfiguration, timer: &mut Timer) {
-    download(
-        config,
-        "input/seattle/N47W122.hgt",
-        "https://dds.cr.usgs.gov/srtm/version2_1/SRTM1/Region_01/N47W122.hgt.zip",
-    );
-    download(
-        config,
-        "input/seattle/osm/washington-latest.osm.pbf",
-        "http://download.geofabrik.de/north-america/us/washington-latest.osm.pbf",
-    );
-    // Soundcast data comes from https://github.com/psrc/soundcast/releases
-    download(
-        config,
-        "input/seattle/parcels_urbansim.txt",
-        "https://www.dropbox.com/s/t9oug9lwhdwfc04/psrc_2014.zip?dl=0",
-    );
-
-    let bounds = geom::GPSBounds::from(
-        geom::LonLat::read_osmosis_polygon("importer/config/seattle/huge_seattle.poly").unwrap(),
-    );
-    // From http://data-seattlecitygis.opendata.arcgis.com/datasets/blockface
-    download_kml(
-        "input/seattle/blockface.bin",
-        "https://opendata.arcgis.com/datasets/a1458ad1abca41869b81f7c0db0cd777_0.kml",
-        &bounds,
-        true,
-        timer,
-    );
-    // From https://data-seattlecitygis.opendata.arcgis.com/datasets/public-garages-or-parking-lots
-    download_kml(
-        "input/seattle/offstreet_parking.bin",
-        "http://data-seattlecitygis.opendata.arcgis.com/datasets/8e52dfde6d5d45948f7a90654c8d50cd_0.kml",
-        &bounds,
-        true,
-        timer
-    );
-
-    download(
-        config,
-        "input/seattle/google_transit/",
-        "http://metro.kingcounty.gov/gtfs/google_transit.zip",
-    );
-
-    // From
-    // https://data-seattlecitygis.opendata.arcgis.com/datasets/5b5c745e0f1f48e7a53acec63a0022ab_0
-    download(
-        config,
-        "input/seattle/collisions.kml",
-        "https://opendata.arcgis.com/datasets/5b5c745e0f1f48e7a53acec63a0022ab_0.kml",
-    );
-
+/// Import Seattle-specific data in custom formats. The input files are downloaded using cfg.json.
+pub fn import_extra_data(timer: &mut Timer) {
     // This is a little expensive, so delete data/input/seattle/collisions.bin to regenerate this.
     if !abstutil::file_exists("data/input/seattle/collisions.bin") {
+        let bounds = geom::GPSBounds::from(
+            geom::LonLat::read_osmosis_polygon("importer/config/seattle/huge_seattle.poly")
+                .unwrap(),
+        );
         let shapes = kml::load("data/input/seattle/collisions.kml", &bounds, true, timer).unwrap();
         let collisions = collisions::import_seattle(
             shapes,
             "https://data-seattlecitygis.opendata.arcgis.com/datasets/5b5c745e0f1f48e7a53acec63a0022ab_0");
         abstutil::write_binary("data/input/seattle/collisions.bin".to_string(), &collisions);
     }
-
-    // From https://data-seattlecitygis.opendata.arcgis.com/datasets/parcels-1
-    download_kml(
-        "input/seattle/zoning_parcels.bin",
-        "https://opendata.arcgis.com/datasets/42863f1debdc47488a1c2b9edd38053e_2.kml",
-        &bounds,
-        true,
-        timer,
-    );
-
-    // From
-    // https://data-seattlecitygis.opendata.arcgis.com/datasets/current-land-use-zoning-detail
-    download_kml(
-        "input/seattle/land_use.bin",
-        "https://opendata.arcgis.com/datasets/dd29065b5d01420e9686570c2b77502b_0.kml",
-        &bounds,
-        false,
-        timer,
-    );
-}
-
-pub fn osm_to_raw(name: &str, timer: &mut Timer, config: &ImporterConfiguration) {
-    input(config, timer);
-    osmconvert(
-        "input/seattle/osm/washington-latest.osm.pbf",
-        format!("importer/config/seattle/{}.poly", name),
-        format!("input/seattle/osm/{}.osm", name),
-        config,
-    );
-
-    let map = convert_osm::convert(
-        convert_osm::Options {
-            osm_input: abstutil::path(format!("input/seattle/osm/{}.osm", name)),
-            name: MapName::seattle(name),
-
-            clip: Some(format!("importer/config/seattle/{}.poly", name)),
-            map_config: map_model::MapConfig {
-                driving_side: map_model::DrivingSide::Right,
-                bikes_can_use_bus_lanes: true,
-                inferred_sidewalks: true,
-            },
-
-            onstreet_parking: convert_osm::OnstreetParking::Blockface(abstutil::path(
-                "input/seattle/blockface.bin",
-            )),
-            public_offstreet_parking: convert_osm::PublicOffstreetParking::GIS(abstutil::path(
-                "input/seattle/offstreet_parking.bin",
-            )),
-            private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(
-                // TODO Utter guesses
-                match name {
-                    "downtown" => 5,
-                    "lakeslice" => 3,
-                    "south_seattle" => 5,
-                    "udistrict" => 5,
-                    _ => 1,
-                },
-            ),
-            elevation: Some(abstutil::path("input/seattle/N47W122.hgt")),
-            // They mess up 16th and E Marginal badly enough to cause gridlock.
-            include_railroads: false,
-        },
-        timer,
-    );
-    map.save();
 }
 
 /// Download and pre-process data needed to generate Seattle scenarios.
 #[cfg(feature = "scenarios")]
 pub fn ensure_popdat_exists(
+    city_cfg: &crate::generic::GenericCityImporter,
     timer: &mut Timer,
-    config: &ImporterConfiguration,
+    config: &crate::configuration::ImporterConfiguration,
 ) -> (crate::soundcast::PopDat, map_model::Map) {
-    let huge_name = MapName::seattle("huge_seattle");
+    let huge_name = abstutil::MapName::seattle("huge_seattle");
 
     if abstutil::file_exists(abstutil::path_popdat()) {
         println!("- {} exists, not regenerating it", abstutil::path_popdat());
@@ -157,7 +44,7 @@ pub fn ensure_popdat_exists(
     }
 
     if !abstutil::file_exists(abstutil::path_raw_map(&huge_name)) {
-        osm_to_raw("huge_seattle", timer, config);
+        city_cfg.osm_to_raw(huge_name.clone(), timer, config);
     }
     let huge_map = if abstutil::file_exists(huge_name.path()) {
         map_model::Map::new(huge_name.path(), timer)
@@ -95,16 +95,9 @@ pub fn download_kml(
     std::fs::rename(tmp, output.replace(".bin", ".kml")).unwrap();
 }
 
-// Uses osmconvert to clip the input .osm (or .pbf) against a polygon and produce some output.
-// Skips if the output exists.
-pub fn osmconvert(
-    input: &str,
-    clipping_polygon: String,
-    output: String,
-    config: &ImporterConfiguration,
-) {
+// Clips a .osm.pbf against a polygon, producing a smaller .osm. Skips if the output exists.
+pub fn clip_osm(input: &str, clipping_polygon: &str, output: String, timer: &mut Timer) {
     let input = abstutil::path(input);
-    let clipping_polygon = clipping_polygon;
     let output = abstutil::path(output);
 
     if Path::new(&output).exists() {
@@ -112,14 +105,7 @@ pub fn osmconvert(
         return;
     }
     println!("- Clipping {} to {}", input, clipping_polygon);
-
-    must_run_cmd(
-        Command::new(&config.osmconvert)
-            .arg(input)
-            .arg(format!("-B={}", clipping_polygon))
-            .arg("--complete-ways")
-            .arg(format!("-o={}", output)),
-    );
+    convert_osm::pbf::clip(&input, clipping_polygon, &output, timer).unwrap();
 }
 
 // Converts a RawMap to a Map.
@@ -149,3 +135,29 @@ pub fn raw_to_map(
 
     map
 }
+
+/// After rebuilding a map, translate scenarios and prebaked results that refer to the previous
+/// version of the map.
+pub fn remap_saved_data(old_map: &map_model::Map, new_map: &map_model::Map, timer: &mut Timer) {
+    let name = new_map.get_name();
+    timer.start(format!("remap IDs for {}", name.describe()));
+    let remap = map_model::IDRemap::new(old_map, new_map);
+    for line in remap.describe(old_map) {
+        timer.note(line);
+    }
+    abstutil::write_binary(abstutil::path_id_remap(name), &remap);
+
+    for scenario_name in abstutil::list_all_objects(abstutil::path_all_scenarios(name)) {
+        let scenario: sim::Scenario =
+            abstutil::read_binary(abstutil::path_scenario(name, &scenario_name), timer);
+        scenario.remap_ids(&remap).save();
+
+        let path = abstutil::path_prebaked_results(name, &scenario_name);
+        if abstutil::file_exists(&path) {
+            let mut analytics: sim::Analytics = abstutil::read_binary(path.clone(), timer);
+            analytics.remap_ids(&remap);
+            abstutil::write_binary(path, &analytics);
+        }
+    }
+    timer.stop(format!("remap IDs for {}", name.describe()));
+}
@@ -332,6 +332,7 @@ impl ColorScheme {
                 LaneType::SharedLeftTurn => self.driving_lane,
                 LaneType::Construction => self.parking_lane,
                 LaneType::LightRail => unreachable!(),
+                LaneType::Buffer => self.sidewalk,
             },
         }
     }
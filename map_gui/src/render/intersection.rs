@@ -3,7 +3,7 @@ use std::cell::RefCell;
 use geom::{Angle, ArrowCap, Distance, Line, PolyLine, Polygon, Pt2D, Ring, Time, EPSILON_DIST};
 use map_model::{
     Direction, DrivingSide, Intersection, IntersectionID, IntersectionType, LaneType, Map, Road,
-    RoadWithStopSign, Turn, TurnType, SIDEWALK_THICKNESS,
+    RoadWithStopSign, SignType, Turn, TurnType, SIDEWALK_THICKNESS,
 };
 use widgetry::{Color, Drawable, GeomBatch, GfxCtx, RewriteColor};
 
@@ -61,8 +61,8 @@ impl DrawIntersection {
         }
 
         for turn in map.get_turns_in_intersection(i.id) {
-            // Avoid double-rendering
-            if turn.turn_type == TurnType::Crosswalk
+            // Avoid double-rendering. Unmarked crosswalks have nothing painted on the ground.
+            if turn.is_marked_crosswalk(map)
                 && !turn.other_crosswalk_ids.iter().any(|id| *id < turn.id)
             {
                 make_crosswalk(&mut default_geom, turn, map, app.cs());
@@ -83,9 +83,9 @@ impl DrawIntersection {
             }
             IntersectionType::StopSign => {
                 for ss in map.get_stop_sign(i.id).roads.values() {
-                    if ss.must_stop {
-                        if let Some((octagon, pole)) = DrawIntersection::stop_sign_geom(ss, map) {
-                            default_geom.push(app.cs().stop_sign, octagon);
+                    if ss.sign_type != SignType::NoSign {
+                        if let Some((sign, pole)) = DrawIntersection::stop_sign_geom(ss, map) {
+                            default_geom.push(app.cs().stop_sign, sign);
                             default_geom.push(app.cs().stop_sign_pole, pole);
                         }
                     }
@@ -110,7 +110,8 @@ impl DrawIntersection {
         g.upload(default_geom)
     }
 
-    // Returns the (octagon, pole) if there's room to draw it.
+    // Returns the (sign, pole) if there's room to draw it. Yield signs are triangles, and stop signs
+    // (or the spot for a missing one) are octagons.
     pub fn stop_sign_geom(ss: &RoadWithStopSign, map: &Map) -> Option<(Polygon, Polygon)> {
         let trim_back = Distance::meters(0.1);
         let edge_lane = map.get_l(ss.lane_closest_to_edge);
@@ -129,7 +130,11 @@ impl DrawIntersection {
             last_line.shift_left(edge_lane.width)
         };
 
-        let octagon = make_octagon(last_line.pt2(), Distance::meters(1.0), last_line.angle());
+        let sign = if ss.sign_type == SignType::Yield {
+            make_triangle(last_line.pt2(), Distance::meters(1.0), last_line.angle())
+        } else {
+            make_octagon(last_line.pt2(), Distance::meters(1.0), last_line.angle())
+        };
         let pole = Line::must_new(
             last_line
                 .pt2()
@@ -140,7 +145,7 @@ impl DrawIntersection {
                 .project_away(Distance::meters(0.9), last_line.angle().opposite()),
         )
         .make_polygons(Distance::meters(0.3));
-        Some((octagon, pole))
+        Some((sign, pole))
     }
 }
 
@@ -357,6 +362,15 @@ fn make_octagon(center: Pt2D, radius: Distance, facing: Angle) -> Polygon {
     .to_polygon()
 }
 
+fn make_triangle(center: Pt2D, radius: Distance, facing: Angle) -> Polygon {
+    Ring::must_new(
+        (0..=3)
+            .map(|i| center.project_away(radius, facing.rotate_degs(f64::from(i * 120))))
+            .collect(),
+    )
+    .to_polygon()
+}
+
 pub fn make_crosswalk(batch: &mut GeomBatch, turn: &Turn, map: &Map, cs: &ColorScheme) {
     if make_rainbow_crosswalk(batch, turn, map) {
         return;
@@ -94,6 +94,17 @@ impl DrawLane {
                 );
             }
             LaneType::Construction => {}
+            LaneType::Buffer => {
+                // Outline the edges, so it's distinguishable from a sidewalk
+                let width = Distance::meters(0.2);
+                let shift = (lane.width - width) / 2.0;
+                for pl in vec![
+                    lane.lane_center_pts.must_shift_right(shift),
+                    lane.lane_center_pts.must_shift_left(shift),
+                ] {
+                    draw.push(general_road_marking, pl.make_polygons(width));
+                }
+            }
             LaneType::LightRail => {
                 let track_width = lane.width / 4.0;
                 draw.push(
@@ -182,6 +193,10 @@ impl DrawLane {
             draw.push(app.cs().private_road.alpha(0.5), self.polygon.clone());
         }
 
+        // Nothing can use a removed road, but it still takes up space
+        if road.removed {
+            draw = draw.color(RewriteColor::ChangeAlpha(0.3));
+        }
         if self.zorder < 0 {
             draw = draw.color(RewriteColor::ChangeAlpha(0.5));
         }
@@ -10,8 +10,10 @@ pub use crate::render::agents::{AgentCache, UnzoomedAgents};
 pub use crate::render::area::DrawArea;
 use crate::render::bike::DrawBike;
 pub use crate::render::building::DrawBuilding;
+pub use crate::render::bus_stop::DrawBusStop;
 use crate::render::car::DrawCar;
 pub use crate::render::intersection::{calculate_corners, DrawIntersection};
+pub use crate::render::lane::DrawLane;
 pub use crate::render::map::DrawMap;
 pub use crate::render::pedestrian::{DrawPedCrowd, DrawPedestrian};
 pub use crate::render::turn::{DrawMovement, DrawUberTurnGroup};
@@ -6,6 +6,7 @@ use petgraph::graphmap::DiGraphMap;
 
 use geom::{Distance, Duration, Speed};
 
+use crate::pathfind::breaks_turn_restrictions;
 pub use crate::pathfind::{
     build_graph_for_pedestrians, build_graph_for_vehicles, driving_cost, WalkingNode,
 };
@@ -13,13 +14,15 @@ use crate::{BuildingID, LaneID, Map, PathConstraints};
 
 /// Calculate the srongy connected components (SCC) of the part of the map accessible by constraints
 /// (ie, the graph of sidewalks or driving+bike lanes). The largest component is the "main" graph;
-/// the rest is disconnected. Returns (lanes in the largest "main" component, all other disconnected
-/// lanes)
+/// the rest is disconnected. Turn restrictions and modal filters that apply all day count. Returns
+/// (lanes in the largest "main" component, all other disconnected lanes)
 pub fn find_scc(map: &Map, constraints: PathConstraints) -> (HashSet<LaneID>, HashSet<LaneID>) {
     let mut graph = DiGraphMap::new();
     for turn in map.all_turns().values() {
         if constraints.can_use(map.get_l(turn.id.src), map)
             && constraints.can_use(map.get_l(turn.id.dst), map)
+            && (constraints == PathConstraints::Pedestrian
+                || !breaks_turn_restrictions(map, &[turn.id], |c| c.applies(constraints, None)))
         {
             graph.add_edge(turn.id.src, turn.id.dst, 1);
         }
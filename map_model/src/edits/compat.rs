@@ -60,6 +60,27 @@ pub fn upgrade(mut value: Value, map: &Map) -> Result<PermanentMapEdits, String>
             .unwrap()
             .insert("version".to_string(), Value::Number(4.into()));
     }
+    if value["version"] == Value::Number(4.into()) {
+        fix_transit_priority(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(5.into()));
+    }
+    if value["version"] == Value::Number(5.into()) {
+        fix_stop_sign_types(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(6.into()));
+    }
+    if value["version"] == Value::Number(6.into()) {
+        fix_lane_widths(&mut value, map)?;
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(7.into()));
+    }
 
     abstutil::from_json(&value.to_string().into_bytes())
 }
@@ -232,6 +253,78 @@ fn fix_map_name(value: &mut Value) {
     }
 }
 
+// Transit signal priority was added to traffic signal edits, turning the TrafficSignal variant into
+// a struct.
+fn fix_transit_priority(value: &mut Value) {
+    walk(value, &|map| {
+        if map.len() == 1 && map.contains_key("TrafficSignal") {
+            let signal = map.remove("TrafficSignal").unwrap();
+            let mut obj = serde_json::Map::new();
+            obj.insert("signal".to_string(), signal);
+            obj.insert("transit_priority".to_string(), Value::Null);
+            map.insert("TrafficSignal".to_string(), Value::Object(obj));
+            true
+        } else {
+            false
+        }
+    })
+}
+
+// Yield signs were added, turning the must_stop boolean for each road into a SignType.
+fn fix_stop_sign_types(value: &mut Value) {
+    walk(value, &|map| {
+        if map.len() == 1 && map.contains_key("StopSign") {
+            let ss = map.get_mut("StopSign").unwrap().as_object_mut().unwrap();
+            if let Some(Value::Array(must_stop)) = ss.remove("must_stop") {
+                let signs = must_stop
+                    .into_iter()
+                    .map(|pair| {
+                        let mut pair = pair.as_array().unwrap().clone();
+                        let sign_type = if pair[1].as_bool().unwrap() {
+                            "Stop"
+                        } else {
+                            "NoSign"
+                        };
+                        pair[1] = Value::String(sign_type.to_string());
+                        Value::Array(pair)
+                    })
+                    .collect();
+                ss.insert("signs".to_string(), Value::Array(signs));
+            }
+            true
+        } else {
+            false
+        }
+    })
+}
+
+// Lane widths were added to road edits. Before that, widths couldn't be changed, so they're the
+// original ones from OSM.
+fn fix_lane_widths(value: &mut Value, map: &Map) -> Result<(), String> {
+    for cmd in value.as_object_mut().unwrap()["commands"]
+        .as_array_mut()
+        .unwrap()
+    {
+        if let Some(obj) = cmd.as_object_mut().unwrap().get_mut("ChangeRoad") {
+            let obj = obj.as_object_mut().unwrap();
+            let r: OriginalRoad = serde_json::from_value(obj["r"].clone()).unwrap();
+            let road = map.get_r(map.find_r_by_osm_id(r)?);
+            let widths = serde_json::to_value(
+                EditRoad::get_orig_from_osm(road, map.get_config()).lane_widths,
+            )
+            .unwrap();
+            for key in vec!["old", "new"] {
+                obj.get_mut(key)
+                    .unwrap()
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("lane_widths".to_string(), widths.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
 // These're old structs used in fix_old_lane_cmds.
 #[derive(Debug, Deserialize)]
 struct OriginalLane {
@@ -0,0 +1,539 @@
+//! Combine and compare different sets of edits to the same map, like proposals made in parallel
+//! by different people. Everything here works on the net effect of the edits -- the state of each
+//! road, intersection, route, or bus stop before and after all of the commands -- so the order of
+//! commands and any intermediate states don't matter.
+//!
+//! Bus stops and routes created by each set of edits pick the next free ID in the original map, so
+//! two proposals adding different routes wind up using the same ID. When combining edits, the
+//! newly created objects from one side are renumbered first, unless the other side creates exactly
+//! the same thing.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use abstutil::retain_btreemap;
+
+use crate::edits::{EditCmd, MapEdits};
+use crate::{BusRouteID, BusStopID, IntersectionID, Map, RoadID};
+
+/// The thing an EditCmd changes. Two commands with the same key touch the same thing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum EditKey {
+    Road(RoadID),
+    Intersection(IntersectionID),
+    RouteSchedule(BusRouteID),
+    BusStop(BusStopID),
+    Route(BusRouteID),
+    RemovedRoad(RoadID),
+}
+
+/// Both sets of edits change the same thing in different ways.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditConflict {
+    /// The net change from the edits doing the merge or rebase
+    pub ours: EditCmd,
+    /// The net change from the other edits
+    pub theirs: EditCmd,
+}
+
+/// One difference between two sets of edits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditDiff {
+    /// Only the first edits change this.
+    OnlyFirst(EditCmd),
+    /// Only the second edits change this.
+    OnlySecond(EditCmd),
+    /// Both edits change this, but differently. (first, second)
+    Different(EditCmd, EditCmd),
+}
+
+impl MapEdits {
+    /// Combines two sets of edits. Everything that only one of them changes is kept. When both
+    /// change the same thing the same way, it's kept once. When both change the same thing in
+    /// different ways, the version in `self` wins, and the conflict is returned. The name and
+    /// description are from `self`.
+    pub fn merge(&self, other: &MapEdits) -> (MapEdits, Vec<EditConflict>) {
+        let mut changes = self.net_changes();
+        let mut conflicts = Vec::new();
+        for (key, theirs) in renumber_created(&changes, other.net_changes()) {
+            if let Some(ours) = changes.get(&key) {
+                if !ours.same_result(&theirs) {
+                    conflicts.push(EditConflict {
+                        ours: ours.clone(),
+                        theirs,
+                    });
+                }
+            } else {
+                changes.insert(key, theirs);
+            }
+        }
+        (self.with_changes(changes), conflicts)
+    }
+
+    /// Replays these edits on top of `onto`, as if they were made after it. When both change the
+    /// same thing in different ways, the version in `self` wins, and the conflict is returned.
+    /// The name and description are from `self`.
+    pub fn rebase(&self, onto: &MapEdits) -> (MapEdits, Vec<EditConflict>) {
+        let mut changes = onto.net_changes();
+        let mut conflicts = Vec::new();
+        for (key, ours) in renumber_created(&changes, self.net_changes()) {
+            if let Some(theirs) = changes.remove(&key) {
+                if !ours.same_result(&theirs) {
+                    conflicts.push(EditConflict {
+                        ours: ours.clone(),
+                        theirs: theirs.clone(),
+                    });
+                }
+                // Keep the original state from before either set of edits
+                changes.insert(key, theirs.then(&ours));
+            } else {
+                changes.insert(key, ours);
+            }
+        }
+        (self.with_changes(changes), conflicts)
+    }
+
+    /// Removes every change that `other` also makes the same way.
+    pub fn subtract(&self, other: &MapEdits) -> MapEdits {
+        let theirs = other.net_changes();
+        let mut changes = self.net_changes();
+        retain_btreemap(&mut changes, |key, ours| {
+            theirs.get(key).map_or(true, |cmd| !ours.same_result(cmd))
+        });
+        self.with_changes(changes)
+    }
+
+    /// Everything that differs between two sets of edits
+    pub fn diff(&self, other: &MapEdits) -> Vec<EditDiff> {
+        let mut first = self.net_changes();
+        let mut results = Vec::new();
+        for (key, second) in other.net_changes() {
+            if let Some(cmd) = first.remove(&key) {
+                if !cmd.same_result(&second) {
+                    results.push(EditDiff::Different(cmd, second));
+                }
+            } else {
+                results.push(EditDiff::OnlySecond(second));
+            }
+        }
+        results.extend(first.into_iter().map(|(_, cmd)| EditDiff::OnlyFirst(cmd)));
+        results.sort_by_key(|x| match x {
+            EditDiff::OnlyFirst(cmd) | EditDiff::OnlySecond(cmd) | EditDiff::Different(cmd, _) => {
+                cmd.key()
+            }
+        });
+        results
+    }
+
+    /// For everything changed, one command going from the original state to the final one.
+    /// Things changed and then changed back are skipped.
+    fn net_changes(&self) -> BTreeMap<EditKey, EditCmd> {
+        let mut changes: BTreeMap<EditKey, EditCmd> = BTreeMap::new();
+        for cmd in &self.commands {
+            let key = cmd.key();
+            let net = if let Some(prev) = changes.remove(&key) {
+                prev.then(cmd)
+            } else {
+                cmd.clone()
+            };
+            changes.insert(key, net);
+        }
+        retain_btreemap(&mut changes, |_, cmd| !cmd.is_noop());
+        changes
+    }
+
+    fn with_changes(&self, changes: BTreeMap<EditKey, EditCmd>) -> MapEdits {
+        let mut commands: Vec<EditCmd> = changes.into_iter().map(|(_, cmd)| cmd).collect();
+        // Use the same order as compress(). Stops have to exist before routes use them, and routes
+        // must stop using stops before they're removed.
+        commands.sort_by_key(|cmd| match cmd {
+            EditCmd::ChangeRoad { .. } => 0,
+            EditCmd::ChangeIntersection { .. } => 1,
+            EditCmd::ChangeBusStop { new: Some(_), .. } => 2,
+            EditCmd::ChangeRoute { .. } => 3,
+            EditCmd::ChangeBusStop { new: None, .. } => 4,
+            EditCmd::ChangeRouteSchedule { .. } => 5,
+            EditCmd::RemoveRoad { .. } => 6,
+        });
+        let mut edits = self.clone();
+        edits.commands = commands;
+        edits
+    }
+}
+
+/// Gives bus stops and routes created by `theirs` new IDs when `ours` creates something different
+/// with the same ID, and updates everything in `theirs` referring to them.
+fn renumber_created(
+    ours: &BTreeMap<EditKey, EditCmd>,
+    theirs: BTreeMap<EditKey, EditCmd>,
+) -> BTreeMap<EditKey, EditCmd> {
+    // Stops first, since routes refer to them
+    let mut stop_ids: BTreeMap<BusStopID, BusStopID> = BTreeMap::new();
+    let mut used_stops: BTreeSet<BusStopID> = BTreeSet::new();
+    for key in ours.keys().chain(theirs.keys()) {
+        if let EditKey::BusStop(id) = key {
+            used_stops.insert(*id);
+        }
+    }
+    for (key, cmd) in &theirs {
+        if let EditKey::BusStop(id) = key {
+            if is_creation(cmd) && ours.get(key).map_or(false, |x| !x.same_result(cmd)) {
+                let new_id = BusStopID {
+                    sidewalk: id.sidewalk,
+                    idx: used_stops
+                        .iter()
+                        .filter(|x| x.sidewalk == id.sidewalk)
+                        .map(|x| x.idx + 1)
+                        .max()
+                        .unwrap(),
+                };
+                used_stops.insert(new_id);
+                stop_ids.insert(*id, new_id);
+            }
+        }
+    }
+    let theirs: Vec<EditCmd> = theirs
+        .into_iter()
+        .map(|(_, cmd)| cmd.renumber(&stop_ids, &BTreeMap::new()))
+        .collect();
+
+    // New routes are appended to the map in order, so the renumbered ones go after everything else
+    let mut route_ids: BTreeMap<BusRouteID, BusRouteID> = BTreeMap::new();
+    let mut next_route = ours
+        .keys()
+        .cloned()
+        .chain(theirs.iter().map(|cmd| cmd.key()))
+        .filter_map(|key| match key {
+            EditKey::Route(id) | EditKey::RouteSchedule(id) => Some(id.0 + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    for cmd in &theirs {
+        if let EditCmd::ChangeRoute { id, .. } = cmd {
+            if is_creation(cmd) && ours.get(&cmd.key()).map_or(false, |x| !x.same_result(cmd)) {
+                route_ids.insert(*id, BusRouteID(next_route));
+                next_route += 1;
+            }
+        }
+    }
+
+    theirs
+        .into_iter()
+        .map(|cmd| {
+            let cmd = cmd.renumber(&BTreeMap::new(), &route_ids);
+            (cmd.key(), cmd)
+        })
+        .collect()
+}
+
+fn is_creation(cmd: &EditCmd) -> bool {
+    match cmd {
+        EditCmd::ChangeBusStop { old: None, .. } | EditCmd::ChangeRoute { old: None, .. } => true,
+        _ => false,
+    }
+}
+
+impl EditCmd {
+    fn key(&self) -> EditKey {
+        match self {
+            EditCmd::ChangeRoad { r, .. } => EditKey::Road(*r),
+            EditCmd::ChangeIntersection { i, .. } => EditKey::Intersection(*i),
+            EditCmd::ChangeRouteSchedule { id, .. } => EditKey::RouteSchedule(*id),
+            EditCmd::ChangeBusStop { id, .. } => EditKey::BusStop(*id),
+            EditCmd::ChangeRoute { id, .. } => EditKey::Route(*id),
+            EditCmd::RemoveRoad { r, .. } => EditKey::RemovedRoad(*r),
+        }
+    }
+
+    /// Combine two commands touching the same thing, using the old state from this one and the
+    /// new state from the next one.
+    fn then(&self, next: &EditCmd) -> EditCmd {
+        let mut cmd = self.clone();
+        match (&mut cmd, next.clone()) {
+            (EditCmd::ChangeRoad { new, .. }, EditCmd::ChangeRoad { new: x, .. }) => {
+                *new = x;
+            }
+            (
+                EditCmd::ChangeIntersection { new, .. },
+                EditCmd::ChangeIntersection { new: x, .. },
+            ) => {
+                *new = x;
+            }
+            (
+                EditCmd::ChangeRouteSchedule { new, .. },
+                EditCmd::ChangeRouteSchedule { new: x, .. },
+            ) => {
+                *new = x;
+            }
+            (EditCmd::ChangeBusStop { new, .. }, EditCmd::ChangeBusStop { new: x, .. }) => {
+                *new = x;
+            }
+            (EditCmd::ChangeRoute { new, .. }, EditCmd::ChangeRoute { new: x, .. }) => {
+                *new = x;
+            }
+            (EditCmd::RemoveRoad { new, .. }, EditCmd::RemoveRoad { new: x, .. }) => {
+                *new = x;
+            }
+            _ => unreachable!(),
+        }
+        cmd
+    }
+
+    fn is_noop(&self) -> bool {
+        match self {
+            EditCmd::ChangeRoad { old, new, .. } => old == new,
+            EditCmd::ChangeIntersection { old, new, .. } => old == new,
+            EditCmd::ChangeRouteSchedule { old, new, .. } => old == new,
+            EditCmd::ChangeBusStop { old, new, .. } => old == new,
+            EditCmd::ChangeRoute { old, new, .. } => old == new,
+            EditCmd::RemoveRoad { old, new, .. } => old == new,
+        }
+    }
+
+    /// Refer to different bus stops and routes
+    fn renumber(
+        mut self,
+        stops: &BTreeMap<BusStopID, BusStopID>,
+        routes: &BTreeMap<BusRouteID, BusRouteID>,
+    ) -> EditCmd {
+        let stop = |id: &mut BusStopID| {
+            if let Some(x) = stops.get(id) {
+                *id = *x;
+            }
+        };
+        let route = |id: &mut BusRouteID| {
+            if let Some(x) = routes.get(id) {
+                *id = *x;
+            }
+        };
+        match self {
+            EditCmd::ChangeRoad { .. }
+            | EditCmd::ChangeIntersection { .. }
+            | EditCmd::RemoveRoad { .. } => {}
+            EditCmd::ChangeRouteSchedule { ref mut id, .. } => {
+                route(id);
+            }
+            EditCmd::ChangeBusStop { ref mut id, .. } => {
+                stop(id);
+            }
+            EditCmd::ChangeRoute {
+                ref mut id,
+                ref mut old,
+                ref mut new,
+            } => {
+                route(id);
+                for r in old.iter_mut().chain(new.iter_mut()) {
+                    r.stops.iter_mut().for_each(|id| stop(id));
+                }
+            }
+        }
+        self
+    }
+
+    /// Assuming both commands touch the same thing, do they leave it in the same state?
+    fn same_result(&self, other: &EditCmd) -> bool {
+        self.then(other) == *self
+    }
+}
+
+impl EditConflict {
+    pub fn describe(&self, map: &Map) -> String {
+        format!(
+            "both edits change this differently: {} vs {}",
+            describe_cmd(&self.ours, map),
+            describe_cmd(&self.theirs, map)
+        )
+    }
+}
+
+impl EditDiff {
+    pub fn describe(&self, map: &Map) -> String {
+        match self {
+            EditDiff::OnlyFirst(cmd) => format!("- {}", describe_cmd(cmd, map)),
+            EditDiff::OnlySecond(cmd) => format!("+ {}", describe_cmd(cmd, map)),
+            EditDiff::Different(first, second) => format!(
+                "~ {} became {}",
+                describe_cmd(first, map),
+                describe_cmd(second, map)
+            ),
+        }
+    }
+}
+
+fn describe_cmd(cmd: &EditCmd, map: &Map) -> String {
+    let (summary, details) = cmd.describe(map);
+    if details.is_empty() {
+        summary
+    } else {
+        format!("{} ({})", summary, details.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{Distance, Duration, Time};
+
+    use super::*;
+    use crate::edits::{EditBusStop, EditRoute};
+    use crate::{LaneID, Position};
+
+    fn schedule(id: usize, old: usize, new: usize) -> EditCmd {
+        let times = |n: usize| {
+            (0..n)
+                .map(|i| Time::START_OF_DAY + Duration::hours(i))
+                .collect()
+        };
+        EditCmd::ChangeRouteSchedule {
+            id: BusRouteID(id),
+            old: times(old),
+            new: times(new),
+        }
+    }
+
+    fn stop_id(idx: usize) -> BusStopID {
+        BusStopID {
+            sidewalk: LaneID(0),
+            idx,
+        }
+    }
+
+    fn new_stop(idx: usize, dist: f64) -> EditCmd {
+        EditCmd::ChangeBusStop {
+            id: stop_id(idx),
+            old: None,
+            new: Some(EditBusStop {
+                name: format!("stop at {}m", dist),
+                sidewalk_pos: Position::new(LaneID(0), Distance::meters(dist)),
+                driving_pos: Position::new(LaneID(1), Distance::meters(dist)),
+                is_train_stop: false,
+            }),
+        }
+    }
+
+    fn new_route(id: usize, name: &str, stops: Vec<BusStopID>) -> EditCmd {
+        EditCmd::ChangeRoute {
+            id: BusRouteID(id),
+            old: None,
+            new: Some(EditRoute {
+                full_name: name.to_string(),
+                short_name: name.to_string(),
+                stops,
+                start: LaneID(1),
+                end_border: None,
+            }),
+        }
+    }
+
+    fn edits(commands: Vec<EditCmd>) -> MapEdits {
+        let mut edits = MapEdits::new();
+        edits.commands = commands;
+        edits
+    }
+
+    #[test]
+    fn merge_and_diff() {
+        // Route 0 is changed twice, route 1 is changed and changed back
+        let a = edits(vec![
+            schedule(0, 3, 2),
+            schedule(1, 3, 1),
+            schedule(0, 2, 1),
+            schedule(1, 1, 3),
+        ]);
+        let b = edits(vec![schedule(0, 3, 1), schedule(2, 3, 0)]);
+        let c = edits(vec![schedule(2, 3, 5)]);
+
+        let (merged, conflicts) = a.merge(&b);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged.commands, vec![schedule(0, 3, 1), schedule(2, 3, 0)]);
+
+        let (merged, conflicts) = b.merge(&c);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(merged.commands, b.commands);
+
+        let (rebased, conflicts) = c.rebase(&b);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(rebased.commands, vec![schedule(0, 3, 1), schedule(2, 3, 5)]);
+
+        assert_eq!(b.subtract(&a).commands, vec![schedule(2, 3, 0)]);
+        assert_eq!(
+            a.diff(&c),
+            vec![
+                EditDiff::OnlyFirst(schedule(0, 3, 1)),
+                EditDiff::OnlySecond(schedule(2, 3, 5))
+            ]
+        );
+    }
+
+    #[test]
+    fn both_add_routes() {
+        // The map has 3 routes and one existing stop on the sidewalk. Both proposals add a stop
+        // and a route using it, so they pick the same IDs.
+        let existing = stop_id(0);
+        let a = edits(vec![
+            new_stop(1, 10.0),
+            new_route(3, "A", vec![existing, stop_id(1)]),
+        ]);
+        let b = edits(vec![
+            new_stop(1, 50.0),
+            new_route(3, "B", vec![stop_id(1), existing]),
+            schedule(3, 24, 12),
+        ]);
+
+        let (merged, conflicts) = a.merge(&b);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            merged.commands,
+            vec![
+                new_stop(1, 10.0),
+                new_stop(2, 50.0),
+                new_route(3, "A", vec![existing, stop_id(1)]),
+                new_route(4, "B", vec![stop_id(2), existing]),
+                schedule(4, 24, 12),
+            ]
+        );
+
+        // Rebasing renumbers the edits being replayed instead
+        let (rebased, conflicts) = a.rebase(&b);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            rebased.commands,
+            vec![
+                new_stop(1, 50.0),
+                new_stop(2, 10.0),
+                new_route(3, "B", vec![stop_id(1), existing]),
+                new_route(4, "A", vec![existing, stop_id(2)]),
+                schedule(3, 24, 12),
+            ]
+        );
+
+        // Creating exactly the same thing isn't a conflict, and it's only kept once
+        let (merged, conflicts) = a.merge(&a);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged.commands, a.commands);
+    }
+
+    #[test]
+    fn remove_roads() {
+        let remove = |r: usize, old: bool, new: bool| EditCmd::RemoveRoad {
+            r: RoadID(r),
+            old,
+            new,
+        };
+        // Road 0 is removed and put back, so only road 1 is removed in the end
+        let a = edits(vec![
+            remove(0, false, true),
+            remove(1, false, true),
+            remove(0, true, false),
+        ]);
+        let b = edits(vec![remove(1, false, true), schedule(0, 3, 1)]);
+
+        // Removals happen after everything else, once bus stops along the road are gone
+        let (merged, conflicts) = a.merge(&b);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            merged.commands,
+            vec![schedule(0, 3, 1), remove(1, false, true)]
+        );
+        assert_eq!(b.subtract(&a).commands, vec![schedule(0, 3, 1)]);
+    }
+}
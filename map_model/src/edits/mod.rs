@@ -4,21 +4,29 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 
+pub use merge::{EditConflict, EditDiff};
 pub use perma::PermanentMapEdits;
+pub use script::{EditRule, EditScript, RoadChange, RoadQuery};
 use serde::{Deserialize, Serialize};
+pub use validate::EditProblem;
 
 use abstutil::{retain_btreemap, retain_btreeset, Timer};
-use geom::{Speed, Time};
+use geom::{Distance, Duration, Line, PolyLine, Pt2D, Ring, Speed, Time, EPSILON_DIST};
 
 use crate::make::initial::lane_specs::get_lane_specs_ltr;
+use crate::make::transit::{default_spawn_times, pick_start_lane};
 use crate::{
-    connectivity, AccessRestrictions, BusRouteID, ControlStopSign, ControlTrafficSignal, Direction,
-    IntersectionID, IntersectionType, LaneID, LaneType, Map, MapConfig, PathConstraints,
-    Pathfinder, Road, RoadID, TurnID, Zone,
+    connectivity, osm, AccessRestrictions, BusRoute, BusRouteID, BusStop, BusStopID,
+    ControlStopSign, ControlTrafficSignal, Direction, IntersectionID, IntersectionType, LaneID,
+    LaneType, Map, MapConfig, ModalFilter, PathConstraints, PathRequest, Pathfinder, Position,
+    Road, RoadID, TransitPriority, TurnID, Zone,
 };
 
 mod compat;
+mod merge;
 mod perma;
+mod script;
+mod validate;
 
 /// Represents changes to a map. Note this isn't serializable -- that's what `PermanentMapEdits`
 /// does.
@@ -35,8 +43,12 @@ pub struct MapEdits {
 
     /// Derived from commands, kept up to date by update_derived
     pub changed_roads: BTreeSet<RoadID>,
+    pub removed_roads: BTreeSet<RoadID>,
     pub original_intersections: BTreeMap<IntersectionID, EditIntersection>,
     pub changed_routes: BTreeSet<BusRouteID>,
+    /// None means the stop or route didn't exist originally.
+    pub original_bus_stops: BTreeMap<BusStopID, Option<EditBusStop>>,
+    pub original_routes: BTreeMap<BusRouteID, Option<EditRoute>>,
 
     /// Some edits are included in the game by default, in data/system/proposals, as "community
     /// proposals." They require a description and may have a link to a write-up.
@@ -49,26 +61,115 @@ pub enum EditIntersection {
     StopSign(ControlStopSign),
     // Don't keep ControlTrafficSignal here, because it contains movements that should be
     // generated after all lane edits are applied.
-    TrafficSignal(seattle_traffic_signals::TrafficSignal),
+    TrafficSignal(
+        seattle_traffic_signals::TrafficSignal,
+        Option<TransitPriority>,
+    ),
     Closed,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditBusStop {
+    pub name: String,
+    /// Must be on the sidewalk from the BusStopID.
+    pub sidewalk_pos: Position,
+    pub driving_pos: Position,
+    pub is_train_stop: bool,
+}
+
+/// Everything about a route except for its schedule, which is edited separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditRoute {
+    pub full_name: String,
+    pub short_name: String,
+    pub stops: Vec<BusStopID>,
+    pub start: LaneID,
+    pub end_border: Option<LaneID>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EditRoad {
     pub lanes_ltr: Vec<(LaneType, Direction)>,
+    /// The width of each lane, in the same order as `lanes_ltr`
+    pub lane_widths: Vec<Distance>,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    #[serde(default)]
+    pub modal_filters: Vec<ModalFilter>,
 }
 
 impl EditRoad {
     pub fn get_orig_from_osm(r: &Road, cfg: &MapConfig) -> EditRoad {
+        let specs = get_lane_specs_ltr(&r.osm_tags, cfg);
         EditRoad {
-            lanes_ltr: get_lane_specs_ltr(&r.osm_tags, cfg)
-                .into_iter()
-                .map(|spec| (spec.lt, spec.dir))
-                .collect(),
+            lanes_ltr: specs.iter().map(|spec| (spec.lt, spec.dir)).collect(),
+            lane_widths: specs.iter().map(|spec| spec.width).collect(),
             speed_limit: r.speed_limit_from_osm(),
             access_restrictions: r.access_restrictions_from_osm(),
+            modal_filters: Vec::new(),
+        }
+    }
+
+    /// The width of the whole road, including sidewalks
+    pub fn total_width(&self) -> Distance {
+        self.lane_widths.iter().cloned().sum()
+    }
+
+    /// The width of the roadway between the curbs -- everything except sidewalks and shoulders
+    pub fn curb_to_curb_width(&self) -> Distance {
+        self.lanes_ltr
+            .iter()
+            .zip(self.lane_widths.iter())
+            .filter(|((lt, _), _)| *lt != LaneType::Sidewalk && *lt != LaneType::Shoulder)
+            .map(|(_, width)| *width)
+            .sum()
+    }
+
+    /// See `Map::check_cross_section`; this compares against the road's original lanes.
+    pub fn fits_within(&self, orig: &EditRoad) -> Result<Vec<String>, String> {
+        if self.lane_widths.len() != self.lanes_ltr.len() {
+            return Err(format!(
+                "{} lanes, but {} widths",
+                self.lanes_ltr.len(),
+                self.lane_widths.len()
+            ));
+        }
+        if self
+            .lane_widths
+            .iter()
+            .any(|width| *width <= Distance::ZERO)
+        {
+            return Err("every lane needs a positive width".to_string());
+        }
+
+        if self.total_width() > orig.total_width() + EPSILON_DIST {
+            return Err(format!(
+                "the lanes need {}, but the road is only {} wide",
+                self.total_width(),
+                orig.total_width()
+            ));
+        }
+        let mut warnings = Vec::new();
+        if self.curb_to_curb_width() > orig.curb_to_curb_width() + EPSILON_DIST {
+            warnings.push(format!(
+                "the roadway is {} between the curbs, but originally was {}, so the curbs would \
+                 have to move",
+                self.curb_to_curb_width(),
+                orig.curb_to_curb_width()
+            ));
+        }
+        Ok(warnings)
+    }
+
+    /// Closes the road to everything except bikes and buses during some times of day, replacing
+    /// any other timed closure. All-day modal filters stay. With no times, only the all-day
+    /// filters are left.
+    pub fn close_during(&mut self, time_windows: Vec<(Time, Time)>) {
+        self.modal_filters
+            .retain(|filter| filter.conditions.time_windows.is_empty());
+        if !time_windows.is_empty() {
+            self.modal_filters
+                .extend(ModalFilter::timed_closure(time_windows));
         }
     }
 
@@ -83,6 +184,12 @@ impl EditRoad {
                 dir += 1;
             }
         }
+        let width = self
+            .lane_widths
+            .iter()
+            .zip(other.lane_widths.iter())
+            .filter(|(w1, w2)| w1 != w2)
+            .count();
 
         let mut changes = Vec::new();
         if lt == 1 {
@@ -95,12 +202,20 @@ impl EditRoad {
         } else if dir > 1 {
             changes.push(format!("{} lane reversal", dir));
         }
+        if width == 1 {
+            changes.push(format!("1 lane width"));
+        } else if width > 1 {
+            changes.push(format!("{} lane widths", width));
+        }
         if self.speed_limit != other.speed_limit {
             changes.push(format!("speed limit"));
         }
         if self.access_restrictions != other.access_restrictions {
             changes.push(format!("access restrictions"));
         }
+        if self.modal_filters != other.modal_filters {
+            changes.push(format!("modal filters"));
+        }
         changes
     }
 }
@@ -122,6 +237,25 @@ pub enum EditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    /// None for old means the stop is created; None for new means it's removed.
+    ChangeBusStop {
+        id: BusStopID,
+        old: Option<EditBusStop>,
+        new: Option<EditBusStop>,
+    },
+    /// None for old means the route is created; None for new means it's removed. Route IDs are
+    /// positions in a list, so routes are always created at the end, and only the most recently
+    /// created route can be removed. Routes from OSM can't be removed; to stop running one, clear
+    /// its schedule instead.
+    ChangeRoute {
+        id: BusRouteID,
+        old: Option<EditRoute>,
+        new: Option<EditRoute>,
+    },
+    /// Removes a road from the map (new is true), or puts back one that was removed. Road and lane
+    /// IDs are used everywhere, so they stay, but the intersections at both ends stop connecting
+    /// to the road.
+    RemoveRoad { r: RoadID, old: bool, new: bool },
 }
 
 pub struct EditEffects {
@@ -141,8 +275,11 @@ impl MapEdits {
             merge_zones: true,
 
             changed_roads: BTreeSet::new(),
+            removed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_bus_stops: BTreeMap::new(),
+            original_routes: BTreeMap::new(),
         }
     }
 
@@ -174,8 +311,11 @@ impl MapEdits {
 
     fn update_derived(&mut self, map: &Map) {
         self.changed_roads.clear();
+        self.removed_roads.clear();
         self.original_intersections.clear();
         self.changed_routes.clear();
+        self.original_bus_stops.clear();
+        self.original_routes.clear();
 
         for cmd in &self.commands {
             match cmd {
@@ -190,18 +330,39 @@ impl MapEdits {
                 EditCmd::ChangeRouteSchedule { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::ChangeBusStop { id, ref old, .. } => {
+                    if !self.original_bus_stops.contains_key(id) {
+                        self.original_bus_stops.insert(*id, old.clone());
+                    }
+                }
+                EditCmd::ChangeRoute { id, ref old, .. } => {
+                    if !self.original_routes.contains_key(id) {
+                        self.original_routes.insert(*id, old.clone());
+                    }
+                }
+                EditCmd::RemoveRoad { r, .. } => {
+                    self.removed_roads.insert(*r);
+                }
             }
         }
 
         retain_btreeset(&mut self.changed_roads, |r| {
             map.get_r_edit(*r) != EditRoad::get_orig_from_osm(map.get_r(*r), &map.config)
         });
+        retain_btreeset(&mut self.removed_roads, |r| map.get_r(*r).removed);
         retain_btreemap(&mut self.original_intersections, |i, orig| {
             map.get_i_edit(*i) != orig.clone()
         });
         retain_btreeset(&mut self.changed_routes, |br| {
-            let r = map.get_br(*br);
-            r.spawn_times != r.orig_spawn_times
+            map.maybe_get_br(*br)
+                .map(|r| r.spawn_times != r.orig_spawn_times)
+                .unwrap_or(false)
+        });
+        retain_btreemap(&mut self.original_bus_stops, |id, orig| {
+            map.get_bs_edit(*id) != orig.clone()
+        });
+        retain_btreemap(&mut self.original_routes, |id, orig| {
+            map.get_br_edit(*id) != orig.clone()
         });
     }
 
@@ -221,6 +382,34 @@ impl MapEdits {
                 new: map.get_i_edit(*i),
             });
         }
+        // Stops have to exist before routes use them, and routes must stop using stops before
+        // they're removed.
+        for (id, old) in &self.original_bus_stops {
+            if map.maybe_get_bs(*id).is_some() {
+                self.commands.push(EditCmd::ChangeBusStop {
+                    id: *id,
+                    old: old.clone(),
+                    new: map.get_bs_edit(*id),
+                });
+            }
+        }
+        // Routes are always created in order of their ID, since this is a BTreeMap.
+        for (id, old) in &self.original_routes {
+            self.commands.push(EditCmd::ChangeRoute {
+                id: *id,
+                old: old.clone(),
+                new: map.get_br_edit(*id),
+            });
+        }
+        for (id, old) in &self.original_bus_stops {
+            if map.maybe_get_bs(*id).is_none() {
+                self.commands.push(EditCmd::ChangeBusStop {
+                    id: *id,
+                    old: old.clone(),
+                    new: None,
+                });
+            }
+        }
         for r in &self.changed_routes {
             let r = map.get_br(*r);
             self.commands.push(EditCmd::ChangeRouteSchedule {
@@ -229,24 +418,40 @@ impl MapEdits {
                 old: r.orig_spawn_times.clone(),
             });
         }
+        // Bus stops along a road have to be removed first
+        for r in &self.removed_roads {
+            self.commands.push(EditCmd::RemoveRoad {
+                r: *r,
+                old: false,
+                new: true,
+            });
+        }
     }
 
     /// Pick apart changed_roads and figure out if an entire road was edited, or just a few lanes.
+    /// Every lane of a removed road counts as changed.
     pub fn changed_lanes(&self, map: &Map) -> (BTreeSet<LaneID>, BTreeSet<RoadID>) {
         let mut lanes = BTreeSet::new();
         let mut roads = BTreeSet::new();
+        for r in &self.removed_roads {
+            lanes.extend(map.get_r(*r).all_lanes());
+        }
         for r in &self.changed_roads {
             let r = map.get_r(*r);
             let orig = EditRoad::get_orig_from_osm(r, map.get_config());
             // What exactly changed?
             if r.speed_limit != orig.speed_limit
                 || r.access_restrictions != orig.access_restrictions
+                || r.modal_filters != orig.modal_filters
             {
                 roads.insert(r.id);
             } else {
                 let lanes_ltr = r.lanes_ltr();
                 for (idx, (lt, dir)) in orig.lanes_ltr.into_iter().enumerate() {
-                    if lanes_ltr[idx].1 != dir || lanes_ltr[idx].2 != lt {
+                    if lanes_ltr[idx].1 != dir
+                        || lanes_ltr[idx].2 != lt
+                        || map.get_l(lanes_ltr[idx].0).width != orig.lane_widths[idx]
+                    {
                         lanes.insert(lanes_ltr[idx].0);
                     }
                 }
@@ -285,11 +490,42 @@ impl EditCmd {
             // TODO Describe changes
             EditCmd::ChangeIntersection { i, new, .. } => match new {
                 EditIntersection::StopSign(_) => format!("stop sign #{}", i.0),
-                EditIntersection::TrafficSignal(_) => format!("traffic signal #{}", i.0),
+                EditIntersection::TrafficSignal(_, _) => format!("traffic signal #{}", i.0),
                 EditIntersection::Closed => format!("close {}", i),
             },
-            EditCmd::ChangeRouteSchedule { id, .. } => {
-                format!("reschedule route {}", map.get_br(*id).short_name)
+            EditCmd::ChangeRouteSchedule { id, new, .. } => {
+                if new.is_empty() {
+                    format!("stop running route {}", map.get_br(*id).short_name)
+                } else {
+                    format!("reschedule route {}", map.get_br(*id).short_name)
+                }
+            }
+            EditCmd::ChangeBusStop { id, old, new } => match (old, new) {
+                (None, Some(ref stop)) => format!("add bus stop {}", stop.name),
+                (Some(ref stop), None) => format!("remove bus stop {}", stop.name),
+                _ => format!("change {}", id),
+            },
+            EditCmd::ChangeRoute { id, old, new } => match (old, new) {
+                (None, Some(ref route)) => format!("create route {}", route.short_name),
+                (Some(ref route), None) => format!("remove route {}", route.short_name),
+                (Some(ref old), Some(ref route)) => {
+                    if old.stops != route.stops {
+                        details.push(format!(
+                            "{} stops, previously {}",
+                            route.stops.len(),
+                            old.stops.len()
+                        ));
+                    }
+                    format!("reroute {}", route.short_name)
+                }
+                (None, None) => id.to_string(),
+            },
+            EditCmd::RemoveRoad { r, new, .. } => {
+                if *new {
+                    format!("remove road #{}", r.0)
+                } else {
+                    format!("restore road #{}", r.0)
+                }
             }
         };
         (summary, details)
@@ -303,14 +539,21 @@ impl EditCmd {
                     return;
                 }
 
+                let old_half_width = map.get_r(*r).get_half_width(map);
                 let road = &mut map.roads[r.0];
                 road.speed_limit = new.speed_limit;
                 road.access_restrictions = new.access_restrictions.clone();
+                road.modal_filters = new.modal_filters.clone();
                 assert_eq!(road.lanes_ltr.len(), new.lanes_ltr.len());
+                let mut widths_changed = false;
                 for (idx, (lt, dir)) in new.lanes_ltr.clone().into_iter().enumerate() {
                     let lane = &mut map.lanes[(road.lanes_ltr[idx].0).0];
                     road.lanes_ltr[idx].2 = lt;
                     lane.lane_type = lt;
+                    if lane.width != new.lane_widths[idx] {
+                        lane.width = new.lane_widths[idx];
+                        widths_changed = true;
+                    }
 
                     // Direction change?
                     if road.lanes_ltr[idx].1 != dir {
@@ -320,24 +563,16 @@ impl EditCmd {
                     }
                 }
 
+                if widths_changed {
+                    recalculate_lane_geometry(*r, old_half_width, map);
+                }
+                let road = &map.roads[r.0];
+
                 effects.changed_roads.insert(road.id);
                 for i in vec![road.src_i, road.dst_i] {
                     effects.changed_intersections.insert(i);
-                    let i = &mut map.intersections[i.0];
-                    i.outgoing_lanes.clear();
-                    i.incoming_lanes.clear();
-                    for r in &i.roads {
-                        for (l, _, _) in map.roads[r.0].lanes_ltr() {
-                            if map.lanes[l.0].src_i == i.id {
-                                i.outgoing_lanes.push(l);
-                            } else {
-                                assert_eq!(map.lanes[l.0].dst_i, i.id);
-                                i.incoming_lanes.push(l);
-                            }
-                        }
-                    }
-
-                    recalculate_turns(i.id, map, effects, timer);
+                    recalculate_intersection_lanes(i, map);
+                    recalculate_turns(i, map, effects, timer);
                 }
             }
             EditCmd::ChangeIntersection {
@@ -357,15 +592,14 @@ impl EditCmd {
                         map.intersections[i.0].intersection_type = IntersectionType::StopSign;
                         map.stop_signs.insert(*i, ss.clone());
                     }
-                    EditIntersection::TrafficSignal(ref raw_ts) => {
+                    EditIntersection::TrafficSignal(ref raw_ts, ref transit_priority) => {
                         map.intersections[i.0].intersection_type = IntersectionType::TrafficSignal;
                         if old == &EditIntersection::Closed {
                             recalculate_turns(*i, map, effects, timer);
                         }
-                        map.traffic_signals.insert(
-                            *i,
-                            ControlTrafficSignal::import(raw_ts.clone(), *i, map).unwrap(),
-                        );
+                        let mut ts = ControlTrafficSignal::import(raw_ts.clone(), *i, map).unwrap();
+                        ts.transit_priority = transit_priority.clone();
+                        map.traffic_signals.insert(*i, ts);
                     }
                     EditIntersection::Closed => {
                         map.intersections[i.0].intersection_type = IntersectionType::Construction;
@@ -379,6 +613,93 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
                 map.bus_routes[id.0].spawn_times = new.clone();
             }
+            EditCmd::ChangeBusStop { id, ref new, .. } => {
+                if map.get_bs_edit(*id) == new.clone() {
+                    return;
+                }
+
+                map.bus_stops.remove(id);
+                map.lanes[id.sidewalk.0].bus_stops.remove(id);
+                if let Some(ref stop) = new {
+                    assert_eq!(stop.sidewalk_pos.lane(), id.sidewalk);
+                    map.lanes[id.sidewalk.0].bus_stops.insert(*id);
+                    map.bus_stops.insert(
+                        *id,
+                        BusStop {
+                            id: *id,
+                            name: stop.name.clone(),
+                            driving_pos: stop.driving_pos,
+                            sidewalk_pos: stop.sidewalk_pos,
+                            is_train_stop: stop.is_train_stop,
+                        },
+                    );
+                }
+            }
+            EditCmd::ChangeRoute { id, ref new, .. } => {
+                if map.get_br_edit(*id) == new.clone() {
+                    return;
+                }
+
+                if let Some(ref route) = new {
+                    if id.0 == map.bus_routes.len() {
+                        let osm_rel_id = new_route_osm_id(*id, map);
+                        map.bus_routes.push(BusRoute {
+                            id: *id,
+                            full_name: route.full_name.clone(),
+                            short_name: route.short_name.clone(),
+                            gtfs_trip_marker: None,
+                            osm_rel_id,
+                            stops: route.stops.clone(),
+                            start: route.start,
+                            end_border: route.end_border,
+                            route_type: PathConstraints::Bus,
+                            spawn_times: default_spawn_times(),
+                            orig_spawn_times: default_spawn_times(),
+                        });
+                    } else {
+                        let br = &mut map.bus_routes[id.0];
+                        br.full_name = route.full_name.clone();
+                        br.short_name = route.short_name.clone();
+                        br.stops = route.stops.clone();
+                        br.start = route.start;
+                        br.end_border = route.end_border;
+                    }
+                } else {
+                    // Loading edits and remove_bus_route_cmd reject anything else
+                    match route_count_after(
+                        *id,
+                        &map.get_br_edit(*id),
+                        &None,
+                        num_original_routes(map),
+                        map.bus_routes.len(),
+                    ) {
+                        Ok(_) => {
+                            map.bus_routes.pop();
+                        }
+                        Err(err) => {
+                            warn!("Ignoring an edit: {}", err);
+                        }
+                    }
+                }
+            }
+            EditCmd::RemoveRoad { r, new, .. } => {
+                if map.get_r(*r).removed == *new {
+                    return;
+                }
+
+                map.roads[r.0].removed = *new;
+                effects.changed_roads.insert(*r);
+                for i in vec![map.roads[r.0].src_i, map.roads[r.0].dst_i] {
+                    effects.changed_intersections.insert(i);
+                    if *new {
+                        map.intersections[i.0].roads.remove(r);
+                    } else {
+                        map.intersections[i.0].roads.insert(*r);
+                    }
+                    recalculate_intersection_lanes(i, map);
+                    recalculate_turns(i, map, effects, timer);
+                }
+            }
         }
     }
 
@@ -399,6 +720,239 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeBusStop { id, old, new } => EditCmd::ChangeBusStop {
+                id,
+                old: new,
+                new: old,
+            },
+            EditCmd::ChangeRoute { id, old, new } => EditCmd::ChangeRoute {
+                id,
+                old: new,
+                new: old,
+            },
+            EditCmd::RemoveRoad { r, old, new } => EditCmd::RemoveRoad {
+                r,
+                old: new,
+                new: old,
+            },
+        }
+    }
+}
+
+/// Routes created by edits don't exist in OSM, but PermanentMapEdits still needs a stable way to
+/// refer to them. They always come after all of the original routes, so number them from -1
+/// downwards.
+fn new_route_osm_id(id: BusRouteID, map: &Map) -> osm::RelationID {
+    let num_original = num_original_routes(map);
+    assert!(id.0 >= num_original);
+    osm::RelationID(-1 - (id.0 - num_original) as i64)
+}
+
+/// Checks that a sequence of commands only creates routes at the end of the list, and only removes
+/// the most recently created one.
+fn check_route_changes(commands: &[EditCmd], map: &Map) -> Result<(), String> {
+    let num_original = num_original_routes(map);
+    let mut num_routes = num_original;
+    for cmd in commands {
+        if let EditCmd::ChangeRoute { id, old, new } = cmd {
+            num_routes = route_count_after(*id, old, new, num_original, num_routes)?;
+        }
+    }
+    Ok(())
+}
+
+/// How many routes there are after one ChangeRoute, or why it's impossible.
+fn route_count_after(
+    id: BusRouteID,
+    old: &Option<EditRoute>,
+    new: &Option<EditRoute>,
+    num_original: usize,
+    num_routes: usize,
+) -> Result<usize, String> {
+    match (old, new) {
+        (None, Some(_)) => {
+            if id.0 == num_routes {
+                Ok(num_routes + 1)
+            } else {
+                Err(format!(
+                    "{} can't be created, since the next new route is {}",
+                    id,
+                    BusRouteID(num_routes)
+                ))
+            }
+        }
+        (Some(_), None) => {
+            if id.0 < num_original {
+                Err(format!(
+                    "{} comes from OSM, so it can't be removed; clear its schedule instead",
+                    id
+                ))
+            } else if id.0 + 1 != num_routes {
+                Err(format!(
+                    "{} can't be removed, since only the most recently created route can be",
+                    id
+                ))
+            } else {
+                Ok(num_routes - 1)
+            }
+        }
+        _ => {
+            if id.0 < num_routes {
+                Ok(num_routes)
+            } else {
+                Err(format!("{} doesn't exist", id))
+            }
+        }
+    }
+}
+
+fn num_original_routes(map: &Map) -> usize {
+    map.bus_routes
+        .iter()
+        .filter(|r| r.osm_rel_id.0 >= 0)
+        .count()
+}
+
+/// Lays out lanes from the left side of the road to the right, like during import. The lanes stay
+/// centered on the road, so if they're narrower than before, there's space left over at the edges.
+/// Everything positioned along the lanes and the polygons of the intersections at both ends follow
+/// the new geometry.
+fn recalculate_lane_geometry(r: RoadID, old_half_width: Distance, map: &mut Map) {
+    let road = &map.roads[r.0];
+    let total_width: Distance = road
+        .lanes_ltr
+        .iter()
+        .map(|(l, _, _)| map.lanes[l.0].width)
+        .sum();
+    let road_left_pts = road
+        .center_pts
+        .shift_left(total_width / 2.0)
+        .unwrap_or_else(|_| road.center_pts.clone());
+
+    let mut old_lengths = BTreeMap::new();
+    let mut width_so_far = Distance::ZERO;
+    for (l, dir, _) in road.lanes_ltr.clone() {
+        let lane = &mut map.lanes[l.0];
+        old_lengths.insert(l, lane.length());
+        let pl = road_left_pts
+            .shift_right(width_so_far + (lane.width / 2.0))
+            .unwrap_or_else(|_| road_left_pts.clone());
+        width_so_far += lane.width;
+        lane.lane_center_pts = if dir == Direction::Fwd {
+            pl
+        } else {
+            pl.reversed()
+        };
+    }
+
+    // Shifting a curved lane changes its length, so keep things at the same fraction of the way
+    // along it
+    let rescale = |pos: Position, map: &Map| -> Position {
+        match old_lengths.get(&pos.lane()) {
+            Some(old_len) if *old_len > Distance::ZERO => {
+                let new_len = map.get_l(pos.lane()).length();
+                Position::new(
+                    pos.lane(),
+                    (pos.dist_along() * (new_len / *old_len)).min(new_len),
+                )
+            }
+            _ => pos,
+        }
+    };
+    for idx in 0..map.buildings.len() {
+        let pos = rescale(map.buildings[idx].sidewalk_pos, map);
+        if pos == map.buildings[idx].sidewalk_pos {
+            continue;
+        }
+        let pt = pos.pt(map);
+        let b = &mut map.buildings[idx];
+        b.sidewalk_pos = pos;
+        b.driveway_geom = move_last_pt(&b.driveway_geom, pt);
+    }
+    let stops: Vec<BusStopID> = map.bus_stops.keys().cloned().collect();
+    for id in stops {
+        let sidewalk_pos = rescale(map.bus_stops[&id].sidewalk_pos, map);
+        let driving_pos = rescale(map.bus_stops[&id].driving_pos, map);
+        let stop = map.bus_stops.get_mut(&id).unwrap();
+        stop.sidewalk_pos = sidewalk_pos;
+        stop.driving_pos = driving_pos;
+    }
+    for idx in 0..map.parking_lots.len() {
+        let sidewalk_pos = rescale(map.parking_lots[idx].sidewalk_pos, map);
+        let driving_pos = rescale(map.parking_lots[idx].driving_pos, map);
+        let (sidewalk_pt, driving_pt) = (sidewalk_pos.pt(map), driving_pos.pt(map));
+        let lot = &mut map.parking_lots[idx];
+        if lot.sidewalk_pos != sidewalk_pos {
+            lot.sidewalk_pos = sidewalk_pos;
+            if let Some(line) = Line::new(lot.sidewalk_line.pt1(), sidewalk_pt) {
+                lot.sidewalk_line = line;
+            }
+        }
+        if lot.driving_pos != driving_pos {
+            lot.driving_pos = driving_pos;
+            lot.driveway_line = move_last_pt(&lot.driveway_line, driving_pt);
+        }
+    }
+
+    // The intersection polygons have two corners where the edges of the road used to end. Move
+    // them to where the edges end now. The rest of each polygon doesn't depend on this road.
+    let road = &map.roads[r.0];
+    let new_half_width = road.get_half_width(map);
+    for i in vec![road.src_i, road.dst_i] {
+        let center = if road.dst_i == i {
+            road.center_pts.clone()
+        } else {
+            road.center_pts.reversed()
+        };
+        let corners = |half_width: Distance| -> Option<Vec<Pt2D>> {
+            Some(vec![
+                center.shift_right(half_width).ok()?.last_pt(),
+                center.shift_left(half_width).ok()?.last_pt(),
+            ])
+        };
+        let (old_corners, new_corners) = match (corners(old_half_width), corners(new_half_width)) {
+            (Some(old), Some(new)) => (old, new),
+            _ => continue,
+        };
+        let mut pts = map.intersections[i.0].polygon.points().clone();
+        let mut moved = false;
+        for (old, new) in old_corners.into_iter().zip(new_corners) {
+            for pt in pts.iter_mut() {
+                if pt.approx_eq(old, Distance::meters(0.1)) {
+                    *pt = new;
+                    moved = true;
+                }
+            }
+        }
+        if !moved {
+            continue;
+        }
+        if let Ok(ring) = Ring::new(pts) {
+            map.intersections[i.0].polygon = ring.to_polygon();
+        }
+    }
+}
+
+fn move_last_pt(pl: &PolyLine, pt: Pt2D) -> PolyLine {
+    let mut pts = pl.points().clone();
+    pts.pop();
+    pts.push(pt);
+    PolyLine::new(pts).unwrap_or_else(|_| pl.clone())
+}
+
+/// The lanes leading to and from an intersection, based on the roads currently connected to it
+fn recalculate_intersection_lanes(id: IntersectionID, map: &mut Map) {
+    let i = &mut map.intersections[id.0];
+    i.outgoing_lanes.clear();
+    i.incoming_lanes.clear();
+    for r in &i.roads {
+        for (l, _, _) in map.roads[r.0].lanes_ltr() {
+            if map.lanes[l.0].src_i == i.id {
+                i.outgoing_lanes.push(l);
+            } else {
+                assert_eq!(map.lanes[l.0].dst_i, i.id);
+                i.incoming_lanes.push(l);
+            }
         }
     }
 }
@@ -489,8 +1043,14 @@ impl Map {
                 .into_iter()
                 .map(|(_, dir, lt)| (lt, dir))
                 .collect(),
+            lane_widths: r
+                .lanes_ltr()
+                .into_iter()
+                .map(|(l, _, _)| self.get_l(l).width)
+                .collect(),
             speed_limit: r.speed_limit,
             access_restrictions: r.access_restrictions.clone(),
+            modal_filters: r.modal_filters.clone(),
         }
     }
 
@@ -501,18 +1061,358 @@ impl Map {
         EditCmd::ChangeRoad { r, old, new }
     }
 
+    /// Produces a command to close a road to everything except bikes and buses during some times
+    /// of day, or to always open it if no times are given. Windows can't wrap around midnight.
+    pub fn timed_closure_cmd(
+        &self,
+        r: RoadID,
+        time_windows: Vec<(Time, Time)>,
+    ) -> Result<EditCmd, String> {
+        for (start, end) in &time_windows {
+            if start >= end || *end > Time::START_OF_DAY + Duration::hours(24) {
+                return Err(format!(
+                    "{} to {} isn't a time window during one day",
+                    start.ampm_tostring(),
+                    end.ampm_tostring()
+                ));
+            }
+        }
+        let old = self.get_r_edit(r);
+        let mut new = old.clone();
+        new.close_during(time_windows);
+        if new == old {
+            return Err(format!("{} already has these timed closures", r));
+        }
+        Ok(EditCmd::ChangeRoad { r, old, new })
+    }
+
+    /// Produces a command to add a modal filter to a road next to an intersection, or remove the
+    /// one that's already there.
+    pub fn toggle_modal_filter_cmd(&self, r: RoadID, i: IntersectionID) -> EditCmd {
+        let road = self.get_r(r);
+        self.edit_road_cmd(r, |new| {
+            if road.modal_filter_at(i).is_some() {
+                new.modal_filters
+                    .retain(|filter| filter.intersection(road) != i);
+            } else {
+                new.modal_filters.push(ModalFilter::new(road, i));
+            }
+        })
+    }
+
+    /// Checks if a road's lanes physically fit where the road originally was. The whole road can't
+    /// get any wider than it was, since the intersections around it were shaped for that width. A
+    /// wider roadway with narrower sidewalks fits, but the curbs would have to move, so that's
+    /// returned as a warning.
+    pub fn check_cross_section(&self, r: RoadID, new: &EditRoad) -> Result<Vec<String>, String> {
+        new.fits_within(&EditRoad::get_orig_from_osm(self.get_r(r), &self.config))
+    }
+
+    /// Can players change the type of this lane? Sidewalks, shoulders, shared left-turn lanes,
+    /// light rail, and lanes on service or removed roads are off-limits.
+    pub fn can_change_lane_type(&self, l: LaneID) -> bool {
+        let lane = self.get_l(l);
+        !lane.is_walkable()
+            && lane.lane_type != LaneType::SharedLeftTurn
+            && !lane.is_light_rail()
+            && !self.get_parent(l).is_service()
+            && !self.get_parent(l).removed
+    }
+
+    /// Produces a command to remove a road, like a slip lane or a freeway ramp. The intersections
+    /// at both ends must still have other roads, and nothing can depend on the road: no buildings,
+    /// parking lots, bus stops, or parking lanes along it, and no running bus or train route that
+    /// needs it to reach its stops.
+    pub fn remove_road_cmd(&self, r: RoadID) -> Result<EditCmd, String> {
+        let road = self.get_r(r);
+        if road.removed {
+            return Err(format!("{} is already removed", r));
+        }
+        for i in vec![road.src_i, road.dst_i] {
+            if self.get_i(i).roads.len() < 2 {
+                return Err(format!("{} would be left with no roads", i));
+            }
+        }
+        let lanes: BTreeSet<LaneID> = road.all_lanes().into_iter().collect();
+        if self
+            .all_buildings()
+            .iter()
+            .any(|b| lanes.contains(&b.sidewalk()))
+        {
+            return Err(format!("buildings are connected to {}", r));
+        }
+        if self.all_parking_lots().iter().any(|lot| {
+            lanes.contains(&lot.sidewalk_pos.lane()) || lanes.contains(&lot.driving_pos.lane())
+        }) {
+            return Err(format!("parking lots are connected to {}", r));
+        }
+        if !road.all_bus_stops(self).is_empty() {
+            return Err(format!("buses stop along {}; remove the stops first", r));
+        }
+        if road
+            .lanes_ltr()
+            .into_iter()
+            .any(|(_, _, lt)| lt == LaneType::Parking)
+        {
+            return Err(format!("{} has parking lanes; change them first", r));
+        }
+        if let Some(route) = self.routes_needing_road(r).into_iter().next() {
+            return Err(format!(
+                "route {} can't reach its stops without {}",
+                self.get_br(route).short_name,
+                r
+            ));
+        }
+        Ok(EditCmd::RemoveRoad {
+            r,
+            old: false,
+            new: true,
+        })
+    }
+
+    /// Checks new lane types for a road, before applying them. Every lane that changes must be
+    /// editable and become a type the lane editor offers, parking lanes need a driving lane, and
+    /// bus stops need a lane for buses. Lanes are compared by position, so this doesn't handle
+    /// adding or removing lanes. Returns every problem found.
+    pub fn check_lane_types(&self, r: RoadID, new: &EditRoad) -> Vec<String> {
+        let road = self.get_r(r);
+        let mut problems = Vec::new();
+        for ((l, _, old_lt), (new_lt, _)) in road.lanes_ltr().into_iter().zip(new.lanes_ltr.iter())
+        {
+            if old_lt == *new_lt {
+                continue;
+            }
+            if !self.can_change_lane_type(l) {
+                problems.push(format!("Can't change {}", old_lt.describe()));
+            }
+            match new_lt {
+                LaneType::Driving
+                | LaneType::Biking
+                | LaneType::Buffer
+                | LaneType::Bus
+                | LaneType::Parking
+                | LaneType::Construction => {}
+                _ => {
+                    problems.push(format!("A lane can't become {}", new_lt.describe()));
+                }
+            }
+        }
+
+        let has = |lt: LaneType| new.lanes_ltr.iter().any(|(x, _)| *x == lt);
+        // TODO Ban two adjacent parking lanes (What about dppd though?)
+        if has(LaneType::Parking) && !has(LaneType::Driving) {
+            problems
+                .push("A parking lane needs a driving lane somewhere on the same road".to_string());
+        }
+        // Don't orphan a bus stop.
+        // TODO This allows a bus stop switching sides of the road. Really need to re-do bus
+        // matching and make sure nothing's broken
+        // (https://github.com/dabreegster/abstreet/issues/93).
+        if !road.all_bus_stops(self).is_empty() && !has(LaneType::Driving) && !has(LaneType::Bus) {
+            problems.push("A bus stop needs a driving or bus lane".to_string());
+        }
+        problems
+    }
+
     /// Panics on borders
     pub fn get_i_edit(&self, i: IntersectionID) -> EditIntersection {
         match self.get_i(i).intersection_type {
             IntersectionType::StopSign => EditIntersection::StopSign(self.get_stop_sign(i).clone()),
             IntersectionType::TrafficSignal => {
-                EditIntersection::TrafficSignal(self.get_traffic_signal(i).export(self))
+                let ts = self.get_traffic_signal(i);
+                EditIntersection::TrafficSignal(ts.export(self), ts.transit_priority.clone())
             }
             IntersectionType::Construction => EditIntersection::Closed,
             IntersectionType::Border => unreachable!(),
         }
     }
 
+    pub fn get_bs_edit(&self, id: BusStopID) -> Option<EditBusStop> {
+        let bs = self.maybe_get_bs(id)?;
+        Some(EditBusStop {
+            name: bs.name.clone(),
+            sidewalk_pos: bs.sidewalk_pos,
+            driving_pos: bs.driving_pos,
+            is_train_stop: bs.is_train_stop,
+        })
+    }
+
+    pub fn get_br_edit(&self, id: BusRouteID) -> Option<EditRoute> {
+        let br = self.maybe_get_br(id)?;
+        Some(EditRoute {
+            full_name: br.full_name.clone(),
+            short_name: br.short_name.clone(),
+            stops: br.stops.clone(),
+            start: br.start,
+            end_border: br.end_border,
+        })
+    }
+
+    /// Produces a command to create a bus stop somewhere along a sidewalk. Buses will stop at the
+    /// closest lane they can use.
+    pub fn new_bus_stop_cmd(
+        &self,
+        sidewalk_pos: Position,
+        name: String,
+    ) -> Result<EditCmd, String> {
+        let sidewalk = sidewalk_pos.lane();
+        if !self.get_l(sidewalk).is_walkable() {
+            return Err(format!("{} isn't a sidewalk", sidewalk));
+        }
+        let driving_lane = self
+            .get_parent(sidewalk)
+            .find_closest_lane(sidewalk, |l| PathConstraints::Bus.can_use(l, self), self)
+            .ok_or_else(|| {
+                format!(
+                    "{} has no lane that buses can use",
+                    self.get_parent(sidewalk).id
+                )
+            })?;
+        let idx = self
+            .get_l(sidewalk)
+            .bus_stops
+            .iter()
+            .map(|bs| bs.idx + 1)
+            .max()
+            .unwrap_or(0);
+        Ok(EditCmd::ChangeBusStop {
+            id: BusStopID { sidewalk, idx },
+            old: None,
+            new: Some(EditBusStop {
+                name,
+                sidewalk_pos,
+                driving_pos: sidewalk_pos.equiv_pos(driving_lane, self),
+                is_train_stop: false,
+            }),
+        })
+    }
+
+    /// Fails if any route, even one that's no longer running, still uses the stop.
+    pub fn remove_bus_stop_cmd(&self, id: BusStopID) -> Result<EditCmd, String> {
+        if let Some(r) = self.all_bus_routes().iter().find(|r| r.stops.contains(&id)) {
+            return Err(format!("route {} still uses this stop", r.short_name));
+        }
+        Ok(EditCmd::ChangeBusStop {
+            id,
+            old: self.get_bs_edit(id),
+            new: None,
+        })
+    }
+
+    /// Produces a command to create a new bus route through some existing stops. It starts on a
+    /// lane leading to the first stop, and ends at the last stop.
+    pub fn new_bus_route_cmd(
+        &self,
+        name: String,
+        stops: Vec<BusStopID>,
+    ) -> Result<EditCmd, String> {
+        let start = self.pick_route_start(&stops)?;
+        let route = EditRoute {
+            full_name: name.clone(),
+            short_name: name,
+            stops,
+            start,
+            end_border: None,
+        };
+        self.check_route_connectivity(&route)?;
+        Ok(EditCmd::ChangeRoute {
+            id: BusRouteID(self.bus_routes.len()),
+            old: None,
+            new: Some(route),
+        })
+    }
+
+    /// Produces a command to remove a route created by edits. Only the most recently created route
+    /// can be removed; routes from OSM can't be removed at all.
+    pub fn remove_bus_route_cmd(&self, id: BusRouteID) -> Result<EditCmd, String> {
+        let old = self.get_br_edit(id);
+        if old.is_none() {
+            return Err(format!("{} doesn't exist", id));
+        }
+        route_count_after(
+            id,
+            &old,
+            &None,
+            num_original_routes(self),
+            self.bus_routes.len(),
+        )?;
+        Ok(EditCmd::ChangeRoute { id, old, new: None })
+    }
+
+    /// Produces a command to change the stops of an existing bus route. If the first or last stop
+    /// changes, the route no longer starts or ends at the original place.
+    pub fn reroute_bus_cmd(
+        &self,
+        id: BusRouteID,
+        stops: Vec<BusStopID>,
+    ) -> Result<EditCmd, String> {
+        let old = self.get_br_edit(id).unwrap();
+        if self.get_br(id).route_type != PathConstraints::Bus {
+            return Err("only bus routes can be changed".to_string());
+        }
+        let mut route = old.clone();
+        if stops.get(0) != old.stops.get(0) {
+            route.start = self.pick_route_start(&stops)?;
+        }
+        if stops.last() != old.stops.last() {
+            route.end_border = None;
+        }
+        route.stops = stops;
+        self.check_route_connectivity(&route)?;
+        Ok(EditCmd::ChangeRoute {
+            id,
+            old: Some(old),
+            new: Some(route),
+        })
+    }
+
+    fn pick_route_start(&self, stops: &Vec<BusStopID>) -> Result<LaneID, String> {
+        if stops.len() < 2 {
+            return Err("a route needs at least 2 stops".to_string());
+        }
+        if stops.iter().any(|bs| self.get_bs(*bs).is_train_stop) {
+            return Err("buses can't use train stops".to_string());
+        }
+        pick_start_lane(
+            self.get_bs(stops[0]).driving_pos,
+            PathConstraints::Bus,
+            self,
+        )
+    }
+
+    // This uses the current pathfinder, so it may be stale if roads were edited and pathfinding
+    // hasn't been recalculated yet.
+    fn check_route_connectivity(&self, route: &EditRoute) -> Result<(), String> {
+        let mut positions = vec![Position::start(route.start)];
+        for bs in &route.stops {
+            positions.push(self.get_bs(*bs).driving_pos);
+        }
+        if let Some(l) = route.end_border {
+            positions.push(Position::end(l, self));
+        }
+        for pair in positions.windows(2) {
+            let req = PathRequest {
+                start: pair[0],
+                end: pair[1],
+                constraints: PathConstraints::Bus,
+            };
+            if req.start.lane() == req.end.lane() && req.start.dist_along() > req.end.dist_along() {
+                return Err(format!(
+                    "Two stops seemingly out of order somewhere on {}",
+                    self.get_parent(req.start.lane()).orig_id
+                ));
+            }
+            if self.pathfind(req.clone()).is_none() {
+                return Err(format!(
+                    "No path for a bus between {} and {}",
+                    self.get_parent(req.start.lane()).orig_id,
+                    self.get_parent(req.end.lane()).orig_id
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub fn save_edits(&self) {
         // Don't overwrite the current edits with the compressed first. Otherwise, undo/redo order
         // in the UI gets messed up.
@@ -619,6 +1519,7 @@ impl Map {
         new_edits.update_derived(self);
         self.edits = new_edits;
         self.pathfinder_dirty = true;
+        self.graphs_at_time.clear();
 
         // Update zones after setting the new edits, since it'll pull merge_zones from there
         if !effects.changed_roads.is_empty() || merge_zones_changed {
@@ -676,3 +1577,123 @@ impl Map {
         self.traffic_signals.insert(signal.id, signal);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use enumset::EnumSet;
+
+    use super::*;
+    use crate::raw::RestrictionConditions;
+
+    fn hours(h: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(h)
+    }
+
+    fn two_way_road() -> EditRoad {
+        EditRoad {
+            lanes_ltr: vec![
+                (LaneType::Sidewalk, Direction::Back),
+                (LaneType::Driving, Direction::Back),
+                (LaneType::Driving, Direction::Fwd),
+                (LaneType::Sidewalk, Direction::Fwd),
+            ],
+            lane_widths: vec![
+                Distance::meters(1.5),
+                Distance::meters(3.0),
+                Distance::meters(3.0),
+                Distance::meters(1.5),
+            ],
+            speed_limit: Speed::miles_per_hour(20.0),
+            access_restrictions: AccessRestrictions::new(),
+            modal_filters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn timed_closures_keep_all_day_filters() {
+        let all_day = ModalFilter {
+            near_start: true,
+            conditions: RestrictionConditions {
+                except: EnumSet::only(PathConstraints::Bike),
+                time_windows: Vec::new(),
+            },
+        };
+        let mut road = two_way_road();
+        road.modal_filters.push(all_day.clone());
+
+        road.close_during(vec![(hours(8), hours(9))]);
+        assert_eq!(road.modal_filters.len(), 3);
+        assert_eq!(road.modal_filters[0], all_day);
+
+        // Changing the times replaces the old closure
+        road.close_during(vec![(hours(15), hours(16))]);
+        assert_eq!(road.modal_filters.len(), 3);
+        assert!(road.modal_filters[1..]
+            .iter()
+            .all(|f| f.conditions.time_windows == vec![(hours(15), hours(16))]));
+
+        road.close_during(Vec::new());
+        assert_eq!(road.modal_filters, vec![all_day]);
+    }
+
+    #[test]
+    fn cross_section_fits() {
+        let orig = two_way_road();
+        assert_eq!(orig.fits_within(&orig), Ok(Vec::new()));
+
+        // Narrower lanes leave space at the edges
+        let mut narrow = orig.clone();
+        narrow.lane_widths[1] = Distance::meters(2.5);
+        narrow.lane_widths[2] = Distance::meters(2.5);
+        assert_eq!(narrow.fits_within(&orig), Ok(Vec::new()));
+
+        // A wider roadway fits by taking space from the sidewalks, but the curbs have to move
+        let mut wide_roadway = orig.clone();
+        wide_roadway.lane_widths = vec![
+            Distance::meters(1.0),
+            Distance::meters(3.5),
+            Distance::meters(3.5),
+            Distance::meters(1.0),
+        ];
+        assert_eq!(wide_roadway.fits_within(&orig).unwrap().len(), 1);
+
+        let mut too_wide = orig.clone();
+        too_wide.lane_widths[0] = Distance::meters(2.0);
+        assert!(too_wide.fits_within(&orig).is_err());
+
+        let mut missing_width = orig.clone();
+        missing_width.lane_widths.pop();
+        assert!(missing_width.fits_within(&orig).is_err());
+
+        let mut zero_width = orig.clone();
+        zero_width.lane_widths[3] = Distance::ZERO;
+        assert!(zero_width.fits_within(&orig).is_err());
+    }
+
+    #[test]
+    fn route_changes() {
+        let route = Some(EditRoute {
+            full_name: "new route".to_string(),
+            short_name: "new route".to_string(),
+            stops: Vec::new(),
+            start: LaneID(0),
+            end_border: None,
+        });
+        // 2 routes come from OSM, and 1 was already created
+        let count = |id, old: &Option<EditRoute>, new: &Option<EditRoute>| {
+            route_count_after(BusRouteID(id), old, new, 2, 3)
+        };
+
+        assert_eq!(count(3, &None, &route), Ok(4));
+        assert!(count(4, &None, &route).is_err());
+        assert!(count(1, &None, &route).is_err());
+
+        assert_eq!(count(2, &route, &None), Ok(2));
+        assert!(count(1, &route, &None).is_err());
+        assert!(count(3, &route, &None).is_err());
+
+        assert_eq!(count(0, &route, &route), Ok(3));
+        assert_eq!(count(2, &route, &route), Ok(3));
+        assert!(count(3, &route, &route).is_err());
+    }
+}
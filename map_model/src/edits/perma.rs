@@ -3,11 +3,17 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, MapName};
-use geom::Time;
+use geom::{Distance, Time};
 
-use crate::edits::{EditCmd, EditIntersection, EditRoad, MapEdits};
+use crate::edits::{
+    check_route_changes, new_route_osm_id, num_original_routes, route_count_after, EditBusStop,
+    EditCmd, EditIntersection, EditRoad, EditRoute, MapEdits,
+};
 use crate::raw::OriginalRoad;
-use crate::{osm, ControlStopSign, IntersectionID, Map};
+use crate::{
+    osm, BusRouteID, BusStopID, ControlStopSign, IntersectionID, LaneID, Map, Position, SignType,
+    TransitPriority,
+};
 
 /// MapEdits are converted to this before serializing. Referencing things like LaneID in a Map won't
 /// work if the basemap is rebuilt from new OSM data, so instead we use stabler OSM IDs that're less
@@ -36,12 +42,52 @@ pub enum PermanentEditIntersection {
             serialize_with = "serialize_btreemap",
             deserialize_with = "deserialize_btreemap"
         )]
-        must_stop: BTreeMap<OriginalRoad, bool>,
+        signs: BTreeMap<OriginalRoad, SignType>,
+    },
+    TrafficSignal {
+        signal: seattle_traffic_signals::TrafficSignal,
+        transit_priority: Option<TransitPriority>,
     },
-    TrafficSignal(seattle_traffic_signals::TrafficSignal),
     Closed,
 }
 
+/// LaneIDs aren't stable, so refer to a lane by its road and position in that road, from left to
+/// right.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentLane {
+    road: OriginalRoad,
+    idx: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentPosition {
+    lane: PermanentLane,
+    dist_along: Distance,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentBusStopID {
+    sidewalk: PermanentLane,
+    idx: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentEditBusStop {
+    name: String,
+    sidewalk_pos: PermanentPosition,
+    driving_pos: PermanentPosition,
+    is_train_stop: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentEditRoute {
+    full_name: String,
+    short_name: String,
+    stops: Vec<PermanentBusStopID>,
+    start: PermanentLane,
+    end_border: Option<PermanentLane>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub enum PermanentEditCmd {
     ChangeRoad {
@@ -59,6 +105,22 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeBusStop {
+        id: PermanentBusStopID,
+        old: Option<PermanentEditBusStop>,
+        new: Option<PermanentEditBusStop>,
+    },
+    /// Routes created by edits have negative relation IDs.
+    ChangeRoute {
+        osm_rel_id: osm::RelationID,
+        old: Option<PermanentEditRoute>,
+        new: Option<PermanentEditRoute>,
+    },
+    RemoveRoad {
+        r: OriginalRoad,
+        old: bool,
+        new: bool,
+    },
 }
 
 impl EditCmd {
@@ -81,6 +143,25 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeBusStop { id, old, new } => PermanentEditCmd::ChangeBusStop {
+                id: bus_stop_to_permanent(*id, map),
+                old: old.as_ref().map(|bs| bs.to_permanent(map)),
+                new: new.as_ref().map(|bs| bs.to_permanent(map)),
+            },
+            EditCmd::ChangeRoute { id, old, new } => PermanentEditCmd::ChangeRoute {
+                // A removed route doesn't exist anymore
+                osm_rel_id: map
+                    .maybe_get_br(*id)
+                    .map(|r| r.osm_rel_id)
+                    .unwrap_or_else(|| new_route_osm_id(*id, map)),
+                old: old.as_ref().map(|r| r.to_permanent(map)),
+                new: new.as_ref().map(|r| r.to_permanent(map)),
+            },
+            EditCmd::RemoveRoad { r, old, new } => PermanentEditCmd::RemoveRoad {
+                r: map.get_r(*r).orig_id,
+                old: *old,
+                new: *new,
+            },
         }
     }
 }
@@ -93,7 +174,7 @@ impl PermanentEditCmd {
                 let num_current = map.get_r(id).lanes_ltr().len();
                 // The basemap changed -- it'd be pretty hard to understand the original
                 // intent of the edit.
-                if num_current != new.lanes_ltr.len() {
+                if num_current != new.lanes_ltr.len() || num_current != new.lane_widths.len() {
                     return Err(format!(
                         "number of lanes in {} is {} now, but {} in the edits",
                         r,
@@ -101,6 +182,9 @@ impl PermanentEditCmd {
                         new.lanes_ltr.len()
                     ));
                 }
+                if let Some(ref toll) = new.access_restrictions.toll {
+                    toll.validate().map_err(|err| format!("{}: {}", r, err))?;
+                }
                 Ok(EditCmd::ChangeRoad { r: id, new, old })
             }
             PermanentEditCmd::ChangeIntersection { i, new, old } => {
@@ -120,11 +204,31 @@ impl PermanentEditCmd {
                 old,
                 new,
             } => {
-                let id = map
-                    .find_br(osm_rel_id)
-                    .ok_or(format!("can't find {}", osm_rel_id))?;
+                let id = route_from_permanent(osm_rel_id, map)?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeBusStop { id, old, new } => {
+                let id = bus_stop_from_permanent(id, map)?;
+                Ok(EditCmd::ChangeBusStop {
+                    id,
+                    old: old.map(|bs| bs.from_permanent(id, map)).transpose()?,
+                    new: new.map(|bs| bs.from_permanent(id, map)).transpose()?,
+                })
+            }
+            PermanentEditCmd::ChangeRoute {
+                osm_rel_id,
+                old,
+                new,
+            } => Ok(EditCmd::ChangeRoute {
+                id: route_from_permanent(osm_rel_id, map)?,
+                old: old.map(|r| r.from_permanent(map)).transpose()?,
+                new: new.map(|r| r.from_permanent(map)).transpose()?,
+            }),
+            PermanentEditCmd::RemoveRoad { r, old, new } => Ok(EditCmd::RemoveRoad {
+                r: map.find_r_by_osm_id(r)?,
+                old,
+                new,
+            }),
         }
     }
 }
@@ -136,7 +240,7 @@ impl MapEdits {
             map_name: map.get_name().clone(),
             edits_name: self.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 4,
+            version: 7,
             proposal_description: self.proposal_description.clone(),
             proposal_link: self.proposal_link.clone(),
             commands: self.commands.iter().map(|cmd| cmd.to_perma(map)).collect(),
@@ -161,9 +265,13 @@ impl PermanentMapEdits {
             merge_zones: self.merge_zones,
 
             changed_roads: BTreeSet::new(),
+            removed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_bus_stops: BTreeMap::new(),
+            original_routes: BTreeMap::new(),
         };
+        check_route_changes(&edits.commands, map)?;
         edits.update_derived(map);
         Ok(edits)
     }
@@ -171,20 +279,40 @@ impl PermanentMapEdits {
     /// Transform permanent edits to MapEdits, looking up the map IDs by the hopefully stabler OSM
     /// IDs. Strip out commands that're broken.
     pub fn to_edits_permissive(self, map: &Map) -> MapEdits {
+        let mut commands: Vec<EditCmd> = self
+            .commands
+            .into_iter()
+            .filter_map(|cmd| cmd.to_cmd(map).ok())
+            .collect();
+        // Routes can only be created and removed in order
+        let num_original = num_original_routes(map);
+        let mut num_routes = num_original;
+        commands.retain(|cmd| match cmd {
+            EditCmd::ChangeRoute { id, old, new } => {
+                match route_count_after(*id, old, new, num_original, num_routes) {
+                    Ok(n) => {
+                        num_routes = n;
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+            _ => true,
+        });
+
         let mut edits = MapEdits {
             edits_name: self.edits_name,
             proposal_description: self.proposal_description,
             proposal_link: self.proposal_link,
-            commands: self
-                .commands
-                .into_iter()
-                .filter_map(|cmd| cmd.to_cmd(map).ok())
-                .collect(),
+            commands,
             merge_zones: self.merge_zones,
 
             changed_roads: BTreeSet::new(),
+            removed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_bus_stops: BTreeMap::new(),
+            original_routes: BTreeMap::new(),
         };
         edits.update_derived(map);
         edits
@@ -195,14 +323,17 @@ impl EditIntersection {
     fn to_permanent(&self, map: &Map) -> PermanentEditIntersection {
         match self {
             EditIntersection::StopSign(ref ss) => PermanentEditIntersection::StopSign {
-                must_stop: ss
+                signs: ss
                     .roads
                     .iter()
-                    .map(|(r, val)| (map.get_r(*r).orig_id, val.must_stop))
+                    .map(|(r, val)| (map.get_r(*r).orig_id, val.sign_type))
                     .collect(),
             },
-            EditIntersection::TrafficSignal(ref raw_ts) => {
-                PermanentEditIntersection::TrafficSignal(raw_ts.clone())
+            EditIntersection::TrafficSignal(ref raw_ts, ref transit_priority) => {
+                PermanentEditIntersection::TrafficSignal {
+                    signal: raw_ts.clone(),
+                    transit_priority: transit_priority.clone(),
+                }
             }
             EditIntersection::Closed => PermanentEditIntersection::Closed,
         }
@@ -212,24 +343,24 @@ impl EditIntersection {
 impl PermanentEditIntersection {
     fn from_permanent(self, i: IntersectionID, map: &Map) -> Result<EditIntersection, String> {
         match self {
-            PermanentEditIntersection::StopSign { must_stop } => {
-                let mut translated_must_stop = BTreeMap::new();
-                for (r, stop) in must_stop {
-                    translated_must_stop.insert(map.find_r_by_osm_id(r)?, stop);
+            PermanentEditIntersection::StopSign { signs } => {
+                let mut translated_signs = BTreeMap::new();
+                for (r, sign_type) in signs {
+                    translated_signs.insert(map.find_r_by_osm_id(r)?, sign_type);
                 }
 
                 // Make sure the roads exactly match up
                 let mut ss = ControlStopSign::new(map, i);
-                if translated_must_stop.len() != ss.roads.len() {
+                if translated_signs.len() != ss.roads.len() {
                     return Err(format!(
                         "Stop sign has {} roads now, but {} from edits",
                         ss.roads.len(),
-                        translated_must_stop.len()
+                        translated_signs.len()
                     ));
                 }
-                for (r, stop) in translated_must_stop {
+                for (r, sign_type) in translated_signs {
                     if let Some(road) = ss.roads.get_mut(&r) {
-                        road.must_stop = stop;
+                        road.sign_type = sign_type;
                     } else {
                         return Err(format!("{} doesn't connect to {}", i, r));
                     }
@@ -237,8 +368,134 @@ impl PermanentEditIntersection {
 
                 Ok(EditIntersection::StopSign(ss))
             }
-            PermanentEditIntersection::TrafficSignal(ts) => Ok(EditIntersection::TrafficSignal(ts)),
+            PermanentEditIntersection::TrafficSignal {
+                signal,
+                transit_priority,
+            } => Ok(EditIntersection::TrafficSignal(signal, transit_priority)),
             PermanentEditIntersection::Closed => Ok(EditIntersection::Closed),
         }
     }
 }
+
+impl EditBusStop {
+    fn to_permanent(&self, map: &Map) -> PermanentEditBusStop {
+        PermanentEditBusStop {
+            name: self.name.clone(),
+            sidewalk_pos: pos_to_permanent(self.sidewalk_pos, map),
+            driving_pos: pos_to_permanent(self.driving_pos, map),
+            is_train_stop: self.is_train_stop,
+        }
+    }
+}
+
+impl PermanentEditBusStop {
+    fn from_permanent(self, id: BusStopID, map: &Map) -> Result<EditBusStop, String> {
+        let sidewalk_pos = pos_from_permanent(self.sidewalk_pos, map)?;
+        if sidewalk_pos.lane() != id.sidewalk {
+            return Err(format!("{} isn't on {}", self.name, id.sidewalk));
+        }
+        Ok(EditBusStop {
+            name: self.name,
+            sidewalk_pos,
+            driving_pos: pos_from_permanent(self.driving_pos, map)?,
+            is_train_stop: self.is_train_stop,
+        })
+    }
+}
+
+impl EditRoute {
+    fn to_permanent(&self, map: &Map) -> PermanentEditRoute {
+        PermanentEditRoute {
+            full_name: self.full_name.clone(),
+            short_name: self.short_name.clone(),
+            stops: self
+                .stops
+                .iter()
+                .map(|bs| bus_stop_to_permanent(*bs, map))
+                .collect(),
+            start: lane_to_permanent(self.start, map),
+            end_border: self.end_border.map(|l| lane_to_permanent(l, map)),
+        }
+    }
+}
+
+impl PermanentEditRoute {
+    fn from_permanent(self, map: &Map) -> Result<EditRoute, String> {
+        Ok(EditRoute {
+            full_name: self.full_name,
+            short_name: self.short_name,
+            stops: self
+                .stops
+                .into_iter()
+                .map(|bs| bus_stop_from_permanent(bs, map))
+                .collect::<Result<Vec<_>, String>>()?,
+            start: lane_from_permanent(self.start, map)?,
+            end_border: self
+                .end_border
+                .map(|l| lane_from_permanent(l, map))
+                .transpose()?,
+        })
+    }
+}
+
+fn lane_to_permanent(l: LaneID, map: &Map) -> PermanentLane {
+    let r = map.get_parent(l);
+    PermanentLane {
+        road: r.orig_id,
+        idx: r.offset(l),
+    }
+}
+
+fn lane_from_permanent(lane: PermanentLane, map: &Map) -> Result<LaneID, String> {
+    let r = map.get_r(map.find_r_by_osm_id(lane.road)?);
+    r.lanes_ltr()
+        .get(lane.idx)
+        .map(|(l, _, _)| *l)
+        .ok_or_else(|| format!("{} doesn't have a lane {}", lane.road, lane.idx))
+}
+
+fn pos_to_permanent(pos: Position, map: &Map) -> PermanentPosition {
+    PermanentPosition {
+        lane: lane_to_permanent(pos.lane(), map),
+        dist_along: pos.dist_along(),
+    }
+}
+
+fn pos_from_permanent(pos: PermanentPosition, map: &Map) -> Result<Position, String> {
+    let l = lane_from_permanent(pos.lane, map)?;
+    if pos.dist_along > map.get_l(l).length() {
+        return Err(format!(
+            "{} is only {} long now, but edits refer to {}",
+            l,
+            map.get_l(l).length(),
+            pos.dist_along
+        ));
+    }
+    Ok(Position::new(l, pos.dist_along))
+}
+
+fn bus_stop_to_permanent(id: BusStopID, map: &Map) -> PermanentBusStopID {
+    PermanentBusStopID {
+        sidewalk: lane_to_permanent(id.sidewalk, map),
+        idx: id.idx,
+    }
+}
+
+fn bus_stop_from_permanent(id: PermanentBusStopID, map: &Map) -> Result<BusStopID, String> {
+    Ok(BusStopID {
+        sidewalk: lane_from_permanent(id.sidewalk, map)?,
+        idx: id.idx,
+    })
+}
+
+// Routes created by edits might not exist in the map yet, so their ID is worked out from the
+// relation ID, instead of looked up.
+fn route_from_permanent(osm_rel_id: osm::RelationID, map: &Map) -> Result<BusRouteID, String> {
+    if osm_rel_id.0 < 0 {
+        return Ok(BusRouteID(
+            num_original_routes(map) + (-1 - osm_rel_id.0) as usize,
+        ));
+    }
+    map.find_br(osm_rel_id)
+        .ok_or(format!("can't find {}", osm_rel_id))
+}
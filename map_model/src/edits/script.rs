@@ -0,0 +1,262 @@
+//! Instead of editing roads one at a time in the UI, describe a policy -- like "every residential
+//! road faster than 25mph becomes 20mph" -- and apply it to an entire map reproducibly.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use geom::Speed;
+
+use crate::edits::{EditCmd, EditRoad, MapEdits};
+use crate::{osm, LaneType, Map, Road, RoadID};
+
+/// A list of rules applied to a map in order. Later rules see the changes made by earlier ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditScript {
+    pub edits_name: String,
+    pub rules: Vec<EditRule>,
+}
+
+/// Make some changes to every road matching a query.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditRule {
+    pub roads: RoadQuery,
+    pub changes: Vec<RoadChange>,
+}
+
+/// Which roads a rule applies to. Every condition that's specified must match.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RoadQuery {
+    /// The OSM highway type must be one of these, like "residential" or "primary".
+    #[serde(default)]
+    pub highway: Vec<String>,
+    /// The road's name must match exactly.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The current speed limit must be strictly greater than this.
+    #[serde(default)]
+    pub speed_limit_above_mph: Option<f64>,
+    /// The current speed limit must be strictly less than this.
+    #[serde(default)]
+    pub speed_limit_below_mph: Option<f64>,
+    /// The road must currently have at least one lane of this type.
+    #[serde(default)]
+    pub has_lane: Option<LaneType>,
+    /// The road must have all of these OSM tags.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RoadChange {
+    SpeedLimitMph(f64),
+    /// Changes every lane of one type to another.
+    ChangeLaneType {
+        from: LaneType,
+        to: LaneType,
+    },
+}
+
+impl EditScript {
+    /// Evaluates the script against the map, with any edits currently applied, and returns new
+    /// edits with the changes added. Roads where a rule would produce something invalid are left
+    /// alone and described in the list of problems.
+    pub fn evaluate(&self, map: &Map) -> (MapEdits, Vec<String>) {
+        // The current state of every road changed so far
+        let mut roads: BTreeMap<RoadID, EditRoad> = BTreeMap::new();
+        let mut problems = Vec::new();
+
+        for (idx, rule) in self.rules.iter().enumerate() {
+            let mut matches = 0;
+            for r in map.all_roads() {
+                let current = roads
+                    .get(&r.id)
+                    .cloned()
+                    .unwrap_or_else(|| map.get_r_edit(r.id));
+                if !rule.roads.matches(r, &current) {
+                    continue;
+                }
+                matches += 1;
+
+                let mut new = current.clone();
+                for change in &rule.changes {
+                    change.apply(&mut new);
+                }
+                if new == current {
+                    continue;
+                }
+                let errors = check_road(map, r.id, &new);
+                if errors.is_empty() {
+                    roads.insert(r.id, new);
+                } else {
+                    for err in errors {
+                        problems.push(format!("Rule {} skipped {}: {}", idx + 1, r.id, err));
+                    }
+                }
+            }
+            if matches == 0 {
+                problems.push(format!("Rule {} didn't match any roads", idx + 1));
+            }
+        }
+
+        let mut edits = map.get_edits().clone();
+        edits.edits_name = self.edits_name.clone();
+        for (r, new) in roads {
+            edits.commands.push(EditCmd::ChangeRoad {
+                r,
+                old: map.get_r_edit(r),
+                new,
+            });
+        }
+        (edits, problems)
+    }
+}
+
+impl RoadQuery {
+    fn matches(&self, road: &Road, current: &EditRoad) -> bool {
+        if !self.highway.is_empty()
+            && !road
+                .osm_tags
+                .get(osm::HIGHWAY)
+                .map(|hwy| self.highway.contains(hwy))
+                .unwrap_or(false)
+        {
+            return false;
+        }
+        if let Some(ref name) = self.name {
+            if road.get_name(None) != *name {
+                return false;
+            }
+        }
+        if let Some(mph) = self.speed_limit_above_mph {
+            if current.speed_limit <= Speed::miles_per_hour(mph) {
+                return false;
+            }
+        }
+        if let Some(mph) = self.speed_limit_below_mph {
+            if current.speed_limit >= Speed::miles_per_hour(mph) {
+                return false;
+            }
+        }
+        if let Some(lt) = self.has_lane {
+            if !current.lanes_ltr.iter().any(|(x, _)| *x == lt) {
+                return false;
+            }
+        }
+        self.tags.iter().all(|(k, v)| road.osm_tags.is(k, v))
+    }
+}
+
+impl RoadChange {
+    fn apply(&self, road: &mut EditRoad) {
+        match self {
+            RoadChange::SpeedLimitMph(mph) => {
+                road.speed_limit = Speed::miles_per_hour(*mph);
+            }
+            RoadChange::ChangeLaneType { from, to } => {
+                for (lt, _) in &mut road.lanes_ltr {
+                    if lt == from {
+                        *lt = *to;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The same rules the lane editor enforces, plus a valid speed limit.
+fn check_road(map: &Map, r: RoadID, new: &EditRoad) -> Vec<String> {
+    let mut problems = map.check_lane_types(r, new);
+    if new.speed_limit <= Speed::ZERO {
+        problems.push("The speed limit must be positive".to_string());
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Distance;
+
+    use super::*;
+    use crate::{AccessRestrictions, Direction};
+
+    #[test]
+    fn parse_script() {
+        let script: EditScript = serde_json::from_str(
+            r#"{
+              "edits_name": "slow streets",
+              "rules": [
+                {
+                  "roads": { "highway": ["residential"], "speed_limit_above_mph": 25.0 },
+                  "changes": [{ "SpeedLimitMph": 20.0 }]
+                },
+                {
+                  "roads": { "name": "Roosevelt Way Northeast", "has_lane": "Parking" },
+                  "changes": [{ "ChangeLaneType": { "from": "Parking", "to": "Biking" } }]
+                }
+              ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(script.edits_name, "slow streets");
+        assert_eq!(script.rules.len(), 2);
+
+        let query = &script.rules[0].roads;
+        assert_eq!(query.highway, vec!["residential".to_string()]);
+        assert_eq!(query.speed_limit_above_mph, Some(25.0));
+        // Conditions that aren't specified don't filter anything
+        assert_eq!(query.name, None);
+        assert_eq!(query.has_lane, None);
+        assert!(query.tags.is_empty());
+
+        let query = &script.rules[1].roads;
+        assert!(query.highway.is_empty());
+        assert_eq!(query.name, Some("Roosevelt Way Northeast".to_string()));
+        assert_eq!(query.has_lane, Some(LaneType::Parking));
+        match script.rules[1].changes[0] {
+            RoadChange::ChangeLaneType { from, to } => {
+                assert_eq!(from, LaneType::Parking);
+                assert_eq!(to, LaneType::Biking);
+            }
+            ref x => panic!("parsed {:?}", x),
+        }
+
+        // Typos in a change shouldn't be silently ignored
+        assert!(serde_json::from_str::<RoadChange>(r#"{ "SpeedLimit": 20.0 }"#).is_err());
+    }
+
+    #[test]
+    fn apply_changes() {
+        let mut road = EditRoad {
+            lanes_ltr: vec![
+                (LaneType::Sidewalk, Direction::Back),
+                (LaneType::Parking, Direction::Back),
+                (LaneType::Driving, Direction::Back),
+                (LaneType::Driving, Direction::Fwd),
+                (LaneType::Parking, Direction::Fwd),
+                (LaneType::Sidewalk, Direction::Fwd),
+            ],
+            lane_widths: vec![Distance::meters(2.0); 6],
+            speed_limit: Speed::miles_per_hour(30.0),
+            access_restrictions: AccessRestrictions::new(),
+            modal_filters: Vec::new(),
+        };
+        RoadChange::SpeedLimitMph(20.0).apply(&mut road);
+        RoadChange::ChangeLaneType {
+            from: LaneType::Parking,
+            to: LaneType::Biking,
+        }
+        .apply(&mut road);
+        assert_eq!(road.speed_limit, Speed::miles_per_hour(20.0));
+        assert_eq!(
+            road.lanes_ltr
+                .iter()
+                .filter(|(lt, _)| *lt == LaneType::Biking)
+                .count(),
+            2
+        );
+        // The sidewalks and driving lanes are untouched
+        assert_eq!(road.lanes_ltr[0], (LaneType::Sidewalk, Direction::Back));
+        assert_eq!(road.lanes_ltr[3], (LaneType::Driving, Direction::Fwd));
+    }
+}
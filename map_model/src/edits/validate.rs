@@ -0,0 +1,493 @@
+//! Check what a set of edits might break -- disconnected lanes, unreachable buildings, incomplete
+//! traffic signals, broken transit routes, zones nobody can enter, and roads too wide to fit.
+//! These checks don't need pathfinding to be up-to-date, so they're usable from the editor,
+//! headless, or the command line.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use petgraph::graphmap::DiGraphMap;
+use serde::Serialize;
+
+use abstutil::Timer;
+
+use crate::edits::MapEdits;
+use crate::{
+    connectivity, BuildingID, BusRouteID, IntersectionID, LaneID, Map, MovementID, PathConstraints,
+    RoadID,
+};
+
+/// Something that's wrong with a map. When checking edits, only problems that the edits introduce
+/// are reported.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum EditProblem {
+    /// These lanes can't be reached from the rest of the map, or can't reach it.
+    DisconnectedLanes {
+        constraints: PathConstraints,
+        lanes: BTreeSet<LaneID>,
+    },
+    /// These buildings can't be reached on foot or by car.
+    UnreachableBuildings {
+        constraints: PathConstraints,
+        buildings: BTreeSet<BuildingID>,
+    },
+    /// No stage of a traffic signal serves these movements, so anybody wanting to make them is
+    /// stuck.
+    SignalMissingMovements {
+        i: IntersectionID,
+        movements: BTreeSet<MovementID>,
+    },
+    /// A bus or train route with scheduled vehicles can't go between two of its stops.
+    BrokenRoute { route: BusRouteID, reason: String },
+    /// Some roads with access restrictions don't touch any other roads, so there's no way in.
+    ZoneWithoutBorders { roads: BTreeSet<RoadID> },
+    /// The lanes of this road don't physically fit in the space the road originally had.
+    ImpossibleCrossSection { road: RoadID, reason: String },
+}
+
+impl Map {
+    /// Temporarily applies the edits to see what problems they introduce, then restores the
+    /// current edits. The map winds up exactly as it was, so this is safe to call on a live map
+    /// without recalculating pathfinding.
+    pub fn check_edits(&mut self, edits: MapEdits, timer: &mut Timer) -> Vec<EditProblem> {
+        let before = self.find_problems(timer);
+        let orig_edits = self.get_edits().clone();
+        let pathfinder_was_dirty = self.pathfinder_dirty;
+        timer.start("apply edits to check");
+        self.try_apply_edits(edits, timer);
+        timer.stop("apply edits to check");
+        let after = self.find_problems(timer);
+        timer.start("restore edits");
+        self.must_apply_edits(orig_edits, timer);
+        timer.stop("restore edits");
+        // Lanes and turns are back to the way they were, so the old pathfinder still works
+        self.pathfinder_dirty = pathfinder_was_dirty;
+
+        after
+            .into_iter()
+            .filter_map(|problem| problem.subtract(&before))
+            .collect()
+    }
+
+    /// Every problem with the map in its current state, whether edits caused it or not.
+    pub fn find_problems(&self, timer: &mut Timer) -> Vec<EditProblem> {
+        let mut problems = Vec::new();
+
+        timer.start("check connectivity");
+        let mut disconnected = BTreeMap::new();
+        for constraints in vec![
+            PathConstraints::Pedestrian,
+            PathConstraints::Car,
+            PathConstraints::Bike,
+            PathConstraints::Bus,
+        ] {
+            let lanes: BTreeSet<LaneID> = connectivity::find_scc(self, constraints)
+                .1
+                .into_iter()
+                .collect();
+            if !lanes.is_empty() {
+                problems.push(EditProblem::DisconnectedLanes {
+                    constraints,
+                    lanes: lanes.clone(),
+                });
+            }
+            disconnected.insert(constraints, lanes);
+        }
+        timer.stop("check connectivity");
+
+        let mut unreachable_on_foot = BTreeSet::new();
+        let mut unreachable_by_car = BTreeSet::new();
+        for b in self.all_buildings() {
+            if disconnected[&PathConstraints::Pedestrian].contains(&b.sidewalk()) {
+                unreachable_on_foot.insert(b.id);
+            }
+            match b.driving_connection(self) {
+                Some((pos, _)) => {
+                    if disconnected[&PathConstraints::Car].contains(&pos.lane()) {
+                        unreachable_by_car.insert(b.id);
+                    }
+                }
+                None => {
+                    unreachable_by_car.insert(b.id);
+                }
+            }
+        }
+        for (constraints, buildings) in vec![
+            (PathConstraints::Pedestrian, unreachable_on_foot),
+            (PathConstraints::Car, unreachable_by_car),
+        ] {
+            if !buildings.is_empty() {
+                problems.push(EditProblem::UnreachableBuildings {
+                    constraints,
+                    buildings,
+                });
+            }
+        }
+
+        for i in self.all_intersections() {
+            if let Some(signal) = self.maybe_get_traffic_signal(i.id) {
+                let movements = signal.missing_turns();
+                if !movements.is_empty() {
+                    problems.push(EditProblem::SignalMissingMovements { i: i.id, movements });
+                }
+            }
+        }
+
+        timer.start("check transit routes");
+        let mut graphs = BTreeMap::new();
+        for route in self.all_bus_routes() {
+            if route.spawn_times.is_empty() {
+                continue;
+            }
+            let graph = graphs
+                .entry(route.route_type)
+                .or_insert_with(|| lane_graph(self, route.route_type));
+            if let Err(reason) = self.check_route(route.id, graph) {
+                problems.push(EditProblem::BrokenRoute {
+                    route: route.id,
+                    reason,
+                });
+            }
+        }
+        timer.stop("check transit routes");
+
+        for zone in self.all_zones() {
+            if zone.borders.is_empty() {
+                problems.push(EditProblem::ZoneWithoutBorders {
+                    roads: zone.members.clone(),
+                });
+            }
+        }
+
+        for r in &self.get_edits().changed_roads {
+            if let Err(reason) = self.check_cross_section(*r, &self.get_r_edit(*r)) {
+                problems.push(EditProblem::ImpossibleCrossSection { road: *r, reason });
+            }
+        }
+
+        problems
+    }
+
+    /// Running bus and train routes that couldn't reach all of their stops without this road
+    pub(crate) fn routes_needing_road(&self, r: RoadID) -> Vec<BusRouteID> {
+        let lanes = self.get_r(r).all_lanes();
+        let mut graphs = BTreeMap::new();
+        let mut routes = Vec::new();
+        for route in self.all_bus_routes() {
+            if route.spawn_times.is_empty() {
+                continue;
+            }
+            let graph = graphs.entry(route.route_type).or_insert_with(|| {
+                let mut graph = lane_graph(self, route.route_type);
+                for l in &lanes {
+                    graph.remove_node(*l);
+                }
+                graph
+            });
+            if self.check_route(route.id, graph).is_err() {
+                routes.push(route.id);
+            }
+        }
+        routes
+    }
+
+    fn check_route(&self, id: BusRouteID, graph: &DiGraphMap<LaneID, ()>) -> Result<(), String> {
+        let route = self.get_br(id);
+        for req in route.all_steps(self) {
+            for pos in vec![req.start, req.end] {
+                if !route.route_type.can_use(self.get_l(pos.lane()), self) {
+                    return Err(format!(
+                        "{} can't use {} anymore",
+                        route.plural_noun(),
+                        pos.lane()
+                    ));
+                }
+            }
+            if req.start.lane() != req.end.lane()
+                && !petgraph::algo::has_path_connecting(
+                    graph,
+                    req.start.lane(),
+                    req.end.lane(),
+                    None,
+                )
+            {
+                return Err(format!(
+                    "{} can't get from {} to {}",
+                    route.plural_noun(),
+                    req.start.lane(),
+                    req.end.lane()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lanes usable by some constraints, connected by turns
+fn lane_graph(map: &Map, constraints: PathConstraints) -> DiGraphMap<LaneID, ()> {
+    let mut graph = DiGraphMap::new();
+    for turn in map.all_turns().values() {
+        if constraints.can_use(map.get_l(turn.id.src), map)
+            && constraints.can_use(map.get_l(turn.id.dst), map)
+        {
+            graph.add_edge(turn.id.src, turn.id.dst, ());
+        }
+    }
+    graph
+}
+
+impl EditProblem {
+    /// Removes anything that's also in one of the existing problems. None if nothing's left.
+    fn subtract(mut self, existing: &[EditProblem]) -> Option<EditProblem> {
+        for other in existing {
+            match (&mut self, other) {
+                (
+                    EditProblem::DisconnectedLanes { constraints, lanes },
+                    EditProblem::DisconnectedLanes {
+                        constraints: c2,
+                        lanes: l2,
+                    },
+                ) if constraints == c2 => {
+                    lanes.retain(|l| !l2.contains(l));
+                    if lanes.is_empty() {
+                        return None;
+                    }
+                }
+                (
+                    EditProblem::UnreachableBuildings {
+                        constraints,
+                        buildings,
+                    },
+                    EditProblem::UnreachableBuildings {
+                        constraints: c2,
+                        buildings: b2,
+                    },
+                ) if constraints == c2 => {
+                    buildings.retain(|b| !b2.contains(b));
+                    if buildings.is_empty() {
+                        return None;
+                    }
+                }
+                (
+                    EditProblem::SignalMissingMovements { i, movements },
+                    EditProblem::SignalMissingMovements {
+                        i: i2,
+                        movements: m2,
+                    },
+                ) if i == i2 => {
+                    movements.retain(|m| !m2.contains(m));
+                    if movements.is_empty() {
+                        return None;
+                    }
+                }
+                (
+                    EditProblem::BrokenRoute { route, .. },
+                    EditProblem::BrokenRoute { route: r2, .. },
+                ) if route == r2 => {
+                    return None;
+                }
+                (
+                    EditProblem::ZoneWithoutBorders { roads },
+                    EditProblem::ZoneWithoutBorders { roads: r2 },
+                ) if roads == r2 => {
+                    return None;
+                }
+                (
+                    EditProblem::ImpossibleCrossSection { road, .. },
+                    EditProblem::ImpossibleCrossSection { road: r2, .. },
+                ) if road == r2 => {
+                    return None;
+                }
+                _ => {}
+            }
+        }
+        Some(self)
+    }
+
+    pub fn describe(&self, map: &Map) -> String {
+        match self {
+            EditProblem::DisconnectedLanes { constraints, lanes } => format!(
+                "{} lanes are disconnected for {:?}",
+                lanes.len(),
+                constraints
+            ),
+            EditProblem::UnreachableBuildings {
+                constraints,
+                buildings,
+            } => format!(
+                "{} buildings can't be reached for {:?}",
+                buildings.len(),
+                constraints
+            ),
+            EditProblem::SignalMissingMovements { i, movements } => format!(
+                "The traffic signal at {} doesn't serve {} movements",
+                i,
+                movements.len()
+            ),
+            EditProblem::BrokenRoute { route, reason } => {
+                let route = map.get_br(*route);
+                format!("Route {} is broken: {}", route.short_name, reason)
+            }
+            EditProblem::ZoneWithoutBorders { roads } => format!(
+                "A zone with {} roads and access restrictions can't be entered",
+                roads.len()
+            ),
+            EditProblem::ImpossibleCrossSection { road, reason } => {
+                format!("{} doesn't fit: {}", road, reason)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DirectedRoadID, Direction};
+
+    fn lanes(constraints: PathConstraints, ids: Vec<usize>) -> EditProblem {
+        EditProblem::DisconnectedLanes {
+            constraints,
+            lanes: ids.into_iter().map(LaneID).collect(),
+        }
+    }
+
+    fn movement(from: usize, to: usize) -> MovementID {
+        MovementID {
+            from: DirectedRoadID {
+                id: RoadID(from),
+                dir: Direction::Fwd,
+            },
+            to: DirectedRoadID {
+                id: RoadID(to),
+                dir: Direction::Fwd,
+            },
+            parent: IntersectionID(0),
+            crosswalk: false,
+        }
+    }
+
+    #[test]
+    fn subtract_partial() {
+        let existing = vec![
+            lanes(PathConstraints::Car, vec![1, 2]),
+            EditProblem::UnreachableBuildings {
+                constraints: PathConstraints::Pedestrian,
+                buildings: vec![BuildingID(5)].into_iter().collect(),
+            },
+            EditProblem::SignalMissingMovements {
+                i: IntersectionID(0),
+                movements: vec![movement(1, 2)].into_iter().collect(),
+            },
+        ];
+
+        assert_eq!(
+            lanes(PathConstraints::Car, vec![1, 2, 3]).subtract(&existing),
+            Some(lanes(PathConstraints::Car, vec![3]))
+        );
+        assert_eq!(
+            EditProblem::UnreachableBuildings {
+                constraints: PathConstraints::Pedestrian,
+                buildings: vec![BuildingID(5), BuildingID(6)].into_iter().collect(),
+            }
+            .subtract(&existing),
+            Some(EditProblem::UnreachableBuildings {
+                constraints: PathConstraints::Pedestrian,
+                buildings: vec![BuildingID(6)].into_iter().collect(),
+            })
+        );
+        assert_eq!(
+            EditProblem::SignalMissingMovements {
+                i: IntersectionID(0),
+                movements: vec![movement(1, 2), movement(2, 1)].into_iter().collect(),
+            }
+            .subtract(&existing),
+            Some(EditProblem::SignalMissingMovements {
+                i: IntersectionID(0),
+                movements: vec![movement(2, 1)].into_iter().collect(),
+            })
+        );
+    }
+
+    #[test]
+    fn subtract_everything() {
+        let existing = vec![
+            lanes(PathConstraints::Bike, vec![1]),
+            lanes(PathConstraints::Bike, vec![2]),
+            EditProblem::BrokenRoute {
+                route: BusRouteID(3),
+                reason: "old reason".to_string(),
+            },
+            EditProblem::ZoneWithoutBorders {
+                roads: vec![RoadID(1), RoadID(2)].into_iter().collect(),
+            },
+            EditProblem::ImpossibleCrossSection {
+                road: RoadID(4),
+                reason: "old reason".to_string(),
+            },
+        ];
+
+        // Spread across two existing problems
+        assert_eq!(
+            lanes(PathConstraints::Bike, vec![1, 2]).subtract(&existing),
+            None
+        );
+        // The reason doesn't matter, just which route or road is broken
+        assert_eq!(
+            EditProblem::BrokenRoute {
+                route: BusRouteID(3),
+                reason: "new reason".to_string(),
+            }
+            .subtract(&existing),
+            None
+        );
+        assert_eq!(
+            EditProblem::ZoneWithoutBorders {
+                roads: vec![RoadID(1), RoadID(2)].into_iter().collect(),
+            }
+            .subtract(&existing),
+            None
+        );
+        assert_eq!(
+            EditProblem::ImpossibleCrossSection {
+                road: RoadID(4),
+                reason: "new reason".to_string(),
+            }
+            .subtract(&existing),
+            None
+        );
+    }
+
+    #[test]
+    fn subtract_unrelated() {
+        let existing = vec![
+            lanes(PathConstraints::Car, vec![1]),
+            EditProblem::BrokenRoute {
+                route: BusRouteID(3),
+                reason: String::new(),
+            },
+            EditProblem::ZoneWithoutBorders {
+                roads: vec![RoadID(1)].into_iter().collect(),
+            },
+        ];
+
+        // Different constraints
+        let problem = lanes(PathConstraints::Bus, vec![1]);
+        assert_eq!(problem.clone().subtract(&existing), Some(problem));
+        // Different route
+        let problem = EditProblem::BrokenRoute {
+            route: BusRouteID(4),
+            reason: String::new(),
+        };
+        assert_eq!(problem.clone().subtract(&existing), Some(problem));
+        // A zone only matches exactly the same roads
+        let problem = EditProblem::ZoneWithoutBorders {
+            roads: vec![RoadID(1), RoadID(2)].into_iter().collect(),
+        };
+        assert_eq!(problem.clone().subtract(&existing), Some(problem));
+        // A different kind of problem
+        let problem = EditProblem::SignalMissingMovements {
+            i: IntersectionID(0),
+            movements: vec![movement(1, 2)].into_iter().collect(),
+        };
+        assert_eq!(problem.clone().subtract(&existing), Some(problem));
+    }
+}
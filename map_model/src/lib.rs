@@ -33,9 +33,11 @@ use geom::{Bounds, Distance, GPSBounds, Polygon};
 
 pub use crate::city::City;
 pub use crate::edits::{
-    EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentMapEdits,
+    EditBusStop, EditCmd, EditConflict, EditDiff, EditEffects, EditIntersection, EditProblem,
+    EditRoad, EditRoute, EditRule, EditScript, MapEdits, PermanentMapEdits, RoadChange, RoadQuery,
 };
 pub use crate::map::{DrivingSide, MapConfig};
+pub use crate::neighborhood::{Cell, Neighborhood, RatRun};
 pub use crate::objects::area::{Area, AreaID, AreaType};
 pub use crate::objects::building::{
     Amenity, Building, BuildingID, BuildingType, NamePerLanguage, OffstreetParking,
@@ -46,16 +48,19 @@ pub use crate::objects::lane::{
     Lane, LaneID, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH,
 };
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
-pub use crate::objects::road::{DirectedRoadID, Direction, Road, RoadID};
-pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::objects::traffic_signals::{ControlTrafficSignal, PhaseType, Stage};
+pub use crate::objects::road::{DirectedRoadID, Direction, ModalFilter, Road, RoadID};
+pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign, SignType};
+pub use crate::objects::traffic_signals::{
+    ControlTrafficSignal, PhaseType, Stage, TransitPriority,
+};
 pub use crate::objects::turn::{
     CompressedMovementID, Movement, MovementID, Turn, TurnID, TurnPriority, TurnType,
 };
-pub use crate::objects::zone::{AccessRestrictions, Zone};
+pub use crate::objects::zone::{AccessRestrictions, Toll, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn, UberTurnGroup};
-use crate::pathfind::Pathfinder;
+use crate::pathfind::{GraphsAtTime, Pathfinder};
 pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
+pub use crate::remap::IDRemap;
 pub use crate::traversable::{Position, Traversable};
 
 mod city;
@@ -63,10 +68,12 @@ pub mod connectivity;
 mod edits;
 mod make;
 mod map;
+mod neighborhood;
 mod objects;
 pub mod osm;
 mod pathfind;
 pub mod raw;
+mod remap;
 mod traversable;
 
 // TODO Minimize uses of these!
@@ -74,6 +81,7 @@ pub const NORMAL_LANE_THICKNESS: Distance = Distance::const_meters(2.5);
 pub(crate) const SERVICE_ROAD_LANE_THICKNESS: Distance = Distance::const_meters(1.5);
 pub const SIDEWALK_THICKNESS: Distance = Distance::const_meters(1.5);
 pub(crate) const SHOULDER_THICKNESS: Distance = Distance::const_meters(0.5);
+pub(crate) const BUFFER_THICKNESS: Distance = Distance::const_meters(1.0);
 
 // The map used by the simulation and UI. This struct is declared here so that the rest of the
 // crate can reach into private fields.
@@ -108,6 +116,9 @@ pub struct Map {
 
     pathfinder: Pathfinder,
     pathfinder_dirty: bool,
+    // Pathfinding that obeys restrictions only applying at some times, built as needed.
+    #[serde(skip_serializing, skip_deserializing)]
+    graphs_at_time: GraphsAtTime,
     // Not the source of truth, just cached.
     zones: Vec<Zone>,
 
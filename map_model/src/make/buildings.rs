@@ -99,6 +99,7 @@ pub fn make_all_buildings(
                     b.polygon.area(),
                     &mut rng,
                 ),
+                jobs_by_sector: BTreeMap::new(),
                 parking: if let Some(n) = b.public_garage_name.clone() {
                     OffstreetParking::PublicGarage(n, b.num_parking_spots)
                 } else {
@@ -5,7 +5,7 @@ use abstutil::Tags;
 use geom::Distance;
 
 use crate::{
-    osm, Direction, DrivingSide, LaneType, MapConfig, NORMAL_LANE_THICKNESS,
+    osm, Direction, DrivingSide, LaneType, MapConfig, BUFFER_THICKNESS, NORMAL_LANE_THICKNESS,
     SERVICE_ROAD_LANE_THICKNESS, SHOULDER_THICKNESS, SIDEWALK_THICKNESS,
 };
 
@@ -23,6 +23,7 @@ fn fwd(lt: LaneType) -> LaneSpec {
         width: match lt {
             LaneType::Sidewalk => SIDEWALK_THICKNESS,
             LaneType::Shoulder => SHOULDER_THICKNESS,
+            LaneType::Buffer => BUFFER_THICKNESS,
             _ => NORMAL_LANE_THICKNESS,
         },
     }
@@ -35,6 +36,7 @@ fn back(lt: LaneType) -> LaneSpec {
         width: match lt {
             LaneType::Sidewalk => SIDEWALK_THICKNESS,
             LaneType::Shoulder => SHOULDER_THICKNESS,
+            LaneType::Buffer => BUFFER_THICKNESS,
             _ => NORMAL_LANE_THICKNESS,
         },
     }
@@ -56,38 +58,47 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
     let oneway =
         tags.is_any("oneway", vec!["yes", "reversible"]) || tags.is("junction", "roundabout");
 
-    // How many driving lanes in each direction?
-    let num_driving_fwd = if let Some(n) = tags
-        .get("lanes:forward")
-        .and_then(|num| num.parse::<usize>().ok())
-    {
+    // How many driving lanes in each direction? The total includes any lanes usable in both
+    // directions, and if only one direction is specified, the other gets the remainder.
+    let num_total = parse_num_lanes(tags, "lanes");
+    let num_both_ways = parse_num_lanes(tags, "lanes:both_ways").unwrap_or(0);
+    let explicit_fwd = parse_num_lanes(tags, "lanes:forward");
+    let explicit_back = parse_num_lanes(tags, "lanes:backward");
+    let num_driving_fwd = if let Some(n) = explicit_fwd {
         n
-    } else if let Some(n) = tags.get("lanes").and_then(|num| num.parse::<usize>().ok()) {
+    } else if let Some(n) = num_total {
         if oneway {
             n
-        } else if n % 2 == 0 {
-            n / 2
+        } else if let Some(back) = explicit_back {
+            n.saturating_sub(back + num_both_ways).max(1)
         } else {
-            // TODO Really, this is ambiguous, but...
-            (n / 2).max(1)
+            let n = n.saturating_sub(num_both_ways);
+            if n % 2 == 0 {
+                n / 2
+            } else {
+                // TODO Really, this is ambiguous, but...
+                (n / 2).max(1)
+            }
         }
     } else {
         // TODO Grrr.
         1
     };
-    let num_driving_back = if let Some(n) = tags
-        .get("lanes:backward")
-        .and_then(|num| num.parse::<usize>().ok())
-    {
+    let num_driving_back = if let Some(n) = explicit_back {
         n
-    } else if let Some(n) = tags.get("lanes").and_then(|num| num.parse::<usize>().ok()) {
+    } else if let Some(n) = num_total {
         if oneway {
             0
-        } else if n % 2 == 0 {
-            n / 2
+        } else if let Some(fwd) = explicit_fwd {
+            n.saturating_sub(fwd + num_both_ways).max(1)
         } else {
-            // TODO Really, this is ambiguous, but...
-            (n / 2).max(1)
+            let n = n.saturating_sub(num_both_ways);
+            if n % 2 == 0 {
+                n / 2
+            } else {
+                // TODO Really, this is ambiguous, but...
+                (n / 2).max(1)
+            }
         }
     } else {
         // TODO Grrr.
@@ -177,16 +188,47 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         }
     }
 
+    // So far, each side only has driving and bus lanes, plus maybe the shared center lane
+    let fwd_widths = tags.get("width:lanes:forward").or_else(|| {
+        if oneway {
+            tags.get("width:lanes")
+        } else {
+            None
+        }
+    });
+    if let Some(widths) = fwd_widths {
+        set_lane_widths(&mut fwd_side, widths, cfg.driving_side);
+    }
+    if let Some(widths) = tags.get("width:lanes:backward") {
+        set_lane_widths(&mut back_side, widths, cfg.driving_side);
+    }
+    let explicit_widths = fwd_widths.is_some() || tags.contains_key("width:lanes:backward");
+
+    // Cycle tracks are physically separated from traffic by a buffer
     if tags.is_any("cycleway", vec!["lane", "track"]) {
+        let track = tags.is("cycleway", "track");
+        if track {
+            fwd_side.push(fwd(LaneType::Buffer));
+        }
         fwd_side.push(fwd(LaneType::Biking));
         if !back_side.is_empty() {
+            if track {
+                back_side.push(back(LaneType::Buffer));
+            }
             back_side.push(back(LaneType::Biking));
         }
     } else if tags.is_any("cycleway:both", vec!["lane", "track"]) {
+        if tags.is("cycleway:both", "track") {
+            fwd_side.push(fwd(LaneType::Buffer));
+            back_side.push(back(LaneType::Buffer));
+        }
         fwd_side.push(fwd(LaneType::Biking));
         back_side.push(back(LaneType::Biking));
     } else {
         if tags.is_any("cycleway:right", vec!["lane", "track"]) {
+            if tags.is("cycleway:right", "track") {
+                fwd_side.push(fwd(LaneType::Buffer));
+            }
             if tags.is("cycleway:right:oneway", "no") || tags.is("oneway:bicycle", "no") {
                 fwd_side.push(back(LaneType::Biking));
             }
@@ -196,12 +238,22 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
             back_side.push(back(LaneType::Biking));
         }
         if tags.is_any("cycleway:left", vec!["lane", "opposite_track", "track"]) {
+            let track = !tags.is("cycleway:left", "lane");
             if oneway {
+                if track {
+                    fwd_side.insert(0, fwd(LaneType::Buffer));
+                }
                 fwd_side.insert(0, fwd(LaneType::Biking));
-                if tags.is("oneway:bicycle", "no") {
+                if tags.is("oneway:bicycle", "no") || tags.is("cycleway:left:oneway", "no") {
                     back_side.push(back(LaneType::Biking));
                 }
             } else {
+                if track {
+                    back_side.push(back(LaneType::Buffer));
+                }
+                if tags.is("cycleway:left:oneway", "no") {
+                    back_side.push(fwd(LaneType::Biking));
+                }
                 back_side.push(back(LaneType::Biking));
             }
         }
@@ -225,7 +277,7 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         fwd_side.push(fwd(LaneType::Sidewalk));
         back_side.push(back(LaneType::Sidewalk));
     } else if tags.is(osm::SIDEWALK, "separate") && cfg.inferred_sidewalks {
-        // TODO Need to snap separate sidewalks to ways. Until then, just do this.
+        // The separately mapped sidewalks couldn't be snapped to this road. Guess.
         fwd_side.push(fwd(LaneType::Sidewalk));
         if !back_side.is_empty() {
             back_side.push(back(LaneType::Sidewalk));
@@ -244,7 +296,7 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         }
     }
 
-    if tags.is(osm::HIGHWAY, "service") || tags.is("narrow", "yes") {
+    if (tags.is(osm::HIGHWAY, "service") || tags.is("narrow", "yes")) && !explicit_widths {
         for spec in fwd_side.iter_mut().chain(back_side.iter_mut()) {
             if spec.lt == LaneType::Driving || spec.lt == LaneType::Parking {
                 spec.width = SERVICE_ROAD_LANE_THICKNESS;
@@ -252,6 +304,13 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         }
     }
 
+    // The total width of the carriageway is only used if the individual lanes aren't tagged
+    if !explicit_widths {
+        if let Some(width) = tags.get("width").and_then(|w| parse_width(w)) {
+            scale_carriageway_width(&mut fwd_side, &mut back_side, width);
+        }
+    }
+
     let mut need_fwd_shoulder = fwd_side
         .last()
         .map(|spec| spec.lt != LaneType::Sidewalk)
@@ -287,6 +346,134 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
     assemble_ltr(fwd_side, back_side, cfg.driving_side)
 }
 
+fn parse_num_lanes(tags: &Tags, key: &str) -> Option<usize> {
+    tags.get(key).and_then(|num| num.parse::<usize>().ok())
+}
+
+/// Parses widths like "3.5", "3.5 m", or "12 ft".
+fn parse_width(value: &str) -> Option<Distance> {
+    let value = value.trim();
+    let meters = if let Some(ft) = value
+        .strip_suffix("ft")
+        .or_else(|| value.strip_suffix('\''))
+    {
+        ft.trim().parse::<f64>().ok()? * 0.3048
+    } else {
+        value.trim_end_matches('m').trim().parse::<f64>().ok()?
+    };
+    if meters.is_finite() && meters > 0.0 {
+        Some(Distance::meters(meters))
+    } else {
+        None
+    }
+}
+
+/// The `width:lanes` family of tags lists widths left-to-right in the direction of travel.
+/// `side` is ordered from the center of the road outwards, and the shared center lane isn't
+/// included in the tag.
+fn set_lane_widths(side: &mut Vec<LaneSpec>, widths: &str, driving_side: DrivingSide) {
+    let mut indices: Vec<usize> = (0..side.len())
+        .filter(|idx| side[*idx].lt != LaneType::SharedLeftTurn)
+        .collect();
+    if driving_side == DrivingSide::Left {
+        indices.reverse();
+    }
+    let parts: Vec<&str> = widths.split('|').collect();
+    if parts.len() != indices.len() {
+        return;
+    }
+    for (idx, part) in indices.into_iter().zip(parts) {
+        if let Some(width) = parse_width(part) {
+            side[idx].width = width;
+        }
+    }
+}
+
+/// `width` describes the entire carriageway, excluding sidewalks. Scale lanes proportionally to
+/// match it, unless the tag looks wildly inconsistent with the lanes.
+fn scale_carriageway_width(
+    fwd_side: &mut Vec<LaneSpec>,
+    back_side: &mut Vec<LaneSpec>,
+    width: Distance,
+) {
+    let is_carriageway = |spec: &LaneSpec| {
+        spec.lt != LaneType::Sidewalk
+            && spec.lt != LaneType::Shoulder
+            && spec.lt != LaneType::LightRail
+    };
+    let current: Distance = fwd_side
+        .iter()
+        .chain(back_side.iter())
+        .filter(|spec| is_carriageway(spec))
+        .map(|spec| spec.width)
+        .sum();
+    if current == Distance::ZERO {
+        return;
+    }
+    let ratio = width / current;
+    if ratio < 0.5 || ratio > 2.0 {
+        return;
+    }
+    for spec in fwd_side.iter_mut().chain(back_side.iter_mut()) {
+        if is_carriageway(spec) {
+            spec.width = spec.width * ratio;
+        }
+    }
+}
+
+/// OSM ways are usually drawn along the center of the road, but the `placement` tag can say
+/// otherwise. Returns how far to shift the way to the right to reach the true center of all the
+/// lanes. See <https://wiki.openstreetmap.org/wiki/Proposed_features/placement>.
+pub fn get_placement_offset(tags: &Tags, specs: &Vec<LaneSpec>) -> Option<Distance> {
+    let (value, dir) = if let Some(value) = tags.get("placement") {
+        (value, None)
+    } else if let Some(value) = tags.get("placement:forward") {
+        (value, Some(Direction::Fwd))
+    } else if let Some(value) = tags.get("placement:backward") {
+        (value, Some(Direction::Back))
+    } else {
+        return None;
+    };
+    let mut parts = value.splitn(2, ':');
+    let kind = parts.next()?;
+    let n = parts.next()?.parse::<usize>().ok()?;
+
+    // Only these are counted by the lanes tag. specs is ordered left-to-right going forwards, so
+    // reverse for backwards lanes.
+    let mut counted: Vec<usize> = (0..specs.len())
+        .filter(|idx| {
+            let spec = &specs[*idx];
+            dir.map(|d| d == spec.dir).unwrap_or(true)
+                && (spec.lt == LaneType::Driving
+                    || spec.lt == LaneType::Bus
+                    || spec.lt == LaneType::SharedLeftTurn
+                    || spec.lt == LaneType::Construction)
+        })
+        .collect();
+    if dir == Some(Direction::Back) {
+        counted.reverse();
+    }
+    let idx = *counted.get(n.checked_sub(1)?)?;
+
+    let left_edge: Distance = specs[0..idx].iter().map(|spec| spec.width).sum();
+    let width = specs[idx].width;
+    let (left, right) = if dir == Some(Direction::Back) {
+        (left_edge + width, left_edge)
+    } else {
+        (left_edge, left_edge + width)
+    };
+    let way = match kind {
+        "left_of" => left,
+        "middle_of" => left_edge + width / 2.0,
+        "right_of" => right,
+        _ => {
+            return None;
+        }
+    };
+    let total: Distance = specs.iter().map(|spec| spec.width).sum();
+    Some(total / 2.0 - way)
+}
+
 fn assemble_ltr(
     mut fwd_side: Vec<LaneSpec>,
     mut back_side: Vec<LaneSpec>,
@@ -321,6 +508,7 @@ mod tests {
             LaneType::SharedLeftTurn => "C",
             LaneType::Construction => "x",
             LaneType::LightRail => "l",
+            LaneType::Buffer => "|",
         }
     }
 
@@ -359,8 +547,8 @@ mod tests {
                     "oneway:bicycle=no",
                 ],
                 DrivingSide::Right,
-                "sbbds",
-                "vv^^^",
+                "sbb|ds",
+                "vv^^^^",
             ),
             (
                 "https://www.openstreetmap.org/way/353690151",
@@ -372,8 +560,8 @@ mod tests {
                     "cycleway:right:oneway=no",
                 ],
                 DrivingSide::Right,
-                "spddddbbps",
-                "vvvv^^v^^^",
+                "spdddd|bbps",
+                "vvvv^^^v^^^",
             ),
             (
                 "https://www.openstreetmap.org/way/389654080",
@@ -387,8 +575,8 @@ mod tests {
                     "cycleway:right:oneway=no",
                 ],
                 DrivingSide::Right,
-                "spdCdbbs",
-                "vvv^^v^^",
+                "spdCd|bbs",
+                "vvv^^^v^^",
             ),
             (
                 "https://www.openstreetmap.org/way/369623526",
@@ -401,8 +589,8 @@ mod tests {
                     "oneway:bicycle=no",
                 ],
                 DrivingSide::Right,
-                "sbbdps",
-                "vv^^^^",
+                "sbb|dps",
+                "vv^^^^^",
             ),
             (
                 "https://www.openstreetmap.org/way/534549104",
@@ -415,8 +603,8 @@ mod tests {
                     "oneway:bicycle=no",
                 ],
                 DrivingSide::Right,
-                "sddbbs",
-                "v^^v^^",
+                "sdd|bbs",
+                "v^^^v^^",
             ),
             (
                 "https://www.openstreetmap.org/way/777565028",
@@ -432,6 +620,37 @@ mod tests {
                 "sdd",
                 "^^^",
             ),
+            (
+                "lanes:backward with only the total",
+                vec!["lanes=4", "lanes:backward=1", "sidewalk=both"],
+                DrivingSide::Right,
+                "sdddds",
+                "vv^^^^",
+            ),
+            (
+                "lanes:both_ways isn't double-counted",
+                vec![
+                    "lanes=5",
+                    "lanes:forward=2",
+                    "lanes:both_ways=1",
+                    "sidewalk=both",
+                ],
+                DrivingSide::Right,
+                "sddCdds",
+                "vvv^^^^",
+            ),
+            (
+                "two-way cycletrack on the left, snapped from a separate cycleway",
+                vec![
+                    "lanes=2",
+                    "sidewalk=both",
+                    "cycleway:left=track",
+                    "cycleway:left:oneway=no",
+                ],
+                DrivingSide::Right,
+                "sbb|dds",
+                "vv^vv^^",
+            ),
         ] {
             let cfg = MapConfig {
                 driving_side,
@@ -466,4 +685,89 @@ mod tests {
         }
         assert!(ok);
     }
+
+    fn driving_widths(input: Vec<&str>, driving_side: DrivingSide) -> Vec<Distance> {
+        let cfg = MapConfig {
+            driving_side,
+            bikes_can_use_bus_lanes: true,
+            inferred_sidewalks: true,
+        };
+        get_lane_specs_ltr(&tags(input), &cfg)
+            .into_iter()
+            .filter(|s| s.lt == LaneType::Driving)
+            .map(|s| s.width)
+            .collect()
+    }
+
+    #[test]
+    fn test_lane_widths() {
+        assert_eq!(
+            driving_widths(
+                vec!["lanes=2", "oneway=yes", "width:lanes=3|2.8 m"],
+                DrivingSide::Right
+            ),
+            vec![Distance::meters(3.0), Distance::meters(2.8)]
+        );
+        // The tag is always left-to-right, but lanes are stored from the center outwards
+        assert_eq!(
+            driving_widths(
+                vec!["lanes=2", "oneway=yes", "width:lanes=3|2.8"],
+                DrivingSide::Left
+            ),
+            vec![Distance::meters(3.0), Distance::meters(2.8)]
+        );
+        // The total width is split between the lanes
+        assert_eq!(
+            driving_widths(vec!["lanes=2", "width=8"], DrivingSide::Right),
+            vec![Distance::meters(4.0), Distance::meters(4.0)]
+        );
+        // Unless it's nonsense
+        assert_eq!(
+            driving_widths(vec!["lanes=2", "width=40"], DrivingSide::Right),
+            vec![NORMAL_LANE_THICKNESS, NORMAL_LANE_THICKNESS]
+        );
+    }
+
+    #[test]
+    fn test_placement() {
+        let cfg = MapConfig {
+            driving_side: DrivingSide::Right,
+            bikes_can_use_bus_lanes: true,
+            inferred_sidewalks: true,
+        };
+        for (input, expected) in vec![
+            (vec!["lanes=2", "oneway=yes", "sidewalk=both"], None),
+            (
+                vec![
+                    "lanes=2",
+                    "oneway=yes",
+                    "sidewalk=both",
+                    "placement=right_of:1",
+                ],
+                Some(Distance::ZERO),
+            ),
+            (
+                vec![
+                    "lanes=2",
+                    "oneway=yes",
+                    "sidewalk=both",
+                    "placement=left_of:1",
+                ],
+                Some(NORMAL_LANE_THICKNESS),
+            ),
+            (
+                vec![
+                    "lanes=2",
+                    "oneway=yes",
+                    "sidewalk=both",
+                    "placement=middle_of:2",
+                ],
+                Some(NORMAL_LANE_THICKNESS / -2.0),
+            ),
+        ] {
+            let tags = tags(input);
+            let specs = get_lane_specs_ltr(&tags, &cfg);
+            assert_eq!(get_placement_offset(&tags, &specs), expected);
+        }
+    }
 }
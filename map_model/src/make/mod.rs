@@ -6,7 +6,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use abstutil::{Parallelism, Tags, Timer};
 use geom::{Bounds, Distance, FindClosest, HashablePt2D, Speed, EPSILON_DIST};
 
-use crate::pathfind::Pathfinder;
+use crate::pathfind::{GraphsAtTime, Pathfinder};
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, AreaType, ControlStopSign,
@@ -20,8 +20,9 @@ pub mod initial;
 mod medians;
 mod parking_lots;
 mod remove_disconnected;
+mod roundabouts;
 pub mod traffic_signals;
-mod transit;
+pub(crate) mod transit;
 pub mod turns;
 mod walking_turns;
 
@@ -60,6 +61,7 @@ impl Map {
             config: raw.config.clone(),
             pathfinder: Pathfinder::Dijkstra,
             pathfinder_dirty: false,
+            graphs_at_time: GraphsAtTime::default(),
             name: raw.name.clone(),
             edits: MapEdits::new(),
         };
@@ -103,22 +105,27 @@ impl Map {
                 turn_restrictions: raw.roads[&r.id]
                     .turn_restrictions
                     .iter()
-                    .filter_map(|(rt, to)| {
+                    .filter_map(|(rt, to, conditions)| {
                         // Missing roads are filtered (like service roads) or clipped out
-                        road_id_mapping.get(to).map(|to| (*rt, *to))
+                        road_id_mapping
+                            .get(to)
+                            .map(|to| (*rt, *to, conditions.clone()))
                     })
                     .collect(),
                 complicated_turn_restrictions: raw.roads[&r.id]
                     .complicated_turn_restrictions
                     .iter()
-                    .filter_map(|(via, to)| {
-                        if let (Some(via), Some(to)) =
-                            (road_id_mapping.get(via), road_id_mapping.get(to))
-                        {
-                            Some((*via, *to))
+                    .filter_map(|(rt, via, to, conditions)| {
+                        let new_via: Option<Vec<RoadID>> = via
+                            .iter()
+                            .map(|r| road_id_mapping.get(r).cloned())
+                            .collect();
+                        if let (Some(new_via), Some(new_to)) = (new_via, road_id_mapping.get(to)) {
+                            Some((*rt, new_via, *new_to, conditions.clone()))
                         } else {
                             timer.warn(format!(
-                                "Complicated turn restriction from {} has invalid via {} or dst {}",
+                                "Complicated turn restriction from {} has invalid via {:?} or dst \
+                                 {}",
                                 r.id, via, to
                             ));
                             None
@@ -144,6 +151,8 @@ impl Map {
                     0
                 },
                 access_restrictions: AccessRestrictions::new(),
+                modal_filters: Vec::new(),
+                removed: false,
             };
             road.speed_limit = road.speed_limit_from_osm();
             road.access_restrictions = road.access_restrictions_from_osm();
@@ -273,8 +282,8 @@ impl Map {
 
         map.zones = Zone::make_all(&map);
 
-        // Create medians first, so they wind up rendering underneath areas from OSM. Sometimes
-        // medians contain mapped grass.
+        // Create medians and roundabout islands first, so they wind up rendering underneath areas
+        // from OSM. Sometimes they contain mapped grass.
         for polygon in medians::find_medians(&map) {
             map.areas.push(Area {
                 id: AreaID(map.areas.len()),
@@ -284,6 +293,15 @@ impl Map {
                 osm_id: None,
             });
         }
+        for polygon in roundabouts::find_islands(&map) {
+            map.areas.push(Area {
+                id: AreaID(map.areas.len()),
+                area_type: AreaType::MedianStrip,
+                polygon,
+                osm_tags: Tags::new(BTreeMap::new()),
+                osm_id: None,
+            });
+        }
         for a in &raw.areas {
             map.areas.push(Area {
                 id: AreaID(map.areas.len()),
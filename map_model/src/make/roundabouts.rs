@@ -0,0 +1,36 @@
+use std::collections::BTreeSet;
+
+use geom::Polygon;
+
+use crate::{DrivingSide, Map};
+
+/// Find the island in the middle of every roundabout, by tracing around the inside of the ring.
+pub fn find_islands(map: &Map) -> Vec<Polygon> {
+    let mut visited = BTreeSet::new();
+    let mut polygons = Vec::new();
+    for r in map.all_roads() {
+        if !r.is_roundabout() {
+            continue;
+        }
+        // The island is on the left of traffic when driving on the right, and vice versa.
+        let lanes_ltr = r.lanes_ltr();
+        let start = if map.get_config().driving_side == DrivingSide::Right {
+            lanes_ltr[0].0
+        } else {
+            lanes_ltr.last().unwrap().0
+        };
+        if visited.contains(&start) {
+            continue;
+        }
+        if let Some((poly, lanes)) = map.get_l(start).trace_around_block(map) {
+            // If we wound up tracing around the outside of the roundabout, or it's not even a
+            // closed ring, then the polygon isn't an island.
+            if lanes.iter().all(|l| map.get_parent(*l).is_roundabout()) {
+                polygons.push(poly);
+            }
+            visited.extend(lanes);
+        }
+        visited.insert(start);
+    }
+    polygons
+}
@@ -95,6 +95,7 @@ fn new(id: IntersectionID, map: &Map) -> ControlTrafficSignal {
         stages: Vec::new(),
         offset: Duration::ZERO,
         movements: Movement::for_i(id, map).unwrap(),
+        transit_priority: None,
     }
 }
 
@@ -310,7 +310,7 @@ impl Matcher {
     }
 }
 
-fn pick_start_lane(
+pub(crate) fn pick_start_lane(
     first_stop: Position,
     constraints: PathConstraints,
     map: &Map,
@@ -340,7 +340,7 @@ fn pick_start_lane(
     ))
 }
 
-fn default_spawn_times() -> Vec<Time> {
+pub(crate) fn default_spawn_times() -> Vec<Time> {
     // Hourly spawning from midnight to 7, then every 30 minutes till 7, then hourly again
     let mut times = Vec::new();
     for i in 0..24 {
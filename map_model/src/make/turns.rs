@@ -149,11 +149,16 @@ fn does_turn_pass_restrictions(turn: &Turn, i: &Intersection, map: &Map) -> bool
     let src = map.get_parent(turn.id.src);
     let dst = map.get_l(turn.id.dst).parent;
 
-    for (restriction, to) in &src.turn_restrictions {
+    for (restriction, to, conditions) in &src.turn_restrictions {
         // The restriction only applies to one direction of the road.
         if !i.roads.contains(to) {
             continue;
         }
+        // Turns that're only restricted for some vehicles or at some times are kept; pathfinding
+        // handles them.
+        if !conditions.is_unconditional() {
+            continue;
+        }
         match restriction {
             RestrictionType::BanTurns => {
                 if dst == *to {
@@ -333,11 +338,26 @@ fn remove_merging_turns(map: &Map, input: Vec<Turn>, turn_type: TurnType) -> Vec
             continue;
         }
 
+        // If OSM turn restrictions explicitly have something like "left|left|", then there are
+        // multiple source lanes.
+        let road = map.get_parent(group[0].id.src);
+        let marked: HashSet<LaneID> = group
+            .iter()
+            .map(|t| t.id.src)
+            .filter(|l| {
+                map.get_l(*l)
+                    .get_turn_restrictions(road)
+                    .map(|types| types.contains(&turn_type))
+                    .unwrap_or(false)
+            })
+            .collect();
+        if marked.len() > 1 {
+            turns.extend(group.into_iter().filter(|t| marked.contains(&t.id.src)));
+            continue;
+        }
+
         // We have multiple lanes all with a turn to the same destination road. Most likely, only
         // the rightmost or leftmost can actually make the turn.
-        // TODO If OSM turn restrictions explicitly have something like "left|left|", then there
-        // are multiple source lanes!
-        let road = map.get_parent(group[0].id.src);
         let src = if turn_type == TurnType::Right {
             group
                 .iter()
@@ -8,12 +8,14 @@ use serde::{Deserialize, Serialize};
 use abstutil::{MapName, Timer};
 use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D, Ring, Time};
 
+use crate::pathfind::breaks_turn_restrictions;
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     osm, Area, AreaID, Building, BuildingID, BuildingType, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, Lane, LaneID,
-    LaneType, Map, MapEdits, MovementID, OffstreetParking, ParkingLot, ParkingLotID, Path,
-    PathConstraints, PathRequest, Pathfinder, Position, Road, RoadID, Turn, TurnID, TurnType, Zone,
+    BusStopID, ControlStopSign, ControlTrafficSignal, GraphsAtTime, Intersection, IntersectionID,
+    Lane, LaneID, LaneType, Map, MapEdits, MovementID, OffstreetParking, ParkingLot, ParkingLotID,
+    Path, PathConstraints, PathRequest, Pathfinder, Position, Road, RoadID, Turn, TurnID, TurnType,
+    Zone,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -163,6 +165,7 @@ impl Map {
             },
             pathfinder: Pathfinder::Dijkstra,
             pathfinder_dirty: false,
+            graphs_at_time: GraphsAtTime::default(),
             name: MapName {
                 city: "blank city".to_string(),
                 map: "blank".to_string(),
@@ -434,10 +437,11 @@ impl Map {
         self.bus_routes.iter().find(|r| r.full_name == name)
     }
 
+    /// Ignores routes that no longer run.
     pub fn get_routes_serving_stop(&self, stop: BusStopID) -> Vec<&BusRoute> {
         let mut routes = Vec::new();
         for r in &self.bus_routes {
-            if r.stops.contains(&stop) {
+            if r.stops.contains(&stop) && !r.spawn_times.is_empty() {
                 routes.push(r);
             }
         }
@@ -541,6 +545,19 @@ impl Map {
         assert!(!self.pathfinder_dirty);
         self.pathfinder.pathfind(req, self)
     }
+    /// Like `pathfind`, but also obeys turn restrictions that only apply at certain times of day.
+    pub fn pathfind_at_time(&self, req: PathRequest, time: Time) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder.pathfind_at_time(req, time, self)
+    }
+    /// Like `pathfind_at_time`, but never entering a zone with a toll. The graph for this is built
+    /// once and reused until the restrictions in effect or the map change.
+    pub fn pathfind_avoiding_tolls(&self, req: PathRequest, time: Time) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.graphs_at_time
+            .get(self, req.constraints, time, true)
+            .pathfind(&req, self)
+    }
     pub fn pathfind_avoiding_lanes(
         &self,
         req: PathRequest,
@@ -550,6 +567,50 @@ impl Map {
         self.pathfinder.pathfind_avoiding_lanes(req, avoid, self)
     }
 
+    /// Does a turn break a turn restriction or pass a modal filter that applies to this type of
+    /// vehicle at some time?
+    pub fn turn_restricted_at(&self, t: TurnID, constraints: PathConstraints, time: Time) -> bool {
+        breaks_turn_restrictions(self, &[t], |c| c.applies(constraints, Some(time)))
+    }
+
+    /// Every time of day when some turn restriction or modal filter starts applying, in order.
+    pub fn restriction_start_times(&self) -> Vec<Time> {
+        self.restriction_times(false)
+    }
+
+    /// Every time of day when some turn restriction or modal filter starts or stops applying, in
+    /// order.
+    pub fn restriction_change_times(&self) -> Vec<Time> {
+        self.restriction_times(true)
+    }
+
+    fn restriction_times(&self, include_ends: bool) -> Vec<Time> {
+        let mut times = Vec::new();
+        for r in self.all_roads() {
+            let all_conditions = r
+                .turn_restrictions
+                .iter()
+                .map(|(_, _, conditions)| conditions)
+                .chain(
+                    r.complicated_turn_restrictions
+                        .iter()
+                        .map(|(_, _, _, conditions)| conditions),
+                )
+                .chain(r.modal_filters.iter().map(|filter| &filter.conditions));
+            for conditions in all_conditions {
+                for (start, end) in &conditions.time_windows {
+                    times.push(*start);
+                    if include_ends {
+                        times.push(*end);
+                    }
+                }
+            }
+        }
+        times.sort();
+        times.dedup();
+        times
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -629,6 +690,10 @@ impl Map {
         self.buildings[b.0].bldg_type = bldg_type;
     }
 
+    pub fn hack_override_jobs_by_sector(&mut self, b: BuildingID, jobs: BTreeMap<String, usize>) {
+        self.buildings[b.0].jobs_by_sector = jobs;
+    }
+
     pub fn hack_override_orig_spawn_times(&mut self, br: BusRouteID, times: Vec<Time>) {
         self.bus_routes[br.0].orig_spawn_times = times.clone();
         self.bus_routes[br.0].spawn_times = times;
@@ -0,0 +1,255 @@
+//! Low-traffic neighborhoods are areas of local roads bounded by main roads, where modal filters
+//! stop drivers from cutting through. This finds the areas, the cells that filters split them into,
+//! and the rat-runs drivers take through them.
+
+use std::collections::BTreeSet;
+
+use petgraph::graphmap::{DiGraphMap, UnGraphMap};
+
+use geom::{Bounds, Distance};
+
+use crate::osm::RoadRank;
+use crate::pathfind::{
+    breaks_turn_restrictions, breaks_turn_restrictions_ignoring_filters, driving_cost,
+};
+use crate::{IntersectionID, LaneID, Map, PathConstraints, RoadID, TurnID};
+
+/// An area of local roads, bounded by main roads or the edge of the map
+#[derive(Clone, Debug, PartialEq)]
+pub struct Neighborhood {
+    pub interior: BTreeSet<RoadID>,
+    /// Where the interior meets main roads or the edge of the map
+    pub borders: BTreeSet<IntersectionID>,
+}
+
+/// Part of a neighborhood that drivers can get around without leaving it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub roads: BTreeSet<RoadID>,
+    /// Where drivers can enter or leave the cell. If this is empty, drivers can't reach the cell at
+    /// all.
+    pub borders: BTreeSet<IntersectionID>,
+}
+
+/// The shortest driving route between two borders of a neighborhood, when it cuts through the
+/// interior instead of staying on main roads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RatRun {
+    pub from: IntersectionID,
+    pub to: IntersectionID,
+    /// The interior roads used, in order
+    pub roads: Vec<RoadID>,
+}
+
+impl Neighborhood {
+    /// Floods out from a local road through other local roads, stopping at main roads. None if the
+    /// road isn't a local road that drivers can use.
+    pub fn from_road(map: &Map, start: RoadID) -> Option<Neighborhood> {
+        if !is_local(map, start) {
+            return None;
+        }
+        let mut interior = BTreeSet::new();
+        let mut borders = BTreeSet::new();
+        let mut queue = vec![start];
+        while let Some(r) = queue.pop() {
+            if !interior.insert(r) {
+                continue;
+            }
+            let road = map.get_r(r);
+            for i in vec![road.src_i, road.dst_i] {
+                let i = map.get_i(i);
+                if i.is_border()
+                    || i.roads
+                        .iter()
+                        .any(|r| !is_local(map, *r) && has_car_lanes(map, *r))
+                {
+                    borders.insert(i.id);
+                    continue;
+                }
+                for next in &i.roads {
+                    if !interior.contains(next) && is_local(map, *next) {
+                        queue.push(*next);
+                    }
+                }
+            }
+        }
+        Some(Neighborhood { interior, borders })
+    }
+
+    /// Groups the interior by where drivers can get without leaving the neighborhood. Modal
+    /// filters and turn restrictions that apply all day split cells apart.
+    pub fn find_cells(&self, map: &Map) -> Vec<Cell> {
+        let mut graph: UnGraphMap<RoadID, ()> = UnGraphMap::new();
+        for r in &self.interior {
+            graph.add_node(*r);
+        }
+        for r in &self.interior {
+            for l in map.get_r(*r).all_lanes() {
+                for turn in map.get_turns_for(l, PathConstraints::Car) {
+                    let dst = map.get_l(turn.id.dst).parent;
+                    if !self.borders.contains(&turn.id.parent)
+                        && self.interior.contains(&dst)
+                        && car_can_turn(map, turn.id)
+                    {
+                        graph.add_edge(*r, dst, ());
+                    }
+                }
+            }
+        }
+
+        let mut cells = Vec::new();
+        for roads in petgraph::algo::kosaraju_scc(&graph) {
+            let roads: BTreeSet<RoadID> = roads.into_iter().collect();
+            let mut borders = BTreeSet::new();
+            for i in &self.borders {
+                for turn in map.get_turns_in_intersection(*i) {
+                    let src = map.get_l(turn.id.src);
+                    let dst = map.get_l(turn.id.dst);
+                    if (roads.contains(&src.parent) || roads.contains(&dst.parent))
+                        && PathConstraints::Car.can_use(src, map)
+                        && PathConstraints::Car.can_use(dst, map)
+                        && car_can_turn(map, turn.id)
+                    {
+                        borders.insert(*i);
+                    }
+                }
+            }
+            cells.push(Cell { roads, borders });
+        }
+        cells
+    }
+
+    /// For every pair of borders, finds the shortest driving route between the main roads there,
+    /// and keeps the ones using the interior. This doesn't need pathfinding to be up-to-date with
+    /// edits. Routes only use the interior and the perimeter, since a detour farther away wouldn't
+    /// be an alternative to cutting through anyway.
+    pub fn find_rat_runs(&self, map: &Map) -> Vec<RatRun> {
+        self.rat_runs(map, true)
+    }
+
+    /// Like `find_rat_runs`, but as if there were no modal filters, to compare against.
+    pub fn find_rat_runs_without_filters(&self, map: &Map) -> Vec<RatRun> {
+        self.rat_runs(map, false)
+    }
+
+    /// The roads around the neighborhood that drivers could use instead of the interior: every
+    /// other road for cars with both ends close to the neighborhood.
+    pub fn perimeter(&self, map: &Map) -> BTreeSet<RoadID> {
+        let mut bounds = Bounds::new();
+        for r in &self.interior {
+            let road = map.get_r(*r);
+            bounds.update(map.get_i(road.src_i).polygon.center());
+            bounds.update(map.get_i(road.dst_i).polygon.center());
+        }
+        bounds.min_x -= PERIMETER_BUFFER.inner_meters();
+        bounds.min_y -= PERIMETER_BUFFER.inner_meters();
+        bounds.max_x += PERIMETER_BUFFER.inner_meters();
+        bounds.max_y += PERIMETER_BUFFER.inner_meters();
+
+        map.all_roads()
+            .iter()
+            .filter(|road| {
+                !self.interior.contains(&road.id)
+                    && has_car_lanes(map, road.id)
+                    && bounds.contains(map.get_i(road.src_i).polygon.center())
+                    && bounds.contains(map.get_i(road.dst_i).polygon.center())
+            })
+            .map(|road| road.id)
+            .collect()
+    }
+
+    fn rat_runs(&self, map: &Map, with_filters: bool) -> Vec<RatRun> {
+        let perimeter = self.perimeter(map);
+        let mut graph: DiGraphMap<LaneID, TurnID> = DiGraphMap::new();
+        for r in self.interior.iter().chain(perimeter.iter()) {
+            for l in map.get_r(*r).all_lanes() {
+                for turn in map.get_turns_for(l, PathConstraints::Car) {
+                    let dst = map.get_l(turn.id.dst).parent;
+                    let allowed = if with_filters {
+                        car_can_turn(map, turn.id)
+                    } else {
+                        !breaks_turn_restrictions_ignoring_filters(map, &[turn.id], |c| {
+                            c.applies(PathConstraints::Car, None)
+                        })
+                    };
+                    if (self.interior.contains(&dst) || perimeter.contains(&dst)) && allowed {
+                        graph.add_edge(turn.id.src, turn.id.dst, turn.id);
+                    }
+                }
+            }
+        }
+
+        // One lane on a main road leading into each border, and one leading away
+        let mut entrances: Vec<(IntersectionID, LaneID)> = Vec::new();
+        let mut exits: Vec<(IntersectionID, LaneID)> = Vec::new();
+        for i in &self.borders {
+            let i = map.get_i(*i);
+            let main_road = |l: &&LaneID| {
+                !self.interior.contains(&map.get_l(**l).parent) && graph.contains_node(**l)
+            };
+            if let Some(l) = i.incoming_lanes.iter().find(main_road) {
+                entrances.push((i.id, *l));
+            }
+            if let Some(l) = i.outgoing_lanes.iter().find(main_road) {
+                exits.push((i.id, *l));
+            }
+        }
+
+        let mut rat_runs = Vec::new();
+        for (from, start) in &entrances {
+            for (to, end) in &exits {
+                if from == to {
+                    continue;
+                }
+                if let Some((_, lanes)) = petgraph::algo::astar(
+                    &graph,
+                    *start,
+                    |l| l == *end,
+                    |(_, _, turn)| {
+                        driving_cost(
+                            map.get_l(turn.src),
+                            map.get_t(*turn),
+                            PathConstraints::Car,
+                            map,
+                        )
+                    },
+                    |_| 0.0,
+                ) {
+                    let mut roads: Vec<RoadID> = Vec::new();
+                    for l in lanes {
+                        let r = map.get_l(l).parent;
+                        if self.interior.contains(&r) && roads.last() != Some(&r) {
+                            roads.push(r);
+                        }
+                    }
+                    if !roads.is_empty() {
+                        rat_runs.push(RatRun {
+                            from: *from,
+                            to: *to,
+                            roads,
+                        });
+                    }
+                }
+            }
+        }
+        rat_runs
+    }
+}
+
+/// How far past the neighborhood to look for main roads that drivers could use instead
+const PERIMETER_BUFFER: Distance = Distance::const_meters(300.0);
+
+fn is_local(map: &Map, r: RoadID) -> bool {
+    map.get_r(r).get_rank() == RoadRank::Local && has_car_lanes(map, r)
+}
+
+fn has_car_lanes(map: &Map, r: RoadID) -> bool {
+    map.get_r(r)
+        .all_lanes()
+        .into_iter()
+        .any(|l| PathConstraints::Car.can_use(map.get_l(l), map))
+}
+
+fn car_can_turn(map: &Map, t: TurnID) -> bool {
+    !breaks_turn_restrictions(map, &[t], |c| c.applies(PathConstraints::Car, None))
+}
@@ -40,6 +40,9 @@ pub struct Building {
     pub label_center: Pt2D,
     pub amenities: Vec<Amenity>,
     pub bldg_type: BuildingType,
+    /// The number of jobs per sector, summing to the number of workers. Only filled out when
+    /// population data breaking down jobs by sector is imported.
+    pub jobs_by_sector: BTreeMap<String, usize>,
     pub parking: OffstreetParking,
     /// Depending on options while importing, these might be empty, to save file space.
     pub osm_tags: Tags,
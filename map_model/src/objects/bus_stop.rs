@@ -62,7 +62,8 @@ pub struct BusRoute {
     pub start: LaneID,
     pub end_border: Option<LaneID>,
     pub route_type: PathConstraints,
-    /// Non-empty, times in order for one day when a vehicle should begin at start.
+    /// Times in order for one day when a vehicle should begin at start. Empty if the route has
+    /// been deleted by edits.
     pub spawn_times: Vec<Time>,
     /// Explicitly store whatever the original was, since this can't be reconstructed without side
     /// input.
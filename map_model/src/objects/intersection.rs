@@ -79,6 +79,23 @@ impl Intersection {
         self.intersection_type == IntersectionType::TrafficSignal
     }
 
+    /// A stop sign intersection where roads join the ring of a roundabout. Vehicles entering yield
+    /// to vehicles already circulating, without stopping first.
+    ///
+    /// This isn't its own `IntersectionType`, because OSM only tags the ways forming the ring, and
+    /// the yielding is exactly what a stop sign with yield signs on the entries does. Keeping it a
+    /// stop sign means the signs can be edited, saved, and reset like any other, and turning the
+    /// intersection into a traffic signal or closing it stops treating it as a roundabout.
+    pub fn is_roundabout(&self, map: &Map) -> bool {
+        self.is_stop_sign()
+            && self
+                .roads
+                .iter()
+                .filter(|r| map.get_r(**r).is_roundabout())
+                .count()
+                >= 2
+    }
+
     pub fn is_light_rail(&self, map: &Map) -> bool {
         self.roads.iter().all(|r| map.get_r(*r).is_light_rail())
     }
@@ -44,6 +44,9 @@ pub enum LaneType {
     SharedLeftTurn,
     Construction,
     LightRail,
+    // A physical barrier, like a curb or planters, separating a bike lane from traffic. Nothing
+    // moves along it.
+    Buffer,
 }
 
 impl LaneType {
@@ -58,6 +61,7 @@ impl LaneType {
             LaneType::SharedLeftTurn => false,
             LaneType::Construction => false,
             LaneType::LightRail => true,
+            LaneType::Buffer => false,
         }
     }
 
@@ -72,13 +76,14 @@ impl LaneType {
             LaneType::SharedLeftTurn => false,
             LaneType::Construction => false,
             LaneType::LightRail => true,
+            LaneType::Buffer => false,
         }
     }
 
     pub fn describe(self) -> &'static str {
         match self {
             LaneType::Driving => "a general-purpose driving lane",
-            LaneType::Biking => "a protected bike lane",
+            LaneType::Biking => "a bike lane",
             LaneType::Bus => "a bus-only lane",
             LaneType::Parking => "an on-street parking lane",
             LaneType::Sidewalk => "a sidewalk",
@@ -86,6 +91,7 @@ impl LaneType {
             LaneType::SharedLeftTurn => "a shared left-turn lane",
             LaneType::Construction => "a lane that's closed for construction",
             LaneType::LightRail => "a light rail track",
+            LaneType::Buffer => "a buffer protecting a bike lane",
         }
     }
 
@@ -100,6 +106,7 @@ impl LaneType {
             LaneType::SharedLeftTurn => "left-turn lane",
             LaneType::Construction => "construction",
             LaneType::LightRail => "light rail track",
+            LaneType::Buffer => "buffer",
         }
     }
 }
@@ -197,6 +204,33 @@ impl Lane {
         self.lane_type == LaneType::Biking
     }
 
+    /// A bike lane that isn't directly next to any lane with moving vehicles, because there's a
+    /// buffer or parking lane in between.
+    pub fn is_protected_bike_lane(&self, map: &Map) -> bool {
+        if !self.is_biking() {
+            return false;
+        }
+        let road = map.get_r(self.parent);
+        let lanes = road.lanes_ltr();
+        let idx = road.offset(self.id);
+        // Look in both directions, skipping over other bike lanes, like for two-way cycletracks
+        for neighbors in vec![
+            lanes[..idx].iter().rev().collect::<Vec<_>>(),
+            lanes[idx + 1..].iter().collect(),
+        ] {
+            for (_, _, lt) in neighbors {
+                if *lt == LaneType::Biking {
+                    continue;
+                }
+                if lt.is_for_moving_vehicles() {
+                    return false;
+                }
+                break;
+            }
+        }
+        true
+    }
+
     pub fn is_bus(&self) -> bool {
         self.lane_type == LaneType::Bus
     }
@@ -258,41 +292,36 @@ impl Lane {
             return None;
         }
         // TODO More warnings if this fails
-        let part = parts[lanes.iter().position(|l| *l == self.id)?];
+        let idx = lanes.iter().position(|l| *l == self.id)?;
+        let part = parts[idx];
         // TODO Probably the target lane should get marked as LaneType::Bus
-        if part == "no" || part == "none" || part == "yes" || part == "psv" || part == "bus" {
+        if part == "no" || part == "yes" || part == "psv" || part == "bus" {
             return None;
         }
-        // Empty means no restrictions
-        if part == "" {
+        if part != "" && part != "none" {
+            return Some(parse_turn_lane(part));
+        }
+
+        // An unmarked lane alongside marked lanes can go straight, and it can also turn if it's on
+        // the edge of the road and no other lane is marked for that turn. If no lane is marked,
+        // then there are no restrictions at all.
+        let marked: Vec<BTreeSet<TurnType>> = parts
+            .iter()
+            .filter(|p| !vec!["", "none", "no", "yes", "psv", "bus"].contains(p))
+            .map(|p| parse_turn_lane(p))
+            .collect();
+        if marked.is_empty() {
             return None;
         }
-        Some(
-            part.split(';')
-                .flat_map(|s| match s {
-                    "left" | "left\\left" => vec![TurnType::Left],
-                    "right" => vec![TurnType::Right],
-                    // TODO What is blank supposed to mean? From few observed cases, same as through
-                    "through" | "" => vec![TurnType::Straight],
-                    // TODO Check this more carefully
-                    "slight_right" | "slight right" | "merge_to_right" | "sharp_right" => {
-                        vec![TurnType::Straight, TurnType::Right]
-                    }
-                    "slight_left" | "slight left" | "merge_to_left" | "sharp_left" => {
-                        vec![TurnType::Straight, TurnType::Left]
-                    }
-                    "reverse" => {
-                        // TODO We need TurnType::UTurn. Until then, u-turns usually show up as
-                        // left turns.
-                        vec![TurnType::Left]
-                    }
-                    s => {
-                        warn!("Unknown turn restriction {}", s);
-                        vec![]
-                    }
-                })
-                .collect(),
-        )
+        let mut restrictions = BTreeSet::new();
+        restrictions.insert(TurnType::Straight);
+        if idx == 0 && !marked.iter().any(|set| set.contains(&TurnType::Left)) {
+            restrictions.insert(TurnType::Left);
+        }
+        if idx == parts.len() - 1 && !marked.iter().any(|set| set.contains(&TurnType::Right)) {
+            restrictions.insert(TurnType::Right);
+        }
+        Some(restrictions)
     }
 
     /// Starting from this lane, follow the lane's left edge to the intersection, continuing to
@@ -365,3 +394,29 @@ impl Lane {
         Some((Ring::new(pts).ok()?.to_polygon(), visited))
     }
 }
+
+fn parse_turn_lane(part: &str) -> BTreeSet<TurnType> {
+    part.split(';')
+        .flat_map(|s| match s {
+            "left" | "left\\left" => vec![TurnType::Left],
+            "right" => vec![TurnType::Right],
+            // TODO What is blank supposed to mean? From few observed cases, same as through
+            "through" | "" => vec![TurnType::Straight],
+            // TODO Check this more carefully
+            "slight_right" | "slight right" | "merge_to_right" | "sharp_right" => {
+                vec![TurnType::Straight, TurnType::Right]
+            }
+            "slight_left" | "slight left" | "merge_to_left" | "sharp_left" => {
+                vec![TurnType::Straight, TurnType::Left]
+            }
+            "reverse" => {
+                // TODO We need TurnType::UTurn. Until then, u-turns usually show up as left turns.
+                vec![TurnType::Left]
+            }
+            s => {
+                warn!("Unknown turn restriction {}", s);
+                vec![]
+            }
+        })
+        .collect()
+}
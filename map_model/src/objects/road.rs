@@ -4,9 +4,9 @@ use enumset::EnumSet;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize, Tags};
-use geom::{Distance, PolyLine, Polygon, Speed};
+use geom::{Distance, PolyLine, Polygon, Speed, Time};
 
-use crate::raw::{OriginalRoad, RestrictionType};
+use crate::raw::{OriginalRoad, RestrictionConditions, RestrictionType};
 use crate::{
     osm, AccessRestrictions, BusStopID, IntersectionID, Lane, LaneID, LaneType, Map,
     PathConstraints, Zone,
@@ -89,18 +89,72 @@ impl DirectedRoadID {
     }
 }
 
+/// Bollards, planters, or a bus gate near one end of a road. Vehicles can't turn into or out of the
+/// road at that end, unless they're exempt. Pedestrians can always pass.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModalFilter {
+    /// Near the road's `src_i` if true, otherwise `dst_i`
+    pub near_start: bool,
+    /// The vehicles that may pass, and when the filter is in effect
+    pub conditions: RestrictionConditions,
+}
+
+impl ModalFilter {
+    /// A filter that only lets bikes and buses through, all day. Buses are exempt so that bus
+    /// routes through the filter keep working, like a bus gate.
+    pub fn new(road: &Road, i: IntersectionID) -> ModalFilter {
+        assert!(road.src_i == i || road.dst_i == i);
+        ModalFilter {
+            near_start: road.src_i == i,
+            conditions: RestrictionConditions {
+                except: PathConstraints::Bike | PathConstraints::Bus,
+                time_windows: Vec::new(),
+            },
+        }
+    }
+
+    /// Filters at both ends of a road, closing it to everything except bikes and buses during some
+    /// times of day. School streets, play streets, and closures for events work like this.
+    pub fn timed_closure(time_windows: Vec<(Time, Time)>) -> Vec<ModalFilter> {
+        vec![true, false]
+            .into_iter()
+            .map(|near_start| ModalFilter {
+                near_start,
+                conditions: RestrictionConditions {
+                    except: PathConstraints::Bike | PathConstraints::Bus,
+                    time_windows: time_windows.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// The intersection the filter is next to
+    pub fn intersection(&self, road: &Road) -> IntersectionID {
+        if self.near_start {
+            road.src_i
+        } else {
+            road.dst_i
+        }
+    }
+}
+
 /// A Road represents a segment between exactly two Intersections. It contains Lanes as children.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Road {
     pub id: RoadID,
     pub osm_tags: Tags,
     /// self is 'from'
-    pub turn_restrictions: Vec<(RestrictionType, RoadID)>,
-    /// self is 'from'. (via, to). Only BanTurns.
-    pub complicated_turn_restrictions: Vec<(RoadID, RoadID)>,
+    pub turn_restrictions: Vec<(RestrictionType, RoadID, RestrictionConditions)>,
+    /// self is 'from'. (restriction, via, to, conditions). The via roads are in order.
+    pub complicated_turn_restrictions:
+        Vec<(RestrictionType, Vec<RoadID>, RoadID, RestrictionConditions)>,
     pub orig_id: OriginalRoad,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    pub modal_filters: Vec<ModalFilter>,
+    /// Removed by edits. The road and its lanes still exist, but the intersections at either end
+    /// don't connect to them, so nothing can use them.
+    pub removed: bool,
     pub zorder: isize,
 
     /// Invariant: A road must contain at least one child
@@ -332,6 +386,11 @@ impl Road {
         self.lanes_ltr().len() == 1 && self.lanes_ltr()[0].2 == LaneType::Sidewalk
     }
 
+    /// Is this road part of the ring of a roundabout?
+    pub fn is_roundabout(&self) -> bool {
+        self.osm_tags.is("junction", "roundabout")
+    }
+
     pub fn is_service(&self) -> bool {
         self.osm_tags.is(osm::HIGHWAY, "service")
     }
@@ -365,9 +424,18 @@ impl Road {
         AccessRestrictions {
             allow_through_traffic,
             cap_vehicles_per_hour: None,
+            toll: None,
         }
     }
 
+    /// The all-day modal filter next to an intersection, if there is one. Timed closures may also
+    /// have filters there.
+    pub fn modal_filter_at(&self, i: IntersectionID) -> Option<&ModalFilter> {
+        self.modal_filters.iter().find(|filter| {
+            filter.intersection(self) == i && filter.conditions.time_windows.is_empty()
+        })
+    }
+
     pub fn get_zone<'a>(&self, map: &'a Map) -> Option<&'a Zone> {
         if !self.is_private() {
             return None;
@@ -46,7 +46,18 @@ pub struct ControlStopSign {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct RoadWithStopSign {
     pub lane_closest_to_edge: LaneID,
-    pub must_stop: bool,
+    pub sign_type: SignType,
+}
+
+/// What traffic approaching an unsignalized intersection from one road has to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignType {
+    /// Traffic from this road has priority.
+    NoSign,
+    /// Come to a complete stop, then proceed.
+    Stop,
+    /// Slow down and proceed when there's a big enough gap in traffic with priority.
+    Yield,
 }
 
 impl ControlStopSign {
@@ -85,7 +96,7 @@ impl ControlStopSign {
                     r.id,
                     RoadWithStopSign {
                         lane_closest_to_edge,
-                        must_stop: false,
+                        sign_type: SignType::NoSign,
                     },
                 );
             }
@@ -96,6 +107,46 @@ impl ControlStopSign {
             return ss;
         }
 
+        // Traffic entering a roundabout yields to traffic already circulating.
+        if map.get_i(id).is_roundabout(map) {
+            for (r, cfg) in ss.roads.iter_mut() {
+                if !map.get_r(*r).is_roundabout() {
+                    cfg.sign_type = SignType::Yield;
+                }
+            }
+            return ss;
+        }
+
+        // If OSM says anything about priority or signs, trust it. Roads tagged with a stop sign
+        // stop, roads tagged with a yield sign or without priority yield to those with it, and
+        // everything else doesn't have a sign.
+        let has_priority = |r: RoadID| {
+            map.get_r(r)
+                .osm_tags
+                .is_any("priority_road", vec!["designated", "yes_unposted"])
+        };
+        let has_sign = |r: RoadID, fwd_key: &str, back_key: &str| {
+            let road = map.get_r(r);
+            if road.dst_i == id {
+                road.osm_tags.contains_key(fwd_key)
+            } else {
+                road.osm_tags.contains_key(back_key)
+            }
+        };
+        let gives_way = |r: RoadID| has_sign(r, osm::GIVE_WAY_FWD, osm::GIVE_WAY_BACK);
+        let stops = |r: RoadID| has_sign(r, osm::STOP_FWD, osm::STOP_BACK);
+        let any_priority = ss.roads.keys().any(|r| has_priority(*r));
+        if any_priority || ss.roads.keys().any(|r| gives_way(*r) || stops(*r)) {
+            for (r, cfg) in ss.roads.iter_mut() {
+                if stops(*r) {
+                    cfg.sign_type = SignType::Stop;
+                } else if gives_way(*r) || (any_priority && !has_priority(*r)) {
+                    cfg.sign_type = SignType::Yield;
+                }
+            }
+            return ss;
+        }
+
         // What's the rank of each road?
         let mut rank: HashMap<RoadID, osm::RoadRank> = HashMap::new();
         for r in ss.roads.keys() {
@@ -111,7 +162,7 @@ impl ControlStopSign {
         // highest-priority roads.
         for (r, cfg) in ss.roads.iter_mut() {
             if ranks.len() == 1 || rank[r] != ranks[0] {
-                cfg.must_stop = true;
+                cfg.sign_type = SignType::Stop;
             }
         }
         ss
@@ -124,19 +175,38 @@ impl ControlStopSign {
         match map.get_t(turn).turn_type {
             TurnType::SharedSidewalkCorner => TurnPriority::Protected,
             // TODO This actually feels like a policy bit that should be flippable.
-            TurnType::Crosswalk => TurnPriority::Protected,
-            _ => {
-                if self.roads[&map.get_l(turn.src).parent].must_stop {
-                    TurnPriority::Yield
+            TurnType::Crosswalk => {
+                // Without markings, pedestrians have to wait for a gap in traffic.
+                if map.get_t(turn).is_marked_crosswalk(map) {
+                    TurnPriority::Protected
                 } else {
+                    TurnPriority::Yield
+                }
+            }
+            _ => {
+                if self.roads[&map.get_l(turn.src).parent].sign_type == SignType::NoSign {
                     TurnPriority::Protected
+                } else {
+                    TurnPriority::Yield
                 }
             }
         }
     }
 
+    pub fn get_sign_type(&self, turn: TurnID, map: &Map) -> SignType {
+        self.roads
+            .get(&map.get_l(turn.src).parent)
+            .map(|ss| ss.sign_type)
+            .unwrap_or(SignType::NoSign)
+    }
+
+    /// Cycle between no sign, a stop sign, and a yield sign.
     pub fn flip_sign(&mut self, r: RoadID) {
         let ss = self.roads.get_mut(&r).unwrap();
-        ss.must_stop = !ss.must_stop;
+        ss.sign_type = match ss.sign_type {
+            SignType::NoSign => SignType::Stop,
+            SignType::Stop => SignType::Yield,
+            SignType::Yield => SignType::NoSign,
+        };
     }
 }
@@ -32,6 +32,8 @@ pub struct ControlTrafficSignal {
         deserialize_with = "deserialize_btreemap"
     )]
     pub movements: BTreeMap<MovementID, Movement>,
+    /// If present, buses get some priority at this signal.
+    pub transit_priority: Option<TransitPriority>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -52,6 +54,32 @@ pub enum PhaseType {
     Adaptive(Duration),
 }
 
+/// Transit signal priority (TSP) lets buses bend the signal timing a little. A bus that won't make
+/// it through before its stage ends can hold the green, and a bus stuck at a red can cut the
+/// conflicting stage short. The limits keep everybody else from being starved.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransitPriority {
+    /// The most that a stage may be extended past its normal end for a bus.
+    pub max_green_extension: Duration,
+    /// The most that a conflicting stage may be cut short for a waiting bus. Stages are never cut
+    /// short before pedestrians have time to cross.
+    pub max_early_termination: Duration,
+    /// If set, buses waiting in a bus lane get this long of a head start at the beginning of their
+    /// stage before other vehicles on the same approach may go. Other vehicles are only held while
+    /// a bus is actually waiting.
+    pub queue_jump: Option<Duration>,
+}
+
+impl TransitPriority {
+    pub fn default_limits() -> TransitPriority {
+        TransitPriority {
+            max_green_extension: Duration::seconds(10.0),
+            max_early_termination: Duration::seconds(10.0),
+            queue_jump: None,
+        }
+    }
+}
+
 impl PhaseType {
     // TODO Maybe don't have this; force callers to acknowledge different policies
     pub fn simple_duration(&self) -> Duration {
@@ -260,8 +288,24 @@ impl Stage {
         true
     }
 
-    pub fn get_priority_of_turn(&self, t: TurnID, parent: &ControlTrafficSignal) -> TurnPriority {
-        self.get_priority_of_movement(parent.turn_to_movement(t))
+    /// Like at stop signs, pedestrians at an unmarked crosswalk have to wait for a gap in traffic,
+    /// even when the signal lets them go.
+    pub fn get_priority_of_turn(
+        &self,
+        t: TurnID,
+        parent: &ControlTrafficSignal,
+        map: &Map,
+    ) -> TurnPriority {
+        let pri = self.get_priority_of_movement(parent.turn_to_movement(t));
+        let turn = map.get_t(t);
+        if pri == TurnPriority::Protected
+            && turn.turn_type == TurnType::Crosswalk
+            && !turn.is_marked_crosswalk(map)
+        {
+            TurnPriority::Yield
+        } else {
+            pri
+        }
     }
 
     pub fn get_priority_of_movement(&self, m: MovementID) -> TurnPriority {
@@ -395,6 +439,7 @@ impl ControlTrafficSignal {
             stages,
             offset: Duration::seconds(raw.offset_seconds as f64),
             movements: Movement::for_i(id, map).unwrap(),
+            transit_priority: None,
         };
         ts.validate()?;
         Ok(ts)
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use abstutil::MultiMap;
 use geom::{Angle, Distance, PolyLine, Pt2D};
 
-use crate::{DirectedRoadID, Direction, IntersectionID, LaneID, Map};
+use crate::{osm, DirectedRoadID, Direction, IntersectionID, LaneID, Map};
 
 /// Turns are uniquely identified by their (src, dst) lanes and their parent intersection.
 /// Intersection is needed to distinguish crosswalks that exist at two ends of a sidewalk.
@@ -114,6 +114,21 @@ impl Turn {
         self.turn_type == TurnType::SharedSidewalkCorner || self.turn_type == TurnType::Crosswalk
     }
 
+    /// Is this a crosswalk with markings on the ground? Crosswalks are assumed to be marked, unless
+    /// a separately mapped crossing in OSM says otherwise.
+    pub fn is_marked_crosswalk(&self, map: &Map) -> bool {
+        if self.turn_type != TurnType::Crosswalk {
+            return false;
+        }
+        let road = map.get_parent(self.id.src);
+        let key = if road.dst_i == self.id.parent {
+            osm::CROSSING_FWD
+        } else {
+            osm::CROSSING_BACK
+        };
+        !road.osm_tags.is(key, "unmarked")
+    }
+
     // TODO Maybe precompute this.
     /// penalties for (lane types, lane-changing, slow lane)
     pub fn penalty(&self, map: &Map) -> (usize, usize, usize) {
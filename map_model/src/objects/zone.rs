@@ -4,6 +4,7 @@
 //! 2) Stay Healthy Streets, where most car traffic is banned, except for trips beginning/ending in
 //!    the zone
 //! 3) Congestion capping, where only so many cars per hour can enter the zone
+//! 4) Congestion pricing, where vehicles pay a toll to enter the zone, maybe only at busy times
 
 use std::collections::BTreeSet;
 
@@ -11,6 +12,8 @@ use enumset::EnumSet;
 use petgraph::graphmap::DiGraphMap;
 use serde::{Deserialize, Serialize};
 
+use geom::{Duration, Time};
+
 use crate::pathfind::{driving_cost, walking_cost, WalkingNode};
 use crate::{
     IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, RoadID, TurnID,
@@ -20,6 +23,8 @@ use crate::{
 pub struct AccessRestrictions {
     pub allow_through_traffic: EnumSet<PathConstraints>,
     pub cap_vehicles_per_hour: Option<usize>,
+    #[serde(default)]
+    pub toll: Option<Toll>,
 }
 
 impl AccessRestrictions {
@@ -27,7 +32,92 @@ impl AccessRestrictions {
         AccessRestrictions {
             allow_through_traffic: EnumSet::all(),
             cap_vehicles_per_hour: None,
+            toll: None,
+        }
+    }
+}
+
+/// A charge for entering a zone, like a cordon or congestion charge. Starting a trip inside the
+/// zone is free.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Toll {
+    /// Dollars to enter the zone, per type of vehicle. Anything not listed is free.
+    pub prices: Vec<(PathConstraints, f64)>,
+    /// The prices are multiplied by a factor depending on the time of day. Each entry starts at a
+    /// time of day and lasts until the next one, so they must be sorted. Before the first entry,
+    /// entering is free. If this is empty, the full price is charged all day.
+    pub schedule: Vec<(Time, f64)>,
+}
+
+impl Toll {
+    /// Charge only cars, the same price all day.
+    pub fn flat(dollars: f64) -> Toll {
+        Toll {
+            prices: vec![(PathConstraints::Car, dollars)],
+            schedule: Vec::new(),
+        }
+    }
+
+    /// How many dollars this kind of vehicle pays to enter the zone at some time. The schedule
+    /// repeats every day.
+    pub fn price(&self, constraints: PathConstraints, time: Time) -> f64 {
+        let base = match self.prices.iter().find(|(c, _)| *c == constraints) {
+            Some((_, dollars)) => *dollars,
+            None => {
+                return 0.0;
+            }
+        };
+        if self.schedule.is_empty() {
+            return base;
+        }
+        let time_of_day = Time::START_OF_DAY
+            + Duration::seconds(time.inner_seconds() % Duration::hours(24).inner_seconds());
+        let mut factor = 0.0;
+        for (start, x) in &self.schedule {
+            if time_of_day >= *start {
+                factor = *x;
+            }
+        }
+        base * factor
+    }
+
+    /// The times of day when the price changes
+    pub fn price_changes(&self) -> Vec<Time> {
+        self.schedule.iter().map(|(t, _)| *t).collect()
+    }
+
+    /// Checks that prices and factors aren't negative, and that the schedule is sorted and stays
+    /// within one day.
+    pub fn validate(&self) -> Result<(), String> {
+        for (constraints, dollars) in &self.prices {
+            if *dollars < 0.0 {
+                return Err(format!("the toll for {:?} is negative", constraints));
+            }
+        }
+        for pair in self.schedule.windows(2) {
+            if pair[0].0 >= pair[1].0 {
+                return Err(format!(
+                    "the toll schedule isn't sorted: {} comes before {}",
+                    pair[0].0.ampm_tostring(),
+                    pair[1].0.ampm_tostring()
+                ));
+            }
+        }
+        for (start, factor) in &self.schedule {
+            if *start >= Time::START_OF_DAY + Duration::hours(24) {
+                return Err(format!(
+                    "the toll schedule has an entry at {}, after the end of the day",
+                    start
+                ));
+            }
+            if *factor < 0.0 {
+                return Err(format!(
+                    "the toll factor starting at {} is negative",
+                    start.ampm_tostring()
+                ));
+            }
         }
+        Ok(())
     }
 }
 
@@ -44,7 +134,7 @@ impl Zone {
     pub fn make_all(map: &Map) -> Vec<Zone> {
         let mut queue = Vec::new();
         for r in map.all_roads() {
-            if r.is_private() {
+            if r.is_private() && !r.removed {
                 queue.push(r.id);
             }
         }
@@ -176,3 +266,65 @@ fn floodfill(map: &Map, start: RoadID) -> Zone {
         restrictions: match_constraints,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(h)
+    }
+
+    fn rush_hours() -> Toll {
+        Toll {
+            prices: vec![(PathConstraints::Car, 10.0), (PathConstraints::Bus, 2.0)],
+            schedule: vec![(time(7), 1.0), (time(10), 0.5), (time(19), 0.0)],
+        }
+    }
+
+    #[test]
+    fn price() {
+        let toll = rush_hours();
+        // Free before the first entry
+        assert_eq!(toll.price(PathConstraints::Car, time(6)), 0.0);
+        assert_eq!(toll.price(PathConstraints::Car, time(7)), 10.0);
+        assert_eq!(toll.price(PathConstraints::Car, time(12)), 5.0);
+        assert_eq!(toll.price(PathConstraints::Bus, time(12)), 1.0);
+        assert_eq!(toll.price(PathConstraints::Car, time(20)), 0.0);
+        // The schedule repeats the next day
+        assert_eq!(toll.price(PathConstraints::Car, time(31)), 10.0);
+        // Unlisted vehicles are free
+        assert_eq!(toll.price(PathConstraints::Bike, time(8)), 0.0);
+        // Without a schedule, the full price is charged all day
+        assert_eq!(Toll::flat(3.0).price(PathConstraints::Car, time(2)), 3.0);
+    }
+
+    #[test]
+    fn price_changes() {
+        assert_eq!(
+            rush_hours().price_changes(),
+            vec![time(7), time(10), time(19)]
+        );
+        assert!(Toll::flat(3.0).price_changes().is_empty());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(rush_hours().validate().is_ok());
+        assert!(Toll::flat(3.0).validate().is_ok());
+
+        let mut toll = rush_hours();
+        toll.schedule.swap(0, 1);
+        assert!(toll.validate().is_err());
+
+        let mut toll = rush_hours();
+        toll.schedule.push((time(19), 1.0));
+        assert!(toll.validate().is_err());
+
+        let mut toll = rush_hours();
+        toll.schedule.push((time(24), 1.0));
+        assert!(toll.validate().is_err());
+
+        assert!(Toll::flat(-1.0).validate().is_err());
+    }
+}
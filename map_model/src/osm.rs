@@ -27,6 +27,18 @@ pub const OSM_REL_ID: &str = "abst:osm_rel_id";
 pub const ENDPT_FWD: &str = "abst:endpt_fwd";
 pub const ENDPT_BACK: &str = "abst:endpt_back";
 
+// Copied from highway=give_way nodes on a road, pointing out which end of the road must yield.
+pub const GIVE_WAY_FWD: &str = "abst:give_way_fwd";
+pub const GIVE_WAY_BACK: &str = "abst:give_way_back";
+// Likewise for highway=stop nodes.
+pub const STOP_FWD: &str = "abst:stop_fwd";
+pub const STOP_BACK: &str = "abst:stop_back";
+
+// Copied from separately mapped footway=crossing ways near the end of a road. The value is
+// "marked" or "unmarked".
+pub const CROSSING_FWD: &str = "abst:crossing_fwd";
+pub const CROSSING_BACK: &str = "abst:crossing_back";
+
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
 pub const INFERRED_SIDEWALKS: &str = "abst:sidewalks_inferred";
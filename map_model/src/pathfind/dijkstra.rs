@@ -1,10 +1,16 @@
 //! Pathfinding without needing to build a separate contraction hierarchy.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
 
 use petgraph::graphmap::DiGraphMap;
 
+use abstutil::MultiMap;
+use geom::{Duration, Time};
+
+use crate::pathfind::breaks_turn_restrictions;
 use crate::pathfind::driving::driving_cost;
+use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 use crate::pathfind::walking::{walking_cost, WalkingNode};
 use crate::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TurnID};
 
@@ -19,15 +25,7 @@ pub fn build_graph_for_vehicles(
     map: &Map,
     constraints: PathConstraints,
 ) -> DiGraphMap<LaneID, TurnID> {
-    let mut graph: DiGraphMap<LaneID, TurnID> = DiGraphMap::new();
-    for l in map.all_lanes() {
-        if constraints.can_use(l, map) {
-            for turn in map.get_turns_for(l.id, constraints) {
-                graph.add_edge(turn.id.src, turn.id.dst, turn.id);
-            }
-        }
-    }
-    graph
+    build_graph(map, constraints, &BTreeSet::new())
 }
 
 pub fn pathfind_avoiding_lanes(
@@ -36,16 +34,207 @@ pub fn pathfind_avoiding_lanes(
     map: &Map,
 ) -> Option<Path> {
     assert_eq!(req.constraints, PathConstraints::Car);
+    let graph = build_graph(map, req.constraints, &avoid);
+    calc_path(graph, &req, map)
+}
+
+/// A graph for vehicles that obeys the turn restrictions and modal filters in effect at one time of
+/// day. Like the contraction hierarchies, each uber-turn through a cluster of intersections is one
+/// step, so restrictions via a road are obeyed too. It can also leave out every road in a zone with
+/// a toll, for drivers looking for a way around. Building this covers the whole map, so
+/// `GraphsAtTime` keeps it around until the restrictions in effect change.
+pub struct GraphAtTime {
+    graph: DiGraphMap<Node, f64>,
+    uber_turns: Vec<UberTurn>,
+    constraints: PathConstraints,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum Node {
+    Lane(LaneID),
+    UberTurn(usize),
+}
+
+impl GraphAtTime {
+    pub fn new(
+        map: &Map,
+        constraints: PathConstraints,
+        time: Time,
+        avoid_tolls: bool,
+    ) -> GraphAtTime {
+        let mut avoid = BTreeSet::new();
+        if avoid_tolls {
+            for z in map.all_zones() {
+                if z.restrictions.toll.is_some() {
+                    for r in &z.members {
+                        avoid.extend(map.get_r(*r).all_lanes());
+                    }
+                }
+            }
+        }
+
+        // Lanes leading into a cluster can only continue through uber-turns, even if none of them
+        // are allowed right now; a single turn in might lead to breaking a restriction via a road.
+        let mut cluster_entrances = BTreeSet::new();
+        let mut uber_turn_entrances: MultiMap<LaneID, usize> = MultiMap::new();
+        let mut uber_turns = Vec::new();
+        for ic in IntersectionCluster::find_all(map) {
+            for ut in ic.uber_turns {
+                cluster_entrances.insert(ut.entry());
+                if ut
+                    .path
+                    .iter()
+                    .all(|t| constraints.can_use(map.get_l(t.dst), map) && !avoid.contains(&t.dst))
+                    && !breaks_turn_restrictions(map, &ut.path, |c| {
+                        c.applies(constraints, Some(time))
+                    })
+                {
+                    uber_turn_entrances.insert(ut.entry(), uber_turns.len());
+                    uber_turns.push(ut);
+                }
+            }
+        }
+
+        let mut graph: DiGraphMap<Node, f64> = DiGraphMap::new();
+        for l in map.all_lanes() {
+            if !constraints.can_use(l, map) || avoid.contains(&l.id) {
+                continue;
+            }
+            if cluster_entrances.contains(&l.id) {
+                for idx in uber_turn_entrances.get(l.id) {
+                    let ut = &uber_turns[*idx];
+                    let cost: f64 = ut
+                        .path
+                        .iter()
+                        .map(|t| driving_cost(map.get_l(t.src), map.get_t(*t), constraints, map))
+                        .sum();
+                    graph.add_edge(Node::Lane(l.id), Node::UberTurn(*idx), cost);
+                    // The cost is already captured for entering the uber-turn
+                    graph.add_edge(Node::UberTurn(*idx), Node::Lane(ut.exit()), 0.0);
+                }
+            } else {
+                for turn in map.get_turns_for(l.id, constraints) {
+                    if !avoid.contains(&turn.id.dst)
+                        && !breaks_turn_restrictions(map, &[turn.id], |c| {
+                            c.applies(constraints, Some(time))
+                        })
+                    {
+                        graph.add_edge(
+                            Node::Lane(l.id),
+                            Node::Lane(turn.id.dst),
+                            driving_cost(l, turn, constraints, map),
+                        );
+                    }
+                }
+            }
+        }
+
+        GraphAtTime {
+            graph,
+            uber_turns,
+            constraints,
+        }
+    }
+
+    // TODO This doesn't handle access-restricted zones.
+    pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<Path> {
+        assert_eq!(req.constraints, self.constraints);
+        let (_, nodes) = petgraph::algo::astar(
+            &self.graph,
+            Node::Lane(req.start.lane()),
+            |n| n == Node::Lane(req.end.lane()),
+            |(_, _, cost)| *cost,
+            |_| 0.0,
+        )?;
+        let mut steps = Vec::new();
+        let mut uber_turns = Vec::new();
+        for pair in nodes.windows(2) {
+            match (pair[0], pair[1]) {
+                (Node::Lane(l1), Node::Lane(l2)) => {
+                    steps.push(PathStep::Lane(l1));
+                    // We don't need to look for this turn in the map; we know it exists.
+                    steps.push(PathStep::Turn(TurnID {
+                        parent: map.get_l(l1).dst_i,
+                        src: l1,
+                        dst: l2,
+                    }));
+                }
+                (Node::Lane(l), Node::UberTurn(ut)) => {
+                    steps.push(PathStep::Lane(l));
+                    let ut = self.uber_turns[ut].clone();
+                    for t in &ut.path {
+                        steps.push(PathStep::Turn(*t));
+                        steps.push(PathStep::Lane(t.dst));
+                    }
+                    steps.pop();
+                    uber_turns.push(ut);
+                }
+                (Node::UberTurn(_), Node::Lane(_)) => {
+                    // Don't add anything; the lane will be added by some other case
+                }
+                (Node::UberTurn(_), Node::UberTurn(_)) => unreachable!(),
+            }
+        }
+        steps.push(PathStep::Lane(req.end.lane()));
+        Some(Path::new(map, steps, req.end.dist_along(), uber_turns))
+    }
+}
+
+/// Caches a `GraphAtTime` for each type of vehicle and each part of the day when the same
+/// restrictions are in effect, with and without tolled zones. Edits invalidate everything.
+#[derive(Default)]
+pub struct GraphsAtTime {
+    // The times of day when restrictions start or stop applying, and the graphs built so far,
+    // keyed by how many of those times have passed
+    cache: Mutex<
+        Option<(
+            Vec<Time>,
+            BTreeMap<(PathConstraints, usize, bool), Arc<GraphAtTime>>,
+        )>,
+    >,
+}
+
+impl GraphsAtTime {
+    pub fn get(
+        &self,
+        map: &Map,
+        constraints: PathConstraints,
+        time: Time,
+        avoid_tolls: bool,
+    ) -> Arc<GraphAtTime> {
+        let mut cache = self.cache.lock().unwrap();
+        let (changes, graphs) =
+            cache.get_or_insert_with(|| (map.restriction_change_times(), BTreeMap::new()));
+        let time_of_day = Time::START_OF_DAY
+            + Duration::seconds(time.inner_seconds() % Duration::hours(24).inner_seconds());
+        let period = changes.iter().filter(|t| **t <= time_of_day).count();
+        graphs
+            .entry((constraints, period, avoid_tolls))
+            .or_insert_with(|| Arc::new(GraphAtTime::new(map, constraints, time, avoid_tolls)))
+            .clone()
+    }
+
+    pub fn clear(&self) {
+        *self.cache.lock().unwrap() = None;
+    }
+}
+
+fn build_graph(
+    map: &Map,
+    constraints: PathConstraints,
+    avoid: &BTreeSet<LaneID>,
+) -> DiGraphMap<LaneID, TurnID> {
     let mut graph: DiGraphMap<LaneID, TurnID> = DiGraphMap::new();
     for l in map.all_lanes() {
-        if req.constraints.can_use(l, map) && !avoid.contains(&l.id) {
-            for turn in map.get_turns_for(l.id, req.constraints) {
-                graph.add_edge(turn.id.src, turn.id.dst, turn.id);
+        if constraints.can_use(l, map) && !avoid.contains(&l.id) {
+            for turn in map.get_turns_for(l.id, constraints) {
+                if !breaks_turn_restrictions(map, &[turn.id], |c| c.applies(constraints, None)) {
+                    graph.add_edge(turn.id.src, turn.id.dst, turn.id);
+                }
             }
         }
     }
-
-    calc_path(graph, &req, map)
+    graph
 }
 
 fn calc_path(graph: DiGraphMap<LaneID, TurnID>, req: &PathRequest, map: &Map) -> Option<Path> {
@@ -8,6 +8,7 @@ use thread_local::ThreadLocal;
 
 use abstutil::MultiMap;
 
+use crate::pathfind::breaks_turn_restrictions;
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 use crate::{Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID};
@@ -144,13 +145,14 @@ fn make_input_graph(
     let mut uber_turn_entrances: MultiMap<LaneID, usize> = MultiMap::new();
     for (idx, ut) in uber_turns.iter().enumerate() {
         // But actually, make sure this uber-turn only contains lanes that can be used by this
-        // vehicle.
         // TODO Need to test editing lanes inside an IntersectionCluster very carefully. See Mercer
         // and Dexter.
+        // vehicle, and that no restriction bans it for this vehicle all day.
         if ut
             .path
             .iter()
             .all(|t| constraints.can_use(map.get_l(t.dst), map))
+            && !breaks_turn_restrictions(map, &ut.path, |c| c.applies(constraints, None))
         {
             uber_turn_entrances.insert(ut.entry(), idx);
         } else {
@@ -179,6 +181,11 @@ fn make_input_graph(
             let indices = uber_turn_entrances.get(l.id);
             if indices.is_empty() {
                 for turn in map.get_turns_for(l.id, constraints) {
+                    // Restrictions that only apply at some times stay in the graph; see
+                    // Map::pathfind_at_time.
+                    if breaks_turn_restrictions(map, &[turn.id], |c| c.applies(constraints, None)) {
+                        continue;
+                    }
                     any = true;
                     input_graph.add_edge(
                         from,
@@ -236,9 +243,11 @@ pub fn driving_cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map:
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
 
-            // TODO Prefer bike lanes, then bus lanes, then driving lanes. For now, express that as
-            // an extra cost.
-            let lt_penalty = if lane.is_biking() {
+            // TODO Prefer protected bike lanes, then bike lanes, then bus lanes, then driving
+            // lanes. For now, express that as an extra cost.
+            let lt_penalty = if lane.is_protected_bike_lane(map) {
+                0.9
+            } else if lane.is_biking() {
                 1.0
             } else if lane.is_bus() {
                 1.1
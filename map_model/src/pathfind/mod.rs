@@ -6,13 +6,14 @@ use std::fmt;
 use enumset::EnumSetType;
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, PolyLine, EPSILON_DIST};
+use geom::{Distance, PolyLine, Time, EPSILON_DIST};
 
 pub use self::ch::ContractionHierarchyPathfinder;
-pub use self::dijkstra::{build_graph_for_pedestrians, build_graph_for_vehicles};
+pub use self::dijkstra::{build_graph_for_pedestrians, build_graph_for_vehicles, GraphsAtTime};
 pub use self::driving::driving_cost;
 pub use self::pathfinder::Pathfinder;
 pub use self::walking::{walking_cost, WalkingNode};
+use crate::raw::{RestrictionConditions, RestrictionType};
 use crate::{
     osm, BuildingID, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID, UberTurn,
 };
@@ -274,6 +275,30 @@ impl Path {
         }
     }
 
+    /// Replaces everything from one step onwards with another path, which must start with the
+    /// same lane and end on the same lane. Not allowed in the middle of an uber-turn, or if the
+    /// replaced part starts with one.
+    pub fn reroute_from(&mut self, idx: usize, other: Path, map: &Map) {
+        assert!(self.currently_inside_ut.is_none());
+        assert_eq!(self.steps[idx], other.steps[0]);
+        assert_eq!(self.last_step(), other.last_step());
+
+        self.steps.truncate(idx);
+        self.steps.extend(other.steps);
+        // Uber-turns already passed have been removed, and the rest were in the replaced part. The
+        // other path has its own, so the vehicle still treats them as one movement.
+        self.uber_turns = other.uber_turns;
+        self.end_dist = other.end_dist;
+        self.total_length = self.crossed_so_far;
+        for step in &self.steps {
+            self.total_length += step.as_traversable().length(map);
+        }
+    }
+
+    pub fn end_dist(&self) -> Distance {
+        self.end_dist
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -394,6 +419,25 @@ impl Path {
         &self.steps
     }
 
+    /// Does the remainder of this path break any turn restriction that applies to this vehicle at
+    /// some time?
+    pub fn breaks_turn_restrictions_at(
+        &self,
+        constraints: PathConstraints,
+        time: Time,
+        map: &Map,
+    ) -> bool {
+        let turns: Vec<TurnID> = self
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Turn(t) => Some(*t),
+                _ => None,
+            })
+            .collect();
+        breaks_turn_restrictions(map, &turns, |c| c.applies(constraints, Some(time)))
+    }
+
     // Not for walking paths
     fn append(&mut self, other: Path, map: &Map) {
         assert!(self.currently_inside_ut.is_none());
@@ -450,6 +494,9 @@ impl PathConstraints {
 
     // TODO Handle private zones here?
     pub fn can_use(self, l: &Lane, map: &Map) -> bool {
+        if map.get_r(l.parent).removed {
+            return false;
+        }
         match self {
             PathConstraints::Pedestrian => l.is_walkable(),
             PathConstraints::Car => l.is_driving(),
@@ -585,22 +632,99 @@ fn validate_continuity(map: &Map, steps: &Vec<PathStep>) {
 }
 
 fn validate_restrictions(map: &Map, steps: &Vec<PathStep>) {
-    for triple in steps.windows(5) {
-        if let (PathStep::Lane(l1), PathStep::Lane(l2), PathStep::Lane(l3)) =
-            (triple[0], triple[2], triple[4])
-        {
-            let from = map.get_parent(l1);
-            let via = map.get_l(l2).parent;
-            let to = map.get_l(l3).parent;
-
-            for (dont_via, dont_to) in &from.complicated_turn_restrictions {
-                if via == *dont_via && to == *dont_to {
-                    panic!(
-                        "Some path does illegal uber-turn: {} -> {} -> {}",
-                        l1, l2, l3
-                    );
+    let turns: Vec<TurnID> = steps
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Turn(t) => Some(*t),
+            _ => None,
+        })
+        .collect();
+    if breaks_turn_restrictions(map, &turns, |c| c.is_unconditional()) {
+        panic!("Some path does an illegal turn: {:?}", steps);
+    }
+}
+
+/// Does a sequence of consecutive turns break any turn restriction or pass a modal filter? Only
+/// restrictions and filters that `applies` says are in effect count. Restrictions via a road need
+/// the whole sequence through it; a sequence ending partway through one doesn't break it.
+pub fn breaks_turn_restrictions<F: Fn(&RestrictionConditions) -> bool>(
+    map: &Map,
+    turns: &[TurnID],
+    applies: F,
+) -> bool {
+    turns
+        .iter()
+        .any(|turn| passes_modal_filter(map, *turn, &applies))
+        || breaks_turn_restrictions_ignoring_filters(map, turns, applies)
+}
+
+/// Does a turn go past a modal filter that `applies` says is in effect?
+pub fn passes_modal_filter<F: Fn(&RestrictionConditions) -> bool>(
+    map: &Map,
+    turn: TurnID,
+    applies: F,
+) -> bool {
+    let from = map.get_parent(turn.src);
+    let dst = map.get_parent(turn.dst);
+    // Turning around before reaching a filter is fine
+    if from.id == dst.id {
+        return false;
+    }
+    vec![from, dst].into_iter().any(|road| {
+        road.modal_filters
+            .iter()
+            .any(|filter| filter.intersection(road) == turn.parent && applies(&filter.conditions))
+    })
+}
+
+/// Like `breaks_turn_restrictions`, but modal filters don't count.
+pub fn breaks_turn_restrictions_ignoring_filters<F: Fn(&RestrictionConditions) -> bool>(
+    map: &Map,
+    turns: &[TurnID],
+    applies: F,
+) -> bool {
+    for (idx, turn) in turns.iter().enumerate() {
+        let from = map.get_parent(turn.src);
+        let dst = map.get_l(turn.dst).parent;
+        let i = map.get_i(turn.parent);
+
+        for (restriction, to, conditions) in &from.turn_restrictions {
+            // The restriction only applies to one direction of the road.
+            if !i.roads.contains(to) || !applies(conditions) {
+                continue;
+            }
+            let ok = match restriction {
+                RestrictionType::BanTurns => dst != *to,
+                RestrictionType::OnlyAllowTurns => dst == *to,
+            };
+            if !ok {
+                return true;
+            }
+        }
+
+        if from.complicated_turn_restrictions.is_empty() {
+            continue;
+        }
+        // The roads visited after leaving 'from'
+        let next: Vec<_> = turns[idx..]
+            .iter()
+            .map(|t| map.get_l(t.dst).parent)
+            .collect();
+        for (restriction, via, to, conditions) in &from.complicated_turn_restrictions {
+            if next[0] != via[0] || !applies(conditions) {
+                continue;
+            }
+            let expected = via.iter().chain(std::iter::once(to));
+            let broken = match restriction {
+                RestrictionType::BanTurns => {
+                    next.len() > via.len() && next.iter().zip(expected).all(|(a, b)| a == b)
                 }
+                RestrictionType::OnlyAllowTurns => next.iter().zip(expected).any(|(a, b)| a != b),
+            };
+            if broken {
+                return true;
             }
         }
     }
+    false
 }
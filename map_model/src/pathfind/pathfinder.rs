@@ -3,6 +3,7 @@ use std::collections::BTreeSet;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
+use geom::Time;
 
 use crate::pathfind::ch::ContractionHierarchyPathfinder;
 use crate::pathfind::walking::{one_step_walking_path, walking_path_to_steps};
@@ -107,6 +108,20 @@ impl Pathfinder {
         self.simple_pathfind(&req, map)
     }
 
+    /// Like `pathfind`, but also obeys turn restrictions that only apply at certain times of day.
+    pub fn pathfind_at_time(&self, req: PathRequest, time: Time, map: &Map) -> Option<Path> {
+        let path = self.pathfind(req.clone(), map)?;
+        if req.constraints == PathConstraints::Pedestrian
+            || !path.breaks_turn_restrictions_at(req.constraints, time, map)
+        {
+            return Some(path);
+        }
+        // The contraction hierarchy doesn't know about the time, so fall back to something slower.
+        map.graphs_at_time
+            .get(map, req.constraints, time, false)
+            .pathfind(&req, map)
+    }
+
     pub fn pathfind_avoiding_lanes(
         &self,
         req: PathRequest,
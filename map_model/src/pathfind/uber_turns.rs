@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use abstutil::MultiMap;
 use geom::{Angle, Distance, PolyLine, Pt2D};
 
+use crate::pathfind::breaks_turn_restrictions;
 use crate::{DirectedRoadID, Direction, IntersectionID, LaneID, Map, TurnID};
 
 /// This only applies to VehiclePathfinder; walking through these intersections is nothing special.
@@ -42,10 +43,12 @@ impl IntersectionCluster {
         // Then look for intersections with complicated turn restrictions.
         let mut graph: UnGraphMap<IntersectionID, ()> = UnGraphMap::new();
         for from in map.all_roads() {
-            for (via, _) in &from.complicated_turn_restrictions {
-                // Each of these tells us 2 intersections to group together
-                let r = map.get_r(*via);
-                graph.add_edge(r.src_i, r.dst_i, ());
+            for (_, via, _, _) in &from.complicated_turn_restrictions {
+                // Each via road tells us 2 intersections to group together
+                for r in via {
+                    let r = map.get_r(*r);
+                    graph.add_edge(r.src_i, r.dst_i, ());
+                }
             }
         }
         for intersections in petgraph::algo::kosaraju_scc(&graph) {
@@ -114,33 +117,16 @@ impl IntersectionCluster {
             uber_turns.extend(flood(entrance, map, &exits));
         }
 
-        // Filter illegal paths
-        let mut all_restrictions = Vec::new();
-        for from in map.all_roads() {
-            for (via, to) in &from.complicated_turn_restrictions {
-                all_restrictions.push((from.id, *via, *to));
-            }
-        }
-
-        // Filter out the restricted ones!
+        // Filter out the illegal paths. Restrictions that only apply to some vehicles or at some
+        // times are handled later by pathfinding.
         let mut illegal = Vec::new();
         uber_turns.retain(|ut| {
-            let mut ok = true;
-            for pair in ut.path.windows(2) {
-                let r1 = map.get_l(pair[0].src).parent;
-                let r2 = map.get_l(pair[0].dst).parent;
-                let r3 = map.get_l(pair[1].dst).parent;
-                if all_restrictions.contains(&(r1, r2, r3)) {
-                    ok = false;
-                    break;
-                }
-            }
-            if ok {
-                true
-            } else {
+            if breaks_turn_restrictions(map, &ut.path, |c| c.is_unconditional()) {
                 // TODO There's surely a method in Vec to do partition like this
                 illegal.push(ut.clone());
                 false
+            } else {
+                true
             }
         });
 
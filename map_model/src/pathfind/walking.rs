@@ -108,12 +108,22 @@ impl SidewalkPathfinder {
         bus_graph: &VehiclePathfinder,
         train_graph: &VehiclePathfinder,
     ) {
-        // The NodeMap is all sidewalks, bus stops, and borders -- it won't change. So we can also
-        // reuse the node ordering.
+        // The NodeMap is all sidewalks, bus stops, and borders. Only new bus stops change it;
+        // removed stops just leave behind a disconnected node.
+        if self.use_transit {
+            for bs in map.all_bus_stops().keys() {
+                self.nodes.get_or_insert(WalkingNode::RideBus(*bs));
+            }
+        }
         let input_graph =
             make_input_graph(map, &self.nodes, self.use_transit, bus_graph, train_graph);
+        // If the number of nodes changed because of bus stop edits, the old node ordering can't
+        // be reused.
         let node_ordering = self.graph.get_node_ordering();
-        self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+        self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering)
+            .unwrap_or_else(|_| fast_paths::prepare(&input_graph));
+        // The calculators are sized for the old graph
+        self.path_calc = ThreadLocal::new();
     }
 
     /// Returns the raw nodes
@@ -304,6 +314,9 @@ fn transit_input_graph(
     // Connect each adjacent stop along a route, with the cost based on how long it'll take a
     // bus to drive between the stops. Optimistically assume no waiting time at a stop.
     for route in map.all_bus_routes() {
+        if route.spawn_times.is_empty() {
+            continue;
+        }
         // TODO Also plug in border starts
         for pair in route.stops.windows(2) {
             let (stop1, stop2) = (map.get_bs(pair[0]), map.get_bs(pair[1]));
@@ -5,15 +5,17 @@
 use std::collections::BTreeMap;
 use std::fmt;
 
+use enumset::EnumSet;
 use petgraph::graphmap::DiGraphMap;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, MapName, Tags, Timer};
-use geom::{Circle, Distance, GPSBounds, PolyLine, Polygon, Pt2D};
+use geom::{Circle, Distance, Duration, GPSBounds, PolyLine, Polygon, Pt2D, Time};
 
-use crate::make::initial::lane_specs::get_lane_specs_ltr;
+use crate::make::initial::lane_specs::{get_lane_specs_ltr, get_placement_offset};
 use crate::{
     osm, Amenity, AreaType, Direction, DrivingSide, IntersectionType, LaneType, MapConfig,
+    PathConstraints,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -248,9 +250,15 @@ pub struct RawRoad {
     /// happened.
     pub center_points: Vec<Pt2D>,
     pub osm_tags: Tags,
-    pub turn_restrictions: Vec<(RestrictionType, OriginalRoad)>,
-    /// (via, to). For turn restrictions where 'via' is an entire road. Only BanTurns.
-    pub complicated_turn_restrictions: Vec<(OriginalRoad, OriginalRoad)>,
+    pub turn_restrictions: Vec<(RestrictionType, OriginalRoad, RestrictionConditions)>,
+    /// (restriction, via, to, conditions). For turn restrictions where 'via' is one or more entire
+    /// roads, listed in the order they're traversed.
+    pub complicated_turn_restrictions: Vec<(
+        RestrictionType,
+        Vec<OriginalRoad>,
+        OriginalRoad,
+        RestrictionConditions,
+    )>,
 }
 
 impl RawRoad {
@@ -271,8 +279,20 @@ impl RawRoad {
             }
         }
 
-        // If there's a sidewalk on only one side, adjust the true center of the road.
         let mut true_center = PolyLine::new(self.center_points.clone()).expect(&id.to_string());
+        // The way may explicitly say where it's drawn relative to the lanes.
+        if let Some(offset) = get_placement_offset(&self.osm_tags, &lane_specs) {
+            let shifted = if offset >= Distance::ZERO {
+                true_center.shift_right(offset)
+            } else {
+                true_center.shift_left(-offset)
+            };
+            if let Ok(pl) = shifted {
+                return (pl, total_width);
+            }
+        }
+
+        // If there's a sidewalk on only one side, adjust the true center of the road.
         match (sidewalk_right, sidewalk_left) {
             (Some(w), None) => {
                 true_center = true_center.must_shift_right(w / 2.0);
@@ -347,8 +367,8 @@ pub struct TurnRestriction(pub OriginalRoad, pub RestrictionType, pub OriginalRo
 
 impl RestrictionType {
     pub fn new(restriction: &str) -> Option<RestrictionType> {
-        // TODO There's a huge space of things not represented yet: time conditions, bus-only, no
-        // right turn on red...
+        // TODO There's a huge space of things not represented yet: bus-only, no right turn on
+        // red...
 
         // There are so many possibilities:
         // https://taginfo.openstreetmap.org/keys/restriction#values
@@ -363,6 +383,212 @@ impl RestrictionType {
     }
 }
 
+/// Limits who a turn restriction applies to and when. By default, it applies to everybody all the
+/// time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RestrictionConditions {
+    /// These vehicles may ignore the restriction. From OSM's `except` tag.
+    pub except: EnumSet<PathConstraints>,
+    /// If non-empty, the restriction only applies during these times of day. Windows never wrap
+    /// around midnight; the end may be 24:00. From OSM's `restriction:conditional` tag.
+    pub time_windows: Vec<(Time, Time)>,
+}
+
+impl RestrictionConditions {
+    /// Parses the tags of a `type=restriction` relation. Returns None if the restriction isn't
+    /// modeled or never applies on a weekday, and an error if the conditions can't be understood.
+    pub fn parse(tags: &Tags) -> Result<Option<(RestrictionType, RestrictionConditions)>, String> {
+        let mut except = EnumSet::new();
+        if let Some(list) = tags.get("except") {
+            for vehicle in list.split(';') {
+                match vehicle.trim() {
+                    "bicycle" => {
+                        except.insert(PathConstraints::Bike);
+                    }
+                    "psv" | "bus" => {
+                        except.insert(PathConstraints::Bus);
+                    }
+                    "motorcar" => {
+                        except.insert(PathConstraints::Car);
+                    }
+                    "motor_vehicle" => {
+                        except.insert(PathConstraints::Car);
+                        except.insert(PathConstraints::Bus);
+                    }
+                    // Emergency vehicles, deliveries, and so on aren't simulated
+                    _ => {}
+                }
+            }
+        }
+
+        let conditional = match tags.get("restriction:conditional") {
+            Some(value) => Some(parse_conditional(value)?),
+            None => None,
+        };
+        let (restriction, time_windows) = match (tags.get("restriction"), conditional) {
+            (Some(restriction), None) => (restriction.as_str(), Vec::new()),
+            (None, Some((restriction, windows))) => {
+                if windows.is_empty() {
+                    return Ok(None);
+                }
+                (restriction, windows)
+            }
+            // The restriction is lifted during the windows
+            (Some(restriction), Some(("none", windows))) => {
+                let windows = complement_windows(windows);
+                if windows.is_empty() {
+                    return Ok(None);
+                }
+                (restriction.as_str(), windows)
+            }
+            // TODO Something like no_left_turn normally and no_u_turn at certain times. Just keep
+            // the unconditional restriction.
+            (Some(restriction), Some(_)) => (restriction.as_str(), Vec::new()),
+            (None, None) => {
+                return Ok(None);
+            }
+        };
+
+        Ok(RestrictionType::new(restriction).map(|rt| {
+            (
+                rt,
+                RestrictionConditions {
+                    except,
+                    time_windows,
+                },
+            )
+        }))
+    }
+
+    /// True if the restriction applies to everybody, all the time.
+    pub fn is_unconditional(&self) -> bool {
+        self.except.is_empty() && self.time_windows.is_empty()
+    }
+
+    /// Does the restriction apply to this type of vehicle at some time? If no time is specified,
+    /// only restrictions that apply all day count.
+    pub fn applies(&self, constraints: PathConstraints, time: Option<Time>) -> bool {
+        if self.except.contains(constraints) {
+            return false;
+        }
+        if self.time_windows.is_empty() {
+            return true;
+        }
+        if let Some(time) = time {
+            // Simulations may run past midnight; every day is the same weekday.
+            let time_of_day = Time::START_OF_DAY
+                + Duration::seconds(time.inner_seconds() % Duration::hours(24).inner_seconds());
+            self.time_windows
+                .iter()
+                .any(|(start, end)| time_of_day >= *start && time_of_day < *end)
+        } else {
+            false
+        }
+    }
+}
+
+/// Parses something like `no_left_turn @ (Mo-Fr 07:00-09:00,16:00-18:00)` into the restriction
+/// and the time windows when it applies on a weekday.
+fn parse_conditional(value: &str) -> Result<(&str, Vec<(Time, Time)>), String> {
+    let parts: Vec<&str> = value.split('@').collect();
+    if parts.len() != 2 {
+        return Err(format!("only a single condition is supported: {}", value));
+    }
+    let restriction = parts[0].trim();
+    let condition = parts[1]
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+
+    let mut windows = Vec::new();
+    // Each rule is an optional list of days, then a list of time ranges
+    for rule in condition.split(';') {
+        let mut days = Vec::new();
+        let mut times = Vec::new();
+        for token in rule.split_whitespace() {
+            if token.contains(':') {
+                times.extend(token.split(',').filter(|x| !x.is_empty()));
+            } else if token
+                .split(|c| c == ',' || c == '-')
+                .all(|d| ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su", "PH", "off"].contains(&d))
+            {
+                days.push(token);
+            } else {
+                // Things like "wet" or "weight>7.5"
+                return Err(format!("unsupported condition: {}", value));
+            }
+        }
+        if days.contains(&"off") {
+            continue;
+        }
+        // Simulations only cover a weekday
+        if !days.is_empty()
+            && !days.iter().any(|d| {
+                ["Mo", "Tu", "We", "Th", "Fr"]
+                    .iter()
+                    .any(|weekday| d.contains(weekday))
+            })
+        {
+            continue;
+        }
+        if times.is_empty() {
+            if days.is_empty() {
+                return Err(format!("unsupported condition: {}", value));
+            }
+            windows.push((Time::START_OF_DAY, Time::START_OF_DAY + Duration::hours(24)));
+        }
+        for range in times {
+            let pair: Vec<&str> = range.split('-').collect();
+            if pair.len() != 2 {
+                return Err(format!("bad time range {} in {}", range, value));
+            }
+            let start = parse_hhmm(pair[0])
+                .ok_or_else(|| format!("bad time range {} in {}", range, value))?;
+            let end = parse_hhmm(pair[1])
+                .ok_or_else(|| format!("bad time range {} in {}", range, value))?;
+            if start <= end {
+                windows.push((start, end));
+            } else {
+                // Wraps around midnight
+                windows.push((start, Time::START_OF_DAY + Duration::hours(24)));
+                windows.push((Time::START_OF_DAY, end));
+            }
+        }
+    }
+    Ok((restriction, windows))
+}
+
+fn parse_hhmm(value: &str) -> Option<Time> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() != 2 {
+        return None;
+    }
+    let hours = parts[0].parse::<usize>().ok()?;
+    let minutes = parts[1].parse::<usize>().ok()?;
+    if hours > 24 || minutes >= 60 {
+        return None;
+    }
+    Some(Time::START_OF_DAY + Duration::hours(hours) + Duration::minutes(minutes))
+}
+
+/// The parts of the day not covered by any of the windows.
+fn complement_windows(mut windows: Vec<(Time, Time)>) -> Vec<(Time, Time)> {
+    windows.sort();
+    let mut result = Vec::new();
+    let mut last = Time::START_OF_DAY;
+    for (start, end) in windows {
+        if start > last {
+            result.push((last, start));
+        }
+        last = last.max(end);
+    }
+    let end_of_day = Time::START_OF_DAY + Duration::hours(24);
+    if last < end_of_day {
+        result.push((last, end_of_day));
+    }
+    result
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawBusRoute {
     pub full_name: String,
@@ -388,3 +614,69 @@ pub struct RawBusStop {
     /// If it's not explicitly mapped, we'll do equiv_pos.
     pub ped_pos: Option<Pt2D>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<&str>) -> Tags {
+        let mut tags = Tags::new(BTreeMap::new());
+        for pair in kv {
+            let parts = pair.split('=').collect::<Vec<_>>();
+            tags.insert(parts[0], parts[1]);
+        }
+        tags
+    }
+
+    fn hm(hours: usize, minutes: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(hours) + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn test_parse_restriction_conditions() {
+        let (rt, cond) = RestrictionConditions::parse(&tags(vec![
+            "restriction=no_left_turn",
+            "except=psv;bicycle",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(rt, RestrictionType::BanTurns);
+        assert!(cond.applies(PathConstraints::Car, None));
+        assert!(!cond.applies(PathConstraints::Bus, None));
+        assert!(!cond.applies(PathConstraints::Bike, None));
+
+        let (rt, cond) = RestrictionConditions::parse(&tags(vec![
+            "restriction:conditional=no_right_turn @ (Mo-Fr 07:00-09:00,16:00-18:30)",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(rt, RestrictionType::BanTurns);
+        assert!(!cond.applies(PathConstraints::Car, None));
+        assert!(cond.applies(PathConstraints::Car, Some(hm(8, 0))));
+        assert!(cond.applies(PathConstraints::Car, Some(hm(18, 29))));
+        assert!(!cond.applies(PathConstraints::Car, Some(hm(12, 0))));
+        // The next day
+        assert!(cond.applies(PathConstraints::Car, Some(hm(24 + 7, 30))));
+
+        // Lifted overnight
+        let (_, cond) = RestrictionConditions::parse(&tags(vec![
+            "restriction=only_straight_on",
+            "restriction:conditional=none @ (22:00-06:00)",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(cond.time_windows, vec![(hm(6, 0), hm(22, 0))]);
+
+        // Only on weekends
+        assert_eq!(
+            RestrictionConditions::parse(&tags(vec![
+                "restriction:conditional=no_left_turn @ (Sa,Su 10:00-16:00)"
+            ])),
+            Ok(None)
+        );
+        assert!(RestrictionConditions::parse(&tags(vec![
+            "restriction:conditional=no_left_turn @ wet"
+        ]))
+        .is_err());
+    }
+}
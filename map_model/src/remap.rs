@@ -0,0 +1,320 @@
+//! Re-importing a map from newer OSM data renumbers every object. An `IDRemap` matches objects
+//! between the old and new version of a map, so that anything referring to the old IDs (saved
+//! scenarios, prebaked results) can be translated.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
+
+use crate::osm;
+use crate::{
+    BuildingID, BusRouteID, BusStopID, CompressedMovementID, DirectedRoadID, IntersectionID,
+    LaneID, Map, MovementID, ParkingLotID, Position, RoadID, TurnID,
+};
+
+/// Maps IDs from an old version of a map to a new one. Objects are matched by their original OSM
+/// IDs. If something was deleted or changed too much, it has no match.
+#[derive(Serialize, Deserialize)]
+pub struct IDRemap {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    roads: BTreeMap<RoadID, RoadID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    lanes: BTreeMap<LaneID, LaneID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    intersections: BTreeMap<IntersectionID, IntersectionID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    buildings: BTreeMap<BuildingID, BuildingID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    parking_lots: BTreeMap<ParkingLotID, ParkingLotID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    bus_stops: BTreeMap<BusStopID, BusStopID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    bus_routes: BTreeMap<BusRouteID, BusRouteID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    compressed_movements: BTreeMap<CompressedMovementID, CompressedMovementID>,
+    /// The length of every lane in the new map, to keep positions valid
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    new_lane_lengths: BTreeMap<LaneID, geom::Distance>,
+    /// Intersections can stop being borders when the map grows, so trips can't start or end there
+    /// anymore
+    new_borders: BTreeSet<IntersectionID>,
+}
+
+impl IDRemap {
+    pub fn new(old: &Map, new: &Map) -> IDRemap {
+        let mut remap = IDRemap {
+            roads: BTreeMap::new(),
+            lanes: BTreeMap::new(),
+            intersections: BTreeMap::new(),
+            buildings: BTreeMap::new(),
+            parking_lots: BTreeMap::new(),
+            bus_stops: BTreeMap::new(),
+            bus_routes: BTreeMap::new(),
+            compressed_movements: BTreeMap::new(),
+            new_lane_lengths: new.all_lanes().iter().map(|l| (l.id, l.length())).collect(),
+            new_borders: new
+                .all_intersections()
+                .iter()
+                .filter(|i| i.is_border())
+                .map(|i| i.id)
+                .collect(),
+        };
+
+        let new_intersections: BTreeMap<osm::NodeID, IntersectionID> = new
+            .all_intersections()
+            .iter()
+            .map(|i| (i.orig_id, i.id))
+            .collect();
+        for i in old.all_intersections() {
+            if let Some(id) = new_intersections.get(&i.orig_id) {
+                remap.intersections.insert(i.id, *id);
+            }
+        }
+
+        let new_roads: BTreeMap<osm::OriginalRoad, RoadID> =
+            new.all_roads().iter().map(|r| (r.orig_id, r.id)).collect();
+        for r in old.all_roads() {
+            if let Some(id) = new_roads.get(&r.orig_id) {
+                remap.roads.insert(r.id, *id);
+
+                // Lanes of the same type and direction are matched up in order, so adding or
+                // removing one kind of lane doesn't disturb the others.
+                let mut new_lanes: BTreeMap<_, Vec<LaneID>> = BTreeMap::new();
+                for (l, dir, lt) in new.get_r(*id).lanes_ltr() {
+                    new_lanes.entry((dir, lt)).or_insert_with(Vec::new).push(l);
+                }
+                let mut seen = Counter::new();
+                for (l, dir, lt) in r.lanes_ltr() {
+                    let key = (dir, lt);
+                    let idx = seen.inc(key) - 1;
+                    if let Some(new_l) = new_lanes.get(&key).and_then(|list| list.get(idx)) {
+                        remap.lanes.insert(l, *new_l);
+                    }
+                }
+            }
+        }
+
+        let new_bldgs: BTreeMap<osm::OsmID, BuildingID> = new
+            .all_buildings()
+            .iter()
+            .map(|b| (b.orig_id, b.id))
+            .collect();
+        for b in old.all_buildings() {
+            if let Some(id) = new_bldgs.get(&b.orig_id) {
+                remap.buildings.insert(b.id, *id);
+            }
+        }
+
+        let new_lots: BTreeMap<osm::OsmID, ParkingLotID> = new
+            .all_parking_lots()
+            .iter()
+            .map(|pl| (pl.osm_id, pl.id))
+            .collect();
+        for pl in old.all_parking_lots() {
+            if let Some(id) = new_lots.get(&pl.osm_id) {
+                remap.parking_lots.insert(pl.id, *id);
+            }
+        }
+
+        // Bus stops don't have OSM IDs. Match the closest stop on the same sidewalk.
+        for bs in old.all_bus_stops().values() {
+            if let Some(new_sidewalk) = remap.lanes.get(&bs.sidewalk_pos.lane()) {
+                if let Some(new_bs) = new.get_l(*new_sidewalk).bus_stops.iter().min_by_key(|id| {
+                    (new.get_bs(**id).sidewalk_pos.dist_along() - bs.sidewalk_pos.dist_along())
+                        .abs()
+                }) {
+                    remap.bus_stops.insert(bs.id, *new_bs);
+                }
+            }
+        }
+
+        let new_routes: BTreeMap<(osm::RelationID, &String), BusRouteID> = new
+            .all_bus_routes()
+            .iter()
+            .map(|br| ((br.osm_rel_id, &br.full_name), br.id))
+            .collect();
+        for br in old.all_bus_routes() {
+            if let Some(id) = new_routes.get(&(br.osm_rel_id, &br.full_name)) {
+                remap.bus_routes.insert(br.id, *id);
+            }
+        }
+
+        for old_ts in old
+            .all_intersections()
+            .iter()
+            .filter_map(|i| old.maybe_get_traffic_signal(i.id))
+        {
+            let new_ts = match remap
+                .intersection(old_ts.id)
+                .and_then(|i| new.maybe_get_traffic_signal(i))
+            {
+                Some(ts) => ts,
+                None => continue,
+            };
+            for (old_idx, m) in old_ts.movements.keys().enumerate() {
+                if let Some(new_idx) = remap
+                    .movement(*m)
+                    .and_then(|m| new_ts.movements.keys().position(|x| *x == m))
+                {
+                    remap.compressed_movements.insert(
+                        CompressedMovementID {
+                            i: old_ts.id,
+                            idx: old_idx as u8,
+                        },
+                        CompressedMovementID {
+                            i: new_ts.id,
+                            idx: new_idx as u8,
+                        },
+                    );
+                }
+            }
+        }
+
+        remap
+    }
+
+    pub fn road(&self, r: RoadID) -> Option<RoadID> {
+        self.roads.get(&r).cloned()
+    }
+
+    pub fn lane(&self, l: LaneID) -> Option<LaneID> {
+        self.lanes.get(&l).cloned()
+    }
+
+    pub fn intersection(&self, i: IntersectionID) -> Option<IntersectionID> {
+        self.intersections.get(&i).cloned()
+    }
+
+    /// Like `intersection`, but only if it's still a border in the new map.
+    pub fn border(&self, i: IntersectionID) -> Option<IntersectionID> {
+        let i = self.intersection(i)?;
+        if self.new_borders.contains(&i) {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    pub fn building(&self, b: BuildingID) -> Option<BuildingID> {
+        self.buildings.get(&b).cloned()
+    }
+
+    pub fn parking_lot(&self, pl: ParkingLotID) -> Option<ParkingLotID> {
+        self.parking_lots.get(&pl).cloned()
+    }
+
+    pub fn bus_stop(&self, bs: BusStopID) -> Option<BusStopID> {
+        self.bus_stops.get(&bs).cloned()
+    }
+
+    pub fn bus_route(&self, br: BusRouteID) -> Option<BusRouteID> {
+        self.bus_routes.get(&br).cloned()
+    }
+
+    pub fn compressed_movement(&self, m: CompressedMovementID) -> Option<CompressedMovementID> {
+        self.compressed_movements.get(&m).cloned()
+    }
+
+    pub fn directed_road(&self, dr: DirectedRoadID) -> Option<DirectedRoadID> {
+        Some(DirectedRoadID {
+            id: self.road(dr.id)?,
+            dir: dr.dir,
+        })
+    }
+
+    pub fn turn(&self, t: TurnID) -> Option<TurnID> {
+        Some(TurnID {
+            parent: self.intersection(t.parent)?,
+            src: self.lane(t.src)?,
+            dst: self.lane(t.dst)?,
+        })
+    }
+
+    pub fn movement(&self, m: MovementID) -> Option<MovementID> {
+        Some(MovementID {
+            from: self.directed_road(m.from)?,
+            to: self.directed_road(m.to)?,
+            parent: self.intersection(m.parent)?,
+            crosswalk: m.crosswalk,
+        })
+    }
+
+    /// If the lane got shorter, the position is clamped to its new length.
+    pub fn position(&self, pos: Position) -> Option<Position> {
+        let l = self.lane(pos.lane())?;
+        Some(Position::new(
+            l,
+            pos.dist_along().min(self.new_lane_lengths[&l]),
+        ))
+    }
+
+    /// Summarizes how much of the old map was matched.
+    pub fn describe(&self, old: &Map) -> Vec<String> {
+        vec![
+            format!(
+                "{}/{} roads matched",
+                self.roads.len(),
+                old.all_roads().len()
+            ),
+            format!(
+                "{}/{} lanes matched",
+                self.lanes.len(),
+                old.all_lanes().len()
+            ),
+            format!(
+                "{}/{} intersections matched",
+                self.intersections.len(),
+                old.all_intersections().len()
+            ),
+            format!(
+                "{}/{} buildings matched",
+                self.buildings.len(),
+                old.all_buildings().len()
+            ),
+            format!(
+                "{}/{} parking lots matched",
+                self.parking_lots.len(),
+                old.all_parking_lots().len()
+            ),
+            format!(
+                "{}/{} bus stops matched",
+                self.bus_stops.len(),
+                old.all_bus_stops().len()
+            ),
+            format!(
+                "{}/{} bus routes matched",
+                self.bus_routes.len(),
+                old.all_bus_routes().len()
+            ),
+        ]
+    }
+}
@@ -5,12 +5,13 @@ use serde::{Deserialize, Serialize};
 use abstutil::Counter;
 use geom::{Distance, Duration, Time};
 use map_model::{
-    BusRouteID, BusStopID, CompressedMovementID, IntersectionID, LaneID, Map, MovementID,
+    BusRouteID, BusStopID, CompressedMovementID, IDRemap, IntersectionID, LaneID, Map, MovementID,
     ParkingLotID, Path, PathRequest, RoadID, Traversable, TurnID,
 };
 
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType,
+    AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TollDecision, TripID, TripMode,
+    TripPhaseType,
 };
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
@@ -64,6 +65,9 @@ pub struct Analytics {
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
 
+    /// How each driver facing a toll responded, and how much they paid
+    pub toll_decisions: Vec<(Time, TripID, TollDecision)>,
+
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
     /// For benchmarking, we may want to disable collecting data.
@@ -88,6 +92,7 @@ impl Analytics {
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            toll_decisions: Vec::new(),
             alerts: Vec::new(),
             record_anything,
         }
@@ -278,6 +283,9 @@ impl Analytics {
             Event::Alert(loc, msg) => {
                 self.alerts.push((time, loc, msg));
             }
+            Event::TollDecision(trip, decision) => {
+                self.toll_decisions.push((time, trip, decision));
+            }
             _ => {}
         }
     }
@@ -292,6 +300,14 @@ impl Analytics {
         }
     }
 
+    /// Dollars collected from tolls so far
+    pub fn toll_revenue(&self) -> f64 {
+        self.toll_decisions
+            .iter()
+            .map(|(_, _, decision)| decision.toll_paid())
+            .sum()
+    }
+
     // TODO If these ever need to be speeded up, just cache the histogram and index in the events
     // list.
 
@@ -492,6 +508,111 @@ impl Analytics {
         }
         pts
     }
+
+    /// Translates results from an old version of the map to a new one, after re-importing. Data
+    /// about objects that no longer exist is dropped.
+    pub fn remap_ids(&mut self, remap: &IDRemap) {
+        self.road_thruput.remap_ids(|r| remap.road(r));
+        self.intersection_thruput
+            .remap_ids(|i| remap.intersection(i));
+        self.traffic_signal_thruput
+            .remap_ids(|m| remap.compressed_movement(m));
+        self.demand = std::mem::take(&mut self.demand)
+            .into_iter()
+            .filter_map(|(m, cnt)| Some((remap.movement(m)?, cnt)))
+            .collect();
+
+        self.bus_arrivals = std::mem::take(&mut self.bus_arrivals)
+            .into_iter()
+            .filter_map(|(t, car, br, bs)| {
+                Some((t, car, remap.bus_route(br)?, remap.bus_stop(bs)?))
+            })
+            .collect();
+        self.passengers_boarding = std::mem::take(&mut self.passengers_boarding)
+            .into_iter()
+            .filter_map(|(bs, list)| {
+                Some((
+                    remap.bus_stop(bs)?,
+                    list.into_iter()
+                        .filter_map(|(t, br, dt)| Some((t, remap.bus_route(br)?, dt)))
+                        .collect(),
+                ))
+            })
+            .collect();
+        self.passengers_alighting = std::mem::take(&mut self.passengers_alighting)
+            .into_iter()
+            .filter_map(|(bs, list)| {
+                Some((
+                    remap.bus_stop(bs)?,
+                    list.into_iter()
+                        .filter_map(|(t, br)| Some((t, remap.bus_route(br)?)))
+                        .collect(),
+                ))
+            })
+            .collect();
+
+        for delays in self.trip_intersection_delays.values_mut() {
+            *delays = std::mem::take(delays)
+                .into_iter()
+                .filter_map(|(t, x)| Some((remap.turn(t)?, x)))
+                .collect();
+        }
+        for speeds in self.lane_speed_percentage.values_mut() {
+            *speeds = std::mem::take(speeds)
+                .into_iter()
+                .filter_map(|(l, x)| Some((remap.lane(l)?, x)))
+                .collect();
+        }
+        for (_, _, req, _) in &mut self.trip_log {
+            *req = req.take().and_then(|req| {
+                Some(PathRequest {
+                    start: remap.position(req.start)?,
+                    end: remap.position(req.end)?,
+                    constraints: req.constraints,
+                })
+            });
+        }
+
+        // The movement indices are only meaningful along with the intersection.
+        let mut intersection_delays = BTreeMap::new();
+        for (i, list) in std::mem::take(&mut self.intersection_delays) {
+            for (idx, t, dt, agent_type) in list {
+                if let Some(m) = remap.compressed_movement(CompressedMovementID { i, idx }) {
+                    intersection_delays
+                        .entry(m.i)
+                        .or_insert_with(Vec::new)
+                        .push((m.idx, t, dt, agent_type));
+                }
+            }
+        }
+        self.intersection_delays = intersection_delays;
+
+        self.parking_lane_changes = std::mem::take(&mut self.parking_lane_changes)
+            .into_iter()
+            .filter_map(|(l, changes)| Some((remap.lane(l)?, changes)))
+            .collect();
+        self.parking_lot_changes = std::mem::take(&mut self.parking_lot_changes)
+            .into_iter()
+            .filter_map(|(pl, changes)| Some((remap.parking_lot(pl)?, changes)))
+            .collect();
+
+        self.alerts.retain(|(_, loc, _)| match loc {
+            AlertLocation::Intersection(i) => remap.intersection(*i).is_some(),
+            AlertLocation::Building(b) => remap.building(*b).is_some(),
+            AlertLocation::Nil | AlertLocation::Person(_) => true,
+        });
+        for (_, loc, _) in &mut self.alerts {
+            match loc {
+                AlertLocation::Intersection(i) => {
+                    *i = remap.intersection(*i).unwrap();
+                }
+                AlertLocation::Building(b) => {
+                    *b = remap.building(*b).unwrap();
+                }
+                AlertLocation::Nil | AlertLocation::Person(_) => {}
+            }
+        }
+    }
 }
 
 impl Default for Analytics {
@@ -558,6 +679,20 @@ impl<X: Ord + Clone> TimeSeriesCount<X> {
         cnt
     }
 
+    fn remap_ids<F: Fn(X) -> Option<X>>(&mut self, remap: F) {
+        let mut counts = BTreeMap::new();
+        for ((id, agent_type, hour), count) in std::mem::take(&mut self.counts) {
+            if let Some(id) = remap(id) {
+                *counts.entry((id, agent_type, hour)).or_insert(0) += count;
+            }
+        }
+        self.counts = counts;
+        self.raw = std::mem::take(&mut self.raw)
+            .into_iter()
+            .filter_map(|(t, agent_type, id)| Some((t, agent_type, remap(id)?)))
+            .collect();
+    }
+
     pub fn all_total_counts(&self) -> Counter<X> {
         let mut cnt = Counter::new();
         for ((id, _, _), value) in &self.counts {
@@ -0,0 +1,203 @@
+//! Runs a scenario on a map without edits and with each of several proposals, for a few RNG
+//! seeds, and compares the results. This is the headless equivalent of the A/B testing mode in the
+//! game, for evaluating many proposals at once.
+//!
+//! Usage: compare_proposals --map=data/system/seattle/maps/montlake.bin
+//! --scenario=data/system/seattle/scenarios/montlake/weekday.bin --seeds=1,2,3
+//! --output=comparison.json proposal1.json proposal2.json
+//!
+//! Each proposal is a `PermanentMapEdits` file. All of the simulations run in parallel. Instead of
+//! listing seeds, `--num_seeds=10` uses that many consecutive seeds. With more than one seed, the
+//! report also summarizes every metric across seeds, with a 95% confidence interval; see
+//! `sim::SeedRuns`.
+
+use serde::Serialize;
+
+use abstutil::{prettyprint_usize, CmdArgs, Timer};
+use map_model::{Map, MapEdits};
+use sim::{RunComparison, Scenario, SeedSummary, SimOptions};
+
+fn main() {
+    let mut args = CmdArgs::new();
+    let map_path = args.required("--map");
+    let scenario_path = args.required("--scenario");
+    let seeds: Option<Vec<u64>> = args.optional_parse("--seeds", |s| {
+        s.split(',').map(|x| x.parse::<u64>()).collect()
+    });
+    let num_seeds = args.optional_parse("--num_seeds", |s| s.parse::<u64>());
+    let seeds: Vec<u64> = match (seeds, num_seeds) {
+        (Some(_), Some(_)) => panic!("Pass --seeds or --num_seeds, not both"),
+        (Some(seeds), None) => seeds,
+        (None, Some(n)) => (0..n).map(|i| sim::SimFlags::RNG_SEED + i).collect(),
+        (None, None) => vec![sim::SimFlags::RNG_SEED],
+    };
+    let output = args
+        .optional("--output")
+        .unwrap_or_else(|| "comparison.json".to_string());
+    let mut edit_paths = Vec::new();
+    while let Some(path) = args.optional_free() {
+        edit_paths.push(path);
+    }
+    args.done();
+    if edit_paths.is_empty() {
+        panic!("Pass in at least one proposal to compare");
+    }
+
+    let mut timer = Timer::new("compare proposals");
+    let baseline = Map::new(map_path, &mut timer);
+    let scenario: Scenario = abstutil::read_binary(scenario_path, &mut timer);
+    if &scenario.map_name != baseline.get_name() {
+        panic!(
+            "Scenario {} is for {}, not {}",
+            scenario.scenario_name,
+            scenario.map_name.describe(),
+            baseline.get_name().describe()
+        );
+    }
+
+    // Apply every proposal up-front, so each simulation can share the map. This keeps a copy of
+    // the map per proposal in memory.
+    let mut maps = vec![baseline.clone()];
+    let mut names = Vec::new();
+    for path in &edit_paths {
+        let mut map = baseline.clone();
+        let edits = match MapEdits::load(&map, path.clone(), &mut timer) {
+            Ok(edits) => edits,
+            Err(err) => panic!("Couldn't load edits from {}: {}", path, err),
+        };
+        timer.start(format!("apply {}", edits.edits_name));
+        names.push(edits.edits_name.clone());
+        map.must_apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        timer.stop(format!("apply {}", names.last().unwrap()));
+        maps.push(map);
+    }
+
+    let mut results = sim::run_with_seeds(
+        &maps.iter().collect::<Vec<_>>(),
+        &scenario,
+        &SimOptions::new("compare_proposals"),
+        &seeds,
+        &mut timer,
+    );
+    // Index 0 is the baseline
+    let after_results = results.split_off(1);
+    let baseline_results = results.pop().unwrap();
+
+    let mut report = Report {
+        map: baseline.get_name().describe(),
+        scenario: scenario.scenario_name.clone(),
+        seeds: seeds.clone(),
+        proposals: Vec::new(),
+    };
+    for (idx, (name, path)) in names.into_iter().zip(edit_paths).enumerate() {
+        let runs: Vec<RunComparison> = seeds
+            .iter()
+            .enumerate()
+            .map(|(i, seed)| {
+                RunComparison::new(*seed, &baseline_results[i], &after_results[idx][i])
+            })
+            .collect();
+        report.proposals.push(ProposalResults {
+            edits_name: name,
+            path,
+            across_seeds: SeedSummary::new(&runs),
+            runs,
+        });
+    }
+
+    report.print();
+    abstutil::write_json(output, &report);
+}
+
+#[derive(Serialize)]
+struct Report {
+    map: String,
+    scenario: String,
+    seeds: Vec<u64>,
+    proposals: Vec<ProposalResults>,
+}
+
+#[derive(Serialize)]
+struct ProposalResults {
+    edits_name: String,
+    path: String,
+    /// One per seed, comparing against the baseline with the same seed
+    runs: Vec<RunComparison>,
+    across_seeds: SeedSummary,
+}
+
+impl Report {
+    fn print(&self) {
+        println!("\n{} on {}", self.scenario, self.map);
+        for proposal in &self.proposals {
+            println!("\n{} ({})", proposal.edits_name, proposal.path);
+            for run in &proposal.runs {
+                println!("  With RNG seed {}:", run.rng_seed);
+                for (mode, changes) in &run.trips {
+                    println!(
+                        "    {}: {} trips, {} faster (saved {}), {} slower (lost {}), total time \
+                         {} -> {}, cancelled {} -> {}",
+                        mode.noun(),
+                        prettyprint_usize(changes.num_trips),
+                        prettyprint_usize(changes.num_faster),
+                        changes.total_saved,
+                        prettyprint_usize(changes.num_slower),
+                        changes.total_lost,
+                        changes.total_before,
+                        changes.total_after,
+                        prettyprint_usize(changes.cancelled_before),
+                        prettyprint_usize(changes.cancelled_after)
+                    );
+                }
+                println!(
+                    "    Delay at traffic signals: {} -> {}",
+                    run.total_intersection_delay_before, run.total_intersection_delay_after
+                );
+                for (i, b, a) in run.intersection_delays.iter().take(5) {
+                    println!("      {}: {} -> {}", i, b, a);
+                }
+                println!("    Biggest changes in road throughput:");
+                for (r, b, a) in run.road_thruput.iter().take(5) {
+                    println!(
+                        "      {}: {} -> {}",
+                        r,
+                        prettyprint_usize(*b),
+                        prettyprint_usize(*a)
+                    );
+                }
+            }
+
+            if self.seeds.len() < 2 {
+                continue;
+            }
+            let summary = &proposal.across_seeds;
+            println!(
+                "  Across {} seeds, with 95% confidence intervals (durations in seconds):",
+                self.seeds.len()
+            );
+            for (mode, x) in &summary.trips {
+                println!(
+                    "    {}: mean trip time {} -> {}, change {}",
+                    mode.noun(),
+                    x.mean_trip_time_before,
+                    x.mean_trip_time_after,
+                    x.mean_trip_time_change.describe_change()
+                );
+                println!(
+                    "      {} faster, {} slower, finished trips change {}",
+                    x.num_faster,
+                    x.num_slower,
+                    x.finished_change.describe_change()
+                );
+            }
+            println!(
+                "    Delay at traffic signals: {} -> {}, change {}",
+                summary.intersection_delay_before,
+                summary.intersection_delay_after,
+                summary.intersection_delay_change.describe_change()
+            );
+        }
+        println!();
+    }
+}
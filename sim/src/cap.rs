@@ -2,8 +2,8 @@ use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Duration, Time};
-use map_model::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TurnID};
+use geom::{Duration, Speed, Time};
+use map_model::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Toll, TurnID};
 
 use crate::mechanics::IntersectionSimState;
 use crate::{CarID, SimOptions, VehicleType};
@@ -15,12 +15,14 @@ type ZoneIdx = usize;
 ///
 /// - trips passing through roads with a per-hour cap
 /// - trips passing through roads with agents currently experiencing some delay
+/// - trips entering zones with a toll
 ///
 /// Transform the trips by:
 ///
 /// - cancelling them
 /// - delaying them
 /// - rerouting them
+/// - charging them, or having the driver leave later or walk instead
 // TODO I'm not sure a single struct is the right way to manage these combinations.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct CapSimState {
@@ -29,6 +31,10 @@ pub(crate) struct CapSimState {
 
     cancel_drivers_delay_threshold: Option<Duration>,
     delay_trips_instead_of_cancelling: Option<Duration>,
+
+    lane_to_toll: BTreeMap<LaneID, usize>,
+    tolls: Vec<Toll>,
+    value_of_time_per_hour: f64,
 }
 
 pub enum CapResult {
@@ -39,6 +45,36 @@ pub enum CapResult {
     // TODO Switch modes
 }
 
+/// How a driver responded to tolls along their route
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TollDecision {
+    /// Drove the original route, paying this many dollars
+    Paid(f64),
+    /// Drove a different route to avoid the toll, paying this many dollars along it
+    Rerouted(f64),
+    /// Left later, when the toll would be cheaper
+    Retimed(Duration),
+    /// Walked instead of driving
+    Walked,
+}
+
+impl TollDecision {
+    /// Dollars actually paid
+    pub fn toll_paid(&self) -> f64 {
+        match self {
+            TollDecision::Paid(dollars) | TollDecision::Rerouted(dollars) => *dollars,
+            TollDecision::Retimed(_) | TollDecision::Walked => 0.0,
+        }
+    }
+}
+
+/// What a driver facing a toll does before leaving
+pub enum BeforeLeaving {
+    Go,
+    WaitUntil(Time),
+    Walk,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct Zone {
     cap: usize,
@@ -54,8 +90,20 @@ impl CapSimState {
             zones: Vec::new(),
             cancel_drivers_delay_threshold: opts.cancel_drivers_delay_threshold.clone(),
             delay_trips_instead_of_cancelling: opts.delay_trips_instead_of_cancelling.clone(),
+            lane_to_toll: BTreeMap::new(),
+            tolls: Vec::new(),
+            value_of_time_per_hour: opts.value_of_time_per_hour,
         };
         for z in map.all_zones() {
+            if let Some(ref toll) = z.restrictions.toll {
+                let idx = sim.tolls.len();
+                for r in &z.members {
+                    for l in map.get_r(*r).all_lanes() {
+                        sim.lane_to_toll.insert(l, idx);
+                    }
+                }
+                sim.tolls.push(toll.clone());
+            }
             if let Some(cap) = z.restrictions.cap_vehicles_per_hour {
                 let idx = sim.zones.len();
                 for r in &z.members {
@@ -112,7 +160,11 @@ impl CapSimState {
                 avoid_lanes.insert(*l);
             }
         }
-        match map.pathfind_avoiding_lanes(req.clone(), avoid_lanes) {
+        // Avoiding lanes doesn't obey restrictions that only apply at certain times
+        match map
+            .pathfind_avoiding_lanes(req.clone(), avoid_lanes)
+            .filter(|path| !path.breaks_turn_restrictions_at(req.constraints, now, map))
+        {
             Some(path) => CapResult::Reroute(path),
             None => {
                 if let Some(delay) = self.delay_trips_instead_of_cancelling {
@@ -164,6 +216,179 @@ impl CapSimState {
     }
 }
 
+// Specific to tolls. Drivers weigh the toll against the extra time other choices take, each
+// valuing their time differently.
+impl CapSimState {
+    pub fn has_tolls(&self) -> bool {
+        !self.tolls.is_empty()
+    }
+
+    /// Before a driver leaves, decide if they should go now, wait for the toll to be cheaper, or
+    /// walk instead. `walk_time` is how long walking would take, if that's possible.
+    pub fn before_leaving(
+        &self,
+        req: &PathRequest,
+        car: CarID,
+        walk_time: Option<Duration>,
+        now: Time,
+        map: &Map,
+    ) -> BeforeLeaving {
+        if self.tolls.is_empty() {
+            return BeforeLeaving::Go;
+        }
+        let path = match map.pathfind_at_time(req.clone(), now) {
+            Some(path) => path,
+            None => {
+                return BeforeLeaving::Go;
+            }
+        };
+        if self.toll_for_path(&path, req.constraints, now, map) <= 0.0 {
+            return BeforeLeaving::Go;
+        }
+
+        let mut best = (
+            self.cheapest_route(req, path.clone(), now, car, map).2,
+            BeforeLeaving::Go,
+        );
+        let drive_time = estimate_duration(&path, map);
+        // Waiting around to leave later isn't as bad as sitting in traffic.
+        for depart in self.cheaper_departures(now) {
+            let toll = self.toll_for_path(&path, req.constraints, depart, map);
+            let cost = self.cost(car, toll, drive_time + (depart - now) * 0.5);
+            if cost < best.0 {
+                best = (cost, BeforeLeaving::WaitUntil(depart));
+            }
+        }
+        if let Some(walk_time) = walk_time {
+            let cost = self.cost(car, 0.0, walk_time);
+            if cost < best.0 {
+                best = (cost, BeforeLeaving::Walk);
+            }
+        }
+        best.1
+    }
+
+    /// Right as a driver starts, decide whether to pay any tolls along the path or take a
+    /// different route.
+    pub fn pay_or_reroute(
+        &self,
+        req: &PathRequest,
+        path: Path,
+        now: Time,
+        car: CarID,
+        map: &Map,
+    ) -> (Path, Option<TollDecision>) {
+        if self.tolls.is_empty() {
+            return (path, None);
+        }
+        let (path, decision, _) = self.cheapest_route(req, path, now, car, map);
+        (path, decision)
+    }
+
+    /// Returns the path, what happened with the toll, and the total cost of the trip.
+    fn cheapest_route(
+        &self,
+        req: &PathRequest,
+        path: Path,
+        now: Time,
+        car: CarID,
+        map: &Map,
+    ) -> (Path, Option<TollDecision>, f64) {
+        let toll = self.toll_for_path(&path, req.constraints, now, map);
+        let cost = self.cost(car, toll, estimate_duration(&path, map));
+        if toll <= 0.0 {
+            return (path, None, cost);
+        }
+
+        if let Some(detour) = map.pathfind_avoiding_tolls(req.clone(), now) {
+            // Starting or ending in a tolled zone might still cost something.
+            let detour_toll = self.toll_for_path(&detour, req.constraints, now, map);
+            let detour_cost = self.cost(car, detour_toll, estimate_duration(&detour, map));
+            if detour_cost < cost {
+                return (
+                    detour,
+                    Some(TollDecision::Rerouted(detour_toll)),
+                    detour_cost,
+                );
+            }
+        }
+        (path, Some(TollDecision::Paid(toll)), cost)
+    }
+
+    /// Dollars for entering all of the zones along a path, starting at some time. Starting inside a
+    /// zone is free.
+    fn toll_for_path(
+        &self,
+        path: &Path,
+        constraints: PathConstraints,
+        start: Time,
+        map: &Map,
+    ) -> f64 {
+        let mut total = 0.0;
+        let mut time = start;
+        let mut current_zone = None;
+        let mut first = true;
+        for step in path.get_steps() {
+            if let PathStep::Lane(l) = step {
+                let zone = self.lane_to_toll.get(l).cloned();
+                if let Some(idx) = zone {
+                    if !first && current_zone != zone {
+                        total += self.tolls[idx].price(constraints, time);
+                    }
+                }
+                current_zone = zone;
+                first = false;
+            }
+            time += estimate_step_duration(step, map);
+        }
+        total
+    }
+
+    /// Times in the next few hours when some toll changes price
+    fn cheaper_departures(&self, now: Time) -> Vec<Time> {
+        let max_wait = Duration::hours(3);
+        let day = Duration::hours(24);
+        let start_of_today =
+            Time::START_OF_DAY + day * (now.inner_seconds() / day.inner_seconds()).floor();
+        let mut times = Vec::new();
+        for toll in &self.tolls {
+            for time_of_day in toll.price_changes() {
+                let mut t = start_of_today + (time_of_day - Time::START_OF_DAY);
+                if t <= now {
+                    t += day;
+                }
+                if t - now <= max_wait {
+                    times.push(t);
+                }
+            }
+        }
+        times
+    }
+
+    /// Drivers value their time somewhere between half and one-and-a-half times the default.
+    fn cost(&self, car: CarID, toll: f64, time: Duration) -> f64 {
+        let value_of_time = self.value_of_time_per_hour * (0.5 + ((car.0 % 100) as f64) / 100.0);
+        toll + value_of_time * time.inner_seconds() / 3600.0
+    }
+}
+
+/// Ignores traffic; just how long a path takes at the speed limit.
+fn estimate_duration(path: &Path, map: &Map) -> Duration {
+    path.get_steps()
+        .iter()
+        .map(|step| estimate_step_duration(step, map))
+        .sum()
+}
+
+fn estimate_step_duration(step: &PathStep, map: &Map) -> Duration {
+    let t = step.as_traversable();
+    let speed = t.speed_limit(map);
+    if speed <= Speed::ZERO {
+        return Duration::ZERO;
+    }
+    t.length(map) / speed
+}
+
 // Specific to the don't-exceed-delay mechanism
 impl CapSimState {
     fn path_crosses_delay(
@@ -6,7 +6,7 @@ use map_model::{
     PathRequest, Traversable, TurnID,
 };
 
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, TripID, TripMode};
+use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, TollDecision, TripID, TripMode};
 
 /// As a simulation runs, different systems emit Events. This cleanly separates the internal
 /// mechanics of the simulation from consumers that just want to know what's happening.
@@ -59,6 +59,9 @@ pub enum Event {
     /// to plumb info into Analytics is Event.
     PathAmended(Path),
 
+    /// A driver facing a toll decided what to do about it
+    TollDecision(TripID, TollDecision),
+
     Alert(AlertLocation, String),
 }
 
@@ -97,7 +100,10 @@ impl TripPhaseType {
             TripPhaseType::RidingBus(r, _, _) => format!("Riding bus {}", map.get_br(r).full_name),
             TripPhaseType::Cancelled => "Trip was cancelled due to some bug".to_string(),
             TripPhaseType::Finished => "Trip finished".to_string(),
-            TripPhaseType::DelayedStart => "Delayed by a previous trip taking too long".to_string(),
+            TripPhaseType::DelayedStart => {
+                "Delayed by a previous trip taking too long, or waiting for a toll to get cheaper"
+                    .to_string()
+            }
         }
     }
 }
@@ -30,19 +30,24 @@ pub use crate::render::{
 
 pub use self::analytics::{Analytics, TripPhase};
 pub(crate) use self::cap::CapSimState;
+pub use self::cap::TollDecision;
 pub(crate) use self::events::Event;
 pub use self::events::{AlertLocation, TripPhaseType};
 pub(crate) use self::make::TripSpec;
 pub use self::make::{
-    BorderSpawnOverTime, ExternalPerson, ExternalTrip, ExternalTripEndpoint, IndividTrip,
-    PersonSpec, Scenario, ScenarioGenerator, ScenarioModifier, SimFlags, SpawnOverTime,
-    TripEndpoint, TripPurpose,
+    BorderSpawnOverTime, DepartureProfile, ExternalPerson, ExternalTrip, ExternalTripEndpoint,
+    IndividTrip, ODEntry, ODMatrix, ODZone, PersonSpec, Scenario, ScenarioGenerator,
+    ScenarioModifier, SimFlags, SpawnOverTime, TripEndpoint, TripPurpose,
 };
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
 };
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::recorder::TrafficRecorder;
+pub use self::replicate::{
+    run_with_seeds, RunComparison, RunSummary, SeedRuns, SeedSummary, TripTimeChanges,
+    TripTimeEstimates,
+};
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{AgentProperties, AlertHandler, DelayCause, Sim, SimCallback, SimOptions};
@@ -59,6 +64,7 @@ mod mechanics;
 mod pandemic;
 mod recorder;
 mod render;
+mod replicate;
 mod router;
 mod scheduler;
 mod sim;
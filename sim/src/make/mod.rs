@@ -8,6 +8,7 @@ pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint};
 pub use self::generator::{BorderSpawnOverTime, ScenarioGenerator, SpawnOverTime};
 pub use self::load::SimFlags;
 pub use self::modifier::ScenarioModifier;
+pub use self::od::{DepartureProfile, ODEntry, ODMatrix, ODZone};
 pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
 pub use self::spawner::TripEndpoint;
 pub(crate) use self::spawner::TripSpec;
@@ -17,6 +18,7 @@ mod external;
 mod generator;
 mod load;
 mod modifier;
+mod od;
 mod scenario;
 mod spawner;
 
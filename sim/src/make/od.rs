@@ -0,0 +1,260 @@
+//! Travel demand models often describe trips as an origin-destination matrix between zones,
+//! instead of individual people. Turn that into individual trips between buildings and borders.
+
+use std::collections::BTreeMap;
+
+use aabb_quadtree::QuadTree;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde::Deserialize;
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Polygon, Time};
+use map_model::{BuildingID, BuildingType, Intersection, Map};
+
+use crate::{IndividTrip, PersonSpec, TripEndpoint, TripMode, TripPurpose};
+
+/// An origin-destination matrix, plus the zones it refers to.
+pub struct ODMatrix {
+    pub zones: Vec<ODZone>,
+    pub entries: Vec<ODEntry>,
+    pub profile: DepartureProfile,
+}
+
+/// A zone, like a census tract or traffic analysis zone. Zones don't have to be inside the map;
+/// trips to or from zones outside of it use the closest border.
+pub struct ODZone {
+    pub name: String,
+    pub polygon: Polygon,
+}
+
+/// Some number of trips between two zones, using one mode, departing during one period of the
+/// day.
+pub struct ODEntry {
+    pub origin: String,
+    pub destination: String,
+    pub mode: TripMode,
+    pub purpose: TripPurpose,
+    pub period: String,
+    /// Fractional trips are rounded randomly.
+    pub count: f64,
+}
+
+/// When trips depart. Each period, like "AM peak", lists the relative number of trips departing
+/// during different hours of the day, as (hour, weight). Departures are spread uniformly within
+/// each hour.
+#[derive(Deserialize)]
+pub struct DepartureProfile {
+    pub periods: BTreeMap<String, Vec<(usize, f64)>>,
+}
+
+impl DepartureProfile {
+    /// A single period, "all day", with departures spread evenly over 24 hours.
+    pub fn all_day() -> DepartureProfile {
+        let mut periods = BTreeMap::new();
+        periods.insert("all day".to_string(), (0..24).map(|hr| (hr, 1.0)).collect());
+        DepartureProfile { periods }
+    }
+}
+
+/// Where trips in one zone can start or end, and how likely each one is.
+struct ZoneEndpoints {
+    origins: Vec<TripEndpoint>,
+    origin_weights: WeightedIndex<f64>,
+    destinations: Vec<TripEndpoint>,
+    destination_weights: WeightedIndex<f64>,
+}
+
+impl ODMatrix {
+    /// Each trip becomes a separate person. Within a zone, buildings are picked in proportion to
+    /// the people living or working there, and borders inside the zone are also used.
+    pub fn disaggregate(
+        self,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> Result<Vec<PersonSpec>, String> {
+        let mut departures: BTreeMap<String, (Vec<usize>, WeightedIndex<f64>)> = BTreeMap::new();
+        for (period, hours) in self.profile.periods {
+            if let Some((hr, _)) = hours.iter().find(|(hr, _)| *hr >= 24) {
+                return Err(format!("Period {} has a departure hour of {}", period, hr));
+            }
+            let weights = WeightedIndex::new(hours.iter().map(|(_, w)| *w))
+                .map_err(|err| format!("Bad weights for period {}: {}", period, err))?;
+            departures.insert(
+                period,
+                (hours.into_iter().map(|(hr, _)| hr).collect(), weights),
+            );
+        }
+
+        // Zones are usually much smaller than the map, so only check the buildings near each one
+        let mut quadtree: QuadTree<BuildingID> = QuadTree::default(map.get_bounds().as_bbox());
+        for b in map.all_buildings() {
+            quadtree.insert_with_box(b.id, b.polygon.get_bounds().as_bbox());
+        }
+        let borders: Vec<&Intersection> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_border())
+            .collect();
+
+        let mut zones: BTreeMap<String, ZoneEndpoints> = BTreeMap::new();
+        timer.start_iter("find endpoints in each zone", self.zones.len());
+        for zone in self.zones {
+            timer.next();
+            let endpoints = zone_endpoints(map, &quadtree, &borders, &zone)
+                .ok_or_else(|| format!("Zone {} has no buildings or borders nearby", zone.name))?;
+            zones.insert(zone.name, endpoints);
+        }
+
+        let mut people = Vec::new();
+        let mut skipped = 0;
+        timer.start_iter("disaggregate trips", self.entries.len());
+        for entry in self.entries {
+            timer.next();
+            let from = zones
+                .get(&entry.origin)
+                .ok_or_else(|| format!("Unknown origin zone {}", entry.origin))?;
+            let to = zones
+                .get(&entry.destination)
+                .ok_or_else(|| format!("Unknown destination zone {}", entry.destination))?;
+            let (hours, hour_weights) = departures
+                .get(&entry.period)
+                .ok_or_else(|| format!("Period {} isn't in the departure profile", entry.period))?;
+            if entry.count < 0.0 || !entry.count.is_finite() {
+                return Err(format!(
+                    "{} trips from {} to {}",
+                    entry.count, entry.origin, entry.destination
+                ));
+            }
+
+            let mut count = entry.count.floor() as usize;
+            if rng.gen_bool(entry.count - entry.count.floor()) {
+                count += 1;
+            }
+            for _ in 0..count {
+                let origin = from.origins[from.origin_weights.sample(rng)].clone();
+                // Trips within one zone shouldn't start and end at the same place. Give up after
+                // a few tries, since a zone might have only one building.
+                let destination = (0..10)
+                    .map(|_| to.destinations[to.destination_weights.sample(rng)].clone())
+                    .find(|endpt| *endpt != origin);
+                let destination = if let Some(endpt) = destination {
+                    endpt
+                } else {
+                    skipped += 1;
+                    continue;
+                };
+
+                let hour = hours[hour_weights.sample(rng)];
+                let depart = Time::START_OF_DAY
+                    + Duration::hours(hour)
+                    + Duration::seconds(rng.gen_range(0.0, 3600.0));
+                people.push(PersonSpec {
+                    orig_id: None,
+                    origin,
+                    trips: vec![IndividTrip::new(
+                        depart,
+                        entry.purpose,
+                        destination,
+                        entry.mode,
+                    )],
+                });
+            }
+        }
+        timer.note(format!(
+            "Disaggregated into {} trips. Skipped {} trips that would start and end at the same \
+             place.",
+            prettyprint_usize(people.len()),
+            prettyprint_usize(skipped)
+        ));
+        Ok(people)
+    }
+}
+
+fn zone_endpoints(
+    map: &Map,
+    quadtree: &QuadTree<BuildingID>,
+    borders: &[&Intersection],
+    zone: &ODZone,
+) -> Option<ZoneEndpoints> {
+    let mut origins = Vec::new();
+    let mut origin_weights = Vec::new();
+    let mut destinations = Vec::new();
+    let mut destination_weights = Vec::new();
+
+    // Sort, so the order doesn't depend on the quadtree
+    let mut bldgs: Vec<BuildingID> = quadtree
+        .query(zone.polygon.get_bounds().as_bbox())
+        .into_iter()
+        .map(|(b, _, _)| *b)
+        .collect();
+    bldgs.sort();
+    for b in bldgs {
+        let b = map.get_b(b);
+        if !zone.polygon.contains_pt(b.label_center) {
+            continue;
+        }
+        // Buildings where nobody lives or works still occasionally get visited
+        let weight = 1.0
+            + match b.bldg_type {
+                BuildingType::Residential { num_residents, .. } => num_residents,
+                BuildingType::ResidentialCommercial(residents, workers) => residents + workers,
+                BuildingType::Commercial(workers) => workers,
+                BuildingType::Empty => 0,
+            } as f64;
+        origins.push(TripEndpoint::Bldg(b.id));
+        origin_weights.push(weight);
+        destinations.push(TripEndpoint::Bldg(b.id));
+        destination_weights.push(weight);
+    }
+
+    // Borders inside the zone represent traffic passing through it. Weight them like a busy
+    // building.
+    let border_weight = origin_weights.iter().cloned().fold(1.0, f64::max);
+    for i in borders {
+        if !zone.polygon.contains_pt(i.polygon.center()) {
+            continue;
+        }
+        if i.is_incoming_border() {
+            origins.push(TripEndpoint::Border(i.id));
+            origin_weights.push(border_weight);
+        }
+        if i.is_outgoing_border() {
+            destinations.push(TripEndpoint::Border(i.id));
+            destination_weights.push(border_weight);
+        }
+    }
+
+    // Zones outside the map are reached through the closest border.
+    let center = zone.polygon.center();
+    let closest_border = |incoming: bool| -> Option<TripEndpoint> {
+        borders
+            .iter()
+            .filter(|i| {
+                if incoming {
+                    i.is_incoming_border()
+                } else {
+                    i.is_outgoing_border()
+                }
+            })
+            .min_by_key(|i| center.dist_to(i.polygon.center()))
+            .map(|i| TripEndpoint::Border(i.id))
+    };
+    if origins.is_empty() {
+        origins.push(closest_border(true)?);
+        origin_weights.push(1.0);
+    }
+    if destinations.is_empty() {
+        destinations.push(closest_border(false)?);
+        destination_weights.push(1.0);
+    }
+
+    Some(ZoneEndpoints {
+        origins,
+        origin_weights: WeightedIndex::new(origin_weights).ok()?,
+        destinations,
+        destination_weights: WeightedIndex::new(destination_weights).ok()?,
+    })
+}
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use abstutil::{prettyprint_usize, Counter, MapName, Parallelism, Timer};
 use geom::{Distance, Speed, Time};
-use map_model::{BuildingID, Map, OffstreetParking, RoadID};
+use map_model::{BuildingID, IDRemap, Map, OffstreetParking, RoadID};
 
 use crate::make::fork_rng;
 use crate::{
@@ -285,6 +285,50 @@ impl Scenario {
         per_bldg
     }
 
+    /// Translates a scenario from an old version of the map to a new one, after re-importing.
+    /// Trips going somewhere that no longer exists, or to a border that isn't a border anymore,
+    /// are dropped. Since each trip starts where the previous one ended, the rest of that person's
+    /// trips are dropped too. People starting somewhere like that are removed.
+    pub fn remap_ids(mut self, remap: &IDRemap) -> Scenario {
+        let count_trips =
+            |people: &Vec<PersonSpec>| -> usize { people.iter().map(|p| p.trips.len()).sum() };
+        let orig_people = self.people.len();
+        let orig_trips = count_trips(&self.people);
+        self.people = self
+            .people
+            .into_iter()
+            .filter_map(|mut person| {
+                person.origin = person.origin.remap_ids(remap)?;
+                let mut trips = Vec::new();
+                for mut trip in person.trips {
+                    match trip.destination.remap_ids(remap) {
+                        Some(destination) => {
+                            trip.destination = destination;
+                            trips.push(trip);
+                        }
+                        None => {
+                            break;
+                        }
+                    }
+                }
+                if trips.is_empty() {
+                    return None;
+                }
+                person.trips = trips;
+                Some(person)
+            })
+            .collect();
+        println!(
+            "{} of {} people and {} of {} trips dropped, because they visit somewhere that no \
+             longer exists in the new map",
+            prettyprint_usize(orig_people - self.people.len()),
+            prettyprint_usize(orig_people),
+            prettyprint_usize(orig_trips - count_trips(&self.people)),
+            prettyprint_usize(orig_trips)
+        );
+        self
+    }
+
     pub fn remove_weird_schedules(mut self) -> Scenario {
         let orig = self.people.len();
         self.people.retain(|person| match person.check_schedule() {
@@ -6,7 +6,8 @@ use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    BuildingID, BusRouteID, BusStopID, IDRemap, IntersectionID, Map, PathConstraints, PathRequest,
+    Position,
 };
 
 use crate::{
@@ -306,6 +307,18 @@ pub enum TripEndpoint {
 }
 
 impl TripEndpoint {
+    /// Translates an endpoint from an old version of the map to a new one, if it still exists.
+    /// Borders must still be borders.
+    pub fn remap_ids(&self, remap: &IDRemap) -> Option<TripEndpoint> {
+        match self {
+            TripEndpoint::Bldg(b) => remap.building(*b).map(TripEndpoint::Bldg),
+            TripEndpoint::Border(i) => remap.border(*i).map(TripEndpoint::Border),
+            TripEndpoint::SuddenlyAppear(pos) => {
+                remap.position(*pos).map(TripEndpoint::SuddenlyAppear)
+            }
+        }
+    }
+
     pub fn path_req(
         from: TripEndpoint,
         to: TripEndpoint,
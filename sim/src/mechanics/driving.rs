@@ -136,6 +136,7 @@ impl DrivingSimState {
                         params.start_dist,
                         &car.vehicle,
                         ctx.parking,
+                        now,
                         ctx.map,
                         car.trip_and_person,
                         &mut self.events,
@@ -313,6 +314,7 @@ impl DrivingSimState {
                         front,
                         &car.vehicle,
                         ctx.parking,
+                        now,
                         ctx.map,
                         car.trip_and_person,
                         &mut self.events,
@@ -413,6 +415,7 @@ impl DrivingSimState {
                 let last_step = car.router.advance(
                     &car.vehicle,
                     ctx.parking,
+                    now,
                     ctx.map,
                     car.trip_and_person,
                     &mut self.events,
@@ -485,6 +488,7 @@ impl DrivingSimState {
                     our_dist,
                     &car.vehicle,
                     ctx.parking,
+                    now,
                     ctx.map,
                     car.trip_and_person,
                     &mut self.events,
@@ -1064,6 +1068,27 @@ impl DrivingSimState {
         affected
     }
 
+    /// Some turn restrictions or modal filters just started applying. Reroute vehicles that would
+    /// break them, unless they're already committed to doing so.
+    pub fn reroute_around_restrictions(&mut self, now: Time, map: &Map) {
+        for car in self.cars.values_mut() {
+            let committed = match car.state {
+                CarState::Crossing(_, _) | CarState::Unparking(_, _, _) => false,
+                CarState::Queued { .. } | CarState::WaitingToAdvance { .. } => true,
+                CarState::Parking(_, _, _) | CarState::IdlingAtStop(_, _) => {
+                    continue;
+                }
+            };
+            if car
+                .router
+                .reroute_around_restrictions(committed, &car.vehicle, now, map)
+            {
+                self.events
+                    .push(Event::PathAmended(car.router.get_path().clone()));
+            }
+        }
+    }
+
     /// Finds vehicles that're laggy heads on affected parts of the map.
     pub fn find_vehicles_affected_by_live_edits(
         &self,
@@ -7,17 +7,21 @@ use abstutil::{
 };
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, PhaseType,
-    Traversable, TurnID, TurnPriority, TurnType, UberTurn,
+    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, LaneType, Map,
+    PhaseType, SignType, Traversable, Turn, TurnID, TurnPriority, TurnType, UberTurn,
 };
 
 use crate::mechanics::car::Car;
 use crate::mechanics::Queue;
 use crate::{
     AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    VehicleType,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
+/// Vehicles yielding to traffic with priority need at least this much time before that traffic
+/// arrives.
+const CRITICAL_GAP: Duration = Duration::const_seconds(4.0);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
 
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
@@ -47,6 +51,10 @@ pub(crate) struct IntersectionSimState {
     total_repeat_requests: usize,
     not_allowed_requests: usize,
     blocked_by_someone_requests: usize,
+
+    // How many times did transit signal priority change a signal's timing?
+    transit_priority_extensions: usize,
+    transit_priority_early_terminations: usize,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -72,6 +80,11 @@ struct State {
 struct SignalState {
     current_stage: usize,
     stage_ends_at: Time,
+    // Usually stage_ends_at minus the stage's duration, but transit signal priority can stretch or
+    // shrink a stage.
+    stage_started_at: Time,
+    // Transit signal priority may only adjust each stage once.
+    transit_priority_used: bool,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -96,6 +109,9 @@ impl IntersectionSimState {
             total_repeat_requests: 0,
             not_allowed_requests: 0,
             blocked_by_someone_requests: 0,
+
+            transit_priority_extensions: 0,
+            transit_priority_early_terminations: 0,
         };
         if sim.disable_turn_conflicts {
             sim.use_freeform_policy_everywhere = true;
@@ -194,7 +210,7 @@ impl IntersectionSimState {
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
             let stage = &signal.stages[self.state[&i].signal.as_ref().unwrap().current_stage];
             for (req, _) in all {
-                match stage.get_priority_of_turn(req.turn, signal) {
+                match stage.get_priority_of_turn(req.turn, signal, map) {
                     TurnPriority::Protected => {
                         protected.push(req);
                     }
@@ -267,7 +283,7 @@ impl IntersectionSimState {
                 // could pass in all of the Queues here and use that to count all incoming agents,
                 // even ones a little farther away.
                 if state.waiting.keys().all(|req| {
-                    old_stage.get_priority_of_turn(req.turn, signal) != TurnPriority::Protected
+                    old_stage.get_priority_of_turn(req.turn, signal, map) != TurnPriority::Protected
                 }) {
                     signal_state.current_stage += 1;
                     self.events.push(Event::Alert(
@@ -281,6 +297,8 @@ impl IntersectionSimState {
             signal_state.current_stage = 0;
         }
 
+        signal_state.stage_started_at = now;
+        signal_state.transit_priority_used = false;
         signal_state.stage_ends_at = now
             + signal.stages[signal_state.current_stage]
                 .phase_type
@@ -362,7 +380,7 @@ impl IntersectionSimState {
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(turn.parent) {
             self.traffic_signal_policy(&req, map, signal, speed, now, Some(scheduler))
         } else if let Some(ref sign) = map.maybe_get_stop_sign(turn.parent) {
-            self.stop_sign_policy(&req, map, sign, now, scheduler)
+            self.stop_sign_policy(&req, map, sign, now, scheduler, readonly_pair)
         } else {
             unreachable!()
         };
@@ -628,6 +646,11 @@ impl IntersectionSimState {
                     / (self.total_repeat_requests as f64))
                     .round()
             ),
+            format!(
+                "transit signal priority extended {} stages and ended {} stages early",
+                prettyprint_usize(self.transit_priority_extensions),
+                prettyprint_usize(self.transit_priority_early_terminations)
+            ),
         ]
     }
 
@@ -697,11 +720,42 @@ impl IntersectionSimState {
         sign: &ControlStopSign,
         now: Time,
         scheduler: &mut Scheduler,
+        readonly_pair: Option<(&FixedMap<CarID, Car>, &HashMap<Traversable, Queue>)>,
     ) -> bool {
         let our_priority = sign.get_priority(req.turn, map);
         assert!(our_priority != TurnPriority::Banned);
         let our_time = self.state[&req.turn.parent].waiting[req];
 
+        // Yield signs (including entering a roundabout) don't require stopping first, just
+        // waiting for a big enough gap in traffic with priority.
+        if our_priority == TurnPriority::Yield
+            && sign.get_sign_type(req.turn, map) == SignType::Yield
+        {
+            let turn = map.get_t(req.turn);
+            // Anybody with priority who's already waiting to go gets to go first. They'll wake us
+            // up after finishing their turn.
+            if self.state[&req.turn.parent].waiting.keys().any(|other| {
+                sign.get_priority(other.turn, map) == TurnPriority::Protected
+                    && map.get_t(other.turn).conflicts_with(turn)
+            }) {
+                return false;
+            }
+            if let Some((cars, queues)) = readonly_pair {
+                if let Some(eta) = approaching_priority_traffic(turn, map, sign, now, cars, queues)
+                {
+                    // Try again once they've had a chance to reach the intersection.
+                    let retry = if eta < WAIT_AT_STOP_SIGN {
+                        WAIT_AT_STOP_SIGN
+                    } else {
+                        eta
+                    };
+                    scheduler.push(now + retry, Command::update_agent(req.agent));
+                    return false;
+                }
+            }
+            return true;
+        }
+
         if our_priority == TurnPriority::Yield && now < our_time + WAIT_AT_STOP_SIGN {
             // Since we have "ownership" of scheduling for req.agent, don't need to use
             // scheduler.update.
@@ -750,11 +804,30 @@ impl IntersectionSimState {
         let our_time = state.waiting[req];
 
         // Can't go at all this stage.
-        let our_priority = stage.get_priority_of_turn(req.turn, signal);
+        let our_priority = stage.get_priority_of_turn(req.turn, signal, map);
         if our_priority == TurnPriority::Banned {
+            // But maybe a bus can cut the stage short.
+            if let Some(s) = scheduler {
+                self.transit_priority_early_termination(req, signal, map, now, s);
+            }
             return false;
         }
 
+        // Give buses in a bus lane a head start.
+        if let Some(head_start) = signal
+            .transit_priority
+            .as_ref()
+            .and_then(|tsp| tsp.queue_jump)
+        {
+            let jump_ends_at = signal_state.stage_started_at + head_start;
+            if now < jump_ends_at && waits_for_queue_jump(req, state.waiting.keys(), map) {
+                if let Some(s) = scheduler {
+                    s.push(jump_ends_at, Command::update_agent(req.agent));
+                }
+                return false;
+            }
+        }
+
         if our_priority == TurnPriority::Yield
             && now < our_time + WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL
         {
@@ -791,7 +864,10 @@ impl IntersectionSimState {
                         req, full_stage_duration
                     ),
                 ));
-            } else {
+            } else if !scheduler
+                .map(|s| self.transit_priority_extension(req, signal, now + time_to_cross, s))
+                .unwrap_or(false)
+            {
                 return false;
             }
         }
@@ -799,6 +875,91 @@ impl IntersectionSimState {
         true
     }
 
+    // A bus won't make it through before the stage ends. If transit signal priority allows, hold
+    // the green until the bus finishes the turn. Returns true if the stage was extended.
+    fn transit_priority_extension(
+        &mut self,
+        req: &Request,
+        signal: &ControlTrafficSignal,
+        finish_turn_at: Time,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let tsp = match signal.transit_priority {
+            Some(ref tsp) if is_bus(req.agent) => tsp,
+            _ => {
+                return false;
+            }
+        };
+        let signal_state = self
+            .state
+            .get_mut(&req.turn.parent)
+            .unwrap()
+            .signal
+            .as_mut()
+            .unwrap();
+        if signal_state.transit_priority_used
+            || finish_turn_at > signal_state.stage_ends_at + tsp.max_green_extension
+        {
+            return false;
+        }
+        signal_state.stage_ends_at = finish_turn_at;
+        signal_state.transit_priority_used = true;
+        scheduler.update(finish_turn_at, Command::UpdateIntersection(req.turn.parent));
+        self.transit_priority_extensions += 1;
+        true
+    }
+
+    // A bus is stuck at a red light. If transit signal priority allows, end the current stage
+    // early. The bus still has to wait for a stage that lets it go; this just gets there sooner.
+    fn transit_priority_early_termination(
+        &mut self,
+        req: &Request,
+        signal: &ControlTrafficSignal,
+        map: &Map,
+        now: Time,
+        scheduler: &mut Scheduler,
+    ) {
+        let tsp = match signal.transit_priority {
+            Some(ref tsp) if is_bus(req.agent) => tsp,
+            _ => {
+                return;
+            }
+        };
+        // Is there any stage where the bus can go?
+        if signal
+            .stages
+            .iter()
+            .all(|stage| stage.get_priority_of_turn(req.turn, signal, map) == TurnPriority::Banned)
+        {
+            return;
+        }
+        let signal_state = self
+            .state
+            .get_mut(&req.turn.parent)
+            .unwrap()
+            .signal
+            .as_mut()
+            .unwrap();
+        if signal_state.transit_priority_used {
+            return;
+        }
+        // Never cut off pedestrians who started crossing at the beginning of the stage.
+        let earliest_end = signal_state.stage_started_at
+            + signal.get_min_crossing_time(signal_state.current_stage);
+        let mut shorten_by = signal_state.stage_ends_at - now.max(earliest_end);
+        if shorten_by > tsp.max_early_termination {
+            shorten_by = tsp.max_early_termination;
+        }
+        if shorten_by <= Duration::ZERO {
+            return;
+        }
+        let new_end = signal_state.stage_ends_at - shorten_by;
+        signal_state.stage_ends_at = new_end;
+        signal_state.transit_priority_used = true;
+        scheduler.update(new_end, Command::UpdateIntersection(req.turn.parent));
+        self.transit_priority_early_terminations += 1;
+    }
+
     // If true, the request can go.
     fn handle_accepted_conflicts(
         &mut self,
@@ -900,6 +1061,10 @@ impl SignalState {
         let mut state = SignalState {
             current_stage: 0,
             stage_ends_at: now,
+            // We might be starting in the middle of a stage, but this is only used by queue jumps,
+            // so just pretend the stage is starting now.
+            stage_started_at: now,
+            transit_priority_used: false,
         };
 
         let signal = map.get_traffic_signal(id);
@@ -925,6 +1090,80 @@ impl SignalState {
     }
 }
 
+fn is_bus(agent: AgentID) -> bool {
+    match agent {
+        AgentID::Car(car) => car.1 == VehicleType::Bus,
+        _ => false,
+    }
+}
+
+// Is this request from a vehicle that has to let a bus waiting in a bus lane on the same approach
+// go first? The queue jump is actuated, so nobody is held up when no bus is there.
+fn waits_for_queue_jump<'a, I: Iterator<Item = &'a Request>>(
+    req: &Request,
+    mut waiting: I,
+    map: &Map,
+) -> bool {
+    match req.agent {
+        AgentID::Car(car) if car.1 != VehicleType::Bus => {}
+        _ => {
+            return false;
+        }
+    }
+    let src = map.get_l(req.turn.src);
+    if src.is_bus() {
+        return false;
+    }
+    waiting.any(|other| {
+        let lane = map.get_l(other.turn.src);
+        is_bus(other.agent) && lane.is_bus() && lane.parent == src.parent
+    })
+}
+
+// If a vehicle with priority will reach the intersection soon and make a turn conflicting with this
+// one, returns how long until they arrive.
+fn approaching_priority_traffic(
+    turn: &Turn,
+    map: &Map,
+    sign: &ControlStopSign,
+    now: Time,
+    cars: &FixedMap<CarID, Car>,
+    queues: &HashMap<Traversable, Queue>,
+) -> Option<Duration> {
+    for l in &map.get_i(turn.id.parent).incoming_lanes {
+        let lane = map.get_l(*l);
+        if !lane.lane_type.is_for_moving_vehicles()
+            || sign.roads.get(&lane.parent).map(|ss| ss.sign_type) != Some(SignType::NoSign)
+        {
+            continue;
+        }
+        let queue = match queues.get(&Traversable::Lane(*l)) {
+            Some(q) => q,
+            None => continue,
+        };
+        let speed_limit = map.get_r(lane.parent).speed_limit;
+        // Farthest along is first, so the first vehicle that's too far away means the rest are
+        // too
+        for (car, dist) in queue.get_car_positions(now, cars, queues) {
+            let speed = cars[&car]
+                .vehicle
+                .max_speed
+                .map(|s| s.min(speed_limit))
+                .unwrap_or(speed_limit);
+            let eta = (queue.geom_len - dist) / speed;
+            if eta > CRITICAL_GAP {
+                break;
+            }
+            if let Some(Traversable::Turn(t)) = cars[&car].router.maybe_next() {
+                if map.get_t(t).conflicts_with(turn) {
+                    return Some(eta);
+                }
+            }
+        }
+    }
+    None
+}
+
 fn allow_block_the_box(i: &Intersection) -> bool {
     // Degenerate intersections are often just artifacts of how roads are split up in OSM. Allow
     // vehicles to get stuck in them, since the only possible thing they could block is pedestrians
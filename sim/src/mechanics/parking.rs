@@ -7,7 +7,7 @@ use abstutil::{
     deserialize_btreemap, deserialize_multimap, retain_btreemap, serialize_btreemap,
     serialize_multimap, MultiMap, Timer,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, PolyLine, Pt2D, Time};
 use map_model::{
     BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
     PathStep, Position, Traversable, TurnID,
@@ -58,12 +58,14 @@ pub trait ParkingSim {
     /// Unrealistically assumes the driver has knowledge of currently free parking spots, even if
     /// they're far away. Since they don't reserve the spot in advance, somebody else can still beat
     /// them there, producing some nice, realistic churn if there's too much contention.
-    /// The first PathStep is the turn after start, NOT PathStep::Lane(start).
+    /// The first PathStep is the turn after start, NOT PathStep::Lane(start). Turn restrictions and
+    /// modal filters in effect `now` are obeyed.
     fn path_to_free_parking_spot(
         &self,
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)>;
     fn collect_events(&mut self) -> Vec<Event>;
@@ -535,6 +537,7 @@ impl ParkingSim for NormalParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
@@ -573,7 +576,9 @@ impl ParkingSim for NormalParkingSimState {
                 }
             }
             for turn in map.get_turns_for(current, PathConstraints::Car) {
-                if !backrefs.contains_key(&turn.id.dst) {
+                if !backrefs.contains_key(&turn.id.dst)
+                    && !map.turn_restricted_at(turn.id, PathConstraints::Car, now)
+                {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     backrefs.insert(turn.id.dst, turn.id);
                     // Remember, keep things negative
@@ -921,6 +926,7 @@ impl ParkingSim for InfiniteParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         // TODO This impl is copied from NormalParkingSimState. Instead, we already know the
@@ -961,7 +967,9 @@ impl ParkingSim for InfiniteParkingSimState {
                 }
             }
             for turn in map.get_turns_for(current, PathConstraints::Car) {
-                if !backrefs.contains_key(&turn.id.dst) {
+                if !backrefs.contains_key(&turn.id.dst)
+                    && !map.turn_restricted_at(turn.id, PathConstraints::Car, now)
+                {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     backrefs.insert(turn.id.dst, turn.id);
                     // Remember, keep things negative
@@ -0,0 +1,451 @@
+//! Simulations are chaotic; a small edit can make some trips faster and others slower just by
+//! shuffling the order agents arrive somewhere. To tell a real effect from noise, run the same
+//! scenario with several RNG seeds, before and after some edits, and summarize each metric across
+//! seeds with a 95% confidence interval. Each seed's run before and after is paired, so a change
+//! is only called significant if it's consistent across seeds.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use serde::Serialize;
+
+use abstutil::{Counter, Parallelism, Timer};
+use geom::{Duration, MeanEstimate, Time};
+use map_model::{IntersectionID, Map, RoadID};
+
+use crate::{AlertHandler, Analytics, Scenario, Sim, SimOptions, TripID, TripMode};
+
+/// Runs a scenario on each map with every seed, all in parallel. Returns the runs for each map, in
+/// the same order as the seeds. Alerts are silenced.
+pub fn run_with_seeds(
+    maps: &[&Map],
+    scenario: &Scenario,
+    opts: &SimOptions,
+    seeds: &[u64],
+    timer: &mut Timer,
+) -> Vec<Vec<RunSummary>> {
+    let mut requests = Vec::new();
+    for idx in 0..maps.len() {
+        for seed in seeds {
+            requests.push((idx, *seed));
+        }
+    }
+    let mut results = timer.parallelize(
+        "run simulations",
+        Parallelism::Fastest,
+        requests,
+        |(idx, seed)| RunSummary::new(run(maps[idx], scenario, opts, seed)),
+    );
+    // The results are in the same order as the requests
+    let mut per_map = Vec::new();
+    for _ in maps {
+        let rest = results.split_off(seeds.len());
+        per_map.push(results);
+        results = rest;
+    }
+    per_map
+}
+
+fn run(map: &Map, scenario: &Scenario, opts: &SimOptions, seed: u64) -> Analytics {
+    let mut timer = Timer::throwaway();
+    let mut opts = opts.clone();
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(map, opts, &mut timer);
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    scenario.instantiate(&mut sim, map, &mut rng, &mut timer);
+    sim.timed_step(
+        map,
+        sim.get_end_of_day() - Time::START_OF_DAY,
+        &mut None,
+        &mut timer,
+    );
+    sim.get_analytics().clone()
+}
+
+/// The same scenario, run before and after some edits with several seeds.
+pub struct SeedRuns {
+    pub seeds: Vec<u64>,
+    pub before: Vec<RunSummary>,
+    pub after: Vec<RunSummary>,
+}
+
+impl SeedRuns {
+    pub fn new(
+        before: &Map,
+        after: &Map,
+        scenario: &Scenario,
+        opts: &SimOptions,
+        seeds: Vec<u64>,
+        timer: &mut Timer,
+    ) -> SeedRuns {
+        let mut runs = run_with_seeds(&[before, after], scenario, opts, &seeds, timer);
+        let after = runs.pop().unwrap();
+        let before = runs.pop().unwrap();
+        SeedRuns {
+            seeds,
+            before,
+            after,
+        }
+    }
+
+    /// Summarizes the trips passing the filter across every seed.
+    pub fn trip_estimates(&self, keep: &dyn Fn(TripID, TripMode) -> bool) -> TripTimeEstimates {
+        let per_run: Vec<TripTimeChanges> = self
+            .before
+            .iter()
+            .zip(self.after.iter())
+            .map(|(b, a)| TripTimeChanges::new(b, a, keep))
+            .collect();
+        TripTimeEstimates::new(&per_run.iter().collect::<Vec<_>>())
+    }
+}
+
+/// The parts of Analytics needed for comparison. Analytics from many simulations can be large, so
+/// only keep this much around.
+pub struct RunSummary {
+    /// None if the trip was cancelled
+    pub trips: BTreeMap<TripID, (TripMode, Option<Duration>)>,
+    pub intersection_delays: BTreeMap<IntersectionID, Duration>,
+    pub road_thruput: Counter<RoadID>,
+    pub intersection_thruput: Counter<IntersectionID>,
+}
+
+impl RunSummary {
+    pub fn new(analytics: Analytics) -> RunSummary {
+        let mut trips = BTreeMap::new();
+        for (_, id, mode, maybe_dt) in analytics.finished_trips {
+            trips.insert(id, (mode, maybe_dt));
+        }
+        let mut intersection_delays = BTreeMap::new();
+        for (i, delays) in analytics.intersection_delays {
+            intersection_delays.insert(i, delays.into_iter().map(|(_, _, dt, _)| dt).sum());
+        }
+        RunSummary {
+            trips,
+            intersection_delays,
+            road_thruput: analytics.road_thruput.all_total_counts(),
+            intersection_thruput: analytics.intersection_thruput.all_total_counts(),
+        }
+    }
+}
+
+/// Compares one run before and after, using the same seed.
+#[derive(Serialize)]
+pub struct RunComparison {
+    pub rng_seed: u64,
+    pub trips: BTreeMap<TripMode, TripTimeChanges>,
+    /// Only traffic signals record delays
+    pub total_intersection_delay_before: Duration,
+    pub total_intersection_delay_after: Duration,
+    /// (intersection, total delay before, total delay after), only for intersections that changed,
+    /// sorted by the largest change first
+    pub intersection_delays: Vec<(IntersectionID, Duration, Duration)>,
+    /// (road, agents passing through before, after), only for roads that changed, sorted by the
+    /// largest change first
+    pub road_thruput: Vec<(RoadID, usize, usize)>,
+    /// Same as road_thruput
+    pub intersection_thruput: Vec<(IntersectionID, usize, usize)>,
+}
+
+/// Except for the finished and cancelled counts, only counts trips that finished in both
+/// simulations.
+#[derive(Default, Serialize)]
+pub struct TripTimeChanges {
+    pub num_trips: usize,
+    pub num_faster: usize,
+    pub num_slower: usize,
+    pub total_saved: Duration,
+    pub total_lost: Duration,
+    pub total_before: Duration,
+    pub total_after: Duration,
+    pub finished_before: usize,
+    pub finished_after: usize,
+    pub cancelled_before: usize,
+    pub cancelled_after: usize,
+}
+
+impl TripTimeChanges {
+    /// Only looks at trips passing the filter.
+    pub fn new(
+        before: &RunSummary,
+        after: &RunSummary,
+        keep: &dyn Fn(TripID, TripMode) -> bool,
+    ) -> TripTimeChanges {
+        let mut changes = TripTimeChanges::default();
+        for (id, (mode, maybe_before)) in &before.trips {
+            if !keep(*id, *mode) {
+                continue;
+            }
+            let maybe_after = after.trips.get(id).and_then(|(_, dt)| *dt);
+            match (maybe_before, maybe_after) {
+                (Some(b), Some(a)) => {
+                    changes.num_trips += 1;
+                    changes.total_before += *b;
+                    changes.total_after += a;
+                    if a < *b {
+                        changes.num_faster += 1;
+                        changes.total_saved += *b - a;
+                    } else if a > *b {
+                        changes.num_slower += 1;
+                        changes.total_lost += a - *b;
+                    }
+                }
+                (None, _) => {
+                    changes.cancelled_before += 1;
+                }
+                _ => {}
+            }
+            if maybe_before.is_some() {
+                changes.finished_before += 1;
+            }
+        }
+        for (id, (mode, maybe_after)) in &after.trips {
+            if !keep(*id, *mode) {
+                continue;
+            }
+            if maybe_after.is_some() {
+                changes.finished_after += 1;
+            } else {
+                changes.cancelled_after += 1;
+            }
+        }
+        changes
+    }
+}
+
+impl RunComparison {
+    pub fn new(rng_seed: u64, before: &RunSummary, after: &RunSummary) -> RunComparison {
+        let modes: BTreeSet<TripMode> = before
+            .trips
+            .values()
+            .chain(after.trips.values())
+            .map(|(mode, _)| *mode)
+            .collect();
+        let mut trips = BTreeMap::new();
+        for mode in modes {
+            trips.insert(mode, TripTimeChanges::new(before, after, &|_, m| m == mode));
+        }
+
+        let mut intersection_delays = Vec::new();
+        let all_intersections: BTreeSet<IntersectionID> = before
+            .intersection_delays
+            .keys()
+            .chain(after.intersection_delays.keys())
+            .cloned()
+            .collect();
+        for i in all_intersections {
+            let b = before
+                .intersection_delays
+                .get(&i)
+                .cloned()
+                .unwrap_or(Duration::ZERO);
+            let a = after
+                .intersection_delays
+                .get(&i)
+                .cloned()
+                .unwrap_or(Duration::ZERO);
+            if a != b {
+                intersection_delays.push((i, b, a));
+            }
+        }
+        intersection_delays
+            .sort_by_key(|(_, b, a)| std::cmp::Reverse(if a > b { *a - *b } else { *b - *a }));
+
+        RunComparison {
+            rng_seed,
+            trips,
+            total_intersection_delay_before: before.intersection_delays.values().cloned().sum(),
+            total_intersection_delay_after: after.intersection_delays.values().cloned().sum(),
+            intersection_delays,
+            road_thruput: compare_counts(&before.road_thruput, &after.road_thruput),
+            intersection_thruput: compare_counts(
+                &before.intersection_thruput,
+                &after.intersection_thruput,
+            ),
+        }
+    }
+}
+
+fn compare_counts<X: Ord + Clone>(
+    before: &Counter<X>,
+    after: &Counter<X>,
+) -> Vec<(X, usize, usize)> {
+    let mut results: Vec<(X, usize, usize)> = before
+        .clone()
+        .compare(after.clone())
+        .into_iter()
+        .filter(|(_, b, a)| a != b)
+        .collect();
+    results.sort_by_key(|(_, b, a)| std::cmp::Reverse(if a > b { a - b } else { b - a }));
+    results
+}
+
+/// Each metric summarized over all seeds. Durations are in seconds.
+#[derive(Serialize)]
+pub struct SeedSummary {
+    pub trips: BTreeMap<TripMode, TripTimeEstimates>,
+    pub intersection_delay_before: MeanEstimate,
+    pub intersection_delay_after: MeanEstimate,
+    pub intersection_delay_change: MeanEstimate,
+}
+
+/// The same metrics as the trip summaries dashboard, plus how many trips finish. Durations are in
+/// seconds.
+#[derive(Serialize)]
+pub struct TripTimeEstimates {
+    pub num_faster: MeanEstimate,
+    pub num_slower: MeanEstimate,
+    pub total_saved: MeanEstimate,
+    pub total_lost: MeanEstimate,
+    /// Over trips that finished in both simulations
+    pub mean_trip_time_before: MeanEstimate,
+    pub mean_trip_time_after: MeanEstimate,
+    /// Paired by seed, so this is the one to check for significance
+    pub mean_trip_time_change: MeanEstimate,
+    pub finished_before: MeanEstimate,
+    pub finished_after: MeanEstimate,
+    pub finished_change: MeanEstimate,
+    pub cancelled_change: MeanEstimate,
+}
+
+impl TripTimeEstimates {
+    /// One set of changes per seed
+    pub fn new(per_run: &[&TripTimeChanges]) -> TripTimeEstimates {
+        let estimate = |f: &dyn Fn(&TripTimeChanges) -> f64| {
+            MeanEstimate::new(per_run.iter().map(|x| f(*x)).collect())
+        };
+        let mean_time = |total: Duration, n: usize| {
+            if n == 0 {
+                0.0
+            } else {
+                total.inner_seconds() / (n as f64)
+            }
+        };
+        TripTimeEstimates {
+            num_faster: estimate(&|x| x.num_faster as f64),
+            num_slower: estimate(&|x| x.num_slower as f64),
+            total_saved: estimate(&|x| x.total_saved.inner_seconds()),
+            total_lost: estimate(&|x| x.total_lost.inner_seconds()),
+            mean_trip_time_before: estimate(&|x| mean_time(x.total_before, x.num_trips)),
+            mean_trip_time_after: estimate(&|x| mean_time(x.total_after, x.num_trips)),
+            mean_trip_time_change: estimate(&|x| {
+                mean_time(x.total_after - x.total_before, x.num_trips)
+            }),
+            finished_before: estimate(&|x| x.finished_before as f64),
+            finished_after: estimate(&|x| x.finished_after as f64),
+            finished_change: estimate(&|x| x.finished_after as f64 - x.finished_before as f64),
+            cancelled_change: estimate(&|x| x.cancelled_after as f64 - x.cancelled_before as f64),
+        }
+    }
+}
+
+impl SeedSummary {
+    pub fn new(runs: &[RunComparison]) -> SeedSummary {
+        let modes: BTreeSet<TripMode> = runs
+            .iter()
+            .flat_map(|run| run.trips.keys().cloned())
+            .collect();
+        let mut trips = BTreeMap::new();
+        for mode in modes {
+            let empty = TripTimeChanges::default();
+            let per_run: Vec<&TripTimeChanges> = runs
+                .iter()
+                .map(|run| run.trips.get(&mode).unwrap_or(&empty))
+                .collect();
+            trips.insert(mode, TripTimeEstimates::new(&per_run));
+        }
+
+        let delays =
+            |f: &dyn Fn(&RunComparison) -> f64| MeanEstimate::new(runs.iter().map(f).collect());
+        SeedSummary {
+            trips,
+            intersection_delay_before: delays(&|run| {
+                run.total_intersection_delay_before.inner_seconds()
+            }),
+            intersection_delay_after: delays(&|run| {
+                run.total_intersection_delay_after.inner_seconds()
+            }),
+            intersection_delay_change: delays(&|run| {
+                (run.total_intersection_delay_after - run.total_intersection_delay_before)
+                    .inner_seconds()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(trips: Vec<(usize, TripMode, Option<f64>)>) -> RunSummary {
+        RunSummary {
+            trips: trips
+                .into_iter()
+                .map(|(id, mode, dt)| (TripID(id), (mode, dt.map(Duration::seconds))))
+                .collect(),
+            intersection_delays: BTreeMap::new(),
+            road_thruput: Counter::new(),
+            intersection_thruput: Counter::new(),
+        }
+    }
+
+    #[test]
+    fn trip_time_changes() {
+        let before = summary(vec![
+            (0, TripMode::Drive, Some(100.0)),
+            (1, TripMode::Drive, Some(50.0)),
+            (2, TripMode::Drive, None),
+            (3, TripMode::Walk, Some(10.0)),
+        ]);
+        let after = summary(vec![
+            (0, TripMode::Drive, Some(80.0)),
+            (1, TripMode::Drive, Some(60.0)),
+            (2, TripMode::Drive, Some(30.0)),
+            (3, TripMode::Walk, None),
+        ]);
+
+        let drive = TripTimeChanges::new(&before, &after, &|_, mode| mode == TripMode::Drive);
+        assert_eq!(drive.num_trips, 2);
+        assert_eq!(drive.num_faster, 1);
+        assert_eq!(drive.num_slower, 1);
+        assert_eq!(drive.total_saved, Duration::seconds(20.0));
+        assert_eq!(drive.total_lost, Duration::seconds(10.0));
+        assert_eq!(drive.finished_before, 2);
+        assert_eq!(drive.finished_after, 3);
+        assert_eq!(drive.cancelled_before, 1);
+        assert_eq!(drive.cancelled_after, 0);
+
+        let comparison = RunComparison::new(42, &before, &after);
+        assert_eq!(comparison.trips[&TripMode::Walk].cancelled_after, 1);
+        assert_eq!(comparison.trips[&TripMode::Walk].num_trips, 0);
+
+        let only_first = TripTimeChanges::new(&before, &after, &|id, _| id == TripID(0));
+        assert_eq!(only_first.num_faster, 1);
+        assert_eq!(only_first.num_slower, 0);
+    }
+
+    #[test]
+    fn estimates_across_seeds() {
+        // Every seed makes the trip 10 to 20 seconds faster, so the change is significant
+        let runs = SeedRuns {
+            seeds: vec![1, 2, 3],
+            before: vec![
+                summary(vec![(0, TripMode::Bike, Some(100.0))]),
+                summary(vec![(0, TripMode::Bike, Some(110.0))]),
+                summary(vec![(0, TripMode::Bike, Some(120.0))]),
+            ],
+            after: vec![
+                summary(vec![(0, TripMode::Bike, Some(90.0))]),
+                summary(vec![(0, TripMode::Bike, Some(95.0))]),
+                summary(vec![(0, TripMode::Bike, Some(100.0))]),
+            ],
+        };
+        let x = runs.trip_estimates(&|_, _| true);
+        assert_eq!(x.mean_trip_time_change.mean, -15.0);
+        assert!(x.mean_trip_time_change.is_significant());
+        assert_eq!(x.num_faster.mean, 1.0);
+        assert_eq!(x.finished_change.mean, 0.0);
+        assert!(!x.finished_change.is_significant());
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Time};
 use map_model::{
     BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
     Position, Traversable, Turn, TurnID,
@@ -151,6 +151,7 @@ impl Router {
         &mut self,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        now: Time,
         map: &Map,
         trip_and_person: Option<(TripID, PersonID)>,
         events: &mut Vec<Event>,
@@ -162,6 +163,7 @@ impl Router {
                 Distance::ZERO,
                 vehicle,
                 parking,
+                now,
                 map,
                 trip_and_person,
                 events,
@@ -189,6 +191,7 @@ impl Router {
         front: Distance,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        now: Time,
         map: &Map,
         // TODO Not so nice to plumb all of this here
         trip_and_person: Option<(TripID, PersonID)>,
@@ -266,8 +269,8 @@ impl Router {
                         assert!(new_pos.dist_along() >= front);
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
+                        if let Some((new_path_steps, new_spot, new_pos)) = parking
+                            .path_to_free_parking_spot(current_lane, vehicle, target, now, map)
                         {
                             assert!(!new_path_steps.is_empty());
                             for step in new_path_steps {
@@ -459,6 +462,69 @@ impl Router {
         }
     }
 
+    /// If the rest of the path breaks a turn restriction or modal filter that applies now, look for
+    /// another way. If `committed` is true, the vehicle is waiting to make the next turn, so that
+    /// has to stay. Vehicles looking for parking or following a bus route keep their path. Returns
+    /// true if the path changed.
+    pub fn reroute_around_restrictions(
+        &mut self,
+        committed: bool,
+        vehicle: &Vehicle,
+        now: Time,
+        map: &Map,
+    ) -> bool {
+        match self.goal {
+            Goal::FollowBusRoute { .. } => {
+                return false;
+            }
+            Goal::ParkNearBuilding {
+                started_looking: true,
+                ..
+            } => {
+                return false;
+            }
+            _ => {}
+        }
+        let constraints = vehicle.vehicle_type.to_constraints();
+        if self.path.currently_inside_ut().is_some()
+            || !self.path.breaks_turn_restrictions_at(constraints, now, map)
+        {
+            return false;
+        }
+
+        let idx = match self.path.current_step() {
+            PathStep::Lane(_) | PathStep::ContraflowLane(_) => {
+                if !committed {
+                    0
+                } else if self.path.about_to_start_ut().is_some() {
+                    return false;
+                } else {
+                    2
+                }
+            }
+            PathStep::Turn(_) => 1,
+        };
+        if idx >= self.path.get_steps().len() {
+            return false;
+        }
+        let start = self.path.get_steps()[idx].as_lane();
+        let end = self.path.last_step().as_lane();
+        if start == end {
+            return false;
+        }
+        let req = PathRequest {
+            start: Position::new(start, Distance::ZERO),
+            end: Position::new(end, self.path.end_dist()),
+            constraints,
+        };
+        if let Some(path) = map.pathfind_at_time(req, now) {
+            self.path.reroute_from(idx, path, map);
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn is_parking(&self) -> bool {
         match self.goal {
             Goal::ParkNearBuilding {
@@ -112,6 +112,9 @@ pub struct SimOptions {
     /// delay the start of the trip by this amount, and try again. If conditions are still
     /// problematic, repeat a fixed 3 times before cancelling.
     pub delay_trips_instead_of_cancelling: Option<Duration>,
+    /// How many dollars an hour of travel is worth to the average driver, when deciding whether
+    /// to pay a toll, avoid it, leave later, or walk instead.
+    pub value_of_time_per_hour: f64,
     /// Don't collect any analytics. Only useful for benchmarking and debugging gridlock more
     /// quickly.
     pub skip_analytics: bool,
@@ -154,6 +157,9 @@ impl SimOptions {
                 .optional_parse("--cancel_drivers_delay_threshold", Duration::parse),
             delay_trips_instead_of_cancelling: args
                 .optional_parse("--delay_trips_instead_of_cancelling", Duration::parse),
+            value_of_time_per_hour: args
+                .optional_parse("--value_of_time_per_hour", |s| s.parse::<f64>())
+                .unwrap_or(20.0),
             skip_analytics: args.enabled("--skip_analytics"),
         }
     }
@@ -190,6 +196,7 @@ impl SimOptions {
             disable_turn_conflicts: false,
             cancel_drivers_delay_threshold: None,
             delay_trips_instead_of_cancelling: None,
+            value_of_time_per_hour: 20.0,
             skip_analytics: false,
         }
     }
@@ -199,6 +206,7 @@ impl SimOptions {
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions, timer: &mut Timer) -> Sim {
         let mut scheduler = Scheduler::new();
+        schedule_timed_restrictions(map, Time::START_OF_DAY, &mut scheduler);
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, timer),
@@ -298,9 +306,13 @@ impl Sim {
         {
             spot.clone()
         } else {
-            let (_, spot, _) =
-                self.parking
-                    .path_to_free_parking_spot(driving_lane, &vehicle, b, map)?;
+            let (_, spot, _) = self.parking.path_to_free_parking_spot(
+                driving_lane,
+                &vehicle,
+                b,
+                self.time,
+                map,
+            )?;
             spot
         };
 
@@ -633,6 +645,10 @@ impl Sim {
             Command::StartBus(r, _) => {
                 self.start_bus(map.get_br(r), map);
             }
+            Command::TimedRestrictions => {
+                self.driving.reroute_around_restrictions(self.time, map);
+                schedule_timed_restrictions(map, self.time, &mut self.scheduler);
+            }
         }
 
         // Record events at precisely the time they occur.
@@ -890,6 +906,11 @@ impl Sim {
 
         self.driving.handle_live_edits(map);
         self.intersections.handle_live_edits(map);
+        // Edits might add restrictions in effect right now, so the trips that weren't cancelled
+        // may need another way
+        self.driving.reroute_around_restrictions(self.time, map);
+        self.scheduler.cancel(Command::TimedRestrictions);
+        schedule_timed_restrictions(map, self.time, &mut self.scheduler);
 
         (num_trips_cancelled, num_parked_cars)
     }
@@ -1027,3 +1048,67 @@ impl Sim {
         self.recorder.take().unwrap().save(map);
     }
 }
+
+/// Schedule the next time after `now` when some turn restrictions or modal filters start applying.
+fn schedule_timed_restrictions(map: &Map, now: Time, scheduler: &mut Scheduler) {
+    if let Some(t) = next_restriction_start(&map.restriction_start_times(), now) {
+        scheduler.push(t, Command::TimedRestrictions);
+    }
+}
+
+/// Given the times of day when restrictions start applying, finds the next one strictly after
+/// `now`. Every day of the simulation is the same, so this may be tomorrow.
+fn next_restriction_start(times: &[Time], now: Time) -> Option<Time> {
+    let day = Duration::hours(24);
+    let today = day * (now.inner_seconds() / day.inner_seconds()).floor();
+    for offset in vec![today, today + day] {
+        for t in times {
+            if *t + offset > now {
+                return Some(*t + offset);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: usize, m: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(h) + Duration::minutes(m)
+    }
+
+    #[test]
+    fn test_next_restriction_start() {
+        let times = vec![time(8, 30), time(15, 0)];
+        assert_eq!(
+            next_restriction_start(&times, time(0, 0)),
+            Some(time(8, 30))
+        );
+        // A window starting right now was already handled
+        assert_eq!(
+            next_restriction_start(&times, time(8, 30)),
+            Some(time(15, 0))
+        );
+        // After the last one, wrap around to tomorrow
+        assert_eq!(
+            next_restriction_start(&times, time(15, 0)),
+            Some(time(32, 30))
+        );
+        assert_eq!(
+            next_restriction_start(&times, time(23, 59)),
+            Some(time(32, 30))
+        );
+        // Simulations can run past midnight
+        assert_eq!(
+            next_restriction_start(&times, time(30, 0)),
+            Some(time(32, 30))
+        );
+        assert_eq!(
+            next_restriction_start(&times, time(33, 0)),
+            Some(time(39, 0))
+        );
+        assert_eq!(next_restriction_start(&[], time(8, 0)), None);
+    }
+}
@@ -9,13 +9,14 @@ use map_model::{
     Position,
 };
 
-use crate::cap::CapResult;
+use crate::cap::{BeforeLeaving, CapResult};
 use crate::sim::Ctx;
 use crate::{
     AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
     Event, IndividTrip, OrigPersonID, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID,
-    PersonSpec, Scenario, SidewalkPOI, SidewalkSpot, TransitSimState, TripEndpoint, TripID,
-    TripPhaseType, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
+    PersonSpec, Scenario, SidewalkPOI, SidewalkSpot, TollDecision, TransitSimState, TripEndpoint,
+    TripID, TripPhaseType, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType,
+    WalkingSimState,
 };
 
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
@@ -80,6 +81,8 @@ impl TripManager {
             vehicles,
             delayed_trips: Vec::new(),
             on_bus: None,
+            waiting_to_leave: None,
+            left_car_at: None,
         });
         self.get_person(id).unwrap()
     }
@@ -135,8 +138,11 @@ impl TripManager {
         assert!(self.trips[trip.0].info.cancellation_reason.is_none());
 
         let person = &mut self.people[self.trips[trip.0].person.0];
-        if let PersonState::Trip(_) = person.state {
-            // Previous trip isn't done. Defer this one!
+        if person.waiting_to_leave == Some(trip) {
+            person.waiting_to_leave = None;
+        }
+        if matches!(person.state, PersonState::Trip(_)) || person.waiting_to_leave.is_some() {
+            // Previous trip isn't done, or hasn't even started. Defer this one!
             if false {
                 self.events.push(Event::Alert(
                     AlertLocation::Person(person.id),
@@ -155,8 +161,15 @@ impl TripManager {
             ));
             return;
         }
+        let spec = match self.respond_to_tolls_before_leaving(now, trip, spec, ctx) {
+            Some(spec) => spec,
+            None => {
+                return;
+            }
+        };
         self.trips[trip.0].started = true;
 
+        let person = &mut self.people[self.trips[trip.0].person.0];
         match spec {
             TripSpec::VehicleAppearing {
                 start_pos,
@@ -627,7 +640,7 @@ impl TripManager {
             None
         } else {
             ctx.map
-                .pathfind(req.clone())
+                .pathfind_at_time(req.clone(), now)
                 .map(|path| drive_to.make_router(bike, path, ctx.map))
         };
         if let Some(router) = maybe_router {
@@ -981,6 +994,137 @@ impl TripManager {
         }
     }
 
+    /// Drivers facing a toll might leave later or walk instead. Returns None if the trip will start
+    /// later, or the spec to start now, which might've changed to walking.
+    fn respond_to_tolls_before_leaving(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        spec: TripSpec,
+        ctx: &mut Ctx,
+    ) -> Option<TripSpec> {
+        if !ctx.cap.has_tolls() {
+            return Some(spec);
+        }
+        let person = &self.people[self.trips[trip.0].person.0];
+        let inputs = match spec {
+            TripSpec::VehicleAppearing {
+                start_pos,
+                ref goal,
+                use_vehicle,
+                ..
+            } => {
+                let constraints = use_vehicle.1.to_constraints();
+                goal.goal_pos(constraints, ctx.map).map(|end| {
+                    let req = PathRequest {
+                        start: start_pos,
+                        end,
+                        constraints,
+                    };
+                    (req, use_vehicle, None)
+                })
+            }
+            TripSpec::UsingParkedCar {
+                car,
+                start_bldg,
+                ref goal,
+            } => {
+                if let (Some(parked_car), Some(end)) = (
+                    ctx.parking.lookup_parked_car(car),
+                    goal.goal_pos(PathConstraints::Car, ctx.map),
+                ) {
+                    let req = PathRequest {
+                        start: ctx.parking.spot_to_driving_pos(
+                            parked_car.spot,
+                            &parked_car.vehicle,
+                            ctx.map,
+                        ),
+                        end,
+                        constraints: PathConstraints::Car,
+                    };
+                    // Only consider walking the whole way when the trip ends at a building
+                    let walk = if let DrivingGoal::ParkNear(b) = goal {
+                        let start = SidewalkSpot::building(start_bldg, ctx.map);
+                        let goal = SidewalkSpot::building(*b, ctx.map);
+                        ctx.map
+                            .pathfind(PathRequest {
+                                start: start.sidewalk_pos,
+                                end: goal.sidewalk_pos,
+                                constraints: PathConstraints::Pedestrian,
+                            })
+                            .map(|path| (path.total_length() / person.ped_speed, start, goal))
+                    } else {
+                        None
+                    };
+                    Some((req, car, walk))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let (req, car, walk) = match inputs {
+            Some(x) => x,
+            None => {
+                return Some(spec);
+            }
+        };
+
+        // After walking somewhere instead of driving, the car is still back where the person
+        // started, so keep walking until they're back there.
+        if let TripSpec::UsingParkedCar { start_bldg, .. } = spec {
+            let person = &mut self.people[self.trips[trip.0].person.0];
+            if let Some((left_car, b)) = person.left_car_at {
+                if left_car == car && b == start_bldg {
+                    person.left_car_at = None;
+                } else if left_car == car {
+                    if let Some((_, start, goal)) = walk {
+                        return Some(self.walk_instead(trip, start, goal));
+                    }
+                }
+            }
+        }
+
+        match ctx.cap.before_leaving(
+            &req,
+            car,
+            walk.as_ref().map(|(walk_time, _, _)| *walk_time),
+            now,
+            ctx.map,
+        ) {
+            BeforeLeaving::Go => Some(spec),
+            BeforeLeaving::WaitUntil(time) => {
+                self.events
+                    .push(Event::TollDecision(trip, TollDecision::Retimed(time - now)));
+                self.events.push(Event::TripPhaseStarting(
+                    trip,
+                    person.id,
+                    None,
+                    TripPhaseType::DelayedStart,
+                ));
+                self.people[self.trips[trip.0].person.0].waiting_to_leave = Some(trip);
+                ctx.scheduler.push(time, Command::StartTrip(trip, spec));
+                None
+            }
+            BeforeLeaving::Walk => {
+                if let TripSpec::UsingParkedCar { start_bldg, .. } = spec {
+                    self.people[self.trips[trip.0].person.0].left_car_at = Some((car, start_bldg));
+                }
+                self.events
+                    .push(Event::TollDecision(trip, TollDecision::Walked));
+                let (_, start, goal) = walk.unwrap();
+                Some(self.walk_instead(trip, start, goal))
+            }
+        }
+    }
+
+    fn walk_instead(&mut self, trip: TripID, start: SidewalkSpot, goal: SidewalkSpot) -> TripSpec {
+        let trip = &mut self.trips[trip.0];
+        trip.info.mode = TripMode::Walk;
+        trip.legs = VecDeque::from(vec![TripLeg::Walk(goal.clone())]);
+        TripSpec::JustWalking { start, goal }
+    }
+
     /// Returns the path to use if successful. Caller is responsible for handling both the success
     /// and failure case.
     fn maybe_spawn_car(
@@ -993,16 +1137,16 @@ impl TripManager {
     ) -> Result<Path, String> {
         let path = ctx
             .map
-            .pathfind(req.clone())
+            .pathfind_at_time(req.clone(), now)
             .ok_or_else(|| format!("no path for {}", req))?;
         match ctx
             .cap
             .maybe_cap_path(&req, path, now, car, ctx.intersections, ctx.map)
         {
-            CapResult::OK(path) => Ok(path),
+            CapResult::OK(path) => Ok(self.pay_or_reroute(ctx, now, trip, &req, path, car)),
             CapResult::Reroute(path) => {
                 self.trips[trip.0].info.capped = true;
-                Ok(path)
+                Ok(self.pay_or_reroute(ctx, now, trip, &req, path, car))
             }
             CapResult::Cancel { reason } => {
                 self.trips[trip.0].info.capped = true;
@@ -1011,6 +1155,22 @@ impl TripManager {
             CapResult::Delay(_) => todo!(),
         }
     }
+
+    fn pay_or_reroute(
+        &mut self,
+        ctx: &mut Ctx,
+        now: Time,
+        trip: TripID,
+        req: &PathRequest,
+        path: Path,
+        car: CarID,
+    ) -> Path {
+        let (path, decision) = ctx.cap.pay_or_reroute(req, path, now, car, ctx.map);
+        if let Some(decision) = decision {
+            self.events.push(Event::TollDecision(trip, decision));
+        }
+        path
+    }
 }
 
 // Cancelling trips
@@ -1073,7 +1233,7 @@ impl TripManager {
                         .map(|(spot, _)| spot.clone())
                         .or_else(|| {
                             ctx.parking
-                                .path_to_free_parking_spot(driving_lane, &vehicle, b, ctx.map)
+                                .path_to_free_parking_spot(driving_lane, &vehicle, b, now, ctx.map)
                                 .map(|(_, spot, _)| spot)
                         })
                     {
@@ -1513,6 +1673,10 @@ pub struct Person {
 
     delayed_trips: Vec<(TripID, TripSpec)>,
     on_bus: Option<CarID>,
+    /// A trip put off until a toll is cheaper. Later trips wait for it.
+    waiting_to_leave: Option<TripID>,
+    /// The car stays parked here after walking instead of paying a toll
+    left_car_at: Option<(CarID, BuildingID)>,
 }
 
 impl Person {
@@ -6,15 +6,51 @@ use std::io::Write;
 use rand::seq::SliceRandom;
 
 use abstutil::{MapName, Timer};
-use geom::{Duration, Time};
-use map_model::{IntersectionID, Map};
-use sim::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
+use geom::{Distance, Duration, GPSBounds, LonLat, Polygon, Speed, Time};
+use map_model::{
+    osm, AreaType, BusRouteID, ControlTrafficSignal, Direction, EditCmd, EditIntersection,
+    EditProblem, EditRoute, EditRule, EditScript, IDRemap, IntersectionID, IntersectionType,
+    LaneID, LaneType, Map, Neighborhood, Path, PathConstraints, PathRequest, Position, RoadChange,
+    RoadID, RoadQuery, SignType, TransitPriority, TurnPriority,
+};
+use sim::{
+    DepartureProfile, IndividTrip, ODEntry, ODMatrix, ODZone, PersonSpec, Scenario, TripEndpoint,
+    TripMode, TripPurpose,
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     test_lane_changing(&import_map(abstutil::path(
         "../tests/input/lane_selection.osm",
     )))?;
     test_map_importer()?;
+    test_osm_change()?;
+    test_remap()?;
+    test_od()?;
+    test_pbf_clipping()?;
+    test_neighborhood(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_timed_closure(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_lane_widths(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_new_routes(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_queue_jump(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_edit_script(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_check_edits(import_map(abstutil::path(
+        "../tests/input/neighborhood.osm",
+    )))?;
+    test_remove_slip_lane(import_map(abstutil::path("../tests/input/slip_lane.osm")))?;
+    test_roundabout(import_map(abstutil::path("../tests/input/roundabout.osm")))?;
+    test_signs(import_map(abstutil::path("../tests/input/signs.osm")))?;
     check_proposals()?;
     smoke_test()?;
     Ok(())
@@ -38,31 +74,1115 @@ fn test_map_importer() -> Result<(), std::io::Error> {
     Ok(())
 }
 
+/// Apply an OsmChange to a handcrafted .osm, and check that deletions, node tags, clipping, and
+/// new turn restrictions all make it into the RawMap.
+fn test_osm_change() -> Result<(), Box<dyn std::error::Error>> {
+    let mut timer = Timer::new("apply synthetic .osc");
+    let raw = convert_osm::osc::apply_osc(
+        &[abstutil::path("../tests/input/neighborhood.osc")],
+        import_options(abstutil::path("../tests/input/neighborhood.osm")),
+        &mut timer,
+    )?;
+
+    assert!(raw.roads.keys().all(|r| r.osm_way_id != osm::WayID(112)));
+    assert!(!raw.intersections.contains_key(&osm::NodeID(8)));
+    assert_eq!(
+        raw.intersections[&osm::NodeID(7)].intersection_type,
+        IntersectionType::TrafficSignal
+    );
+
+    // The new road leaves the map to the west, so it's clipped there
+    let border = &raw.intersections[&osm::NodeID(9)];
+    assert_eq!(border.intersection_type, IntersectionType::Border);
+    assert!(border.point.x().abs() < 0.1);
+    let (_, new_road) = raw
+        .roads
+        .iter()
+        .find(|(id, _)| id.osm_way_id == osm::WayID(120))
+        .ok_or("the new road is missing")?;
+    assert_eq!(*new_road.center_points.last().unwrap(), border.point);
+
+    let (_, from) = raw
+        .roads
+        .iter()
+        .find(|(id, _)| id.osm_way_id == osm::WayID(110))
+        .ok_or("the local road is missing")?;
+    assert_eq!(from.turn_restrictions.len(), 1);
+    assert_eq!(from.turn_restrictions[0].1.osm_way_id, osm::WayID(111));
+    Ok(())
+}
+
+/// Import a map, then import it again with a boundary further south and a road deleted. Borders
+/// in the old map that are now inside the boundary must not be used by the remapped scenario.
+fn test_remap() -> Result<(), Box<dyn std::error::Error>> {
+    let mut timer = Timer::new("remap between two imports");
+    let options = |poly: &str| {
+        let mut opts = import_options(abstutil::path("../tests/input/neighborhood.osm"));
+        opts.clip = Some(abstutil::path(format!("../tests/input/{}.poly", poly)));
+        opts
+    };
+    let old = Map::create_from_raw(
+        convert_osm::convert(options("remap_before"), &mut timer),
+        true,
+        true,
+        &mut timer,
+    );
+    let new = Map::create_from_raw(
+        convert_osm::osc::apply_osc(
+            &[abstutil::path("../tests/input/remap.osc")],
+            options("remap_after"),
+            &mut timer,
+        )?,
+        true,
+        true,
+        &mut timer,
+    );
+    let remap = IDRemap::new(&old, &new);
+
+    let find_i = |map: &Map, node: i64| map.find_i_by_osm_id(osm::NodeID(node));
+    let find_r = |map: &Map, way: i64| -> Result<RoadID, String> {
+        map.all_roads()
+            .iter()
+            .find(|r| r.orig_id.osm_way_id == osm::WayID(way))
+            .map(|r| r.id)
+            .ok_or(format!("way {} is missing", way))
+    };
+    // The main roads still leave the map at the south
+    let (south, east) = (find_i(&old, 3)?, find_i(&old, 6)?);
+    assert!(old.get_i(south).is_border());
+    assert_eq!(remap.border(south), Some(find_i(&new, 3)?));
+    // The dead-end used to be cut off by the boundary, but it's entirely inside now
+    let dead_end = find_i(&old, 8)?;
+    assert!(old.get_i(dead_end).is_border());
+    assert_eq!(remap.intersection(dead_end), Some(find_i(&new, 8)?));
+    assert_eq!(remap.border(dead_end), None);
+    // Every lane on the local road matches, but nothing on the deleted road does
+    let local = find_r(&old, 110)?;
+    assert_eq!(remap.road(local), Some(find_r(&new, 110)?));
+    assert!(old
+        .get_r(local)
+        .all_lanes()
+        .into_iter()
+        .all(|l| remap.lane(l).is_some()));
+    let deleted = find_r(&old, 100)?;
+    assert_eq!(remap.road(deleted), None);
+
+    let on_road = |r: RoadID| {
+        TripEndpoint::SuddenlyAppear(Position::new(
+            old.get_r(r).all_lanes()[0],
+            Distance::meters(1.0),
+        ))
+    };
+    let person = |origin: TripEndpoint, destinations: Vec<TripEndpoint>| PersonSpec {
+        orig_id: None,
+        origin,
+        trips: destinations
+            .into_iter()
+            .map(|to| {
+                IndividTrip::new(
+                    Time::START_OF_DAY,
+                    TripPurpose::Shopping,
+                    to,
+                    TripMode::Drive,
+                )
+            })
+            .collect(),
+    };
+    let mut scenario = Scenario::empty(&old, "remap");
+    // Starts at a border that isn't one anymore
+    scenario.people.push(person(
+        TripEndpoint::Border(dead_end),
+        vec![TripEndpoint::Border(east)],
+    ));
+    // Only the first trip survives; the second ends at the old border, and the third starts there
+    scenario.people.push(person(
+        TripEndpoint::Border(south),
+        vec![
+            on_road(local),
+            TripEndpoint::Border(dead_end),
+            TripEndpoint::Border(east),
+        ],
+    ));
+    // Goes to the deleted road
+    scenario
+        .people
+        .push(person(TripEndpoint::Border(east), vec![on_road(deleted)]));
+    // Unaffected
+    scenario.people.push(person(
+        TripEndpoint::Border(south),
+        vec![TripEndpoint::Border(east)],
+    ));
+
+    let scenario = scenario.remap_ids(&remap);
+    assert_eq!(scenario.people.len(), 2);
+    assert_eq!(
+        scenario.people[0].origin,
+        TripEndpoint::Border(find_i(&new, 3)?)
+    );
+    assert_eq!(scenario.people[0].trips.len(), 1);
+    match scenario.people[0].trips[0].destination {
+        TripEndpoint::SuddenlyAppear(pos) => {
+            assert_eq!(new.get_l(pos.lane()).parent, find_r(&new, 110)?);
+            assert_eq!(pos.dist_along(), Distance::meters(1.0));
+        }
+        ref x => panic!("the first trip goes to {:?} now", x),
+    }
+    assert_eq!(
+        scenario.people[1].trips[0].destination,
+        TripEndpoint::Border(find_i(&new, 6)?)
+    );
+    Ok(())
+}
+
+/// Turn a small origin-destination matrix into trips between a few buildings and the borders left
+/// by clipping the south of the map.
+fn test_od() -> Result<(), Box<dyn std::error::Error>> {
+    let mut timer = Timer::new("disaggregate an OD matrix");
+    let mut opts = import_options(abstutil::path("../tests/input/neighborhood.osm"));
+    opts.clip = Some(abstutil::path("../tests/input/remap_before.poly"));
+    let map = Map::create_from_raw(
+        convert_osm::osc::apply_osc(&[abstutil::path("../tests/input/od.osc")], opts, &mut timer)?,
+        true,
+        true,
+        &mut timer,
+    );
+    let bldg = |way: i64| -> Result<TripEndpoint, String> {
+        map.all_buildings()
+            .iter()
+            .find(|b| b.orig_id == osm::OsmID::Way(osm::WayID(way)))
+            .map(|b| TripEndpoint::Bldg(b.id))
+            .ok_or(format!("building {} is missing", way))
+    };
+    let border = |node: i64| -> Result<TripEndpoint, String> {
+        Ok(TripEndpoint::Border(
+            map.find_i_by_osm_id(osm::NodeID(node))?,
+        ))
+    };
+    let zone = |name: &str, lon1: f64, lat1: f64, lon2: f64, lat2: f64| ODZone {
+        name: name.to_string(),
+        polygon: Polygon::rectangle_two_corners(
+            LonLat::new(lon1, lat1).to_pt(map.get_gps_bounds()),
+            LonLat::new(lon2, lat2).to_pt(map.get_gps_bounds()),
+        )
+        .unwrap(),
+    };
+    let entry = |origin: &str, destination: &str, period: &str, count: f64| ODEntry {
+        origin: origin.to_string(),
+        destination: destination.to_string(),
+        mode: TripMode::Drive,
+        purpose: TripPurpose::Work,
+        period: period.to_string(),
+        count,
+    };
+    let od = |entries: Vec<ODEntry>, hours: Vec<(usize, f64)>| {
+        let mut profile = DepartureProfile::all_day();
+        profile.periods.insert("morning".to_string(), hours);
+        ODMatrix {
+            zones: vec![
+                // Just the house on the west
+                zone("west", -122.4500, 47.7222, -122.4490, 47.7230),
+                // Just the house in the middle. It's the only thing in there, so trips can't stay
+                // inside the zone.
+                zone("middle", -122.4490, 47.7222, -122.4480, 47.7230),
+                // Only the borders along the southern edge
+                zone("south", -122.4505, 47.7195, -122.4455, 47.7213),
+                // Far to the east, off the map
+                zone("east", -122.4000, 47.7200, -122.3900, 47.7300),
+            ],
+            entries,
+            profile,
+        }
+        .disaggregate(
+            &map,
+            &mut sim::SimFlags::for_test("test_od").make_rng(),
+            &mut Timer::throwaway(),
+        )
+    };
+
+    let people = od(
+        vec![
+            entry("west", "middle", "morning", 3.0),
+            entry("east", "west", "all day", 2.0),
+            entry("south", "west", "all day", 4.0),
+            entry("middle", "middle", "all day", 5.0),
+        ],
+        vec![(7, 1.0), (8, 3.0)],
+    )?;
+    assert_eq!(people.len(), 9);
+    for person in &people[0..3] {
+        assert_eq!(person.origin, bldg(1100)?);
+        assert_eq!(person.trips[0].destination, bldg(1101)?);
+        let depart = person.trips[0].depart;
+        assert!(depart >= Time::START_OF_DAY + Duration::hours(7));
+        assert!(depart < Time::START_OF_DAY + Duration::hours(9));
+    }
+    // The east side of the main road is the closest way in
+    for person in &people[3..5] {
+        assert_eq!(person.origin, border(6)?);
+        assert_eq!(person.trips[0].destination, bldg(1100)?);
+    }
+    let south_borders = vec![border(3)?, border(6)?, border(8)?];
+    for person in &people[5..9] {
+        assert!(south_borders.contains(&person.origin));
+        assert_eq!(person.trips[0].destination, bldg(1100)?);
+    }
+
+    // Mistakes in the input are errors
+    assert!(od(vec![entry("west", "north", "all day", 1.0)], vec![(7, 1.0)]).is_err());
+    assert!(od(
+        vec![entry("west", "middle", "evening", 1.0)],
+        vec![(7, 1.0)]
+    )
+    .is_err());
+    assert!(od(
+        vec![entry("west", "middle", "all day", -1.0)],
+        vec![(7, 1.0)]
+    )
+    .is_err());
+    assert!(od(Vec::new(), vec![(24, 1.0)]).is_err());
+    Ok(())
+}
+
+/// Read a tiny .osm.pbf, with and without clipping it to a boundary. Relations that come before
+/// the relations they contain must still find them, and clipping must keep whole ways crossing
+/// the boundary. The clipped result is also written as a .osm, which must read back the same.
+fn test_pbf_clipping() -> Result<(), Box<dyn std::error::Error>> {
+    let mut timer = Timer::new("read synthetic .osm.pbf");
+    let input = abstutil::path("../tests/input/clip.osm.pbf");
+    let poly = abstutil::path("../tests/input/clip.poly");
+    let nodes = |ids: Vec<i64>| -> Vec<osm::NodeID> { ids.into_iter().map(osm::NodeID).collect() };
+    let ways = |ids: Vec<i64>| -> Vec<osm::WayID> { ids.into_iter().map(osm::WayID).collect() };
+    let rel = |id: i64| osm::RelationID(id);
+
+    let everything = convert_osm::pbf::read(&input, &GPSBounds::new(), None, &mut timer)?;
+    assert_eq!(
+        everything.nodes.keys().cloned().collect::<Vec<_>>(),
+        nodes(vec![1, 2, 3, 4, 5])
+    );
+    assert_eq!(
+        everything.ways.keys().cloned().collect::<Vec<_>>(),
+        ways(vec![10, 11, 12])
+    );
+    assert_eq!(everything.relations.len(), 4);
+    assert_eq!(
+        everything.relations[&rel(20)].members,
+        vec![
+            ("".to_string(), osm::OsmID::Relation(rel(21))),
+            ("".to_string(), osm::OsmID::Relation(rel(22)))
+        ]
+    );
+
+    let boundary = LonLat::read_osmosis_polygon(&poly)?;
+    let clipped = convert_osm::pbf::read(
+        &input,
+        &GPSBounds::from(boundary.clone()),
+        Some(boundary),
+        &mut timer,
+    )?;
+    // Way 11 crosses the boundary, so node 3 outside is kept
+    assert_eq!(
+        clipped.nodes.keys().cloned().collect::<Vec<_>>(),
+        nodes(vec![1, 2, 3])
+    );
+    assert_eq!(
+        clipped.ways.keys().cloned().collect::<Vec<_>>(),
+        ways(vec![10, 11])
+    );
+    // Nothing in 22 is inside, and 23 only contained 22
+    assert_eq!(
+        clipped.relations.keys().cloned().collect::<Vec<_>>(),
+        vec![rel(20), rel(21)]
+    );
+    assert_eq!(
+        clipped.relations[&rel(20)].members,
+        vec![("".to_string(), osm::OsmID::Relation(rel(21)))]
+    );
+    assert_eq!(clipped.relations[&rel(21)].members.len(), 3);
+
+    let output = std::env::temp_dir().join("clip.osm").display().to_string();
+    convert_osm::pbf::clip(&input, &poly, &output, &mut timer)?;
+    let reread = convert_osm::reader::read(&output, &GPSBounds::new(), &mut timer)?;
+    std::fs::remove_file(&output)?;
+    assert_eq!(reread.gps_bounds, clipped.gps_bounds);
+    for (id, node) in &clipped.nodes {
+        assert!(reread.nodes[id]
+            .pt
+            .approx_eq(node.pt, Distance::meters(0.01)));
+        assert_eq!(reread.nodes[id].tags, node.tags);
+    }
+    for (id, way) in &clipped.ways {
+        assert_eq!(reread.ways[id].nodes, way.nodes);
+        assert_eq!(reread.ways[id].tags, way.tags);
+    }
+    assert_eq!(
+        reread.ways[&osm::WayID(10)].tags.get("name").unwrap(),
+        "A & B"
+    );
+    for (id, relation) in &clipped.relations {
+        assert_eq!(reread.relations[id].members, relation.members);
+        assert_eq!(reread.relations[id].tags, relation.tags);
+    }
+    assert_eq!(reread.relations.len(), clipped.relations.len());
+    Ok(())
+}
+
+/// A local road cuts between two main roads, with a dead-end branching off of it. Check that it's
+/// found as a rat-run, and that a modal filter splits the neighborhood into two cells.
+fn test_neighborhood(mut map: Map) -> Result<(), String> {
+    let road = |map: &Map, way: i64| -> RoadID {
+        map.all_roads()
+            .iter()
+            .find(|r| r.orig_id.osm_way_id == osm::WayID(way))
+            .unwrap()
+            .id
+    };
+    let (west, east, dead_end) = (road(&map, 110), road(&map, 111), road(&map, 112));
+    let west_main = map.find_i_by_osm_id(osm::NodeID(2))?;
+    let east_main = map.find_i_by_osm_id(osm::NodeID(5))?;
+    let junction = map.find_i_by_osm_id(osm::NodeID(7))?;
+
+    let neighborhood =
+        Neighborhood::from_road(&map, west).ok_or("the local road isn't part of a neighborhood")?;
+    assert_eq!(
+        neighborhood.interior,
+        vec![west, east, dead_end].into_iter().collect()
+    );
+    assert_eq!(
+        neighborhood.borders,
+        vec![west_main, east_main].into_iter().collect()
+    );
+    let cells = neighborhood.find_cells(&map);
+    assert_eq!(cells.len(), 1);
+    assert_eq!(cells[0].borders, neighborhood.borders);
+    // Cutting through in both directions
+    assert_eq!(neighborhood.find_rat_runs(&map).len(), 2);
+
+    let mut edits = map.get_edits().clone();
+    edits
+        .commands
+        .push(map.toggle_modal_filter_cmd(east, junction));
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+
+    let mut cells = neighborhood.find_cells(&map);
+    cells.sort_by_key(|c| c.roads.len());
+    assert_eq!(cells.len(), 2);
+    assert_eq!(cells[0].roads, vec![east].into_iter().collect());
+    assert_eq!(cells[0].borders, vec![east_main].into_iter().collect());
+    assert_eq!(cells[1].roads, vec![west, dead_end].into_iter().collect());
+    assert_eq!(cells[1].borders, vec![west_main].into_iter().collect());
+    assert!(neighborhood.find_rat_runs(&map).is_empty());
+    assert_eq!(neighborhood.find_rat_runs_without_filters(&map).len(), 2);
+    Ok(())
+}
+
+/// Close the local road between the two main roads in the morning. Check that pathfinding only
+/// avoids it then, and that splicing another route into a path keeps its length right.
+fn test_timed_closure(mut map: Map) -> Result<(), String> {
+    let hours = |h: usize| Time::START_OF_DAY + Duration::hours(h);
+    let closed = map
+        .all_roads()
+        .iter()
+        .find(|r| r.orig_id.osm_way_id == osm::WayID(110))
+        .unwrap()
+        .id;
+    let west_main = map.find_i_by_osm_id(osm::NodeID(2))?;
+    let east_main = map.find_i_by_osm_id(osm::NodeID(5))?;
+
+    let mut edits = map.get_edits().clone();
+    edits
+        .commands
+        .push(map.timed_closure_cmd(closed, vec![(hours(8), hours(9))])?);
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+    assert_eq!(map.restriction_start_times(), vec![hours(8)]);
+    assert_eq!(map.restriction_change_times(), vec![hours(8), hours(9)]);
+
+    // Heading north on West Main, then south on East Main
+    let driving_lane = |r: i64, f: &dyn Fn(&map_model::Lane) -> bool| -> LaneID {
+        map.all_lanes()
+            .iter()
+            .find(|l| {
+                l.is_driving() && map.get_r(l.parent).orig_id.osm_way_id == osm::WayID(r) && f(l)
+            })
+            .unwrap()
+            .id
+    };
+    let start = driving_lane(101, &|l| l.dst_i == west_main);
+    let end = driving_lane(103, &|l| l.src_i == east_main);
+    let req = PathRequest {
+        start: Position::start(start),
+        end: Position::end(end, &map),
+        constraints: PathConstraints::Car,
+    };
+    let uses_closed_road = |path: &Path| {
+        path.get_steps().iter().any(|step| {
+            step.as_traversable()
+                .maybe_lane()
+                .map(|l| map.get_l(l).parent)
+                == Some(closed)
+        })
+    };
+
+    // The contraction hierarchy ignores the closure, since it doesn't apply all day
+    let mut path = map.pathfind(req.clone()).unwrap();
+    assert!(uses_closed_road(&path));
+    assert!(!path.breaks_turn_restrictions_at(PathConstraints::Car, hours(7), &map));
+    assert!(path.breaks_turn_restrictions_at(PathConstraints::Car, hours(8), &map));
+    assert!(map.pathfind_at_time(req.clone(), hours(7)).is_some());
+    assert!(uses_closed_road(
+        &map.pathfind_at_time(req.clone(), hours(7)).unwrap()
+    ));
+    let detour = map.pathfind_at_time(req.clone(), hours(8)).unwrap();
+    assert!(!uses_closed_road(&detour));
+    // Every simulated day is the same
+    assert!(!uses_closed_road(
+        &map.pathfind_at_time(req.clone(), hours(32)).unwrap()
+    ));
+
+    // Partway along the first lane, switch to the detour
+    path.reroute_from(0, detour.clone(), &map);
+    assert_eq!(path.get_steps(), detour.get_steps());
+    assert_eq!(path.total_length(), detour.total_length());
+
+    // Once past the first lane and turn, the length crossed so far stays part of the total
+    let mut path = map.pathfind(req.clone()).unwrap();
+    path.shift(&map);
+    path.shift(&map);
+    let crossed = path.crossed_so_far();
+    let rest = map
+        .pathfind_at_time(
+            PathRequest {
+                start: Position::start(path.current_step().as_lane()),
+                end: Position::end(end, &map),
+                constraints: PathConstraints::Car,
+            },
+            hours(7),
+        )
+        .unwrap();
+    path.reroute_from(0, rest.clone(), &map);
+    assert_eq!(path.crossed_so_far(), crossed);
+    assert!((path.total_length() - (crossed + rest.total_length())).abs() < Distance::meters(0.01));
+    Ok(())
+}
+
+/// Narrow the lanes of the local road between the two main roads. Check that the intersections
+/// shrink to match, and that widening the lanes beyond the original road is a problem.
+fn test_lane_widths(mut map: Map) -> Result<(), String> {
+    let r = map
+        .all_roads()
+        .iter()
+        .find(|r| r.orig_id.osm_way_id == osm::WayID(110))
+        .unwrap()
+        .id;
+    let (i1, i2) = (map.get_r(r).src_i, map.get_r(r).dst_i);
+    let area = |map: &Map, i: IntersectionID| map.get_i(i).polygon.area();
+    let orig_areas = (area(&map, i1), area(&map, i2));
+
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(map.edit_road_cmd(r, |new| {
+        for width in &mut new.lane_widths {
+            *width = *width * 0.5;
+        }
+    }));
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+
+    assert!(area(&map, i1) < orig_areas.0);
+    assert!(area(&map, i2) < orig_areas.1);
+    // The corners of the intersections are where the edges of the road end now
+    let road = map.get_r(r);
+    let half_width = road.get_half_width(&map);
+    for (i, pl) in vec![
+        (road.dst_i, road.center_pts.clone()),
+        (road.src_i, road.center_pts.reversed()),
+    ] {
+        for corner in vec![
+            pl.shift_left(half_width)?.last_pt(),
+            pl.shift_right(half_width)?.last_pt(),
+        ] {
+            assert!(map
+                .get_i(i)
+                .polygon
+                .points()
+                .iter()
+                .any(|pt| pt.approx_eq(corner, Distance::meters(0.1))));
+        }
+    }
+
+    // Back to the original widths
+    let mut edits = map.get_edits().clone();
+    edits.commands.clear();
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    assert!((area(&map, i1) - orig_areas.0).abs() < 0.1);
+    assert!((area(&map, i2) - orig_areas.1).abs() < 0.1);
+
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(map.edit_road_cmd(r, |new| {
+        new.lane_widths[0] = new.lane_widths[0] * 2.0;
+    }));
+    let problems = map.check_edits(edits, &mut Timer::throwaway());
+    assert!(problems.iter().any(|p| match p {
+        EditProblem::ImpossibleCrossSection { road, .. } => *road == r,
+        _ => false,
+    }));
+    Ok(())
+}
+
+/// Create two stops and two routes, then remove the second route. Check that only the newest route
+/// can be removed, and that the edits survive being saved and compressed.
+fn test_new_routes(mut map: Map) -> Result<(), String> {
+    let r = map
+        .all_roads()
+        .iter()
+        .find(|r| r.orig_id.osm_way_id == osm::WayID(110))
+        .unwrap()
+        .id;
+    let sidewalk = map
+        .get_r(r)
+        .all_lanes()
+        .into_iter()
+        .find(|l| map.get_l(*l).is_walkable())
+        .unwrap();
+    let len = map.get_l(sidewalk).length();
+
+    let mut stops = Vec::new();
+    for (name, pct) in vec![("A", 1.0 / 3.0), ("B", 2.0 / 3.0)] {
+        let cmd = map.new_bus_stop_cmd(Position::new(sidewalk, len * pct), name.to_string())?;
+        if let EditCmd::ChangeBusStop { id, .. } = cmd {
+            stops.push(id);
+        }
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(cmd);
+        map.must_apply_edits(edits, &mut Timer::throwaway());
+    }
+    let start = map.get_bs(stops[0]).driving_pos.lane();
+
+    let num_routes = map.all_bus_routes().len();
+    let (r1, r2) = (BusRouteID(num_routes), BusRouteID(num_routes + 1));
+    let mut edits = map.get_edits().clone();
+    for (id, name) in vec![(r1, "R1"), (r2, "R2")] {
+        edits.commands.push(EditCmd::ChangeRoute {
+            id,
+            old: None,
+            new: Some(EditRoute {
+                full_name: name.to_string(),
+                short_name: name.to_string(),
+                stops: stops.clone(),
+                start,
+                end_border: None,
+            }),
+        });
+    }
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    assert_eq!(map.all_bus_routes().len(), num_routes + 2);
+
+    let mut edits = map.get_edits().clone();
+    edits
+        .commands
+        .push(map.reroute_bus_cmd(r1, vec![stops[0]])?);
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    assert_eq!(map.get_br(r1).stops, vec![stops[0]]);
+
+    // R2 has to go first
+    assert!(map.remove_bus_route_cmd(r1).is_err());
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(map.remove_bus_route_cmd(r2)?);
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    assert_eq!(map.all_bus_routes().len(), num_routes + 1);
+    assert!(map.maybe_get_br(r2).is_none());
+
+    let edits = map.get_edits().clone();
+    let loaded = edits.to_permanent(&map).to_edits(&map)?;
+    assert_eq!(loaded.commands, edits.commands);
+
+    let mut compressed = edits.clone();
+    compressed.commands.clear();
+    compressed.compress(&map);
+    let route_idx = |r: BusRouteID| {
+        compressed.commands.iter().position(|cmd| match cmd {
+            EditCmd::ChangeRoute { id, .. } => *id == r,
+            _ => false,
+        })
+    };
+    for stop in &stops {
+        let created = compressed.commands.iter().position(|cmd| match cmd {
+            EditCmd::ChangeBusStop { id, .. } => id == stop,
+            _ => false,
+        });
+        assert!(created.unwrap() < route_idx(r1).unwrap());
+    }
+    assert!(route_idx(r2).is_none());
+
+    let loaded = compressed.to_permanent(&map).to_edits(&map)?;
+    assert_eq!(loaded.commands, compressed.commands);
+    map.must_apply_edits(loaded, &mut Timer::throwaway());
+    assert_eq!(map.all_bus_routes().len(), num_routes + 1);
+    assert_eq!(map.get_br(r1).stops, vec![stops[0]]);
+    Ok(())
+}
+
+/// Heading north on West Main next to a bus lane, through a traffic signal with a queue jump longer
+/// than any stage. With no bus waiting, a car shouldn't be held up at all.
+fn test_queue_jump(mut map: Map) -> Result<(), String> {
+    let r = map
+        .all_roads()
+        .iter()
+        .find(|r| r.orig_id.osm_way_id == osm::WayID(101))
+        .unwrap()
+        .id;
+    let west_main = map.find_i_by_osm_id(osm::NodeID(2))?;
+    let (south, east) = (
+        map.find_i_by_osm_id(osm::NodeID(3))?,
+        map.find_i_by_osm_id(osm::NodeID(6))?,
+    );
+
+    // Turn the sidewalk beside the northbound lane into a bus lane
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(map.edit_road_cmd(r, |new| {
+        let idx = new
+            .lanes_ltr
+            .iter()
+            .position(|(lt, dir)| *lt == LaneType::Sidewalk && *dir == Direction::Back)
+            .unwrap();
+        new.lanes_ltr[idx].0 = LaneType::Bus;
+    }));
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+
+    let trip_time = |map: &mut Map, queue_jump: Option<Duration>| -> Option<Duration> {
+        let mut tsp = TransitPriority::default_limits();
+        tsp.queue_jump = queue_jump;
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeIntersection {
+            i: west_main,
+            old: map.get_i_edit(west_main),
+            new: EditIntersection::TrafficSignal(
+                ControlTrafficSignal::new(map, west_main, &mut Timer::throwaway()).export(map),
+                Some(tsp),
+            ),
+        });
+        map.must_apply_edits(edits, &mut Timer::throwaway());
+        map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+        let mut scenario = Scenario::empty(map, "queue_jump");
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            origin: TripEndpoint::Border(south),
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY,
+                TripPurpose::Shopping,
+                TripEndpoint::Border(east),
+                TripMode::Drive,
+            )],
+        });
+        let mut opts = sim::SimOptions::new("test_queue_jump");
+        opts.alerts = sim::AlertHandler::Silence;
+        let mut sim = sim::Sim::new(map, opts, &mut Timer::throwaway());
+        let mut rng = sim::SimFlags::for_test("test_queue_jump").make_rng();
+        scenario.instantiate(&mut sim, map, &mut rng, &mut Timer::throwaway());
+        while !sim.is_done() && sim.time() < Time::START_OF_DAY + Duration::minutes(30) {
+            sim.tiny_step(map, &mut None);
+        }
+        sim.get_analytics()
+            .finished_trips
+            .get(0)
+            .and_then(|(_, _, _, dt)| *dt)
+    };
+
+    let without = trip_time(&mut map, None).ok_or("the car didn't finish without a queue jump")?;
+    let with = trip_time(&mut map, Some(Duration::hours(1)))
+        .ok_or("the car was held by a queue jump, even though no bus was waiting")?;
+    assert_eq!(without, with);
+    Ok(())
+}
+
+/// Run an edit script with some rules that the lane editor would refuse. Only the valid changes
+/// are kept, and each rule sees the changes made by earlier ones.
+fn test_edit_script(mut map: Map) -> Result<(), String> {
+    let rule = |roads: RoadQuery, changes: Vec<RoadChange>| EditRule { roads, changes };
+    let named = |name: &str| RoadQuery {
+        name: Some(name.to_string()),
+        ..Default::default()
+    };
+    let change_lt = |from: LaneType, to: LaneType| RoadChange::ChangeLaneType { from, to };
+    let script = EditScript {
+        edits_name: "test_edit_script".to_string(),
+        rules: vec![
+            rule(
+                RoadQuery {
+                    highway: vec!["residential".to_string()],
+                    ..Default::default()
+                },
+                vec![RoadChange::SpeedLimitMph(15.0)],
+            ),
+            // Parking needs a driving lane
+            rule(
+                named("Dead End"),
+                vec![change_lt(LaneType::Driving, LaneType::Parking)],
+            ),
+            // Sidewalks can't be changed
+            rule(
+                named("West Main"),
+                vec![change_lt(LaneType::Sidewalk, LaneType::Bus)],
+            ),
+            // Only matches because of the first rule
+            rule(
+                RoadQuery {
+                    name: Some("Cut Through".to_string()),
+                    speed_limit_below_mph: Some(20.0),
+                    ..Default::default()
+                },
+                vec![change_lt(LaneType::Driving, LaneType::Bus)],
+            ),
+            rule(
+                RoadQuery {
+                    highway: vec!["motorway".to_string()],
+                    ..Default::default()
+                },
+                vec![RoadChange::SpeedLimitMph(70.0)],
+            ),
+        ],
+    };
+    let (edits, problems) = script.evaluate(&map);
+
+    let problems_for = |idx: usize| {
+        problems
+            .iter()
+            .filter(|p| p.starts_with(&format!("Rule {} ", idx)))
+            .collect::<Vec<_>>()
+    };
+    assert!(problems_for(1).is_empty());
+    assert_eq!(problems_for(2).len(), 1);
+    assert!(problems_for(2)[0]
+        .ends_with("A parking lane needs a driving lane somewhere on the same road"));
+    // Two sidewalks on each of the two roads
+    assert_eq!(problems_for(3).len(), 4);
+    assert!(problems_for(3)
+        .iter()
+        .all(|p| p.ends_with("Can't change a sidewalk")));
+    assert!(problems_for(4).is_empty());
+    assert_eq!(problems_for(5), vec!["Rule 5 didn't match any roads"]);
+
+    // Only the residential roads changed
+    assert_eq!(edits.commands.len(), 3);
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    for r in map.all_roads() {
+        let lane_types: Vec<LaneType> = r.lanes_ltr().into_iter().map(|(_, _, lt)| lt).collect();
+        match r.orig_id.osm_way_id.0 {
+            110 | 111 => {
+                assert_eq!(r.speed_limit, Speed::miles_per_hour(15.0));
+                assert!(lane_types.contains(&LaneType::Bus));
+                assert!(!lane_types.contains(&LaneType::Driving));
+            }
+            112 => {
+                assert_eq!(r.speed_limit, Speed::miles_per_hour(15.0));
+                assert!(!lane_types.contains(&LaneType::Parking));
+            }
+            _ => {
+                assert!(lane_types.contains(&LaneType::Sidewalk));
+                assert!(!lane_types.contains(&LaneType::Bus));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Close the intersection leading to the dead-end, and check that the dead-end's lanes are reported
+/// as newly disconnected. Checking happens on the live map, so afterwards the edits and pathfinding
+/// should be untouched.
+fn test_check_edits(mut map: Map) -> Result<(), String> {
+    let cut_through = map.find_i_by_osm_id(osm::NodeID(7))?;
+    let west_main = map.find_i_by_osm_id(osm::NodeID(2))?;
+    let dead_end = map
+        .all_roads()
+        .iter()
+        .find(|r| r.orig_id.osm_way_id == osm::WayID(112))
+        .unwrap()
+        .id;
+    let lanes_for = |constraints: PathConstraints| -> Vec<LaneID> {
+        map.get_r(dead_end)
+            .all_lanes()
+            .into_iter()
+            .filter(|l| constraints.can_use(map.get_l(*l), &map))
+            .collect()
+    };
+    let dead_end_sidewalks = lanes_for(PathConstraints::Pedestrian);
+    let dead_end_driving = lanes_for(PathConstraints::Car);
+    assert!(!dead_end_sidewalks.is_empty());
+    assert!(!dead_end_driving.is_empty());
+
+    // Nothing's wrong with the dead-end to start with
+    let disconnected = |problems: &Vec<EditProblem>, constraints, lanes: &Vec<LaneID>| {
+        problems.iter().any(|p| match p {
+            EditProblem::DisconnectedLanes {
+                constraints: c,
+                lanes: l,
+            } => *c == constraints && lanes.iter().all(|x| l.contains(x)),
+            _ => false,
+        })
+    };
+    let before = map.find_problems(&mut Timer::throwaway());
+    assert!(!disconnected(
+        &before,
+        PathConstraints::Car,
+        &dead_end_driving
+    ));
+
+    let orig_edits = map.get_edits().clone();
+    let mut edits = orig_edits.clone();
+    edits.commands.push(EditCmd::ChangeIntersection {
+        i: cut_through,
+        new: EditIntersection::Closed,
+        old: map.get_i_edit(cut_through),
+    });
+    let problems = map.check_edits(edits, &mut Timer::throwaway());
+    assert!(disconnected(
+        &problems,
+        PathConstraints::Car,
+        &dead_end_driving
+    ));
+    assert!(disconnected(
+        &problems,
+        PathConstraints::Pedestrian,
+        &dead_end_sidewalks
+    ));
+    // Only new problems are reported
+    for p in &problems {
+        assert!(!before.contains(p));
+    }
+
+    // The map is back to the way it was, and pathfinding still works without recalculating
+    assert_eq!(map.get_edits(), &orig_edits);
+    assert!(!map.get_i(cut_through).is_closed());
+    let start = map
+        .all_lanes()
+        .iter()
+        .find(|l| {
+            l.is_driving()
+                && map.get_r(l.parent).orig_id.osm_way_id == osm::WayID(101)
+                && l.dst_i == west_main
+        })
+        .unwrap()
+        .id;
+    let end = *dead_end_driving
+        .iter()
+        .find(|l| map.get_l(**l).src_i == cut_through)
+        .unwrap();
+    assert!(map
+        .pathfind(PathRequest {
+            start: Position::start(start),
+            end: Position::end(end, &map),
+            constraints: PathConstraints::Car,
+        })
+        .is_some());
+    Ok(())
+}
+
+/// Remove the slip lane cutting the corner, and check that turns and routes no longer use it, and
+/// that restoring it brings it back.
+fn test_remove_slip_lane(mut map: Map) -> Result<(), String> {
+    let find_r = |way: i64| -> RoadID {
+        map.all_roads()
+            .iter()
+            .find(|r| r.orig_id.osm_way_id == osm::WayID(way))
+            .unwrap()
+            .id
+    };
+    let link = find_r(210);
+    let west_approach = find_r(200);
+    let corner = map.find_i_by_osm_id(osm::NodeID(2))?;
+    let south = map.find_i_by_osm_id(osm::NodeID(6))?;
+    let link_lanes = map.get_r(link).all_lanes();
+
+    let driving_lane = |r: RoadID, f: &dyn Fn(&map_model::Lane) -> bool| -> LaneID {
+        map.get_r(r)
+            .all_lanes()
+            .into_iter()
+            .find(|l| map.get_l(*l).is_driving() && f(map.get_l(*l)))
+            .unwrap()
+    };
+    let req = PathRequest {
+        start: Position::start(driving_lane(west_approach, &|l| l.dst_i == corner)),
+        end: Position::end(driving_lane(find_r(204), &|l| l.src_i == south), &map),
+        constraints: PathConstraints::Car,
+    };
+    let uses_link = |map: &Map| {
+        map.pathfind(req.clone())
+            .unwrap()
+            .get_steps()
+            .iter()
+            .any(|step| {
+                step.as_traversable()
+                    .maybe_lane()
+                    .map(|l| map.get_l(l).parent)
+                    == Some(link)
+            })
+    };
+    assert!(uses_link(&map));
+
+    // The road leading into the map can't go, since its border would be left with nothing
+    assert!(map.remove_road_cmd(west_approach).is_err());
+
+    let orig_edits = map.get_edits().clone();
+    let mut edits = orig_edits.clone();
+    edits.commands.push(map.remove_road_cmd(link)?);
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+    assert!(map.get_r(link).removed);
+    assert!(!map.get_i(corner).roads.contains(&link));
+    assert!(!map.get_i(south).roads.contains(&link));
+    assert!(map
+        .all_turns()
+        .values()
+        .all(|t| !link_lanes.contains(&t.id.src) && !link_lanes.contains(&t.id.dst)));
+    assert!(!uses_link(&map));
+    assert!(map.remove_road_cmd(link).is_err());
+    for p in map.find_problems(&mut Timer::throwaway()) {
+        if let EditProblem::DisconnectedLanes { lanes, .. } = p {
+            assert!(lanes.iter().all(|l| !link_lanes.contains(l)));
+        }
+    }
+
+    // The removal survives saving and loading
+    let perma = map.get_edits().to_permanent(&map);
+    assert_eq!(perma.to_edits(&map)?.commands, map.get_edits().commands);
+
+    // Putting the road back makes it the shortest way again
+    map.must_apply_edits(orig_edits, &mut Timer::throwaway());
+    map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+    assert!(!map.get_r(link).removed);
+    assert!(map.get_i(corner).roads.contains(&link));
+    assert!(uses_link(&map));
+    Ok(())
+}
+
+/// Check that entering the roundabout yields to circulating traffic, that the island in the middle
+/// is found, and that traffic from every direction makes it through without gridlock.
+fn test_roundabout(map: Map) -> Result<(), String> {
+    let mut ring = Vec::new();
+    let mut borders = Vec::new();
+    for id in 1..=4 {
+        ring.push(map.find_i_by_osm_id(osm::NodeID(id))?);
+        borders.push(map.find_i_by_osm_id(osm::NodeID(10 + id))?);
+    }
+
+    for i in &ring {
+        let i = map.get_i(*i);
+        assert!(i.is_roundabout(&map));
+        let sign = map.get_stop_sign(i.id);
+        for (r, cfg) in &sign.roads {
+            let expected = if map.get_r(*r).is_roundabout() {
+                SignType::NoSign
+            } else {
+                SignType::Yield
+            };
+            assert_eq!(cfg.sign_type, expected);
+        }
+        for t in &i.turns {
+            if map.get_t(*t).between_sidewalks() {
+                continue;
+            }
+            let expected = if map.get_parent(t.src).is_roundabout() {
+                TurnPriority::Protected
+            } else {
+                TurnPriority::Yield
+            };
+            assert_eq!(sign.get_priority(*t, &map), expected);
+        }
+    }
+    for i in &borders {
+        assert!(!map.get_i(*i).is_roundabout(&map));
+    }
+
+    let center = LonLat::new(-122.45, 47.72).to_pt(map.get_gps_bounds());
+    assert!(map
+        .all_areas()
+        .iter()
+        .any(|a| a.area_type == AreaType::MedianStrip && a.polygon.contains_pt(center)));
+
+    // Everybody takes the first exit or goes straight across, so some circulate past other entries
+    let mut scenario = Scenario::empty(&map, "roundabout");
+    for idx in 0..100 {
+        let from = borders[idx % 4];
+        let to = borders[(idx % 4 + 1 + idx % 2) % 4];
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            origin: TripEndpoint::Border(from),
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY + Duration::seconds(idx as f64),
+                TripPurpose::Shopping,
+                TripEndpoint::Border(to),
+                TripMode::Drive,
+            )],
+        });
+    }
+    let mut opts = sim::SimOptions::new("test_roundabout");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(&map, opts, &mut Timer::throwaway());
+    let mut rng = sim::SimFlags::for_test("test_roundabout").make_rng();
+    scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+    let limit = Time::START_OF_DAY + Duration::minutes(30);
+    while !sim.is_done() && sim.time() < limit {
+        sim.tiny_step(&map, &mut None);
+    }
+    if !sim.is_done() {
+        return Err(format!(
+            "Traffic through the roundabout is stuck at {}",
+            sim.time()
+        ));
+    }
+    let finished = &sim.get_analytics().finished_trips;
+    assert_eq!(finished.len(), 100);
+    assert!(finished.iter().all(|(_, _, _, dt)| dt.is_some()));
+    Ok(())
+}
+
+/// Yield and stop signs can be tagged on the intersection node or on the road approaching it.
+fn test_signs(map: Map) -> Result<(), String> {
+    let check = |node: i64, expected: Vec<(i64, SignType)>| -> Result<(), String> {
+        let i = map.find_i_by_osm_id(osm::NodeID(node))?;
+        for (r, cfg) in &map.get_stop_sign(i).roads {
+            let way = map.get_r(*r).orig_id.osm_way_id.0;
+            let sign_type = expected.iter().find(|(w, _)| *w == way).unwrap().1;
+            assert_eq!(cfg.sign_type, sign_type, "way {} at node {}", way, node);
+        }
+        Ok(())
+    };
+    // A yield sign on the intersection applies to the minor road
+    check(2, vec![(100, SignType::NoSign), (101, SignType::Yield)])?;
+    // An all-way stop applies to everything, even the main road
+    check(3, vec![(100, SignType::Stop), (102, SignType::Stop)])?;
+    // A stop sign before the intersection only applies to that road, even though all of the roads
+    // are the same rank
+    check(22, vec![(103, SignType::NoSign), (104, SignType::Stop)])?;
+    Ok(())
+}
+
 /// Run the contents of a .osm through the full map importer with default options.
 fn import_map(path: String) -> Map {
     let mut timer = abstutil::Timer::new("convert synthetic map");
-    let raw = convert_osm::convert(
-        convert_osm::Options {
-            name: MapName::new("oneshot", &abstutil::basename(&path)),
-            osm_input: path,
-            clip: None,
-            map_config: map_model::MapConfig {
-                driving_side: map_model::DrivingSide::Right,
-                bikes_can_use_bus_lanes: true,
-                inferred_sidewalks: true,
-            },
-            onstreet_parking: convert_osm::OnstreetParking::JustOSM,
-            public_offstreet_parking: convert_osm::PublicOffstreetParking::None,
-            private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(0),
-            elevation: None,
-            include_railroads: true,
-        },
-        &mut timer,
-    );
+    let raw = convert_osm::convert(import_options(path), &mut timer);
     let map = Map::create_from_raw(raw, true, true, &mut timer);
     map
 }
 
+fn import_options(path: String) -> convert_osm::Options {
+    convert_osm::Options {
+        name: MapName::new("oneshot", &abstutil::basename(&path)),
+        osm_input: path,
+        clip: None,
+        map_config: map_model::MapConfig {
+            driving_side: map_model::DrivingSide::Right,
+            bikes_can_use_bus_lanes: true,
+            inferred_sidewalks: true,
+        },
+        onstreet_parking: convert_osm::OnstreetParking::JustOSM,
+        public_offstreet_parking: convert_osm::PublicOffstreetParking::None,
+        private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(0),
+        elevation: None,
+        include_railroads: true,
+    }
+}
+
 /// Verify what turns are generated by writing (from lane, to lane, turn type).
 fn dump_turn_goldenfile(map: &Map) -> Result<(), std::io::Error> {
     let path = abstutil::path(format!("../tests/goldenfiles/{}.txt", map.get_name().map));